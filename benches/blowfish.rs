@@ -0,0 +1,40 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main, Throughput};
+
+use rustyroad::blowfish::Blowfish;
+use rustyroad::pk2::constants::{BLOCK_SIZE, KEY_BYTES, SALT};
+
+/// Sizes of a single cipher block, a PK2 entry, a PK2 block and a large packet or file.
+const SIZES: [usize; 4] = [8, 128, BLOCK_SIZE, 64 * 1024];
+
+fn encrypt(c: &mut Criterion) {
+    let blowfish = Blowfish::new(KEY_BYTES, SALT).unwrap();
+    let mut group = c.benchmark_group("blowfish_encrypt");
+    for size in SIZES {
+        let mut data = vec![0x5Au8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| blowfish.encrypt(&mut data).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn decrypt(c: &mut Criterion) {
+    let blowfish = Blowfish::new(KEY_BYTES, SALT).unwrap();
+    let mut group = c.benchmark_group("blowfish_decrypt");
+    for size in SIZES {
+        let mut data = vec![0x5Au8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| blowfish.decrypt(&mut data).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn key_schedule(c: &mut Criterion) {
+    c.bench_function("blowfish_key_schedule", |b| b.iter(|| Blowfish::new(KEY_BYTES, SALT).unwrap()));
+}
+
+criterion_group!(benches, encrypt, decrypt, key_schedule);
+criterion_main!(benches);
@@ -0,0 +1,98 @@
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main, Throughput};
+
+use rustyroad::blowfish::Blowfish;
+use rustyroad::pk2::archive::Archive;
+use rustyroad::pk2::constants::{BLOCK_SIZE, CHECKSUM, ENTRIES_PER_BLOCK, ENTRY_SIZE, HEADER_SIZE, KEY_BYTES, SALT, SIGNATURE, VERSION};
+
+const FILE_COUNT: usize = 1024;
+const FILE_SIZE: usize = 16 * 1024;
+
+/// Writes an archive with [FILE_COUNT] files of [FILE_SIZE] bytes each into the root directory.
+fn write_fixture(path: &Path) {
+    let blowfish = Blowfish::new(KEY_BYTES, SALT).unwrap();
+    let mut header = [0u8; HEADER_SIZE];
+    header[0..30].copy_from_slice(SIGNATURE);
+    header[30..34].copy_from_slice(&VERSION.to_le_bytes());
+    header[34] = 1;
+    let mut checksum = *CHECKSUM;
+    blowfish.encrypt(&mut checksum).unwrap();
+    header[35..38].copy_from_slice(&checksum[..3]);
+
+    let slots = FILE_COUNT + 1;
+    let blocks = slots.div_ceil(ENTRIES_PER_BLOCK);
+    let data_offset = (HEADER_SIZE + blocks * BLOCK_SIZE) as u64;
+    let mut block_data = vec![0u8; blocks * BLOCK_SIZE];
+    for slot in 0..slots {
+        let entry = &mut block_data[slot * ENTRY_SIZE..(slot + 1) * ENTRY_SIZE];
+        if slot == 0 {
+            entry[0] = 1;
+            entry[1] = b'.';
+            entry[106..114].copy_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+        } else {
+            let name = format!("file_{:04}.dat", slot - 1);
+            entry[0] = 2;
+            entry[1..1 + name.len()].copy_from_slice(name.as_bytes());
+            entry[106..114].copy_from_slice(&(data_offset + ((slot - 1) * FILE_SIZE) as u64).to_le_bytes());
+            entry[114..118].copy_from_slice(&(FILE_SIZE as u32).to_le_bytes());
+        }
+    }
+    for block in 0..blocks - 1 {
+        let next_block = (HEADER_SIZE + (block + 1) * BLOCK_SIZE) as u64;
+        let last_entry = (block + 1) * BLOCK_SIZE - ENTRY_SIZE;
+        block_data[last_entry + 118..last_entry + 126].copy_from_slice(&next_block.to_le_bytes());
+    }
+    blowfish.encrypt(&mut block_data).unwrap();
+
+    let mut file = File::create(path).unwrap();
+    file.write_all(&header).unwrap();
+    file.write_all(&block_data).unwrap();
+    for i in 0..FILE_COUNT {
+        file.write_all(&vec![i as u8; FILE_SIZE]).unwrap();
+    }
+}
+
+fn fixture_path() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    create_dir_all(dir).unwrap();
+    let path = dir.join("bench_concurrent_reads.pk2");
+    write_fixture(&path);
+    path
+}
+
+/// Reads all files of the archive split among given number of threads, which share the archive.
+fn read_all(archive: &Archive, paths: &[PathBuf], threads: usize) -> usize {
+    thread::scope(|scope| {
+        paths.chunks(paths.len().div_ceil(threads))
+            .map(|chunk| scope.spawn(move || chunk.iter()
+                .map(|p| archive.read(p).unwrap().len())
+                .sum::<usize>()))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum()
+    })
+}
+
+fn concurrent_reads(c: &mut Criterion) {
+    let archive = Archive::open(&fixture_path()).unwrap();
+    let paths: Vec<PathBuf> = (0..FILE_COUNT)
+        .map(|i| PathBuf::from(format!("file_{:04}.dat", i)))
+        .collect();
+
+    let mut group = c.benchmark_group("pk2_concurrent_reads");
+    group.throughput(Throughput::Bytes((FILE_COUNT * FILE_SIZE) as u64));
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+            b.iter(|| read_all(&archive, &paths, threads))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, concurrent_reads);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+/// Credentials a client presents when it connects to the agent server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    /// Token handed out by the gateway server for this login.
+    pub token: u32,
+    pub username: String,
+    pub password: String,
+}
+
+/// Decides which account a client logs in as.
+pub trait Authenticator: Send + Sync {
+    /// Returns the account of given credentials, or `None` if they're invalid.
+    fn authenticate(&self, credentials: &Credentials) -> Option<u32>;
+}
+
+/// Rejects every login, since there's no gateway server handing out tokens yet.
+pub struct NoLogins;
+
+impl Authenticator for NoLogins {
+    fn authenticate(&self, _credentials: &Credentials) -> Option<u32> {
+        None
+    }
+}
+
+/// Accepts every login without checking token or password, for development only. Each username
+/// is an account of its own, whose ID is derived from the name regardless of its case, so it
+/// stays the same across restarts.
+pub struct TrustedLogins;
+
+impl Authenticator for TrustedLogins {
+    fn authenticate(&self, credentials: &Credentials) -> Option<u32> {
+        Some(account_id(&credentials.username))
+    }
+}
+
+/// Hashes a username with FNV-1a.
+fn account_id(username: &str) -> u32 {
+    username.to_lowercase().bytes().fold(0x811C9DC5, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(username: &str) -> Credentials {
+        Credentials { token: 0, username: username.to_string(), password: "secret".to_string() }
+    }
+
+    #[test]
+    fn trusts_logins_only_when_asked_to() {
+        assert_eq!(NoLogins.authenticate(&credentials("Rusty")), None);
+        let account = TrustedLogins.authenticate(&credentials("Rusty")).unwrap();
+        assert_eq!(TrustedLogins.authenticate(&credentials("rusty")), Some(account));
+        assert_ne!(TrustedLogins.authenticate(&credentials("Road")), Some(account));
+    }
+}
@@ -0,0 +1,11 @@
+pub mod character_data;
+pub mod chat;
+pub mod errors;
+pub mod handler;
+pub mod inventory;
+pub mod join;
+pub mod login;
+pub mod movement;
+pub mod opcodes;
+pub mod selection;
+pub mod spawn;
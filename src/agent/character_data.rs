@@ -0,0 +1,669 @@
+use crate::agent::movement::write_destination;
+use crate::agent::opcodes::{CHARACTER_DATA, CHARACTER_DATA_BEGIN, CHARACTER_DATA_END};
+use crate::character::inventory::{AVATAR_SLOTS, DEFAULT_INVENTORY_SIZE, inventory_size};
+use crate::character::record::{Character, Item, Mastery};
+use crate::net::packet::{Packet, PacketWriter, MAX_DATA_SIZE};
+use crate::textdata::registry::Registry;
+use crate::world::movement::{RUN_SPEED, WALK_SPEED};
+use crate::world::position::{Position, RegionId};
+
+/// `ActivationFlag` of the client configuration telling the hotkeys etc. are set.
+const CONFIG_ACTIVATED: u8 = 7;
+/// `PVPFlag` of characters not taking part in a battle.
+pub const PVP_FLAG_NONE: u8 = 0xFF;
+/// Binding options following the magic options of equipment.
+const BINDING_SOCKET: u8 = 1;
+const BINDING_ADVANCED_ELIXIR: u8 = 2;
+/// Quest type with a time limit, which carries the remaining time.
+const QUEST_TIME_LIMITED: u8 = 28;
+/// Quest type without objectives.
+const QUEST_WITHOUT_OBJECTIVES: u8 = 8;
+/// Quest type which also lists the NPCs involved.
+const QUEST_WITH_NPCS: u8 = 88;
+
+/// Whether an entity is alive.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LifeState {
+    Alive = 1,
+    Dead = 2,
+}
+
+/// What an entity is currently doing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MotionState {
+    Standing = 0,
+    Walking = 2,
+    Running = 3,
+    Sitting = 4,
+}
+
+/// A binding option of equipment, i.e. a socket stone or an advanced elixir.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingOption {
+    pub slot: u8,
+    pub id: u32,
+    pub value: u32,
+}
+
+/// The part of an item depending on its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemData {
+    /// Weapons, armor, shields and accessories (`TypeID` 3, 1).
+    Equipment {
+        plus: u8,
+        /// The white stats, 5 bits per stat.
+        variance: u64,
+        durability: u32,
+        /// Blue stats as type and value.
+        magic_options: Vec<(u32, u32)>,
+        sockets: Vec<BindingOption>,
+        advanced_elixirs: Vec<BindingOption>,
+    },
+    /// Stackable items like potions and scrolls (`TypeID` 3, 3).
+    Stack { quantity: u16 },
+    /// Magic and attribute stones (`TypeID` 3, 3, 11), which also carry their assimilation
+    /// probability.
+    Stone { quantity: u16, assimilation: u8 },
+}
+
+/// An item in a slot of the inventory or avatar inventory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryItem {
+    pub slot: u8,
+    pub ref_item_id: u32,
+    pub data: ItemData,
+}
+
+impl InventoryItem {
+    /// Takes a stored item, which is classified by its reference data. Returns `None` for items
+    /// missing in it.
+    pub fn new(item: &Item, registry: &Registry) -> Option<InventoryItem> {
+        let reference = registry.items.get(item.ref_item_id)?;
+        let data = match reference.common.type_id {
+            [3, 1, _, _] => ItemData::Equipment {
+                plus: item.plus,
+                variance: item.variance,
+                durability: item.durability,
+                magic_options: item.magic_options.clone(),
+                sockets: Vec::new(),
+                advanced_elixirs: Vec::new(),
+            },
+            [3, 3, 11, 1] | [3, 3, 11, 2] => ItemData::Stone { quantity: item.quantity, assimilation: 0 },
+            _ => ItemData::Stack { quantity: item.quantity },
+        };
+        Some(InventoryItem { slot: item.slot, ref_item_id: item.ref_item_id, data })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skill {
+    pub id: u32,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuestObjective {
+    pub id: u8,
+    pub completed: bool,
+    pub name: String,
+    /// Progress of each task, e.g. the number of monsters killed.
+    pub tasks: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveQuest {
+    pub ref_quest_id: u32,
+    pub achievements: u8,
+    pub requires_share_party: bool,
+    pub kind: u8,
+    /// Seconds left of quests with a time limit.
+    pub remaining_time: u32,
+    pub status: u8,
+    pub objectives: Vec<QuestObjective>,
+    /// NPCs involved in quests listing them.
+    pub npcs: Vec<u32>,
+}
+
+/// A started theme of the collection book.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionTheme {
+    pub index: u32,
+    pub started: u32,
+    pub pages: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Buff {
+    pub ref_skill_id: u32,
+    pub duration: u32,
+    /// Whether the character cast the buff, only sent for buffs shared with the party.
+    pub creator: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotkey {
+    pub slot: u8,
+    pub kind: u8,
+    pub data: u32,
+}
+
+/// The data of the joining character ([CHARACTER_DATA]) in the layout of vSRO 1.188. Fields
+/// default to a new character standing at the origin of region 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterData {
+    pub server_time: u32,
+    pub ref_obj_id: u32,
+    pub scale: u8,
+    pub level: u8,
+    pub max_level: u8,
+    pub exp: u64,
+    pub skill_exp: u32,
+    pub gold: u64,
+    pub skill_points: u32,
+    pub stat_points: u16,
+    pub berserk_points: u8,
+    pub gathered_exp: u32,
+    pub hp: u32,
+    pub mp: u32,
+    pub auto_invest_exp: u8,
+    pub daily_pk: u8,
+    pub total_pk: u16,
+    pub pk_penalty: u32,
+    pub berserk_level: u8,
+    pub free_pvp: u8,
+    pub inventory_size: u8,
+    pub inventory: Vec<InventoryItem>,
+    pub avatar_inventory_size: u8,
+    pub avatar_inventory: Vec<InventoryItem>,
+    pub masteries: Vec<Mastery>,
+    pub skills: Vec<Skill>,
+    pub completed_quests: Vec<u32>,
+    pub active_quests: Vec<ActiveQuest>,
+    pub collection_book: Vec<CollectionTheme>,
+    pub unique_id: u32,
+    pub position: Position,
+    pub angle: u16,
+    /// Destination of a movement in progress.
+    pub destination: Option<Position>,
+    pub running: bool,
+    pub life_state: LifeState,
+    pub motion_state: MotionState,
+    pub status: u8,
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub berserk_speed: f32,
+    pub buffs: Vec<Buff>,
+    pub name: String,
+    pub job_name: String,
+    pub job_type: u8,
+    pub job_level: u8,
+    pub job_exp: u32,
+    pub job_contribution: u32,
+    pub job_reward: u32,
+    pub pvp_state: u8,
+    /// Unique ID of the ridden transport.
+    pub transport: Option<u32>,
+    pub in_combat: bool,
+    pub pvp_flag: u8,
+    pub guide_flag: u64,
+    pub jid: u32,
+    pub gm: bool,
+    pub hotkeys: Vec<Hotkey>,
+    pub auto_hp: u16,
+    pub auto_mp: u16,
+    pub auto_universal: u16,
+    pub auto_potion_delay: u8,
+    pub blocked_whispers: Vec<String>,
+}
+
+impl Default for CharacterData {
+    fn default() -> Self {
+        CharacterData {
+            server_time: 0,
+            ref_obj_id: 0,
+            scale: 0,
+            level: 1,
+            max_level: 1,
+            exp: 0,
+            skill_exp: 0,
+            gold: 0,
+            skill_points: 0,
+            stat_points: 0,
+            berserk_points: 0,
+            gathered_exp: 0,
+            hp: 0,
+            mp: 0,
+            auto_invest_exp: 0,
+            daily_pk: 0,
+            total_pk: 0,
+            pk_penalty: 0,
+            berserk_level: 0,
+            free_pvp: 0,
+            inventory_size: DEFAULT_INVENTORY_SIZE,
+            inventory: Vec::new(),
+            avatar_inventory_size: AVATAR_SLOTS,
+            avatar_inventory: Vec::new(),
+            masteries: Vec::new(),
+            skills: Vec::new(),
+            completed_quests: Vec::new(),
+            active_quests: Vec::new(),
+            collection_book: Vec::new(),
+            unique_id: 0,
+            position: Position::new(RegionId(0), 0.0, 0.0, 0.0),
+            angle: 0,
+            destination: None,
+            running: true,
+            life_state: LifeState::Alive,
+            motion_state: MotionState::Standing,
+            status: 0,
+            walk_speed: WALK_SPEED,
+            run_speed: RUN_SPEED,
+            berserk_speed: RUN_SPEED * 2.0,
+            buffs: Vec::new(),
+            name: String::new(),
+            job_name: String::new(),
+            job_type: 0,
+            job_level: 0,
+            job_exp: 0,
+            job_contribution: 0,
+            job_reward: 0,
+            pvp_state: 0,
+            transport: None,
+            in_combat: false,
+            pvp_flag: PVP_FLAG_NONE,
+            guide_flag: 0,
+            jid: 0,
+            gm: false,
+            hotkeys: Vec::new(),
+            auto_hp: 0,
+            auto_mp: 0,
+            auto_universal: 0,
+            auto_potion_delay: 0,
+            blocked_whispers: Vec::new(),
+        }
+    }
+}
+
+impl CharacterData {
+    /// Takes the stats, position and items of a stored character, which spawns with given
+    /// unique ID. Items are classified by their reference data, the ones missing in it are
+    /// left out.
+    pub fn new(character: &Character, unique_id: u32, registry: &Registry) -> CharacterData {
+        let items = |items: &[Item]| items.iter()
+            .filter_map(|item| {
+                let converted = InventoryItem::new(item, registry);
+                if converted.is_none() {
+                    warn!("character {} has unknown item {}", character.name, item.ref_item_id);
+                }
+                converted
+            })
+            .collect();
+        CharacterData {
+            ref_obj_id: character.ref_obj_id,
+            scale: character.scale,
+            level: character.level,
+            max_level: character.level,
+            exp: character.exp,
+            gold: character.gold,
+            skill_points: character.skill_points,
+            stat_points: character.stat_points,
+            hp: character.hp,
+            mp: character.mp,
+            inventory_size: inventory_size(registry, character.ref_obj_id),
+            inventory: items(&character.items),
+            avatar_inventory: items(&character.avatars),
+            masteries: character.masteries.clone(),
+            skills: character.skills.iter().map(|id| Skill { id: *id, enabled: true }).collect(),
+            unique_id,
+            position: character.position,
+            name: character.name.clone(),
+            ..CharacterData::default()
+        }
+    }
+
+    /// Serializes the data into its begin, data and end packets. The data is split into as many
+    /// packets as needed.
+    pub fn packets(&self) -> Vec<Packet> {
+        let mut writer = PacketWriter::new(CHARACTER_DATA);
+        self.write(&mut writer);
+        let data = writer.build().data;
+        let mut packets = vec![Packet::new(CHARACTER_DATA_BEGIN, Vec::new())];
+        packets.extend(data.chunks(MAX_DATA_SIZE).map(|chunk| Packet::new(CHARACTER_DATA, chunk.to_vec())));
+        packets.push(Packet::new(CHARACTER_DATA_END, Vec::new()));
+        packets
+    }
+
+    fn write(&self, writer: &mut PacketWriter) {
+        writer.u32(self.server_time)
+            .u32(self.ref_obj_id)
+            .u8(self.scale)
+            .u8(self.level)
+            .u8(self.max_level)
+            .u64(self.exp)
+            .u32(self.skill_exp)
+            .u64(self.gold)
+            .u32(self.skill_points)
+            .u16(self.stat_points)
+            .u8(self.berserk_points)
+            .u32(self.gathered_exp)
+            .u32(self.hp)
+            .u32(self.mp)
+            .u8(self.auto_invest_exp)
+            .u8(self.daily_pk)
+            .u16(self.total_pk)
+            .u32(self.pk_penalty)
+            .u8(self.berserk_level)
+            .u8(self.free_pvp);
+        write_inventory(writer, self.inventory_size, &self.inventory);
+        write_inventory(writer, self.avatar_inventory_size, &self.avatar_inventory);
+
+        writer.u8(0);
+        for mastery in &self.masteries {
+            writer.bool(true).u32(mastery.id).u8(mastery.level);
+        }
+        writer.bool(false).u8(0);
+        for skill in &self.skills {
+            writer.bool(true).u32(skill.id).bool(skill.enabled);
+        }
+        writer.bool(false);
+
+        let completed_quests = &self.completed_quests[..self.completed_quests.len().min(u16::MAX as usize)];
+        writer.u16(completed_quests.len() as u16);
+        completed_quests.iter().for_each(|quest| { writer.u32(*quest); });
+        write_list(writer, &self.active_quests, write_quest);
+
+        writer.u8(0).u32(self.collection_book.len() as u32);
+        for theme in &self.collection_book {
+            writer.u32(theme.index).u32(theme.started).u32(theme.pages);
+        }
+
+        let position = self.position;
+        writer.u32(self.unique_id)
+            .u16(position.region.0).f32(position.x).f32(position.y).f32(position.z)
+            .u16(self.angle)
+            .bool(self.destination.is_some())
+            .bool(self.running);
+        match &self.destination {
+            Some(destination) => write_destination(writer, destination),
+            None => { writer.u8(0).u16(self.angle); }
+        }
+
+        writer.u8(self.life_state as u8)
+            .u8(0)
+            .u8(self.motion_state as u8)
+            .u8(self.status)
+            .f32(self.walk_speed)
+            .f32(self.run_speed)
+            .f32(self.berserk_speed);
+        write_list(writer, &self.buffs, |writer, buff| {
+            writer.u32(buff.ref_skill_id).u32(buff.duration);
+            if let Some(creator) = buff.creator {
+                writer.bool(creator);
+            }
+        });
+
+        writer.string(&self.name)
+            .string(&self.job_name)
+            .u8(self.job_type)
+            .u8(self.job_level)
+            .u32(self.job_exp)
+            .u32(self.job_contribution)
+            .u32(self.job_reward)
+            .u8(self.pvp_state)
+            .bool(self.transport.is_some())
+            .bool(self.in_combat);
+        if let Some(transport) = self.transport {
+            writer.u32(transport);
+        }
+        writer.u8(self.pvp_flag)
+            .u64(self.guide_flag)
+            .u32(self.jid)
+            .bool(self.gm);
+
+        writer.u8(CONFIG_ACTIVATED);
+        write_list(writer, &self.hotkeys, |writer, hotkey| { writer.u8(hotkey.slot).u8(hotkey.kind).u32(hotkey.data); });
+        writer.u16(self.auto_hp)
+            .u16(self.auto_mp)
+            .u16(self.auto_universal)
+            .u8(self.auto_potion_delay);
+        write_list(writer, &self.blocked_whispers, |writer, name| { writer.string(name); });
+        writer.u32(0).u8(0);
+    }
+}
+
+fn write_inventory(writer: &mut PacketWriter, size: u8, items: &[InventoryItem]) {
+    writer.u8(size);
+    write_list(writer, items, write_item);
+}
+
+/// Writes an item with its slot, as in the inventory or when it was added to it.
+pub(crate) fn write_item(writer: &mut PacketWriter, item: &InventoryItem) {
+    // rent type: not rented
+    writer.u8(item.slot).u32(0).u32(item.ref_item_id);
+    match &item.data {
+        ItemData::Equipment { plus, variance, durability, magic_options, sockets, advanced_elixirs } => {
+            writer.u8(*plus).u64(*variance).u32(*durability);
+            write_list(writer, magic_options, |writer, (kind, value)| { writer.u32(*kind).u32(*value); });
+            write_binding_options(writer, BINDING_SOCKET, sockets);
+            write_binding_options(writer, BINDING_ADVANCED_ELIXIR, advanced_elixirs);
+        }
+        ItemData::Stack { quantity } => { writer.u16(*quantity); }
+        ItemData::Stone { quantity, assimilation } => { writer.u16(*quantity).u8(*assimilation); }
+    }
+}
+
+fn write_binding_options(writer: &mut PacketWriter, kind: u8, options: &[BindingOption]) {
+    writer.u8(kind);
+    write_list(writer, options, |writer, option| { writer.u8(option.slot).u32(option.id).u32(option.value); });
+}
+
+fn write_quest(writer: &mut PacketWriter, quest: &ActiveQuest) {
+    writer.u32(quest.ref_quest_id)
+        .u8(quest.achievements)
+        .bool(quest.requires_share_party)
+        .u8(quest.kind);
+    if quest.kind == QUEST_TIME_LIMITED {
+        writer.u32(quest.remaining_time);
+    }
+    writer.u8(quest.status);
+    if quest.kind != QUEST_WITHOUT_OBJECTIVES {
+        write_list(writer, &quest.objectives, |writer, objective| {
+            writer.u8(objective.id).bool(objective.completed).string(&objective.name);
+            write_list(writer, &objective.tasks, |writer, task| { writer.u32(*task); });
+        });
+    }
+    if quest.kind == QUEST_WITH_NPCS {
+        write_list(writer, &quest.npcs, |writer, npc| { writer.u32(*npc); });
+    }
+}
+
+/// Writes the count of a list as `u8` followed by its elements. Lists with more elements than the
+/// count can hold are cut off, so that the count always matches the written elements.
+fn write_list<T>(writer: &mut PacketWriter, items: &[T], mut write: impl FnMut(&mut PacketWriter, &T)) {
+    let items = &items[..items.len().min(u8::MAX as usize)];
+    writer.u8(items.len() as u8);
+    items.iter().for_each(|item| write(writer, item));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::character::record::{Item, SLOT_WEAPON};
+    use crate::character::store::tests::character;
+    use crate::textdata::object;
+
+    use super::*;
+
+    fn data() -> CharacterData {
+        CharacterData {
+            server_time: 0x01020304,
+            ref_obj_id: 1907,
+            scale: 0x44,
+            level: 5,
+            max_level: 6,
+            exp: 300,
+            gold: 1000,
+            skill_points: 10,
+            stat_points: 3,
+            hp: 250,
+            mp: 220,
+            inventory: vec![
+                InventoryItem {
+                    slot: SLOT_WEAPON,
+                    ref_item_id: 3633,
+                    data: ItemData::Equipment {
+                        plus: 1,
+                        variance: 0,
+                        durability: 30,
+                        magic_options: vec![(1, 2)],
+                        sockets: Vec::new(),
+                        advanced_elixirs: Vec::new(),
+                    },
+                },
+                InventoryItem { slot: 13, ref_item_id: 62, data: ItemData::Stack { quantity: 5 } },
+            ],
+            masteries: vec![Mastery { id: 257, level: 1 }],
+            skills: vec![Skill { id: 1, enabled: true }],
+            completed_quests: vec![1],
+            unique_id: 7,
+            position: Position::new(RegionId(0x61A8), 10.0, 0.0, 20.0),
+            name: "Rusty".to_string(),
+            hotkeys: vec![Hotkey { slot: 0, kind: 70, data: 1 }],
+            ..CharacterData::default()
+        }
+    }
+
+    #[test]
+    fn writes_character_data() {
+        let packets = data().packets();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0], Packet::new(CHARACTER_DATA_BEGIN, Vec::new()));
+        assert_eq!(packets[2], Packet::new(CHARACTER_DATA_END, Vec::new()));
+        assert_eq!(packets[1].opcode, CHARACTER_DATA);
+        let expected: Vec<u8> = [
+            // server time, model, scale, level and max level
+            &[4, 3, 2, 1, 0x73, 0x07, 0, 0, 0x44, 5, 6][..],
+            // exp, skill exp, gold, skill points, stat points, berserk points, gathered exp
+            &[0x2C, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xE8, 3, 0, 0, 0, 0, 0, 0, 10, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0],
+            // hp, mp, auto invest exp, daily pk, total pk, pk penalty, berserk level, free pvp
+            &[250, 0, 0, 0, 220, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            // inventory: a +1 weapon with a magic option and a stack of 5
+            &[45, 2],
+            &[6, 0, 0, 0, 0, 0x31, 0x0E, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 30, 0, 0, 0, 1, 1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 2, 0],
+            &[13, 0, 0, 0, 0, 62, 0, 0, 0, 5, 0],
+            // avatar inventory
+            &[5, 0],
+            // masteries and skills
+            &[0, 1, 1, 1, 0, 0, 1, 0],
+            &[0, 1, 1, 0, 0, 0, 1, 0],
+            // completed and active quests, collection book
+            &[1, 0, 1, 0, 0, 0, 0],
+            &[0, 0, 0, 0, 0],
+            // unique id, position and angle
+            &[7, 0, 0, 0, 0xA8, 0x61, 0, 0, 0x20, 0x41, 0, 0, 0, 0, 0, 0, 0xA0, 0x41, 0, 0],
+            // standing, running, angle
+            &[0, 1, 0, 0, 0],
+            // alive, motion state, status, speeds and buffs
+            &[1, 0, 0, 0, 0, 0, 0x80, 0x41, 0, 0, 0x48, 0x42, 0, 0, 0xC8, 0x42, 0],
+            // name, job name, job type and level, job exp, contribution and reward
+            &[5, 0, b'R', b'u', b's', b't', b'y', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            // pvp state, transport, in combat, pvp flag, guide flag, jid, gm
+            &[0, 0, 0, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            // hotkeys
+            &[7, 1, 0, 70, 1, 0, 0, 0],
+            // auto potion, blocked whispers, trailer
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ].concat();
+        assert_eq!(packets[1].data, expected);
+    }
+
+    #[test]
+    fn writes_optional_fields() {
+        let mut data = CharacterData {
+            destination: Some(Position::new(RegionId(0x61A8), 100.0, 0.0, 200.0)),
+            transport: Some(9),
+            buffs: vec![Buff { ref_skill_id: 3, duration: 4, creator: Some(true) }, Buff { ref_skill_id: 5, duration: 6, creator: None }],
+            active_quests: vec![ActiveQuest {
+                ref_quest_id: 1,
+                achievements: 0,
+                requires_share_party: false,
+                kind: QUEST_TIME_LIMITED,
+                remaining_time: 60,
+                status: 1,
+                objectives: vec![QuestObjective { id: 1, completed: false, name: "a".to_string(), tasks: vec![3] }],
+                npcs: vec![],
+            }],
+            ..CharacterData::default()
+        };
+        let packets = data.packets();
+        let mut reader = packets[1].reader();
+        // stats, empty inventories, masteries and skills, completed quests
+        reader.bytes(60 + 2 + 2 + 2 + 2 + 2, "header").unwrap();
+        assert_eq!(reader.u8().unwrap(), 1);
+        assert_eq!(reader.bytes(23, "quest").unwrap(), &[
+            1, 0, 0, 0, 0, 0, QUEST_TIME_LIMITED, 60, 0, 0, 0, 1, 1, 1, 0, 1, 0, b'a', 1, 3, 0, 0, 0,
+        ]);
+        reader.bytes(5 + 4 + 14 + 2 + 2, "position").unwrap();
+        assert_eq!(reader.bytes(2 + 6, "destination").unwrap(), &[0xA8, 0x61, 100, 0, 0, 0, 200, 0]);
+        reader.bytes(4 + 12, "state").unwrap();
+        assert_eq!(reader.u8().unwrap(), 2);
+        assert_eq!(reader.bytes(9 + 8, "buffs").unwrap(), &[3, 0, 0, 0, 4, 0, 0, 0, 1, 5, 0, 0, 0, 6, 0, 0, 0]);
+        reader.bytes(4 + 14, "job").unwrap();
+        assert_eq!(reader.bytes(7, "transport").unwrap(), &[0, 1, 0, 9, 0, 0, 0]);
+
+        data.completed_quests = (0..9000).collect();
+        let packets = data.packets();
+        let sizes: Vec<usize> = packets.iter().map(|packet| packet.data.len()).collect();
+        assert_eq!(sizes.len(), 4);
+        assert_eq!(sizes[1], MAX_DATA_SIZE);
+    }
+
+    #[test]
+    fn cuts_off_oversized_lists() {
+        let mut data = data();
+        data.blocked_whispers = (0..300).map(|i| format!("Player{:03}", i)).collect();
+        let oversized = data.packets();
+        data.blocked_whispers.truncate(u8::MAX as usize);
+        assert_eq!(oversized, data.packets());
+        let written = &oversized[1].data;
+        let whispers = written.len() - 5 - 255 * 11;
+        assert_eq!(written[whispers - 1], 255);
+    }
+
+    #[test]
+    fn takes_stored_characters() {
+        let item = object::tests::item(&[(1, "3633"), (2, "ITEM_CH_SWORD_01_A_DEF"), (9, "3"), (10, "1"), (11, "6"), (12, "2")]);
+        let registry = Registry::new(Vec::new(), vec![item], Vec::new()).unwrap();
+        let character = Character {
+            level: 3,
+            exp: 10,
+            gold: 20,
+            skill_points: 30,
+            stat_points: 6,
+            hp: 240,
+            mp: 230,
+            items: vec![Item { slot: SLOT_WEAPON, ref_item_id: 3633, plus: 2, durability: 32, quantity: 1, ..Item::default() }, Item { slot: 13, ref_item_id: 62, plus: 0, quantity: 1, ..Item::default() }],
+            masteries: vec![Mastery { id: 257, level: 3 }],
+            skills: vec![1],
+            ..character(1, "Rusty")
+        };
+        let data = CharacterData::new(&character, 7, &registry);
+        assert_eq!((data.ref_obj_id, data.level, data.gold, data.hp), (1907, 3, 20, 240));
+        assert_eq!(data.unique_id, 7);
+        assert_eq!(data.position, character.position);
+        assert_eq!(data.inventory_size, DEFAULT_INVENTORY_SIZE);
+        assert_eq!(data.masteries, character.masteries);
+        assert_eq!(data.skills, vec![Skill { id: 1, enabled: true }]);
+        assert_eq!(data.inventory, vec![InventoryItem {
+            slot: SLOT_WEAPON,
+            ref_item_id: 3633,
+            data: ItemData::Equipment {
+                plus: 2,
+                variance: 0,
+                durability: 32,
+                magic_options: Vec::new(),
+                sockets: Vec::new(),
+                advanced_elixirs: Vec::new(),
+            },
+        }]);
+    }
+}
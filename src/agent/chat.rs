@@ -0,0 +1,309 @@
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::agent::errors::Error;
+use crate::agent::handler::Agent;
+use crate::agent::inventory::quantity_update;
+use crate::agent::opcodes::{CLIENT_CHAT, SERVER_CHAT, SERVER_CHAT_MESSAGE};
+use crate::character::inventory::{Container, Inventory};
+use crate::chat::errors::Error as ChatError;
+use crate::net::errors::Error::InvalidPacket;
+use crate::net::packet::{Packet, PacketWriter};
+
+const CHAT_ALL: u8 = 1;
+const CHAT_PRIVATE: u8 = 2;
+const CHAT_ALL_GM: u8 = 3;
+const CHAT_PARTY: u8 = 4;
+const CHAT_GUILD: u8 = 5;
+const CHAT_GLOBAL: u8 = 6;
+const CHAT_NOTICE: u8 = 7;
+
+const RESULT_SUCCESS: u8 = 1;
+const RESULT_FAILURE: u8 = 2;
+
+/// Error codes of undelivered messages, which the client shows as messages.
+const ERROR_FAILED: u16 = 0x0001;
+const ERROR_NOT_FOUND: u16 = 0x0003;
+const ERROR_RATE_LIMITED: u16 = 0x0004;
+
+/// Code name prefix of the items consumed by global chat messages.
+const GLOBAL_CHAT_ITEM: &str = "ITEM_MALL_GLOBAL_CHATTING";
+
+/// The channel of a chat message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Channel {
+    /// Players nearby.
+    All,
+    /// The character with given name.
+    Private(String),
+    /// Players nearby, sent by a game master.
+    AllGm,
+    Party,
+    Guild,
+    /// Everyone in the game, consuming a global chatting item of given inventory slot.
+    Global { slot: u8 },
+    /// Everyone in the game without a sender, reserved to game masters.
+    Notice,
+}
+
+impl Channel {
+    fn kind(&self) -> u8 {
+        match self {
+            Channel::All => CHAT_ALL,
+            Channel::Private(_) => CHAT_PRIVATE,
+            Channel::AllGm => CHAT_ALL_GM,
+            Channel::Party => CHAT_PARTY,
+            Channel::Guild => CHAT_GUILD,
+            Channel::Global { .. } => CHAT_GLOBAL,
+            Channel::Notice => CHAT_NOTICE,
+        }
+    }
+}
+
+/// A chat message sent by a client ([CLIENT_CHAT]). The index is chosen by the client to match
+/// the result to the message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub channel: Channel,
+    pub index: u8,
+    pub message: String,
+}
+
+/// Handles a chat message of a session in the game: it's delivered to the recipients of its
+/// channel and the sender gets the result.
+pub async fn handle(agent: &Agent, session: Uuid, packet: &Packet) -> Result<(), Error> {
+    let request = parse_request(packet)?;
+    let name = match agent.joined.lock().unwrap().get(&session) {
+        Some(character) => character.name.clone(),
+        None => {
+            debug!("session {} tried to chat without a character", session);
+            return Ok(());
+        }
+    };
+    let mut writer = PacketWriter::new(SERVER_CHAT);
+    match deliver(agent, session, &name, &request, Instant::now()) {
+        Ok((recipients, message, update)) => {
+            agent.sessions.broadcast(recipients, &message);
+            if let Some(update) = update {
+                agent.sessions.send(session, update);
+            }
+            writer.u8(RESULT_SUCCESS);
+        }
+        Err(err) => {
+            debug!("chat message of character {} failed: {}", name, err);
+            writer.u8(RESULT_FAILURE).u16(error_code(&err));
+        }
+    }
+    writer.u8(request.channel.kind()).u8(request.index);
+    agent.sessions.send(session, writer.build());
+    Ok(())
+}
+
+/// Parses a chat message ([CLIENT_CHAT]).
+pub fn parse_request(packet: &Packet) -> Result<Request, Error> {
+    let mut reader = packet.reader();
+    let kind = reader.u8()?;
+    let index = reader.u8()?;
+    let channel = match kind {
+        CHAT_ALL => Channel::All,
+        CHAT_PRIVATE => Channel::Private(reader.string()?),
+        CHAT_ALL_GM => Channel::AllGm,
+        CHAT_PARTY => Channel::Party,
+        CHAT_GUILD => Channel::Guild,
+        CHAT_GLOBAL => Channel::Global { slot: reader.u8()? },
+        CHAT_NOTICE => Channel::Notice,
+        _ => return Err(InvalidPacket { opcode: CLIENT_CHAT, message: "unknown chat type" }.into()),
+    };
+    Ok(Request { channel, index, message: reader.utf16_string()? })
+}
+
+/// Checks a message of a character and builds it for its recipients ([SERVER_CHAT_MESSAGE]).
+/// Global messages consume their item only once they're deliverable, which also returns the
+/// update of the item for the sender.
+fn deliver(agent: &Agent, session: Uuid, name: &str, request: &Request, now: Instant) -> Result<(Vec<Uuid>, Packet, Option<Packet>), ChatError> {
+    if request.message.is_empty() {
+        return Err(ChatError::EmptyMessage);
+    }
+    let game_master = agent.chat.is_game_master(&session);
+    if matches!(request.channel, Channel::AllGm | Channel::Notice) && !game_master {
+        return Err(ChatError::NotAllowed);
+    }
+    let message = if game_master {
+        request.message.clone()
+    } else {
+        agent.chat.filter().filter(&session, &request.message, now)?
+    };
+
+    let mut joined = agent.joined.lock().unwrap();
+    let others = |sessions: &[Uuid]| -> Vec<Uuid> {
+        sessions.iter().filter(|other| **other != session).copied().collect()
+    };
+    let mut update = None;
+    let mut writer = PacketWriter::new(SERVER_CHAT_MESSAGE);
+    writer.u8(request.channel.kind());
+    let recipients = match &request.channel {
+        Channel::All | Channel::AllGm => {
+            let world = agent.world.lock().unwrap();
+            let player = world.player(&session).ok_or_else(|| ChatError::NotFound(name.to_string()))?;
+            writer.u32(player.unique_id);
+            others(&world.nearby(player.position(now).region))
+        }
+        Channel::Private(receiver) => {
+            let recipient = joined.iter()
+                .find(|(_, character)| character.name.eq_ignore_ascii_case(receiver))
+                .map(|(recipient, _)| *recipient)
+                .ok_or_else(|| ChatError::NotFound(receiver.clone()))?;
+            writer.string(name);
+            vec![recipient]
+        }
+        Channel::Party => {
+            let parties = agent.chat.parties().lock().unwrap();
+            let party = parties.group(&session).ok_or(ChatError::NoParty)?;
+            writer.string(name);
+            others(parties.members(party))
+        }
+        Channel::Guild => {
+            let guilds = agent.chat.guilds().lock().unwrap();
+            let guild = guilds.group(&session).ok_or(ChatError::NoGuild)?;
+            writer.string(name);
+            others(guilds.members(guild))
+        }
+        Channel::Global { slot } => {
+            let registry = agent.characters.registry();
+            let character = joined.get_mut(&session).ok_or_else(|| ChatError::NotFound(name.to_string()))?;
+            let mut inventory = Inventory::new(character, registry);
+            let is_chat_item = inventory.get(Container::Inventory, *slot)
+                .and_then(|item| registry.items.get(item.ref_item_id))
+                .is_some_and(|item| item.common.code_name.starts_with(GLOBAL_CHAT_ITEM));
+            if !is_chat_item {
+                return Err(ChatError::NoChatItem(*slot));
+            }
+            inventory.remove(*slot, 1).map_err(|_| ChatError::NoChatItem(*slot))?;
+            let left = inventory.get(Container::Inventory, *slot).map_or(0, |item| item.quantity);
+            update = Some(quantity_update(*slot, left));
+            writer.string(name);
+            others(&joined.keys().copied().collect::<Vec<_>>())
+        }
+        Channel::Notice => others(&joined.keys().copied().collect::<Vec<_>>()),
+    };
+    writer.utf16_string(&message);
+    Ok((recipients, writer.build(), update))
+}
+
+fn error_code(err: &ChatError) -> u16 {
+    match err {
+        ChatError::NotFound(_) => ERROR_NOT_FOUND,
+        ChatError::RateLimited => ERROR_RATE_LIMITED,
+        _ => ERROR_FAILED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc::Receiver;
+
+    use crate::agent::handler::tests::{agent, connect};
+    use crate::agent::opcodes::SERVER_ITEM_UPDATE;
+    use crate::character::record::Item;
+    use crate::character::store::MemoryStore;
+    use crate::character::store::tests::character;
+    use crate::chat::filter::WordFilter;
+    use crate::chat::state::Chat;
+    use crate::world::position::{Position, RegionId};
+
+    use super::*;
+
+    fn chat(kind: u8, index: u8, prefix: &[u8], message: &str) -> Packet {
+        PacketWriter::new(CLIENT_CHAT).u8(kind).u8(index).bytes(prefix).utf16_string(message).build()
+    }
+
+    /// Returns the data of the chat packets a session received.
+    fn received(receiver: &mut Receiver<Packet>, opcode: u16) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        while let Ok(packet) = receiver.try_recv() {
+            if packet.opcode == opcode {
+                packets.push(packet.data);
+            }
+        }
+        packets
+    }
+
+    #[test]
+    fn parses_requests() {
+        let request = parse_request(&chat(CHAT_PRIVATE, 3, &[4, 0, b'R', b'o', b'a', b'd'], "hi")).unwrap();
+        assert_eq!(request, Request { channel: Channel::Private("Road".to_string()), index: 3, message: "hi".to_string() });
+        let request = parse_request(&chat(CHAT_GLOBAL, 4, &[13], "hi")).unwrap();
+        assert_eq!(request.channel, Channel::Global { slot: 13 });
+        assert!(parse_request(&chat(0x42, 0, &[], "hi")).is_err());
+    }
+
+    #[tokio::test]
+    async fn delivers_messages() {
+        let agent = agent(Arc::new(MemoryStore::new()), Chat::new(Box::new(WordFilter::parse("darn"))));
+        let mut receivers = Vec::new();
+        let mut players = Vec::new();
+        for (name, region) in [("Rusty", 0x61A8), ("Road", 0x61A9), ("Far", 0x6000)] {
+            let session = Uuid::new_v4();
+            let receiver = connect(&agent, session, 16);
+            let mut character = character(1, name);
+            character.position = Position::new(RegionId(region), 100.0, 0.0, 100.0);
+            character.items.push(Item { slot: 13, ref_item_id: 7400, quantity: 2, ..Item::default() });
+            agent.join(session, character).await;
+            receivers.push(receiver);
+            players.push(session);
+        }
+        let (rusty, far) = (players[0], players[2]);
+        let send = |packet: Packet| agent.handle(rusty, packet);
+
+        send(chat(CHAT_ALL, 1, &[], "darn hi")).await.unwrap();
+        assert_eq!(received(&mut receivers[0], SERVER_CHAT), vec![vec![RESULT_SUCCESS, CHAT_ALL, 1]]);
+        assert_eq!(received(&mut receivers[1], SERVER_CHAT_MESSAGE), vec![
+            vec![CHAT_ALL, 1, 0, 0, 0, 7, 0, b'*', 0, b'*', 0, b'*', 0, b'*', 0, b' ', 0, b'h', 0, b'i', 0],
+        ]);
+        assert!(received(&mut receivers[2], SERVER_CHAT_MESSAGE).is_empty());
+
+        send(chat(CHAT_PRIVATE, 2, &[3, 0, b'f', b'a', b'r'], "hi")).await.unwrap();
+        assert_eq!(received(&mut receivers[2], SERVER_CHAT_MESSAGE), vec![
+            vec![CHAT_PRIVATE, 5, 0, b'R', b'u', b's', b't', b'y', 2, 0, b'h', 0, b'i', 0],
+        ]);
+        send(chat(CHAT_PRIVATE, 3, &[3, 0, b'N', b'o', b'b'], "hi")).await.unwrap();
+        assert_eq!(received(&mut receivers[0], SERVER_CHAT), vec![
+            vec![RESULT_SUCCESS, CHAT_PRIVATE, 2], vec![RESULT_FAILURE, 3, 0, CHAT_PRIVATE, 3],
+        ]);
+
+        send(chat(CHAT_PARTY, 4, &[], "hi")).await.unwrap();
+        agent.chat().parties().lock().unwrap().join(1, rusty);
+        agent.chat().parties().lock().unwrap().join(1, far);
+        send(chat(CHAT_PARTY, 5, &[], "hi")).await.unwrap();
+        assert_eq!(received(&mut receivers[2], SERVER_CHAT_MESSAGE).len(), 1);
+        assert!(received(&mut receivers[1], SERVER_CHAT_MESSAGE).is_empty());
+        assert_eq!(received(&mut receivers[0], SERVER_CHAT), vec![
+            vec![RESULT_FAILURE, 1, 0, CHAT_PARTY, 4], vec![RESULT_SUCCESS, CHAT_PARTY, 5],
+        ]);
+
+        send(chat(CHAT_GLOBAL, 6, &[13], "hi")).await.unwrap();
+        send(chat(CHAT_GLOBAL, 7, &[14], "hi")).await.unwrap();
+        assert_eq!(received(&mut receivers[1], SERVER_CHAT_MESSAGE).len(), 1);
+        assert_eq!(received(&mut receivers[2], SERVER_CHAT_MESSAGE).len(), 1);
+        assert_eq!(agent.character(&rusty).unwrap().items[1].quantity, 1);
+        let sent: Vec<Packet> = std::iter::from_fn(|| receivers[0].try_recv().ok()).collect();
+        assert_eq!(sent, vec![
+            quantity_update(13, 1),
+            Packet::new(SERVER_CHAT, vec![RESULT_SUCCESS, CHAT_GLOBAL, 6]),
+            Packet::new(SERVER_CHAT, vec![RESULT_FAILURE, 1, 0, CHAT_GLOBAL, 7]),
+        ]);
+        assert_eq!(sent[0].opcode, SERVER_ITEM_UPDATE);
+        assert_eq!(sent[0].data, vec![13, 0x08, 1, 0]);
+
+        send(chat(CHAT_NOTICE, 8, &[], "hi")).await.unwrap();
+        agent.chat().set_game_master(rusty, true);
+        send(chat(CHAT_NOTICE, 9, &[], "darn")).await.unwrap();
+        assert_eq!(received(&mut receivers[1], SERVER_CHAT_MESSAGE), vec![vec![CHAT_NOTICE, 4, 0, b'd', 0, b'a', 0, b'r', 0, b'n', 0]]);
+        assert_eq!(received(&mut receivers[0], SERVER_CHAT), vec![
+            vec![RESULT_FAILURE, 1, 0, CHAT_NOTICE, 8], vec![RESULT_SUCCESS, CHAT_NOTICE, 9],
+        ]);
+    }
+}
@@ -0,0 +1,23 @@
+use std::fmt::{Display, Formatter};
+
+use crate::net;
+
+#[derive(std::fmt::Debug)]
+pub enum Error {
+    Packet(net::errors::Error),
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Packet(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<net::errors::Error> for Error {
+    fn from(err: net::errors::Error) -> Self {
+        Error::Packet(err)
+    }
+}
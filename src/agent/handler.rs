@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::account::{Authenticator, NoLogins};
+use crate::agent::errors::Error;
+use crate::agent::{chat, inventory, join, login, movement, selection, spawn};
+use crate::agent::opcodes::{CLIENT_AGENT_AUTH, CLIENT_CHARACTER_JOIN, CLIENT_CHARACTER_SELECTION, CLIENT_CHAT, CLIENT_ITEM_MOVE, CLIENT_MOVEMENT};
+use crate::character::record::Character;
+use crate::character::selection::CharacterSelection;
+use crate::chat::state::Chat;
+use crate::net::packet::Packet;
+use crate::net::server::Sessions;
+use crate::world::state::World;
+
+/// Interval in which the visibility of moving entities is updated (see [Agent::update_sight]).
+pub const SIGHT_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+/// Interval in which the characters in the game are saved (see [Agent::autosave]).
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Handles the packets of the agent server, which runs the game world.
+#[derive(Clone)]
+pub struct Agent {
+    pub(crate) sessions: Sessions,
+    pub(crate) world: Arc<Mutex<World>>,
+    pub(crate) characters: Arc<CharacterSelection>,
+    pub(crate) chat: Arc<Chat>,
+    pub(crate) authenticator: Arc<dyn Authenticator>,
+    /// Accounts of the logged in sessions.
+    accounts: Arc<Mutex<HashMap<Uuid, u32>>>,
+    /// Stored characters of the sessions in the game, whose state is merged from the world
+    /// when saving. Has to be locked before the world when both are needed.
+    pub(crate) joined: Arc<Mutex<HashMap<Uuid, Character>>>,
+}
+
+impl Agent {
+    pub fn new(sessions: Sessions, world: World, characters: CharacterSelection, chat: Chat) -> Agent {
+        Agent {
+            sessions,
+            world: Arc::new(Mutex::new(world)),
+            characters: Arc::new(characters),
+            chat: Arc::new(chat),
+            authenticator: Arc::new(NoLogins),
+            accounts: Arc::default(),
+            joined: Arc::default(),
+        }
+    }
+
+    /// Replaces the authenticator deciding which account a session logs in as. By default, all
+    /// logins are rejected ([NoLogins]).
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Agent {
+        self.authenticator = Arc::new(authenticator);
+        self
+    }
+
+    /// Binds a session to the account it authenticated as. Until then, its character selection
+    /// requests are ignored.
+    pub fn login(&self, session: Uuid, account: u32) {
+        self.accounts.lock().unwrap().insert(session, account);
+    }
+
+    /// Returns the account a session is logged in as.
+    pub fn account(&self, session: &Uuid) -> Option<u32> {
+        self.accounts.lock().unwrap().get(session).copied()
+    }
+
+    /// Spawns the stored character a session joins the game with. Returns its unique ID.
+    pub async fn join(&self, session: Uuid, character: Character) -> u32 {
+        let (unique_id, events) = {
+            let mut world = self.world.lock().unwrap();
+            let events = world.spawn(session, &character.name, character.ref_obj_id, character.position);
+            (world.player(&session).map(|player| player.unique_id).unwrap_or_default(), events)
+        };
+        self.joined.lock().unwrap().insert(session, character);
+        spawn::send_sight_events(self, events, Instant::now()).await;
+        unique_id
+    }
+
+    /// Returns the character of a session in the game, updated with its current position.
+    pub fn character(&self, session: &Uuid) -> Option<Character> {
+        let mut character = self.joined.lock().unwrap().get(session)?.clone();
+        if let Some(player) = self.world.lock().unwrap().player(session) {
+            character.position = player.position(Instant::now());
+        }
+        Some(character)
+    }
+
+    /// Saves the characters of all sessions in the game. Has to be called every
+    /// [AUTOSAVE_INTERVAL].
+    pub async fn autosave(&self) {
+        let sessions: Vec<Uuid> = self.joined.lock().unwrap().keys().copied().collect();
+        let characters: Vec<Character> = sessions.iter().filter_map(|session| self.character(session)).collect();
+        if !characters.is_empty() {
+            debug!("saving {} characters", characters.len());
+            self.save(characters).await;
+        }
+    }
+
+    /// Saves characters on a blocking thread, since the store may do I/O. Failures are logged.
+    async fn save(&self, characters: Vec<Character>) {
+        let store = self.characters.store().clone();
+        let saved = tokio::task::spawn_blocking(move || {
+            for character in characters {
+                if let Err(err) = store.save(&character) {
+                    error!("failed to save character {}: {}", character.name, err);
+                }
+            }
+        }).await;
+        if let Err(err) = saved {
+            error!("saving characters was cancelled: {}", err);
+        }
+    }
+
+    /// Returns the shared world state.
+    pub fn world(&self) -> &Arc<Mutex<World>> {
+        &self.world
+    }
+
+    /// Returns the shared chat state, e.g. to add sessions to parties or guilds.
+    pub fn chat(&self) -> &Arc<Chat> {
+        &self.chat
+    }
+
+    /// Handles a packet received from given session.
+    pub async fn handle(&self, session: Uuid, packet: Packet) -> Result<(), Error> {
+        match packet.opcode {
+            CLIENT_AGENT_AUTH => login::handle(self, session, &packet).await,
+            CLIENT_CHARACTER_SELECTION => selection::handle(self, session, &packet).await,
+            CLIENT_CHARACTER_JOIN => join::handle(self, session, &packet).await,
+            CLIENT_MOVEMENT => movement::handle(self, session, &packet).await,
+            CLIENT_ITEM_MOVE => inventory::handle(self, session, &packet).await,
+            CLIENT_CHAT => chat::handle(self, session, &packet).await,
+            opcode => {
+                debug!("session {} sent unhandled packet {:04X}", session, opcode);
+                Ok(())
+            }
+        }
+    }
+
+    /// Logs out a closed session, removes its character from the world and saves it.
+    pub async fn disconnected(&self, session: Uuid) {
+        self.accounts.lock().unwrap().remove(&session);
+        self.chat.disconnected(&session);
+        let character = self.character(&session);
+        self.joined.lock().unwrap().remove(&session);
+        let despawned = self.world.lock().unwrap().despawn(&session);
+        if let Some((_, events)) = despawned {
+            spawn::send_sight_events(self, events, Instant::now()).await;
+        }
+        if let Some(character) = character {
+            self.save(vec![character]).await;
+        }
+    }
+
+    /// Sends spawn and despawn packets for the entities which came into or went out of sight
+    /// since the last update. Has to be called every [SIGHT_UPDATE_INTERVAL].
+    pub async fn update_sight(&self) {
+        let now = Instant::now();
+        let events = self.world.lock().unwrap().update_sight(now);
+        spawn::send_sight_events(self, events, now).await;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use tokio::sync::mpsc::{channel, Receiver};
+
+    use crate::character::inventory::tests::registry;
+    use crate::character::names::NameRules;
+    use crate::character::store::{CharacterStore, MemoryStore};
+    use crate::character::store::tests::character;
+    use crate::world::movement::Movement;
+    use crate::world::navigation::NavMap;
+    use crate::world::position::{Position, RegionId};
+
+    use super::*;
+
+    /// Creates an agent with an empty world and the reference data of
+    /// [registry](crate::character::inventory::tests::registry), which keeps its characters in
+    /// given store.
+    pub(crate) fn agent(store: Arc<MemoryStore>, chat: Chat) -> Agent {
+        let characters = CharacterSelection::new(store, Arc::new(registry()), NameRules::default());
+        Agent::new(Sessions::default(), World::new(NavMap::new()), characters, chat)
+    }
+
+    /// Connects a session to the agent. Returns the receiver of the packets sent to it.
+    pub(crate) fn connect(agent: &Agent, session: Uuid, capacity: usize) -> Receiver<Packet> {
+        let (disconnect, _) = channel(1);
+        let (sender, receiver) = channel(capacity);
+        agent.sessions.insert(session, (disconnect, sender));
+        receiver
+    }
+
+    #[tokio::test]
+    async fn saves_characters_in_the_game() {
+        let store = Arc::new(MemoryStore::new());
+        let agent = agent(store.clone(), Chat::default());
+        let session = Uuid::new_v4();
+        let created = store.create(character(1, "Rusty")).unwrap();
+        assert_eq!(agent.join(session, created.clone()).await, 1);
+
+        let moved = |x: f32| {
+            let position = Position::new(RegionId(0x61A8), x, 0.0, 1330.0);
+            agent.world().lock().unwrap().player_mut(&session).unwrap().movement = Movement::standing(position);
+            position
+        };
+        let position = moved(100.0);
+        agent.autosave().await;
+        assert_eq!(store.find("Rusty").unwrap().unwrap().position, position);
+
+        let position = moved(200.0);
+        agent.disconnected(session).await;
+        assert_eq!(store.find("Rusty").unwrap().unwrap().position, position);
+        assert_eq!(agent.character(&session), None);
+        assert!(agent.world().lock().unwrap().player(&session).is_none());
+    }
+}
@@ -0,0 +1,276 @@
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::agent::character_data::{write_item, InventoryItem};
+use crate::agent::errors::Error;
+use crate::agent::handler::Agent;
+use crate::agent::opcodes::{CLIENT_ITEM_MOVE, SERVER_ITEM_MOVE, SERVER_ITEM_UPDATE};
+use crate::agent::spawn;
+use crate::character::errors::Error as CharacterError;
+use crate::character::inventory::{Container, Inventory};
+use crate::character::record::Character;
+use crate::net::errors::Error::InvalidPacket;
+use crate::net::packet::{Packet, PacketWriter};
+use crate::textdata::registry::Registry;
+use crate::world::npc::Npc;
+use crate::world::position::Position;
+use crate::world::state::{SightEvent, World, INTERACTION_RANGE};
+
+const MOVE_INVENTORY: u8 = 0x00;
+const MOVE_STORAGE: u8 = 0x01;
+const MOVE_INVENTORY_TO_STORAGE: u8 = 0x02;
+const MOVE_STORAGE_TO_INVENTORY: u8 = 0x03;
+const MOVE_PICK: u8 = 0x06;
+const MOVE_DROP: u8 = 0x07;
+const MOVE_BUY: u8 = 0x08;
+const MOVE_SELL: u8 = 0x09;
+const MOVE_INVENTORY_TO_AVATAR: u8 = 0x23;
+const MOVE_AVATAR_TO_INVENTORY: u8 = 0x24;
+
+/// Kind of an item update ([SERVER_ITEM_UPDATE]) which sets the quantity of a stack.
+const UPDATE_QUANTITY: u8 = 0x08;
+
+const RESULT_SUCCESS: u8 = 1;
+const RESULT_FAILURE: u8 = 2;
+
+/// Error codes of failed moves, which the client shows as messages.
+const ERROR_FAILED: u16 = 0x1800;
+const ERROR_NOT_ENOUGH_GOLD: u16 = 0x1807;
+const ERROR_CANNOT_EQUIP: u16 = 0x1816;
+const ERROR_INVENTORY_FULL: u16 = 0x1818;
+const ERROR_OUT_OF_REACH: u16 = 0x1819;
+
+/// An item move of a character ([CLIENT_ITEM_MOVE]). NPCs and items on the ground are referred
+/// to by their unique ID, a quantity of `0` moves the whole stack.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Moves, splits or merges items within the inventory, including equipping and unequipping.
+    Inventory { from: u8, to: u8, quantity: u16 },
+    Storage { from: u8, to: u8, quantity: u16, npc: u32 },
+    InventoryToStorage { from: u8, to: u8, npc: u32 },
+    StorageToInventory { from: u8, to: u8, npc: u32 },
+    Pick { item: u32 },
+    Drop { slot: u8 },
+    Buy { tab: u8, slot: u8, quantity: u16, npc: u32 },
+    Sell { slot: u8, quantity: u16, npc: u32 },
+    InventoryToAvatar { from: u8, to: u8 },
+    AvatarToInventory { from: u8, to: u8 },
+}
+
+/// Handles an item move of a session in the game and sends the result.
+pub async fn handle(agent: &Agent, session: Uuid, packet: &Packet) -> Result<(), Error> {
+    let request = parse_request(packet)?;
+    let (response, events) = {
+        let mut joined = agent.joined.lock().unwrap();
+        let mut world = agent.world.lock().unwrap();
+        let character = joined.get_mut(&session);
+        let position = world.player(&session).map(|player| player.position(Instant::now()));
+        match (character, position) {
+            (Some(character), Some(position)) => process(&mut world, agent.characters.registry(), character, position, request),
+            _ => {
+                debug!("session {} tried to move items without a character", session);
+                return Ok(());
+            }
+        }
+    };
+    agent.sessions.send(session, response);
+    spawn::send_sight_events(agent, events, Instant::now()).await;
+    Ok(())
+}
+
+/// Parses an item move ([CLIENT_ITEM_MOVE]).
+pub fn parse_request(packet: &Packet) -> Result<Request, Error> {
+    let mut reader = packet.reader();
+    match reader.u8()? {
+        MOVE_INVENTORY => Ok(Request::Inventory { from: reader.u8()?, to: reader.u8()?, quantity: reader.u16()? }),
+        MOVE_STORAGE => Ok(Request::Storage { from: reader.u8()?, to: reader.u8()?, quantity: reader.u16()?, npc: reader.u32()? }),
+        MOVE_INVENTORY_TO_STORAGE => Ok(Request::InventoryToStorage { from: reader.u8()?, to: reader.u8()?, npc: reader.u32()? }),
+        MOVE_STORAGE_TO_INVENTORY => Ok(Request::StorageToInventory { from: reader.u8()?, to: reader.u8()?, npc: reader.u32()? }),
+        MOVE_PICK => Ok(Request::Pick { item: reader.u32()? }),
+        MOVE_DROP => Ok(Request::Drop { slot: reader.u8()? }),
+        MOVE_BUY => Ok(Request::Buy { tab: reader.u8()?, slot: reader.u8()?, quantity: reader.u16()?, npc: reader.u32()? }),
+        MOVE_SELL => Ok(Request::Sell { slot: reader.u8()?, quantity: reader.u16()?, npc: reader.u32()? }),
+        MOVE_INVENTORY_TO_AVATAR => Ok(Request::InventoryToAvatar { from: reader.u8()?, to: reader.u8()? }),
+        MOVE_AVATAR_TO_INVENTORY => Ok(Request::AvatarToInventory { from: reader.u8()?, to: reader.u8()? }),
+        _ => Err(InvalidPacket { opcode: CLIENT_ITEM_MOVE, message: "unknown move type" }.into()),
+    }
+}
+
+/// Performs an item move of a character standing at given position and builds its result
+/// ([SERVER_ITEM_MOVE]), along with the sight events of items dropped or picked up. The
+/// character is left unchanged if the move fails.
+pub fn process(world: &mut World, registry: &Registry, character: &mut Character, position: Position, request: Request) -> (Packet, Vec<SightEvent>) {
+    let mut events = Vec::new();
+    let mut writer = PacketWriter::new(SERVER_ITEM_MOVE);
+    writer.u8(RESULT_SUCCESS);
+    let name = character.name.clone();
+    let mut inventory = Inventory::new(character, registry);
+    let result = match request {
+        Request::Inventory { from, to, quantity } => {
+            let quantity = moved_quantity(&inventory, Container::Inventory, from, quantity);
+            inventory.move_item((Container::Inventory, from), (Container::Inventory, to), quantity).map(|_| {
+                writer.u8(MOVE_INVENTORY).u8(from).u8(to).u16(quantity).u8(0);
+            })
+        }
+        Request::Storage { from, to, quantity, npc } => storage_npc(world, npc, &position).and_then(|_| {
+            let quantity = moved_quantity(&inventory, Container::Storage, from, quantity);
+            inventory.move_item((Container::Storage, from), (Container::Storage, to), quantity).map(|_| {
+                writer.u8(MOVE_STORAGE).u8(from).u8(to).u16(quantity);
+            })
+        }),
+        Request::InventoryToStorage { from, to, npc } => storage_npc(world, npc, &position).and_then(|_| {
+            inventory.move_item((Container::Inventory, from), (Container::Storage, to), 0).map(|_| {
+                writer.u8(MOVE_INVENTORY_TO_STORAGE).u8(from).u8(to);
+            })
+        }),
+        Request::StorageToInventory { from, to, npc } => storage_npc(world, npc, &position).and_then(|_| {
+            inventory.move_item((Container::Storage, from), (Container::Inventory, to), 0).map(|_| {
+                writer.u8(MOVE_STORAGE_TO_INVENTORY).u8(from).u8(to);
+            })
+        }),
+        Request::Pick { item } => match world.ground_item(item) {
+            Some(ground) if ground.position.in_range(&position, INTERACTION_RANGE) => {
+                inventory.add(ground.item.clone()).map(|slots| {
+                    events = world.pick_item(item).map(|(_, events)| events).unwrap_or_default();
+                    writer.u8(MOVE_PICK);
+                    let picked = inventory.get(Container::Inventory, slots[0])
+                        .and_then(|picked| InventoryItem::new(picked, registry))
+                        .expect("picked item is known");
+                    write_item(&mut writer, &picked);
+                })
+            }
+            _ => Err(CharacterError::OutOfReach(item)),
+        },
+        Request::Drop { slot } => inventory.remove(slot, 0).map(|dropped| {
+            events = world.drop_item(dropped, position).1;
+            writer.u8(MOVE_DROP).u8(slot);
+        }),
+        Request::Buy { tab, slot, quantity, npc } => shop_npc(world, npc, &position).and_then(|shop| {
+            let good = shop.good(tab, slot).ok_or(CharacterError::InvalidSlot(slot))?;
+            inventory.buy(good, quantity).map(|slots| {
+                writer.u8(MOVE_BUY).u8(tab).u8(slot).u8(slots.len() as u8);
+                slots.iter().for_each(|slot| { writer.u8(*slot); });
+                writer.u16(quantity);
+            })
+        }),
+        Request::Sell { slot, quantity, npc } => shop_npc(world, npc, &position).and_then(|_| {
+            inventory.sell(slot, quantity).map(|sold| {
+                writer.u8(MOVE_SELL).u8(slot).u16(sold.quantity).u32(npc);
+            })
+        }),
+        Request::InventoryToAvatar { from, to } => {
+            inventory.move_item((Container::Inventory, from), (Container::Avatar, to), 0).map(|_| {
+                writer.u8(MOVE_INVENTORY_TO_AVATAR).u8(from).u8(to);
+            })
+        }
+        Request::AvatarToInventory { from, to } => {
+            inventory.move_item((Container::Avatar, from), (Container::Inventory, to), 0).map(|_| {
+                writer.u8(MOVE_AVATAR_TO_INVENTORY).u8(from).u8(to);
+            })
+        }
+    };
+    match result {
+        Ok(()) => (writer.build(), events),
+        Err(err) => {
+            debug!("item move of character {} failed: {}", name, err);
+            let mut writer = PacketWriter::new(SERVER_ITEM_MOVE);
+            writer.u8(RESULT_FAILURE).u16(error_code(&err));
+            (writer.build(), Vec::new())
+        }
+    }
+}
+
+/// Builds the new quantity of an inventory slot ([SERVER_ITEM_UPDATE]). A quantity of `0`
+/// empties the slot.
+pub fn quantity_update(slot: u8, quantity: u16) -> Packet {
+    PacketWriter::new(SERVER_ITEM_UPDATE).u8(slot).u8(UPDATE_QUANTITY).u16(quantity).build()
+}
+
+fn error_code(err: &CharacterError) -> u16 {
+    match err {
+        CharacterError::NotEnoughGold => ERROR_NOT_ENOUGH_GOLD,
+        CharacterError::CannotEquip(_) => ERROR_CANNOT_EQUIP,
+        CharacterError::InventoryFull => ERROR_INVENTORY_FULL,
+        CharacterError::OutOfReach(_) => ERROR_OUT_OF_REACH,
+        _ => ERROR_FAILED,
+    }
+}
+
+/// Resolves the quantity of a move, which is the whole stack for `0`.
+fn moved_quantity(inventory: &Inventory, container: Container, slot: u8, quantity: u16) -> u16 {
+    match inventory.get(container, slot) {
+        Some(item) if quantity == 0 => item.quantity,
+        _ => quantity,
+    }
+}
+
+/// Returns the NPC with given unique ID if it's within reach.
+fn npc<'w>(world: &'w World, unique_id: u32, position: &Position) -> Option<&'w Npc> {
+    world.npc(unique_id).filter(|npc| npc.position.in_range(position, INTERACTION_RANGE))
+}
+
+fn storage_npc<'w>(world: &'w World, unique_id: u32, position: &Position) -> Result<&'w Npc, CharacterError> {
+    npc(world, unique_id, position).filter(|npc| npc.storage).ok_or(CharacterError::OutOfReach(unique_id))
+}
+
+fn shop_npc<'w>(world: &'w World, unique_id: u32, position: &Position) -> Result<&'w Npc, CharacterError> {
+    npc(world, unique_id, position).filter(|npc| npc.is_shop()).ok_or(CharacterError::OutOfReach(unique_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::character::inventory::tests::registry;
+    use crate::character::store::tests::character;
+    use crate::world::navigation::NavMap;
+    use crate::world::position::RegionId;
+
+    use super::*;
+
+    fn request(data: &[u8]) -> Request {
+        parse_request(&Packet::new(CLIENT_ITEM_MOVE, data.to_vec())).unwrap()
+    }
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(request(&[0x00, 13, 6, 0, 0]), Request::Inventory { from: 13, to: 6, quantity: 0 });
+        assert_eq!(request(&[0x06, 7, 0, 0, 0]), Request::Pick { item: 7 });
+        assert_eq!(request(&[0x08, 1, 2, 5, 0, 3, 0, 0, 0]), Request::Buy { tab: 1, slot: 2, quantity: 5, npc: 3 });
+        assert_eq!(request(&[0x24, 0, 14]), Request::AvatarToInventory { from: 0, to: 14 });
+        assert!(parse_request(&Packet::new(CLIENT_ITEM_MOVE, vec![0x42])).is_err());
+    }
+
+    #[test]
+    fn moves_items_in_the_world() {
+        let registry = registry();
+        let mut world = World::new(NavMap::new());
+        let position = Position::new(RegionId(0x61A8), 980.0, 0.0, 1330.0);
+        let mut npc = Npc::new(2000, Position::new(RegionId(0x61A8), 1000.0, 0.0, 1330.0));
+        npc.goods = vec![vec![3633, 62]];
+        npc.storage = true;
+        let (npc, _) = world.add_npc(npc);
+        let (far, _) = world.add_npc(Npc { goods: vec![vec![62]], ..Npc::new(2001, Position::new(RegionId(0x61A9), 980.0, 0.0, 1330.0)) });
+        let mut character = character(1, "Rusty");
+        character.gold = 100;
+        let mut process = |request| process(&mut world, &registry, &mut character, position, request).0.reader().bytes(3, "result").unwrap().to_vec();
+
+        assert_eq!(process(Request::Buy { tab: 0, slot: 1, quantity: 5, npc }), [1, MOVE_BUY, 0]);
+        assert_eq!(process(Request::Buy { tab: 0, slot: 0, quantity: 1, npc: far }), [2, 0x19, 0x18]);
+        assert_eq!(process(Request::Buy { tab: 0, slot: 1, quantity: 10, npc }), [2, 0x07, 0x18]);
+        assert_eq!(process(Request::Inventory { from: 13, to: 14, quantity: 2 }), [1, MOVE_INVENTORY, 13]);
+        assert_eq!(process(Request::Sell { slot: 14, quantity: 0, npc }), [1, MOVE_SELL, 14]);
+        assert_eq!(process(Request::InventoryToStorage { from: 13, to: 0, npc }), [1, MOVE_INVENTORY_TO_STORAGE, 13]);
+        assert_eq!(process(Request::StorageToInventory { from: 0, to: 20, npc }), [1, MOVE_STORAGE_TO_INVENTORY, 0]);
+        assert_eq!(process(Request::Drop { slot: 20 }), [1, MOVE_DROP, 20]);
+        let dropped = npc + 2;
+        assert_eq!(process(Request::Pick { item: dropped + 1 }), [2, 0x19, 0x18]);
+        assert_eq!(process(Request::Pick { item: dropped }), [1, MOVE_PICK, 13]);
+        assert_eq!(process(Request::Drop { slot: 20 }), [2, 0x00, 0x18]);
+
+        assert!(world.ground_item(dropped).is_none());
+        assert_eq!(character.gold, 100 - 5 * 10 + 2 * 4);
+        assert_eq!(character.items.iter().map(|item| (item.slot, item.ref_item_id, item.quantity)).collect::<Vec<_>>(), vec![
+            (6, 3633, 1), (13, 62, 3),
+        ]);
+    }
+}
@@ -0,0 +1,132 @@
+use uuid::Uuid;
+
+use crate::agent::character_data::CharacterData;
+use crate::agent::errors::Error;
+use crate::agent::handler::Agent;
+use crate::agent::opcodes::SERVER_CHARACTER_JOIN;
+use crate::character::errors::Error as CharacterError;
+use crate::character::record::Character;
+use crate::net::packet::{Packet, PacketWriter};
+
+const RESULT_SUCCESS: u8 = 1;
+const RESULT_FAILURE: u8 = 2;
+
+/// Error codes of failed joins, which the client shows as messages.
+const ERROR_FAILED: u16 = 0x401;
+const ERROR_NOT_FOUND: u16 = 0x406;
+const ERROR_ALREADY_JOINED: u16 = 0x407;
+
+/// Handles a logged in session joining the game with one of its characters. The character
+/// is spawned in the world (see [Agent::join]), and the client gets the result followed by the
+/// [CharacterData].
+pub async fn handle(agent: &Agent, session: Uuid, packet: &Packet) -> Result<(), Error> {
+    let name = parse_request(packet)?;
+    let account = match agent.account(&session) {
+        Some(account) => account,
+        None => {
+            debug!("session {} tried to join without login", session);
+            return Ok(());
+        }
+    };
+    let character = match select(agent, session, account, &name) {
+        Ok(character) => character,
+        Err(err) => {
+            match err {
+                CharacterError::Storage(_) => error!("account {} failed to join as {}: {}", account, name, err),
+                _ => debug!("account {} failed to join as {}: {}", account, name, err),
+            }
+            agent.sessions.send(session, PacketWriter::new(SERVER_CHARACTER_JOIN).u8(RESULT_FAILURE).u16(error_code(&err)).build());
+            return Ok(());
+        }
+    };
+    agent.sessions.send(session, PacketWriter::new(SERVER_CHARACTER_JOIN).u8(RESULT_SUCCESS).build());
+    let unique_id = agent.join(session, character.clone()).await;
+    for packet in CharacterData::new(&character, unique_id, agent.characters.registry()).packets() {
+        agent.sessions.send(session, packet);
+    }
+    Ok(())
+}
+
+/// Parses the name of the character a session joins with
+/// ([CLIENT_CHARACTER_JOIN](crate::agent::opcodes::CLIENT_CHARACTER_JOIN)).
+pub fn parse_request(packet: &Packet) -> Result<String, Error> {
+    Ok(packet.reader().string()?)
+}
+
+/// Loads the character a session joins with. A session joins only once, and a character
+/// can't be in the game twice.
+fn select(agent: &Agent, session: Uuid, account: u32, name: &str) -> Result<Character, CharacterError> {
+    let character = agent.characters.select(account, name)?;
+    let joined = agent.joined.lock().unwrap();
+    if joined.contains_key(&session) || joined.values().any(|other| other.id == character.id) {
+        return Err(CharacterError::AlreadyJoined(character.name));
+    }
+    Ok(character)
+}
+
+fn error_code(err: &CharacterError) -> u16 {
+    match err {
+        CharacterError::NotFound(_) | CharacterError::BeingDeleted(_) => ERROR_NOT_FOUND,
+        CharacterError::AlreadyJoined(_) => ERROR_ALREADY_JOINED,
+        _ => ERROR_FAILED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::agent::handler::tests::{agent, connect};
+    use crate::agent::opcodes::{CHARACTER_DATA, CHARACTER_DATA_BEGIN, CHARACTER_DATA_END, CLIENT_CHARACTER_JOIN};
+    use crate::character::store::{CharacterStore, MemoryStore};
+    use crate::character::store::tests::character;
+    use crate::chat::state::Chat;
+
+    use super::*;
+
+    fn join(name: &str) -> Packet {
+        PacketWriter::new(CLIENT_CHARACTER_JOIN).string(name).build()
+    }
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(parse_request(&join("Rusty")).unwrap(), "Rusty");
+        assert!(parse_request(&Packet::new(CLIENT_CHARACTER_JOIN, vec![5, 0])).is_err());
+    }
+
+    #[tokio::test]
+    async fn joins_with_characters_of_the_account() {
+        let store = Arc::new(MemoryStore::new());
+        store.create(character(1, "Rusty")).unwrap();
+        store.create(character(2, "Road")).unwrap();
+        let agent = agent(store, Chat::default());
+        let session = Uuid::new_v4();
+        let mut receiver = connect(&agent, session, 16);
+
+        agent.handle(session, join("Rusty")).await.unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        agent.login(session, 1);
+        agent.handle(session, join("Road")).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().data, vec![RESULT_FAILURE, 0x06, 0x04]);
+        assert_eq!(agent.character(&session), None);
+
+        agent.handle(session, join("rusty")).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().data, vec![RESULT_SUCCESS]);
+        assert_eq!(receiver.recv().await.unwrap().opcode, CHARACTER_DATA_BEGIN);
+        let data = receiver.recv().await.unwrap();
+        assert_eq!(data.opcode, CHARACTER_DATA);
+        let unique_id = agent.world().lock().unwrap().player(&session).unwrap().unique_id;
+        let expected = CharacterData::new(&agent.character(&session).unwrap(), unique_id, agent.characters.registry());
+        assert_eq!(data, expected.packets()[1]);
+        assert_eq!(receiver.recv().await.unwrap().opcode, CHARACTER_DATA_END);
+
+        let other = Uuid::new_v4();
+        let mut other_receiver = connect(&agent, other, 4);
+        agent.login(other, 1);
+        agent.handle(other, join("Rusty")).await.unwrap();
+        assert_eq!(other_receiver.recv().await.unwrap().data, vec![RESULT_FAILURE, 0x07, 0x04]);
+        agent.handle(session, join("Rusty")).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().data, vec![RESULT_FAILURE, 0x07, 0x04]);
+    }
+}
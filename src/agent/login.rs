@@ -0,0 +1,78 @@
+use uuid::Uuid;
+
+use crate::account::Credentials;
+use crate::agent::errors::Error;
+use crate::agent::handler::Agent;
+use crate::agent::opcodes::SERVER_AGENT_AUTH;
+use crate::net::packet::{Packet, PacketWriter};
+
+const RESULT_SUCCESS: u8 = 1;
+const RESULT_FAILURE: u8 = 2;
+
+/// Error code of rejected logins, which the client shows as a message.
+const ERROR_INVALID_LOGIN: u8 = 0x02;
+
+/// Handles the login of a session: valid credentials bind it to their account (see
+/// [Agent::login]), and the client gets the result.
+pub async fn handle(agent: &Agent, session: Uuid, packet: &Packet) -> Result<(), Error> {
+    let credentials = parse_request(packet)?;
+    let mut writer = PacketWriter::new(SERVER_AGENT_AUTH);
+    match agent.authenticator.authenticate(&credentials) {
+        Some(account) => {
+            agent.login(session, account);
+            writer.u8(RESULT_SUCCESS);
+        }
+        None => {
+            debug!("session {} failed to log in as {}", session, credentials.username);
+            writer.u8(RESULT_FAILURE).u8(ERROR_INVALID_LOGIN);
+        }
+    }
+    agent.sessions.send(session, writer.build());
+    Ok(())
+}
+
+/// Parses the login of a session ([CLIENT_AGENT_AUTH](crate::agent::opcodes::CLIENT_AGENT_AUTH)).
+/// The locale and MAC address following the credentials are ignored.
+pub fn parse_request(packet: &Packet) -> Result<Credentials, Error> {
+    let mut reader = packet.reader();
+    Ok(Credentials { token: reader.u32()?, username: reader.string()?, password: reader.string()? })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::account::TrustedLogins;
+    use crate::agent::handler::tests::{agent, connect};
+    use crate::agent::opcodes::CLIENT_AGENT_AUTH;
+    use crate::character::store::MemoryStore;
+    use crate::chat::state::Chat;
+
+    use super::*;
+
+    fn login(username: &str) -> Packet {
+        PacketWriter::new(CLIENT_AGENT_AUTH).u32(7).string(username).string("secret").u8(22).bytes(&[0; 6]).build()
+    }
+
+    #[test]
+    fn parses_requests() {
+        let credentials = parse_request(&login("Rusty")).unwrap();
+        assert_eq!(credentials, Credentials { token: 7, username: "Rusty".to_string(), password: "secret".to_string() });
+        assert!(parse_request(&Packet::new(CLIENT_AGENT_AUTH, vec![7, 0, 0, 0, 5, 0])).is_err());
+    }
+
+    #[tokio::test]
+    async fn logs_in_sessions() {
+        let agent = agent(Arc::new(MemoryStore::new()), Chat::default());
+        let session = Uuid::new_v4();
+        let mut receiver = connect(&agent, session, 4);
+        agent.handle(session, login("Rusty")).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().data, vec![RESULT_FAILURE, ERROR_INVALID_LOGIN]);
+        assert_eq!(agent.account(&session), None);
+
+        let agent = agent.with_authenticator(TrustedLogins);
+        agent.handle(session, login("Rusty")).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().data, vec![RESULT_SUCCESS]);
+        assert!(agent.account(&session).is_some());
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::agent::errors::Error;
+use crate::agent::handler::Agent;
+use crate::agent::opcodes::{CLIENT_MOVEMENT, SERVER_MOVEMENT};
+use crate::net::errors::Error::InvalidPacket;
+use crate::net::packet::{Packet, PacketReader, PacketWriter};
+use crate::world::movement::{MoveTarget, Movement};
+use crate::world::position::{Position, RegionId};
+
+/// Handles a movement request: the destination is validated against the terrain and the
+/// resulting movement is sent to all clients nearby, including the moving one.
+pub async fn handle(agent: &Agent, session: Uuid, packet: &Packet) -> Result<(), Error> {
+    let target = parse_request(packet)?;
+    let now = Instant::now();
+    let (packet, recipients) = {
+        let mut world = agent.world.lock().unwrap();
+        let movement = match world.move_player(&session, target, now) {
+            Some(movement) => movement,
+            None => {
+                debug!("session {} tried to move without a character", session);
+                return Ok(());
+            }
+        };
+        let unique_id = world.player(&session).map(|player| player.unique_id).unwrap_or_default();
+        let mut recipients = world.nearby(movement.source.region);
+        for recipient in world.nearby(movement.destination.region) {
+            if !recipients.contains(&recipient) {
+                recipients.push(recipient);
+            }
+        }
+        (movement_packet(unique_id, &movement), recipients)
+    };
+    agent.sessions.broadcast(recipients, &packet);
+    Ok(())
+}
+
+/// Parses a movement request ([CLIENT_MOVEMENT]).
+pub fn parse_request(packet: &Packet) -> Result<MoveTarget, Error> {
+    let mut reader = packet.reader();
+    if reader.bool()? {
+        let region = RegionId(reader.u16()?);
+        let (x, y, z) = read_coordinates(&mut reader, region)?;
+        Ok(MoveTarget::Destination(Position::new(region, x, y, z)))
+    } else {
+        let action = reader.u8()?;
+        if action > 1 {
+            return Err(InvalidPacket { opcode: CLIENT_MOVEMENT, message: "unknown angle action" }.into());
+        }
+        Ok(MoveTarget::Direction(reader.u16()?))
+    }
+}
+
+/// Builds the packet telling clients about a movement of given entity ([SERVER_MOVEMENT]).
+pub fn movement_packet(unique_id: u32, movement: &Movement) -> Packet {
+    let (source, destination) = (movement.source, movement.destination);
+    let mut writer = PacketWriter::new(SERVER_MOVEMENT);
+    writer.u32(unique_id).bool(true);
+    write_destination(&mut writer, &destination);
+    writer.bool(true).u16(source.region.0);
+    if source.region.is_dungeon() {
+        writer.i32((source.x * 10.0) as i32).f32(source.y).i32((source.z * 10.0) as i32);
+    } else {
+        writer.i16((source.x * 10.0) as i16).f32(source.y).i16((source.z * 10.0) as i16);
+    }
+    writer.build()
+}
+
+/// Writes the region and coordinates of a destination, which are shorts on the field and ints in
+/// dungeons.
+pub(crate) fn write_destination(writer: &mut PacketWriter, destination: &Position) {
+    writer.u16(destination.region.0);
+    if destination.region.is_dungeon() {
+        writer.i32(destination.x as i32).i32(destination.y as i32).i32(destination.z as i32);
+    } else {
+        writer.i16(destination.x as i16).i16(destination.y as i16).i16(destination.z as i16);
+    }
+}
+
+/// Reads the coordinates of a destination, which are shorts on the field and ints in dungeons.
+fn read_coordinates(reader: &mut PacketReader, region: RegionId) -> Result<(f32, f32, f32), Error> {
+    if region.is_dungeon() {
+        Ok((reader.i32()? as f32, reader.i32()? as f32, reader.i32()? as f32))
+    } else {
+        Ok((reader.i16()? as f32, reader.i16()? as f32, reader.i16()? as f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::agent::handler::tests::agent;
+    use crate::character::store::MemoryStore;
+    use crate::chat::state::Chat;
+
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        let packet = PacketWriter::new(CLIENT_MOVEMENT).u8(1).u16(0x61A8).i16(960).i16(-3).i16(1080).build();
+        assert_eq!(parse_request(&packet).unwrap(), MoveTarget::Destination(Position::new(RegionId(0x61A8), 960.0, -3.0, 1080.0)));
+        let packet = PacketWriter::new(CLIENT_MOVEMENT).u8(1).u16(0x8001).i32(-5000).i32(10).i32(3000).build();
+        assert_eq!(parse_request(&packet).unwrap(), MoveTarget::Destination(Position::new(RegionId(0x8001), -5000.0, 10.0, 3000.0)));
+        let packet = PacketWriter::new(CLIENT_MOVEMENT).u8(0).u8(1).u16(0x4000).build();
+        assert_eq!(parse_request(&packet).unwrap(), MoveTarget::Direction(0x4000));
+        assert!(parse_request(&PacketWriter::new(CLIENT_MOVEMENT).u8(1).u16(0x61A8).build()).is_err());
+    }
+
+    #[test]
+    fn writes_movement() {
+        let source = Position::new(RegionId(0x61A8), 10.5, 2.0, 20.0);
+        let destination = Position::new(RegionId(0x61A9), 100.0, 3.0, 200.0);
+        let packet = movement_packet(7, &Movement::new(source, destination, 50.0, Instant::now()));
+        assert_eq!(packet.opcode, SERVER_MOVEMENT);
+        assert_eq!(packet.data, vec![
+            7, 0, 0, 0, 1, 0xA9, 0x61, 100, 0, 3, 0, 200, 0,
+            1, 0xA8, 0x61, 105, 0, 0, 0, 0, 0x40, 200, 0,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn moves_spawned_players() {
+        let agent = agent(Arc::new(MemoryStore::new()), Chat::default());
+        let session = Uuid::new_v4();
+        let request = PacketWriter::new(CLIENT_MOVEMENT).u8(1).u16(0x61A8).i16(960).i16(0).i16(1080).build();
+        agent.handle(session, request.clone()).await.unwrap();
+
+        agent.world().lock().unwrap().spawn(session, "player", 1907, Position::new(RegionId(0x61A8), 900.0, 0.0, 1080.0));
+        agent.handle(session, request).await.unwrap();
+        let world = agent.world().lock().unwrap();
+        assert_eq!(world.player(&session).unwrap().movement.destination, Position::new(RegionId(0x61A8), 960.0, 0.0, 1080.0));
+    }
+}
@@ -0,0 +1,41 @@
+/// Client requests to move its character (see [MoveTarget](crate::world::movement::MoveTarget)).
+pub const CLIENT_MOVEMENT: u16 = 0x7021;
+/// Movement of an entity, sent to all clients seeing it.
+pub const SERVER_MOVEMENT: u16 = 0xB021;
+/// Starts a group of spawned or despawned entities.
+pub const ENTITY_GROUP_SPAWN_BEGIN: u16 = 0x3017;
+/// Ends a group of spawned or despawned entities.
+pub const ENTITY_GROUP_SPAWN_END: u16 = 0x3018;
+/// Data of spawned entities or IDs of despawned entities of a group.
+pub const ENTITY_GROUP_SPAWN_DATA: u16 = 0x3019;
+/// Client logs in with the token of the gateway server and its credentials.
+pub const CLIENT_AGENT_AUTH: u16 = 0x6103;
+/// Result of a login.
+pub const SERVER_AGENT_AUTH: u16 = 0xA103;
+/// Client lists, creates, deletes or restores characters on the selection screen, or checks
+/// whether a name is available.
+pub const CLIENT_CHARACTER_SELECTION: u16 = 0x7007;
+/// Result of a character selection action.
+pub const SERVER_CHARACTER_SELECTION: u16 = 0xB007;
+/// Client joins the game with a character of its account.
+pub const CLIENT_CHARACTER_JOIN: u16 = 0x7001;
+/// Result of joining the game, followed by the character data on success.
+pub const SERVER_CHARACTER_JOIN: u16 = 0xB001;
+/// Starts the character data sent when joining the game.
+pub const CHARACTER_DATA_BEGIN: u16 = 0x34A5;
+/// Data of the joining character (see [CharacterData](crate::agent::character_data::CharacterData)).
+pub const CHARACTER_DATA: u16 = 0x3013;
+/// Ends the character data.
+pub const CHARACTER_DATA_END: u16 = 0x34A6;
+/// Client moves, equips, drops, picks up, buys or sells items.
+pub const CLIENT_ITEM_MOVE: u16 = 0x7034;
+/// Result of an item move.
+pub const SERVER_ITEM_MOVE: u16 = 0xB034;
+/// Changes an item in the inventory outside of a move, e.g. when it's consumed.
+pub const SERVER_ITEM_UPDATE: u16 = 0x3040;
+/// Client sends a chat message (see [Channel](crate::agent::chat::Channel)).
+pub const CLIENT_CHAT: u16 = 0x7025;
+/// Result of a chat message, sent to its sender.
+pub const SERVER_CHAT: u16 = 0xB025;
+/// A chat message, sent to its recipients.
+pub const SERVER_CHAT_MESSAGE: u16 = 0x3026;
@@ -0,0 +1,247 @@
+use std::time::SystemTime;
+
+use uuid::Uuid;
+
+use crate::agent::errors::Error;
+use crate::agent::handler::Agent;
+use crate::agent::opcodes::{CLIENT_CHARACTER_SELECTION, SERVER_CHARACTER_SELECTION};
+use crate::character::errors::Error as CharacterError;
+use crate::character::record::Character;
+use crate::character::selection::{CharacterSelection, NewCharacter};
+use crate::net::errors::Error::InvalidPacket;
+use crate::net::packet::{Packet, PacketWriter};
+
+const ACTION_CREATE: u8 = 1;
+const ACTION_LIST: u8 = 2;
+const ACTION_DELETE: u8 = 3;
+const ACTION_CHECK_NAME: u8 = 4;
+const ACTION_RESTORE: u8 = 5;
+
+const RESULT_SUCCESS: u8 = 1;
+const RESULT_FAILURE: u8 = 2;
+
+/// Error codes of failed actions, which the client shows as messages.
+const ERROR_FAILED: u16 = 0x401;
+const ERROR_CHARACTER_LIMIT: u16 = 0x405;
+const ERROR_NOT_FOUND: u16 = 0x406;
+const ERROR_INVALID_NAME: u16 = 0x40C;
+const ERROR_INVALID_CHARACTER: u16 = 0x40D;
+const ERROR_NAME_TAKEN: u16 = 0x410;
+
+/// An action on the character selection screen ([CLIENT_CHARACTER_SELECTION]).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Create(NewCharacter),
+    List,
+    Delete(String),
+    CheckName(String),
+    Restore(String),
+}
+
+/// Handles a character selection action of a logged in session and sends the result.
+pub async fn handle(agent: &Agent, session: Uuid, packet: &Packet) -> Result<(), Error> {
+    let request = parse_request(packet)?;
+    let account = match agent.account(&session) {
+        Some(account) => account,
+        None => {
+            debug!("session {} tried to select a character without login", session);
+            return Ok(());
+        }
+    };
+    let response = process(&agent.characters, account, request, SystemTime::now());
+    agent.sessions.send(session, response);
+    Ok(())
+}
+
+/// Parses a character selection action ([CLIENT_CHARACTER_SELECTION]).
+pub fn parse_request(packet: &Packet) -> Result<Request, Error> {
+    let mut reader = packet.reader();
+    match reader.u8()? {
+        ACTION_CREATE => Ok(Request::Create(NewCharacter {
+            name: reader.string()?,
+            ref_obj_id: reader.u32()?,
+            scale: reader.u8()?,
+            chest: reader.u32()?,
+            legs: reader.u32()?,
+            foot: reader.u32()?,
+            weapon: reader.u32()?,
+        })),
+        ACTION_LIST => Ok(Request::List),
+        ACTION_DELETE => Ok(Request::Delete(reader.string()?)),
+        ACTION_CHECK_NAME => Ok(Request::CheckName(reader.string()?)),
+        ACTION_RESTORE => Ok(Request::Restore(reader.string()?)),
+        _ => Err(InvalidPacket { opcode: CLIENT_CHARACTER_SELECTION, message: "unknown action" }.into()),
+    }
+}
+
+/// Performs an action for given account and builds its result ([SERVER_CHARACTER_SELECTION]).
+pub fn process(selection: &CharacterSelection, account: u32, request: Request, now: SystemTime) -> Packet {
+    let (action, result) = match request {
+        Request::Create(new) => (ACTION_CREATE, selection.create(account, new, now).map(|_| Vec::new())),
+        Request::List => (ACTION_LIST, selection.list(account, now)),
+        Request::Delete(name) => (ACTION_DELETE, selection.delete(account, &name, now).map(|_| Vec::new())),
+        Request::CheckName(name) => (ACTION_CHECK_NAME, selection.check_name(&name).map(|_| Vec::new())),
+        Request::Restore(name) => (ACTION_RESTORE, selection.restore(account, &name).map(|_| Vec::new())),
+    };
+    let mut writer = PacketWriter::new(SERVER_CHARACTER_SELECTION);
+    writer.u8(action);
+    match result {
+        Ok(characters) => {
+            writer.u8(RESULT_SUCCESS);
+            if action == ACTION_LIST {
+                writer.u8(characters.len() as u8);
+                characters.iter().for_each(|character| write_character(&mut writer, character, now));
+            }
+        }
+        Err(err) => {
+            match err {
+                CharacterError::Storage(_) => error!("character selection of account {} failed: {}", account, err),
+                _ => debug!("character selection of account {} failed: {}", account, err),
+            }
+            writer.u8(RESULT_FAILURE).u16(error_code(&err));
+        }
+    }
+    writer.build()
+}
+
+fn error_code(err: &CharacterError) -> u16 {
+    match err {
+        CharacterError::InvalidName(_) => ERROR_INVALID_NAME,
+        CharacterError::NameTaken(_) => ERROR_NAME_TAKEN,
+        CharacterError::CharacterLimit => ERROR_CHARACTER_LIMIT,
+        CharacterError::NotFound(_) => ERROR_NOT_FOUND,
+        CharacterError::InvalidModel(_) | CharacterError::InvalidItem(_) => ERROR_INVALID_CHARACTER,
+        _ => ERROR_FAILED,
+    }
+}
+
+/// Writes a character of the list with its equipment. Characters being deleted carry the
+/// remaining minutes until their deletion.
+fn write_character(writer: &mut PacketWriter, character: &Character, now: SystemTime) {
+    writer.u32(character.ref_obj_id)
+        .string(&character.name)
+        .u8(character.scale)
+        .u8(character.level)
+        .u64(character.exp)
+        .u16(character.strength)
+        .u16(character.intelligence)
+        .u16(character.stat_points)
+        .u32(character.hp)
+        .u32(character.mp);
+    match character.deletion_remaining(now) {
+        Some(remaining) => writer.bool(true).u32(remaining.as_secs().div_ceil(60) as u32),
+        None => writer.bool(false),
+    };
+    // guild member class, guild rename required, academy member class
+    writer.u8(0).bool(false).u8(0);
+    let equipment: Vec<_> = character.equipment().collect();
+    writer.u8(equipment.len() as u8);
+    for item in equipment {
+        writer.u32(item.ref_item_id).u8(item.plus);
+    }
+    // avatar items
+    writer.u8(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::agent::handler::tests::{agent, connect};
+    use crate::character::names::NameRules;
+    use crate::character::record::{Item, SLOT_WEAPON};
+    use crate::character::store::{CharacterStore, MemoryStore};
+    use crate::character::store::tests::character;
+    use crate::chat::state::Chat;
+    use crate::textdata::registry::Registry;
+
+    use super::*;
+
+    fn store() -> Arc<MemoryStore> {
+        let store = MemoryStore::new();
+        store.create(Character {
+            items: vec![Item { slot: SLOT_WEAPON, ref_item_id: 3633, plus: 2, quantity: 1, ..Item::default() }, Item { slot: 13, ref_item_id: 62, plus: 0, quantity: 1, ..Item::default() }],
+            skills: Vec::new(),
+            ..character(1, "Rusty")
+        }).unwrap();
+        Arc::new(store)
+    }
+
+    fn selection() -> CharacterSelection {
+        let registry = Registry::new(Vec::new(), Vec::new(), Vec::new()).unwrap();
+        CharacterSelection::new(store(), Arc::new(registry), NameRules::default())
+    }
+
+    #[test]
+    fn parses_requests() {
+        let packet = PacketWriter::new(CLIENT_CHARACTER_SELECTION)
+            .u8(ACTION_CREATE).string("Rusty").u32(1907).u8(0x44).u32(3640).u32(3641).u32(3642).u32(3633)
+            .build();
+        assert_eq!(parse_request(&packet).unwrap(), Request::Create(NewCharacter {
+            name: "Rusty".to_string(), ref_obj_id: 1907, scale: 0x44, chest: 3640, legs: 3641, foot: 3642, weapon: 3633,
+        }));
+        let packet = PacketWriter::new(CLIENT_CHARACTER_SELECTION).u8(ACTION_RESTORE).string("Rusty").build();
+        assert_eq!(parse_request(&packet).unwrap(), Request::Restore("Rusty".to_string()));
+        assert_eq!(parse_request(&Packet::new(CLIENT_CHARACTER_SELECTION, vec![ACTION_LIST])).unwrap(), Request::List);
+        assert!(parse_request(&Packet::new(CLIENT_CHARACTER_SELECTION, vec![9])).is_err());
+        assert!(parse_request(&Packet::new(CLIENT_CHARACTER_SELECTION, vec![ACTION_DELETE, 5, 0])).is_err());
+    }
+
+    #[test]
+    fn writes_character_list() {
+        let selection = selection();
+        let now = SystemTime::now();
+        let packet = process(&selection, 1, Request::List, now);
+        assert_eq!(packet.opcode, SERVER_CHARACTER_SELECTION);
+        assert_eq!(packet.data, vec![
+            ACTION_LIST, RESULT_SUCCESS, 1,
+            0x73, 0x07, 0, 0, 5, 0, b'R', b'u', b's', b't', b'y', 0x44, 1,
+            0, 0, 0, 0, 0, 0, 0, 0, 20, 0, 20, 0, 0, 0, 200, 0, 0, 0, 200, 0, 0, 0,
+            0, 0, 0, 0,
+            1, 0x31, 0x0E, 0, 0, 2,
+            0,
+        ]);
+
+        let packet = process(&selection, 1, Request::Delete("Rusty".to_string()), now);
+        assert_eq!(packet.data, vec![ACTION_DELETE, RESULT_SUCCESS]);
+        let packet = process(&selection, 1, Request::List, now + Duration::from_secs(90));
+        let mut reader = packet.reader();
+        reader.bytes(3 + 4 + 7 + 2 + 8 + 6 + 8, "character").unwrap();
+        assert!(reader.bool().unwrap());
+        assert_eq!(reader.u32().unwrap(), 7 * 24 * 60 - 1);
+    }
+
+    #[test]
+    fn reports_failures() {
+        let selection = selection();
+        let now = SystemTime::now();
+        let packet = process(&selection, 1, Request::CheckName("rusty".to_string()), now);
+        assert_eq!(packet.data, vec![ACTION_CHECK_NAME, RESULT_FAILURE, 0x10, 0x04]);
+        let packet = process(&selection, 1, Request::CheckName("Road".to_string()), now);
+        assert_eq!(packet.data, vec![ACTION_CHECK_NAME, RESULT_SUCCESS]);
+        let packet = process(&selection, 2, Request::Restore("Rusty".to_string()), now);
+        assert_eq!(packet.data, vec![ACTION_RESTORE, RESULT_FAILURE, 0x06, 0x04]);
+        let new = NewCharacter { name: "Road".to_string(), ref_obj_id: 1907, scale: 0, chest: 0, legs: 0, foot: 0, weapon: 0 };
+        let packet = process(&selection, 1, Request::Create(new), now);
+        assert_eq!(packet.data, vec![ACTION_CREATE, RESULT_FAILURE, 0x0D, 0x04]);
+    }
+
+    #[tokio::test]
+    async fn answers_logged_in_sessions() {
+        let agent = agent(store(), Chat::default());
+        let session = Uuid::new_v4();
+        let mut receiver = connect(&agent, session, 4);
+
+        let request = Packet::new(CLIENT_CHARACTER_SELECTION, vec![ACTION_LIST]);
+        agent.handle(session, request.clone()).await.unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        agent.login(session, 1);
+        agent.handle(session, request.clone()).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().data[..3], [ACTION_LIST, RESULT_SUCCESS, 1]);
+
+        agent.disconnected(session).await;
+        assert_eq!(agent.account(&session), None);
+    }
+}
@@ -0,0 +1,230 @@
+use std::time::Instant;
+
+use crate::agent::character_data::{InventoryItem, ItemData};
+use crate::agent::handler::Agent;
+use crate::agent::movement::write_destination;
+use crate::agent::opcodes::{ENTITY_GROUP_SPAWN_BEGIN, ENTITY_GROUP_SPAWN_DATA, ENTITY_GROUP_SPAWN_END};
+use crate::net::packet::{Packet, PacketWriter, MAX_DATA_SIZE};
+use crate::textdata::registry::Registry;
+use crate::world::ground::GroundItem;
+use crate::world::npc::Npc;
+use crate::world::player::Player;
+use crate::world::position::Position;
+use crate::world::state::{SightEvent, World};
+
+/// Action of a group spawn telling whether the entities appear or disappear.
+const GROUP_SPAWN: u8 = 1;
+const GROUP_DESPAWN: u8 = 2;
+
+/// Sends the spawn and despawn packets for given sight events to their observers.
+pub async fn send_sight_events(agent: &Agent, events: Vec<SightEvent>, now: Instant) {
+    if events.is_empty() {
+        return;
+    }
+    let packets: Vec<_> = {
+        let world = agent.world.lock().unwrap();
+        let registry = agent.characters.registry();
+        events.iter()
+            .map(|event| match event {
+                SightEvent::Spawn { observer, entities } => (*observer, spawn_packets(&world, registry, entities, now)),
+                SightEvent::Despawn { observer, entities } => (*observer, despawn_packets(entities)),
+            })
+            .collect()
+    };
+    for (observer, packets) in packets {
+        for packet in packets {
+            if !agent.sessions.send(observer, packet) {
+                break;
+            }
+        }
+    }
+}
+
+/// Builds the group spawn packets for given players, NPCs and items on the ground. Entities which
+/// don't exist anymore or are missing in the reference data are left out.
+pub fn spawn_packets(world: &World, registry: &Registry, entities: &[u32], now: Instant) -> Vec<Packet> {
+    let data: Vec<Vec<u8>> = entities.iter()
+        .filter_map(|entity| {
+            let mut writer = PacketWriter::new(ENTITY_GROUP_SPAWN_DATA);
+            if let Some(player) = world.player_by_id(*entity) {
+                write_player(&mut writer, player, now);
+            } else if let Some(npc) = world.npc(*entity) {
+                write_npc(&mut writer, npc);
+            } else {
+                write_ground_item(&mut writer, world.ground_item(*entity)?, registry)?;
+            }
+            Some(writer.build().data)
+        })
+        .collect();
+    group_packets(GROUP_SPAWN, data)
+}
+
+/// Builds the group despawn packets for given entities.
+pub fn despawn_packets(entities: &[u32]) -> Vec<Packet> {
+    group_packets(GROUP_DESPAWN, entities.iter().map(|entity| entity.to_le_bytes().to_vec()).collect())
+}
+
+/// Wraps the data of each entity into a group: a begin packet with the number of entities, as
+/// many data packets as needed and an end packet.
+fn group_packets(action: u8, entities: Vec<Vec<u8>>) -> Vec<Packet> {
+    if entities.is_empty() {
+        return Vec::new();
+    }
+    let mut packets = vec![PacketWriter::new(ENTITY_GROUP_SPAWN_BEGIN).u8(action).u16(entities.len() as u16).build()];
+    let mut data = Vec::new();
+    for entity in entities {
+        if !data.is_empty() && data.len() + entity.len() > MAX_DATA_SIZE {
+            packets.push(Packet::new(ENTITY_GROUP_SPAWN_DATA, std::mem::take(&mut data)));
+        }
+        data.extend(entity);
+    }
+    packets.push(Packet::new(ENTITY_GROUP_SPAWN_DATA, data));
+    packets.push(Packet::new(ENTITY_GROUP_SPAWN_END, Vec::new()));
+    packets
+}
+
+/// Writes the spawn data of a player as seen by other clients. Equipment, buffs, guilds and the
+/// other features which don't exist yet are written as empty.
+fn write_player(writer: &mut PacketWriter, player: &Player, now: Instant) {
+    let position = player.position(now);
+    let moving = player.movement.is_moving(now);
+    writer.u32(player.ref_obj_id)
+        .u8(0) // scale
+        .u8(0) // hwan level
+        .u8(0) // pvp cape
+        .u8(0) // auto invest exp
+        .u8(0).u8(0) // inventory size and equipped items
+        .u8(0).u8(0) // avatar inventory size and equipped avatars
+        .u8(0) // mask
+        .u32(player.unique_id)
+        .u16(position.region.0).f32(position.x).f32(position.y).f32(position.z)
+        .u16(0) // angle
+        .bool(moving)
+        .bool(player.running);
+    if moving {
+        write_destination(writer, &player.movement.destination);
+    } else {
+        writer.u8(0).u16(0); // angle action and angle
+    }
+    writer.u8(1) // life state: alive
+        .u8(0)
+        .u8(0) // motion state
+        .u8(0) // status
+        .f32(player.walk_speed)
+        .f32(player.run_speed)
+        .f32(player.run_speed) // berserk speed
+        .u8(0) // buffs
+        .string(&player.name)
+        .u8(0).u8(0) // job type and level
+        .u8(0) // pk state
+        .u8(0) // transport
+        .u8(0) // in combat
+        .u8(0) // scroll mode
+        .u8(0) // interact mode
+        .u8(0) // guild flag
+        .string("") // guild name
+        .u8(0).u8(0); // equipment cooldown and pk flag
+}
+
+/// Writes the spawn data of a standing NPC.
+fn write_npc(writer: &mut PacketWriter, npc: &Npc) {
+    writer.u32(npc.ref_obj_id).u32(npc.unique_id);
+    write_position(writer, &npc.position);
+    writer.bool(false) // moving
+        .bool(false) // running
+        .u8(0).u16(0) // angle action and angle
+        .u8(1) // life state: alive
+        .u8(0)
+        .u8(0) // motion state
+        .u8(0) // status
+        .f32(0.0).f32(0.0).f32(0.0) // walk, run and berserk speed
+        .u8(0) // buffs
+        .u8(0); // talk flag
+}
+
+/// Writes the spawn data of an item on the ground. Returns `None` for items missing in the
+/// reference data.
+fn write_ground_item(writer: &mut PacketWriter, ground: &GroundItem, registry: &Registry) -> Option<()> {
+    let item = InventoryItem::new(&ground.item, registry)?;
+    writer.u32(item.ref_item_id);
+    if let ItemData::Equipment { plus, .. } = item.data {
+        writer.u8(plus);
+    }
+    writer.u32(ground.unique_id);
+    write_position(writer, &ground.position);
+    writer.bool(false) // owner
+        .u8(0) // rarity
+        .u8(0); // drop source: none
+    Some(())
+}
+
+fn write_position(writer: &mut PacketWriter, position: &Position) {
+    writer.u16(position.region.0).f32(position.x).f32(position.y).f32(position.z)
+        .u16(0); // angle
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::character::inventory::tests::registry;
+    use crate::character::record::Item;
+    use crate::world::navigation::NavMap;
+    use crate::world::position::{Position, RegionId};
+
+    use super::*;
+
+    #[test]
+    fn groups_despawned_entities() {
+        let packets = despawn_packets(&[7, 8]);
+        assert_eq!(packets, vec![
+            Packet::new(ENTITY_GROUP_SPAWN_BEGIN, vec![GROUP_DESPAWN, 2, 0]),
+            Packet::new(ENTITY_GROUP_SPAWN_DATA, vec![7, 0, 0, 0, 8, 0, 0, 0]),
+            Packet::new(ENTITY_GROUP_SPAWN_END, vec![]),
+        ]);
+        assert!(despawn_packets(&[]).is_empty());
+    }
+
+    #[test]
+    fn splits_large_groups() {
+        let packets = group_packets(GROUP_SPAWN, vec![vec![0; 20000], vec![0; 20000], vec![0; 100]]);
+        let sizes: Vec<usize> = packets.iter().map(|p| p.data.len()).collect();
+        assert_eq!(sizes, vec![3, 20000, 20100, 0]);
+    }
+
+    #[test]
+    fn spawns_players_with_their_movement() {
+        let mut world = World::new(NavMap::new());
+        let session = Uuid::new_v4();
+        world.spawn(session, "Trader", 1907, Position::new(RegionId(0x61A8), 100.0, 0.0, 100.0));
+        let packets = spawn_packets(&world, &registry(), &[1, 99], Instant::now());
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].data, vec![GROUP_SPAWN, 1, 0]);
+        let mut reader = packets[1].reader();
+        assert_eq!(reader.u32().unwrap(), 1907);
+        reader.bytes(9, "header").unwrap();
+        assert_eq!(reader.u32().unwrap(), 1);
+        assert_eq!(reader.u16().unwrap(), 0x61A8);
+        assert_eq!(reader.f32().unwrap(), 100.0);
+    }
+
+    #[test]
+    fn spawns_npcs_and_items_on_the_ground() {
+        let mut world = World::new(NavMap::new());
+        let position = Position::new(RegionId(0x61A8), 100.0, 0.0, 100.0);
+        let (npc, _) = world.add_npc(Npc::new(2000, position));
+        let (sword, _) = world.drop_item(Item { ref_item_id: 3633, plus: 3, quantity: 1, ..Item::default() }, position);
+        let (potions, _) = world.drop_item(Item { ref_item_id: 62, quantity: 5, ..Item::default() }, position);
+        let (unknown, _) = world.drop_item(Item { ref_item_id: 1, quantity: 1, ..Item::default() }, position);
+        let packets = spawn_packets(&world, &registry(), &[npc, sword, potions, unknown], Instant::now());
+        assert_eq!(packets[0].data, vec![GROUP_SPAWN, 3, 0]);
+        let mut reader = packets[1].reader();
+        assert_eq!((reader.u32().unwrap(), reader.u32().unwrap(), reader.u16().unwrap()), (2000, npc, 0x61A8));
+        reader.bytes(12 + 2 + 2 + 3 + 4 + 12 + 2, "npc").unwrap();
+        assert_eq!((reader.u32().unwrap(), reader.u8().unwrap(), reader.u32().unwrap()), (3633, 3, sword));
+        reader.bytes(14 + 2 + 3, "sword").unwrap();
+        assert_eq!((reader.u32().unwrap(), reader.u32().unwrap(), reader.u16().unwrap()), (62, potions, 0x61A8));
+        reader.bytes(12 + 2 + 3, "potions").unwrap();
+        assert!(reader.u8().is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs::read;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand};
+
+use rustyroad::pk2::archive::Archive;
+use rustyroad::pk2::constants::KEY;
+use rustyroad::pk2::diff::Change;
+use rustyroad::pk2::errors::Error;
+use rustyroad::pk2::extraction::Extractor;
+use rustyroad::pk2::patch::Patch;
+
+/// Inspects and edits SRO PK2 archives
+#[derive(Parser)]
+#[command(name = "pk2")]
+struct Cli {
+    /// Blowfish key the archives are encrypted with
+    #[arg(short, long, global = true, default_value = KEY)]
+    key: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lists the files of a directory in the archive with their sizes and modification times
+    Ls {
+        archive: PathBuf,
+        /// Directory in the archive, defaults to the root directory
+        path: Option<PathBuf>,
+    },
+    /// Writes the content of a file in the archive to stdout
+    Cat {
+        archive: PathBuf,
+        path: PathBuf,
+    },
+    /// Extracts files of the archive
+    Extract {
+        archive: PathBuf,
+        destination: PathBuf,
+        /// Only extracts files matching the glob pattern, can be repeated
+        #[arg(short, long)]
+        include: Vec<String>,
+        /// Skips files matching the glob pattern, can be repeated
+        #[arg(short, long)]
+        exclude: Vec<String>,
+        /// Number of threads, defaults to the number of CPUs
+        #[arg(short = 'j', long)]
+        threads: Option<usize>,
+    },
+    /// Packs a directory into a new archive
+    Pack {
+        source: PathBuf,
+        archive: PathBuf,
+    },
+    /// Adds a file to the archive or replaces an existing one
+    Add {
+        archive: PathBuf,
+        file: PathBuf,
+        /// Path of the file in the archive
+        path: PathBuf,
+    },
+    /// Removes a file or directory from the archive
+    Rm {
+        archive: PathBuf,
+        path: PathBuf,
+    },
+    /// Reclaims the space wasted by removed and replaced files
+    Compact {
+        archive: PathBuf,
+    },
+    /// Verifies the integrity of the archive
+    Verify {
+        archive: PathBuf,
+        /// Writes a repaired copy of the archive to given location
+        #[arg(long)]
+        repair: Option<PathBuf>,
+    },
+    /// Lists the files which differ between two archives
+    Diff {
+        older: PathBuf,
+        newer: PathBuf,
+    },
+    /// Creates a patch bundle with the changes between two archives
+    MakePatch {
+        older: PathBuf,
+        newer: PathBuf,
+        bundle: PathBuf,
+    },
+    /// Applies a patch bundle to an archive
+    Patch {
+        archive: PathBuf,
+        bundle: PathBuf,
+    },
+}
+
+fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    match run(cli.command, cli.key.as_bytes()) {
+        Ok(success) => exit(if success { 0 } else { 1 }),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            exit(2);
+        }
+    }
+}
+
+/// Runs the command. Returns whether the command succeeded, e.g. the archive has no issues.
+fn run(command: Command, key: &[u8]) -> Result<bool, Error> {
+    match command {
+        Command::Ls { archive, path } => {
+            let archive = Archive::open_with_key(&archive, key)?;
+            let path = path.unwrap_or_default();
+            let dir = archive.root.find_directory(&path).ok_or_else(|| Error::NotFound(path.clone()))?;
+            let mut out = stdout().lock();
+            for (file_path, entry) in dir.files() {
+                writeln!(out, "{:>12}  {}  {}", entry.size, format_time(entry.modified()), path.join(file_path).display())
+                    .map_err(Error::IO)?;
+            }
+        }
+        Command::Cat { archive, path } => {
+            let content = Archive::open_with_key(&archive, key)?.read(&path)?;
+            stdout().write_all(&content).map_err(Error::IO)?;
+        }
+        Command::Extract { archive, destination, include, exclude, threads } => {
+            let archive = Archive::open_with_key(&archive, key)?;
+            let mut extractor = Extractor::new(&archive)
+                .on_progress(|p| eprint!("\r{}/{} files", p.completed, p.total));
+            for pattern in include.iter() {
+                extractor = extractor.include(pattern)?;
+            }
+            for pattern in exclude.iter() {
+                extractor = extractor.exclude(pattern)?;
+            }
+            if let Some(threads) = threads {
+                extractor = extractor.threads(threads);
+            }
+            let summary = extractor.extract(&destination);
+            eprintln!();
+            for (path, err) in summary.failures.iter() {
+                eprintln!("failed to extract {}: {}", path.display(), err);
+            }
+            println!("extracted {} files ({} bytes), skipped {}, failed {}",
+                     summary.extracted, summary.bytes, summary.skipped, summary.failures.len());
+            return Ok(summary.failures.is_empty());
+        }
+        Command::Pack { source, archive } => {
+            let files = Archive::pack(&source, &archive, key)?;
+            println!("packed {} files", files);
+        }
+        Command::Add { archive, file, path } => {
+            let content = read(&file).map_err(Error::IO)?;
+            Archive::open_for_edit(&archive, key)?.add_file(&path, &content)?;
+        }
+        Command::Rm { archive, path } => {
+            Archive::open_for_edit(&archive, key)?.remove(&path)?;
+        }
+        Command::Compact { archive } => {
+            let usage = Archive::compact(&archive, key)?;
+            println!("reclaimed {} bytes", usage.wasted_bytes());
+        }
+        Command::Verify { archive, repair } => {
+            let report = match repair {
+                Some(location) => Archive::repair(&archive, key, &location)?,
+                None => Archive::verify(&archive, key)?,
+            };
+            for issue in report.issues.iter() {
+                println!("{}", issue);
+            }
+            println!("verified {} blocks, {} directories and {} files: {} issues",
+                     report.blocks, report.directories, report.files, report.issues.len());
+            return Ok(report.is_ok());
+        }
+        Command::Diff { older, newer } => {
+            let older = Archive::open_with_key(&older, key)?;
+            let newer = Archive::open_with_key(&newer, key)?;
+            let changes = older.diff(&newer)?;
+            let mut out = stdout().lock();
+            for change in changes.iter() {
+                let (sign, path) = match change {
+                    Change::Added(path) => ('+', path),
+                    Change::Removed(path) => ('-', path),
+                    Change::Modified(path) => ('~', path),
+                };
+                writeln!(out, "{} {}", sign, path.display()).map_err(Error::IO)?;
+            }
+            return Ok(changes.is_empty());
+        }
+        Command::MakePatch { older, newer, bundle } => {
+            let older = Archive::open_with_key(&older, key)?;
+            let newer = Archive::open_with_key(&newer, key)?;
+            let patch = Patch::create(&older, &newer, &bundle)?;
+            println!("created patch with {} changes", patch.entries.len());
+        }
+        Command::Patch { archive, bundle } => {
+            let patch = Patch::open(&bundle)?;
+            let applied = patch.apply(&mut Archive::open_for_edit(&archive, key)?)?;
+            println!("applied {} of {} changes", applied, patch.entries.len());
+        }
+    }
+    Ok(true)
+}
+
+/// Formats a time as UTC date and time, e.g. `2011-04-07 13:37:00`.
+fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    };
+    let (days, secs_of_day) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // converts days since the unix epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day,
+            secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60)
+}
+
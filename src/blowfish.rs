@@ -1,9 +1,12 @@
-use std::convert::TryInto;
 // based on https://github.com/RustCrypto/block-ciphers, copied out as their exposed API is rather unwieldy
 use std::fmt;
 
 use byteorder::{ByteOrder, LE};
 
+/// Size of a blowfish block in bytes. Data is encrypted block by block (ECB), hence its length
+/// must be a multiple of the block size.
+pub const BLOCK_SIZE: usize = 8;
+
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
 pub struct InvalidKey;
 
@@ -14,6 +17,40 @@ impl fmt::Display for InvalidKey {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InvalidLength {
+    /// The length of the input isn't a multiple of [BLOCK_SIZE].
+    Misaligned(usize),
+    /// The output buffer is smaller than the input.
+    OutputTooSmall { input: usize, output: usize },
+}
+
+impl std::error::Error for InvalidLength {}
+impl fmt::Display for InvalidLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidLength::Misaligned(len) => write!(f, "length {} is not a multiple of {}", len, BLOCK_SIZE),
+            InvalidLength::OutputTooSmall { input, output } => write!(f, "output of {} bytes is too small for {} bytes", output, input),
+        }
+    }
+}
+
+/// Returns the given length rounded up to the next multiple of [BLOCK_SIZE].
+pub fn padded_len(len: usize) -> usize {
+    len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+}
+
+/// Pads the data with zeros to the next multiple of [BLOCK_SIZE].
+pub fn pad(data: &mut Vec<u8>) {
+    data.resize(padded_len(data.len()), 0);
+}
+
+/// Number of blocks encrypted together. Interleaving the rounds of independent blocks hides the
+/// latency of the S-box lookups, which otherwise depend on each other within a block.
+const LANES: usize = 4;
+
+// the S-boxes come first and are aligned to a cache line, so they occupy as few lines as possible
+#[repr(C, align(64))]
 pub struct Blowfish {
     s: [[u32; 256]; 4],
     p: [u32; 18],
@@ -39,38 +76,67 @@ impl Blowfish {
             return Err(InvalidKey);
         }
         let mut key = key.to_vec();
-        gen_final_blowfish_key_inplace(&mut key, &salt);
+        gen_final_blowfish_key_inplace(&mut key, salt);
         let mut this = Blowfish { p: P, s: S };
         this.expand_key(&key);
         Ok(this)
     }
 
-    pub fn encrypt(&self, data: &mut [u8]) {
-        data.chunks_exact_mut(8)
-            .for_each(|data| self.encrypt_block(data.try_into().unwrap_or_else(|_| unreachable!())))
+    /// Encrypts the data in place. Its length must be a multiple of [BLOCK_SIZE] (see [pad]).
+    pub fn encrypt(&self, data: &mut [u8]) -> Result<(), InvalidLength> {
+        check_aligned(data.len())?;
+        let mut batches = data.chunks_exact_mut(LANES * BLOCK_SIZE);
+        batches.by_ref().for_each(|batch| self.encrypt_blocks::<LANES>(batch));
+        batches.into_remainder().chunks_exact_mut(BLOCK_SIZE)
+            .for_each(|block| self.encrypt_blocks::<1>(block));
+        Ok(())
+    }
+
+    /// Decrypts the data in place. Its length must be a multiple of [BLOCK_SIZE].
+    pub fn decrypt(&self, data: &mut [u8]) -> Result<(), InvalidLength> {
+        check_aligned(data.len())?;
+        let mut batches = data.chunks_exact_mut(LANES * BLOCK_SIZE);
+        batches.by_ref().for_each(|batch| self.decrypt_blocks::<LANES>(batch));
+        batches.into_remainder().chunks_exact_mut(BLOCK_SIZE)
+            .for_each(|block| self.decrypt_blocks::<1>(block));
+        Ok(())
     }
 
-    pub fn decrypt(&self, data: &mut [u8]) {
-        data.chunks_exact_mut(8)
-            .for_each(|data| self.decrypt_block(data.try_into().unwrap_or_else(|_| unreachable!())))
+    /// Encrypts the input into the beginning of the output buffer, which must be at least as large
+    /// as the input. The length of the input must be a multiple of [BLOCK_SIZE].
+    pub fn encrypt_to(&self, input: &[u8], output: &mut [u8]) -> Result<(), InvalidLength> {
+        let output = copy_to_output(input, output)?;
+        self.encrypt(output)
     }
 
+    /// Decrypts the input into the beginning of the output buffer (see [Blowfish::encrypt_to]).
+    pub fn decrypt_to(&self, input: &[u8], output: &mut [u8]) -> Result<(), InvalidLength> {
+        let output = copy_to_output(input, output)?;
+        self.decrypt(output)
+    }
+
+    /// Returns the input padded with zeros to a multiple of [BLOCK_SIZE] and encrypted.
+    pub fn encrypt_padded(&self, input: &[u8]) -> Vec<u8> {
+        let mut output = input.to_vec();
+        pad(&mut output);
+        self.encrypt(&mut output).unwrap_or_else(|_| unreachable!());
+        output
+    }
+
+    /// Encrypts `N` consecutive blocks, whose halves are little endian words.
     #[inline(always)]
-    fn encrypt_block(&self, block: &mut [u8; 8]) {
-        let l = LE::read_u32(&block[..4]);
-        let r = LE::read_u32(&block[4..]);
-        let (l, r) = self.encrypt_p(l, r);
-        LE::write_u32(&mut block[..4], l);
-        LE::write_u32(&mut block[4..], r);
+    fn encrypt_blocks<const N: usize>(&self, blocks: &mut [u8]) {
+        let (mut l, mut r) = read_blocks::<N>(blocks);
+        self.encrypt_words(&mut l, &mut r);
+        write_blocks(blocks, &l, &r);
     }
 
+    /// Decrypts `N` consecutive blocks, whose halves are little endian words.
     #[inline(always)]
-    fn decrypt_block(&self, block: &mut [u8; 8]) {
-        let l = LE::read_u32(&block[..4]);
-        let r = LE::read_u32(&block[4..]);
-        let (l, r) = self.decrypt_p(l, r);
-        LE::write_u32(&mut block[..4], l);
-        LE::write_u32(&mut block[4..], r);
+    fn decrypt_blocks<const N: usize>(&self, blocks: &mut [u8]) {
+        let (mut l, mut r) = read_blocks::<N>(blocks);
+        self.decrypt_words(&mut l, &mut r);
+        write_blocks(blocks, &l, &r);
     }
 
     fn expand_key(&mut self, key: &[u8]) {
@@ -93,46 +159,109 @@ impl Blowfish {
         }
     }
 
+    // the indices are truncated to bytes, so no bounds checks are needed
     #[allow(clippy::many_single_char_names)]
+    #[inline(always)]
     fn round_function(&self, x: u32) -> u32 {
-        let x = x as usize;
-        let a = self.s[0][x >> 24];
-        let b = self.s[1][(x >> 16) & 0xff];
-        let c = self.s[2][(x >> 8) & 0xff];
-        let d = self.s[3][x & 0xff];
+        let a = self.s[0][(x >> 24) as u8 as usize];
+        let b = self.s[1][(x >> 16) as u8 as usize];
+        let c = self.s[2][(x >> 8) as u8 as usize];
+        let d = self.s[3][x as u8 as usize];
         (a.wrapping_add(b) ^ c).wrapping_add(d)
     }
 
-    fn encrypt_p(&self, mut l: u32, mut r: u32) -> (u32, u32) {
+    fn encrypt_p(&self, l: u32, r: u32) -> (u32, u32) {
+        let (mut l, mut r) = ([l], [r]);
+        self.encrypt_words(&mut l, &mut r);
+        (l[0], r[0])
+    }
+
+    /// Encrypts the halves of `N` blocks round by round. The halves are swapped after the last
+    /// round, so `l` and `r` hold the halves of the encrypted blocks in order.
+    #[inline(always)]
+    fn encrypt_words<const N: usize>(&self, l: &mut [u32; N], r: &mut [u32; N]) {
         for i in 0..8 {
-            l ^= self.p[2 * i];
-            r ^= self.round_function(l);
-            r ^= self.p[2 * i + 1];
-            l ^= self.round_function(r);
+            for j in 0..N {
+                l[j] ^= self.p[2 * i];
+                r[j] ^= self.round_function(l[j]);
+            }
+            for j in 0..N {
+                r[j] ^= self.p[2 * i + 1];
+                l[j] ^= self.round_function(r[j]);
+            }
+        }
+        for j in 0..N {
+            let (left, right) = (r[j] ^ self.p[17], l[j] ^ self.p[16]);
+            l[j] = left;
+            r[j] = right;
         }
-        l ^= self.p[16];
-        r ^= self.p[17];
-        (r, l)
     }
 
-    fn decrypt_p(&self, mut l: u32, mut r: u32) -> (u32, u32) {
+    /// Decrypts the halves of `N` blocks round by round (see [Blowfish::encrypt_words]).
+    #[inline(always)]
+    fn decrypt_words<const N: usize>(&self, l: &mut [u32; N], r: &mut [u32; N]) {
         for i in (1..9).rev() {
-            l ^= self.p[2 * i + 1];
-            r ^= self.round_function(l);
-            r ^= self.p[2 * i];
-            l ^= self.round_function(r);
+            for j in 0..N {
+                l[j] ^= self.p[2 * i + 1];
+                r[j] ^= self.round_function(l[j]);
+            }
+            for j in 0..N {
+                r[j] ^= self.p[2 * i];
+                l[j] ^= self.round_function(r[j]);
+            }
+        }
+        for j in 0..N {
+            let (left, right) = (r[j] ^ self.p[0], l[j] ^ self.p[1]);
+            l[j] = left;
+            r[j] = right;
         }
-        l ^= self.p[1];
-        r ^= self.p[0];
-        (r, l)
     }
 }
 
+/// Reads the halves of `N` consecutive blocks as little endian words.
+#[inline(always)]
+fn read_blocks<const N: usize>(blocks: &[u8]) -> ([u32; N], [u32; N]) {
+    assert_eq!(blocks.len(), N * BLOCK_SIZE);
+    let (mut l, mut r) = ([0; N], [0; N]);
+    for (j, block) in blocks.chunks_exact(BLOCK_SIZE).enumerate() {
+        l[j] = LE::read_u32(&block[..4]);
+        r[j] = LE::read_u32(&block[4..]);
+    }
+    (l, r)
+}
+
+/// Writes the halves of `N` consecutive blocks as little endian words.
+#[inline(always)]
+fn write_blocks<const N: usize>(blocks: &mut [u8], l: &[u32; N], r: &[u32; N]) {
+    for (j, block) in blocks.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+        LE::write_u32(&mut block[..4], l[j]);
+        LE::write_u32(&mut block[4..], r[j]);
+    }
+}
+
+fn check_aligned(len: usize) -> Result<(), InvalidLength> {
+    if !len.is_multiple_of(BLOCK_SIZE) {
+        return Err(InvalidLength::Misaligned(len));
+    }
+    Ok(())
+}
+
+/// Copies the input to the beginning of the output and returns that part of the output.
+fn copy_to_output<'o>(input: &[u8], output: &'o mut [u8]) -> Result<&'o mut [u8], InvalidLength> {
+    check_aligned(input.len())?;
+    if output.len() < input.len() {
+        return Err(InvalidLength::OutputTooSmall { input: input.len(), output: output.len() });
+    }
+    let output = &mut output[..input.len()];
+    output.copy_from_slice(input);
+    Ok(output)
+}
+
 fn gen_final_blowfish_key_inplace(key: &mut [u8], salt: &[u8]) {
     let key_len = key.len().min(56);
 
     let mut base_key = [0; 56];
-    base_key[0..salt.len()].copy_from_slice(&salt);
+    base_key[0..salt.len()].copy_from_slice(salt);
 
     for i in 0..key_len {
         key[i] ^= base_key[i];
@@ -302,4 +431,152 @@ pub const S: [[u32; 256]; 4] = [
         0x02fb8a8c, 0x01c36ae4, 0xd6ebe1f9, 0x90d4f869, 0xa65cdea0, 0x3f09252d, 0xc208e69f,
         0xb74e6132, 0xce77e25b, 0x578fdfe3, 0x3ac372e6,
     ],
-];
\ No newline at end of file
+];
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::pk2::constants::{KEY_BYTES, SALT};
+
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// Standard blowfish reads the halves of a block as big endian words, SRO's variant as little
+    /// endian ones. Swapping the bytes of each word converts between both.
+    fn swap_words(block: &[u8]) -> Vec<u8> {
+        block.chunks(4).flat_map(|word| word.iter().rev().copied()).collect()
+    }
+
+    /// Key, plaintext and ciphertext from Eric Young's standard test vectors.
+    const STANDARD_VECTORS: &[(&str, &str, &str)] = &[
+        ("0000000000000000", "0000000000000000", "4EF997456198DD78"),
+        ("FFFFFFFFFFFFFFFF", "FFFFFFFFFFFFFFFF", "51866FD5B85ECB8A"),
+        ("3000000000000000", "1000000000000001", "7D856F9A613063F2"),
+        ("1111111111111111", "1111111111111111", "2466DD878B963C9D"),
+        ("0123456789ABCDEF", "1111111111111111", "61F9C3802281B096"),
+        ("1111111111111111", "0123456789ABCDEF", "7D0CC630AFDA1EC7"),
+        ("FEDCBA9876543210", "0123456789ABCDEF", "0ACEAB0FC6A0A28D"),
+        ("7CA110454A1A6E57", "01A1D6D039776742", "59C68245EB05282B"),
+    ];
+
+    #[test]
+    fn standard_vectors() {
+        for (key, plain, cipher) in STANDARD_VECTORS {
+            // without a salt the key is used as it is
+            let blowfish = Blowfish::new(&hex(key), &[]).unwrap();
+            let mut data = swap_words(&hex(plain));
+            blowfish.encrypt(&mut data).unwrap();
+            assert_eq!(swap_words(&data), hex(cipher), "key {}", key);
+            blowfish.decrypt(&mut data).unwrap();
+            assert_eq!(swap_words(&data), hex(plain), "key {}", key);
+        }
+    }
+
+    /// Outputs of SRO's default PK2 key and salt, pinning the salted key derivation and the
+    /// little endian block layout.
+    #[test]
+    fn sro_vectors() {
+        let blowfish = Blowfish::new(KEY_BYTES, SALT).unwrap();
+        let mut checksum = *b"Joymax Pak File\0";
+        blowfish.encrypt(&mut checksum).unwrap();
+        assert_eq!(checksum.to_vec(), hex("D8DA30CF32E671FCF85815389C473AF7"));
+
+        let mut data = *b"Silkroad";
+        blowfish.encrypt(&mut data).unwrap();
+        assert_eq!(data.to_vec(), hex("A9194268723F6763"));
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert!(Blowfish::new(b"abc", &[]).is_err());
+        assert!(Blowfish::new(&[0; 57], &[]).is_err());
+        assert!(Blowfish::new(&[0; 56], &[]).is_ok());
+    }
+
+    #[test]
+    fn rejects_misaligned_input() {
+        let blowfish = Blowfish::new(b"169841", &[]).unwrap();
+        let mut data = [0u8; 12];
+        assert_eq!(blowfish.encrypt(&mut data), Err(InvalidLength::Misaligned(12)));
+        assert_eq!(blowfish.decrypt(&mut data), Err(InvalidLength::Misaligned(12)));
+        assert_eq!(data, [0u8; 12]);
+        assert_eq!(blowfish.encrypt_to(&[0; 16], &mut [0; 8]), Err(InvalidLength::OutputTooSmall { input: 16, output: 8 }));
+    }
+
+    #[test]
+    fn pads_to_block_size() {
+        assert_eq!(padded_len(0), 0);
+        assert_eq!(padded_len(1), 8);
+        assert_eq!(padded_len(8), 8);
+        assert_eq!(padded_len(9), 16);
+        let mut data = vec![1, 2, 3];
+        pad(&mut data);
+        assert_eq!(data, vec![1, 2, 3, 0, 0, 0, 0, 0]);
+    }
+
+    /// Straightforward block by block implementation the optimized one is checked against.
+    fn reference_encrypt(blowfish: &Blowfish, data: &mut [u8]) {
+        let f = |x: u32| {
+            let s = &blowfish.s;
+            (s[0][(x >> 24) as usize].wrapping_add(s[1][(x >> 16 & 0xff) as usize]) ^ s[2][(x >> 8 & 0xff) as usize])
+                .wrapping_add(s[3][(x & 0xff) as usize])
+        };
+        for block in data.chunks_exact_mut(BLOCK_SIZE) {
+            let (mut l, mut r) = (LE::read_u32(&block[..4]), LE::read_u32(&block[4..]));
+            for i in 0..8 {
+                l ^= blowfish.p[2 * i];
+                r ^= f(l);
+                r ^= blowfish.p[2 * i + 1];
+                l ^= f(r);
+            }
+            LE::write_u32(&mut block[..4], r ^ blowfish.p[17]);
+            LE::write_u32(&mut block[4..], l ^ blowfish.p[16]);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn matches_reference_implementation(key in prop::collection::vec(any::<u8>(), 4..=56), data in prop::collection::vec(any::<u8>(), 0..200)) {
+            let blowfish = Blowfish::new(&key, SALT).unwrap();
+            let mut expected = data[..data.len() / BLOCK_SIZE * BLOCK_SIZE].to_vec();
+            reference_encrypt(&blowfish, &mut expected);
+
+            let mut encrypted = data[..data.len() / BLOCK_SIZE * BLOCK_SIZE].to_vec();
+            blowfish.encrypt(&mut encrypted).unwrap();
+            prop_assert_eq!(&encrypted, &expected);
+            blowfish.decrypt(&mut encrypted).unwrap();
+            prop_assert_eq!(&encrypted[..], &data[..expected.len()]);
+        }
+
+        #[test]
+        fn encrypts_into_output(key in prop::collection::vec(any::<u8>(), 4..=56), blocks in prop::collection::vec(any::<u8>(), 0..64)) {
+            let blowfish = Blowfish::new(&key, &[]).unwrap();
+            let input = &blocks[..blocks.len() / BLOCK_SIZE * BLOCK_SIZE];
+            let mut in_place = input.to_vec();
+            blowfish.encrypt(&mut in_place).unwrap();
+
+            let mut output = vec![0xAA; input.len() + 3];
+            blowfish.encrypt_to(input, &mut output).unwrap();
+            prop_assert_eq!(&output[..input.len()], &in_place[..]);
+            prop_assert!(output[input.len()..].iter().all(|b| *b == 0xAA));
+
+            let mut decrypted = vec![0; input.len()];
+            blowfish.decrypt_to(&in_place, &mut decrypted).unwrap();
+            prop_assert_eq!(&decrypted[..], input);
+        }
+
+        #[test]
+        fn encrypts_padded(data in prop::collection::vec(any::<u8>(), 0..64)) {
+            let blowfish = Blowfish::new(b"169841", &[]).unwrap();
+            let mut encrypted = blowfish.encrypt_padded(&data);
+            prop_assert_eq!(encrypted.len(), padded_len(data.len()));
+            blowfish.decrypt(&mut encrypted).unwrap();
+            prop_assert_eq!(&encrypted[..data.len()], &data[..]);
+            prop_assert!(encrypted[data.len()..].iter().all(|b| *b == 0));
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod errors;
+pub mod inventory;
+pub mod names;
+pub mod record;
+pub mod selection;
+pub mod store;
@@ -0,0 +1,70 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(std::fmt::Debug, PartialEq)]
+pub enum Error {
+    /// The name violates the [NameRules](crate::character::names::NameRules).
+    InvalidName(&'static str),
+    /// Another character already uses the name.
+    NameTaken(String),
+    /// The account already has the maximum number of characters.
+    CharacterLimit,
+    /// The account has no character with given name.
+    NotFound(String),
+    /// The character is being deleted, so it can't join the game.
+    BeingDeleted(String),
+    /// The character or the session joining with it is already in the game.
+    AlreadyJoined(String),
+    /// The model isn't a player character.
+    InvalidModel(u32),
+    /// The item can't be chosen as starting equipment of the model.
+    InvalidItem(u32),
+    /// The character store failed.
+    Storage(String),
+    /// The slot lies outside of its container.
+    InvalidSlot(u8),
+    /// The slot holds no item.
+    EmptySlot(u8),
+    /// The quantity exceeds the stack or can't be moved into the slot.
+    InvalidQuantity(u16),
+    /// The item isn't in the reference data.
+    UnknownItem(u32),
+    /// The character can't wear the item in the slot.
+    CannotEquip(u32),
+    /// No free slot is left for the items.
+    InventoryFull,
+    /// The character can't afford the items.
+    NotEnoughGold,
+    /// The NPC or item on the ground with given unique ID doesn't exist or is too far away.
+    OutOfReach(u32),
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidName(reason) => write!(f, "invalid name: {}", reason),
+            Error::NameTaken(name) => write!(f, "name {} is already taken", name),
+            Error::CharacterLimit => write!(f, "character limit reached"),
+            Error::NotFound(name) => write!(f, "character {} not found", name),
+            Error::BeingDeleted(name) => write!(f, "character {} is being deleted", name),
+            Error::AlreadyJoined(name) => write!(f, "character {} is already in the game", name),
+            Error::InvalidModel(id) => write!(f, "{} isn't a player model", id),
+            Error::InvalidItem(id) => write!(f, "{} isn't valid starting equipment", id),
+            Error::Storage(message) => write!(f, "character store failed: {}", message),
+            Error::InvalidSlot(slot) => write!(f, "invalid slot {}", slot),
+            Error::EmptySlot(slot) => write!(f, "slot {} is empty", slot),
+            Error::InvalidQuantity(quantity) => write!(f, "invalid quantity {}", quantity),
+            Error::UnknownItem(id) => write!(f, "unknown item {}", id),
+            Error::CannotEquip(id) => write!(f, "item {} can't be equipped there", id),
+            Error::InventoryFull => write!(f, "inventory is full"),
+            Error::NotEnoughGold => write!(f, "not enough gold"),
+            Error::OutOfReach(unique_id) => write!(f, "entity {} is out of reach", unique_id),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Storage(err.to_string())
+    }
+}
@@ -0,0 +1,422 @@
+use crate::character::errors::Error;
+use crate::character::record::{Character, Item, EQUIPMENT_SLOTS, SLOT_WEAPON};
+use crate::textdata::object::{RefObjItem, GENDER_ANY};
+use crate::textdata::registry::Registry;
+
+/// Inventory size of characters whose model isn't known.
+pub const DEFAULT_INVENTORY_SIZE: u8 = 45;
+/// Number of slots of the avatar inventory.
+pub const AVATAR_SLOTS: u8 = 5;
+/// Number of slots of the storage.
+pub const STORAGE_SLOTS: u8 = 150;
+/// Equipment slot of shields, which can't be worn together with two-handed weapons.
+pub const SLOT_SHIELD: u8 = 7;
+
+/// The containers of a character's items.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Container {
+    /// The equipment slots followed by the inventory.
+    Inventory,
+    Avatar,
+    Storage,
+}
+
+/// Returns the equipment slots an item can be worn in, which are empty for items which aren't
+/// equipment (`TypeID` 3, 1).
+pub fn equipment_slots(item: &RefObjItem) -> &'static [u8] {
+    match item.common.type_id {
+        // garment, protector and armor of both races: head, shoulders, chest, legs, hands, feet
+        [3, 1, 1..=3, kind] | [3, 1, 9..=11, kind] => match kind {
+            1 => &[0],
+            2 => &[2],
+            3 => &[1],
+            4 => &[4],
+            5 => &[3],
+            6 => &[5],
+            _ => &[],
+        },
+        [3, 1, 4, _] => &[SLOT_SHIELD],
+        [3, 1, 6, _] => &[SLOT_WEAPON],
+        [3, 1, 7, _] => &[8],
+        // accessories of both races: earring, necklace and rings
+        [3, 1, 5, kind] | [3, 1, 12, kind] => match kind {
+            1 => &[9],
+            2 => &[10],
+            3 => &[11, 12],
+            _ => &[],
+        },
+        _ => &[],
+    }
+}
+
+/// Returns the avatar slot an item can be worn in: hat, dress, attachment, flag and devil's
+/// spirit.
+pub fn avatar_slot(item: &RefObjItem) -> Option<u8> {
+    match item.common.type_id {
+        [3, 1, 13, kind @ 1..=4] => Some(kind - 1),
+        [3, 1, 14, _] => Some(4),
+        _ => None,
+    }
+}
+
+/// Returns the inventory size of given character model, without the equipment slots. Unknown
+/// models and the ones without a size get [DEFAULT_INVENTORY_SIZE].
+pub fn inventory_size(registry: &Registry, ref_obj_id: u32) -> u8 {
+    registry.characters.get(ref_obj_id)
+        .map(|model| model.inventory_size)
+        .filter(|size| *size != 0)
+        .unwrap_or(DEFAULT_INVENTORY_SIZE)
+}
+
+/// The items of a character with the rules of the game: equipment only fits its slots and has
+/// to match the character, stacks are limited by `MaxStack`.
+///
+/// Operations either succeed completely or leave the items untouched.
+pub struct Inventory<'c> {
+    character: &'c mut Character,
+    registry: &'c Registry,
+    size: u8,
+}
+
+impl<'c> Inventory<'c> {
+    pub fn new(character: &'c mut Character, registry: &'c Registry) -> Inventory<'c> {
+        let size = inventory_size(registry, character.ref_obj_id);
+        Inventory { character, registry, size }
+    }
+
+    /// Returns the number of slots of a container, including the equipment slots.
+    pub fn slots(&self, container: Container) -> u8 {
+        match container {
+            Container::Inventory => EQUIPMENT_SLOTS.saturating_add(self.size),
+            Container::Avatar => AVATAR_SLOTS,
+            Container::Storage => STORAGE_SLOTS,
+        }
+    }
+
+    /// Returns the item in a slot.
+    pub fn get(&self, container: Container, slot: u8) -> Option<&Item> {
+        self.items(container).iter().find(|item| item.slot == slot)
+    }
+
+    /// Moves items between two slots of the same or different containers. Moving part of a
+    /// stack splits it into an empty slot or merges it into a stack of the same item, moving
+    /// onto another item swaps both. A quantity of `0` moves the whole stack.
+    pub fn move_item(&mut self, from: (Container, u8), to: (Container, u8), quantity: u16) -> Result<(), Error> {
+        for (container, slot) in [from, to] {
+            if slot >= self.slots(container) {
+                return Err(Error::InvalidSlot(slot));
+            }
+        }
+        if from == to {
+            return Err(Error::InvalidSlot(to.1));
+        }
+        let source = self.get(from.0, from.1).cloned().ok_or(Error::EmptySlot(from.1))?;
+        let reference = self.reference(source.ref_item_id)?;
+        let quantity = if quantity == 0 { source.quantity } else { quantity };
+        if quantity > source.quantity {
+            return Err(Error::InvalidQuantity(quantity));
+        }
+        let destination = self.get(to.0, to.1).cloned();
+        match destination {
+            None if quantity < source.quantity => {
+                self.check_slot(&source, to, &[])?;
+                self.item_mut(from).quantity -= quantity;
+                self.items_mut(to.0).push(Item { slot: to.1, quantity, ..source });
+            }
+            None => {
+                self.check_slot(&source, to, &[from])?;
+                self.item_mut(from).slot = to.1;
+                self.relocate(from, to);
+            }
+            Some(destination) if destination.ref_item_id == source.ref_item_id && reference.max_stack > 1 => {
+                let moved = quantity.min((reference.max_stack as u16).saturating_sub(destination.quantity));
+                if moved == 0 {
+                    return Err(Error::InvalidQuantity(quantity));
+                }
+                self.item_mut(to).quantity += moved;
+                self.item_mut(from).quantity -= moved;
+                if moved == source.quantity {
+                    self.items_mut(from.0).retain(|item| item.slot != from.1);
+                }
+            }
+            Some(destination) => {
+                if quantity < source.quantity {
+                    return Err(Error::InvalidQuantity(quantity));
+                }
+                self.check_slot(&source, to, &[from, to])?;
+                self.check_slot(&destination, from, &[from, to])?;
+                self.items_mut(to.0).retain(|item| item.slot != to.1);
+                self.items_mut(from.0).retain(|item| item.slot != from.1);
+                self.items_mut(to.0).push(Item { slot: to.1, ..source });
+                self.items_mut(from.0).push(Item { slot: from.1, ..destination });
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds items to the inventory, filling up stacks of the same item before using empty
+    /// slots. Returns the slots which changed.
+    pub fn add(&mut self, item: Item) -> Result<Vec<u8>, Error> {
+        let reference = self.reference(item.ref_item_id)?;
+        let max_stack = (reference.max_stack as u16).max(1);
+        if item.quantity == 0 || (max_stack == 1 && item.quantity > 1) {
+            return Err(Error::InvalidQuantity(item.quantity));
+        }
+        let mut remaining = item.quantity;
+        let mut changes = Vec::new();
+        if max_stack > 1 {
+            for stack in self.inventory_items() {
+                if stack.ref_item_id == item.ref_item_id && stack.quantity < max_stack && remaining > 0 {
+                    let added = remaining.min(max_stack - stack.quantity);
+                    changes.push((stack.slot, added));
+                    remaining -= added;
+                }
+            }
+        }
+        let mut free = self.free_slots().into_iter();
+        while remaining > 0 {
+            let slot = free.next().ok_or(Error::InventoryFull)?;
+            let added = remaining.min(max_stack);
+            changes.push((slot, added));
+            remaining -= added;
+        }
+        for (slot, added) in &changes {
+            match self.character.items.iter_mut().find(|stack| stack.slot == *slot) {
+                Some(stack) => stack.quantity += added,
+                None => self.character.items.push(Item { slot: *slot, quantity: *added, ..item.clone() }),
+            }
+        }
+        Ok(changes.into_iter().map(|(slot, _)| slot).collect())
+    }
+
+    /// Takes items out of an inventory slot, e.g. to drop or sell them. Equipped items have to
+    /// be unequipped first. A quantity of `0` takes the whole stack.
+    pub fn remove(&mut self, slot: u8, quantity: u16) -> Result<Item, Error> {
+        if !(EQUIPMENT_SLOTS..self.slots(Container::Inventory)).contains(&slot) {
+            return Err(Error::InvalidSlot(slot));
+        }
+        let item = self.get(Container::Inventory, slot).cloned().ok_or(Error::EmptySlot(slot))?;
+        let quantity = if quantity == 0 { item.quantity } else { quantity };
+        if quantity > item.quantity {
+            return Err(Error::InvalidQuantity(quantity));
+        }
+        if quantity == item.quantity {
+            self.character.items.retain(|item| item.slot != slot);
+        } else {
+            self.item_mut((Container::Inventory, slot)).quantity -= quantity;
+        }
+        Ok(Item { quantity, ..item })
+    }
+
+    /// Buys items for their `Price` and adds them to the inventory. Returns the slots which
+    /// changed.
+    pub fn buy(&mut self, ref_item_id: u32, quantity: u16) -> Result<Vec<u8>, Error> {
+        let reference = self.reference(ref_item_id)?;
+        let price = reference.common.price.saturating_mul(quantity as u64);
+        if price > self.character.gold {
+            return Err(Error::NotEnoughGold);
+        }
+        let slots = self.add(Item::new(0, reference, quantity))?;
+        self.character.gold -= price;
+        Ok(slots)
+    }
+
+    /// Sells items of an inventory slot for their `SellPrice`. Returns the sold items.
+    pub fn sell(&mut self, slot: u8, quantity: u16) -> Result<Item, Error> {
+        let item = self.remove(slot, quantity)?;
+        let price = self.reference(item.ref_item_id)?.common.sell_price;
+        self.character.gold = self.character.gold.saturating_add(price.saturating_mul(item.quantity as u64));
+        Ok(item)
+    }
+
+    fn reference(&self, id: u32) -> Result<&'c RefObjItem, Error> {
+        self.registry.items.get(id).ok_or(Error::UnknownItem(id))
+    }
+
+    /// Checks whether an item may be put into a slot. Equipment and avatar slots only take
+    /// items which fit them and the character; the slots in `moving` are being emptied.
+    fn check_slot(&self, item: &Item, (container, slot): (Container, u8), moving: &[(Container, u8)]) -> Result<(), Error> {
+        let reference = self.reference(item.ref_item_id)?;
+        let fits = match container {
+            Container::Inventory if slot < EQUIPMENT_SLOTS => {
+                equipment_slots(reference).contains(&slot) && self.wearable(reference) && !self.blocked_by_weapon(reference, slot, moving)
+            }
+            Container::Avatar => avatar_slot(reference) == Some(slot) && self.wearable(reference),
+            _ => true,
+        };
+        if fits { Ok(()) } else { Err(Error::CannotEquip(item.ref_item_id)) }
+    }
+
+    /// Whether the character meets the level, gender and race requirements of an item.
+    fn wearable(&self, item: &RefObjItem) -> bool {
+        let model = self.registry.characters.get(self.character.ref_obj_id);
+        item.common.req_levels[0].1 <= self.character.level
+            && model.is_none_or(|model| {
+                (item.req_gender == GENDER_ANY || item.req_gender == model.gender) && item.common.country == model.common.country
+            })
+    }
+
+    /// Whether a two-handed weapon and a shield would be worn together.
+    fn blocked_by_weapon(&self, item: &RefObjItem, slot: u8, moving: &[(Container, u8)]) -> bool {
+        let worn = |slot| {
+            if moving.contains(&(Container::Inventory, slot)) {
+                return None;
+            }
+            self.get(Container::Inventory, slot).and_then(|item| self.registry.items.get(item.ref_item_id))
+        };
+        match slot {
+            SLOT_WEAPON => item.two_handed && worn(SLOT_SHIELD).is_some(),
+            SLOT_SHIELD => worn(SLOT_WEAPON).is_some_and(|weapon| weapon.two_handed),
+            _ => false,
+        }
+    }
+
+    fn items(&self, container: Container) -> &Vec<Item> {
+        match container {
+            Container::Inventory => &self.character.items,
+            Container::Avatar => &self.character.avatars,
+            Container::Storage => &self.character.storage,
+        }
+    }
+
+    fn items_mut(&mut self, container: Container) -> &mut Vec<Item> {
+        match container {
+            Container::Inventory => &mut self.character.items,
+            Container::Avatar => &mut self.character.avatars,
+            Container::Storage => &mut self.character.storage,
+        }
+    }
+
+    /// Returns the item in a slot known to be occupied.
+    fn item_mut(&mut self, (container, slot): (Container, u8)) -> &mut Item {
+        self.items_mut(container).iter_mut().find(|item| item.slot == slot).expect("slot is occupied")
+    }
+
+    /// Moves an item which already got its new slot to the container of that slot.
+    fn relocate(&mut self, from: (Container, u8), to: (Container, u8)) {
+        if from.0 != to.0 {
+            let index = self.items(from.0).iter().position(|item| item.slot == to.1).expect("item was moved");
+            let item = self.items_mut(from.0).remove(index);
+            self.items_mut(to.0).push(item);
+        }
+    }
+
+    /// Returns the items in the inventory slots after the equipment, ordered by slot.
+    fn inventory_items(&self) -> Vec<&Item> {
+        let mut items: Vec<&Item> = self.character.items.iter().filter(|item| item.slot >= EQUIPMENT_SLOTS).collect();
+        items.sort_by_key(|item| item.slot);
+        items
+    }
+
+    fn free_slots(&self) -> Vec<u8> {
+        (EQUIPMENT_SLOTS..self.slots(Container::Inventory)).filter(|slot| self.get(Container::Inventory, *slot).is_none()).collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::character::store::tests::character;
+    use crate::textdata::object;
+
+    use super::*;
+
+    /// Builds an item of the Chinese race with given `TypeID` and further values at their columns.
+    fn item(id: &str, code_name: &str, type_id: [&str; 4], values: &[(usize, &str)]) -> RefObjItem {
+        let mut columns = vec![(0, "1"), (1, id), (2, code_name), (9, type_id[0]), (10, type_id[1]), (11, type_id[2]), (12, type_id[3]), (57, "1"), (58, "2")];
+        columns.extend_from_slice(values);
+        object::tests::item(&columns)
+    }
+
+    /// Returns reference data with a male Chinese model and some of its equipment, avatars,
+    /// potions and global chatting items.
+    pub(crate) fn registry() -> Registry {
+        let model = object::tests::character(&[(0, "1"), (1, "1907"), (2, "CHAR_CH_MAN_ADVENTURER"), (9, "1"), (10, "1"), (58, "1"), (93, "45")]);
+        Registry::new(vec![model], vec![
+            item("3633", "ITEM_CH_SWORD_01_A_DEF", ["3", "1", "6", "2"], &[(64, "32")]),
+            item("3700", "ITEM_CH_BLADE_01_A_DEF", ["3", "1", "6", "3"], &[(93, "1")]),
+            item("3710", "ITEM_CH_SHIELD_01_A_DEF", ["3", "1", "4", "1"], &[]),
+            item("3640", "ITEM_CH_M_CLOTHES_01_BA_A_DEF", ["3", "1", "1", "3"], &[(58, "1")]),
+            item("3643", "ITEM_CH_W_CLOTHES_01_BA_A_DEF", ["3", "1", "1", "3"], &[(58, "0")]),
+            item("3650", "ITEM_CH_M_CLOTHES_02_BA_A_DEF", ["3", "1", "1", "3"], &[(33, "10")]),
+            item("10730", "ITEM_EU_SWORD_01_A_DEF", ["3", "1", "6", "7"], &[(14, "1")]),
+            item("23000", "ITEM_CH_M_HAT_AVATAR", ["3", "1", "13", "1"], &[]),
+            item("62", "ITEM_ETC_HP_POTION_01", ["3", "3", "1", "1"], &[(26, "10"), (31, "4"), (57, "50")]),
+            item("7400", "ITEM_MALL_GLOBAL_CHATTING", ["3", "3", "13", "1"], &[(57, "10")]),
+        ], Vec::new()).unwrap()
+    }
+
+    fn slots(items: &[Item]) -> Vec<(u8, u32, u16)> {
+        let mut slots: Vec<_> = items.iter().map(|item| (item.slot, item.ref_item_id, item.quantity)).collect();
+        slots.sort();
+        slots
+    }
+
+    #[test]
+    fn maps_equipment_slots() {
+        let registry = registry();
+        let slots_of = |id| equipment_slots(registry.items.get(id).unwrap());
+        assert_eq!(slots_of(3633), &[SLOT_WEAPON]);
+        assert_eq!(slots_of(3640), &[1]);
+        assert_eq!(slots_of(3710), &[SLOT_SHIELD]);
+        assert!(slots_of(62).is_empty());
+        assert_eq!(avatar_slot(registry.items.get(23000).unwrap()), Some(0));
+    }
+
+    #[test]
+    fn equips_items() {
+        let registry = registry();
+        let mut character = character(1, "Rusty");
+        for (slot, id) in [(14, 3640), (15, 3643), (16, 3650), (17, 10730), (18, 3700), (19, 3710)] {
+            character.items.push(Item { slot, ref_item_id: id, quantity: 1, ..Item::default() });
+        }
+        let mut inventory = Inventory::new(&mut character, &registry);
+        let inventory_move = |inventory: &mut Inventory, from, to| {
+            inventory.move_item((Container::Inventory, from), (Container::Inventory, to), 0)
+        };
+        inventory_move(&mut inventory, 6, 13).unwrap();
+        inventory_move(&mut inventory, 14, 1).unwrap();
+        assert_eq!(inventory_move(&mut inventory, 15, 1), Err(Error::CannotEquip(3643)));
+        assert_eq!(inventory_move(&mut inventory, 16, 1), Err(Error::CannotEquip(3650)));
+        assert_eq!(inventory_move(&mut inventory, 17, 6), Err(Error::CannotEquip(10730)));
+        assert_eq!(inventory_move(&mut inventory, 13, 5), Err(Error::CannotEquip(3633)));
+
+        inventory_move(&mut inventory, 19, SLOT_SHIELD).unwrap();
+        assert_eq!(inventory_move(&mut inventory, 18, 6), Err(Error::CannotEquip(3700)));
+        inventory_move(&mut inventory, 13, 6).unwrap();
+        assert_eq!(inventory_move(&mut inventory, 18, 6), Err(Error::CannotEquip(3700)));
+        inventory_move(&mut inventory, SLOT_SHIELD, 19).unwrap();
+        inventory_move(&mut inventory, 18, 6).unwrap();
+        assert_eq!(inventory_move(&mut inventory, 19, SLOT_SHIELD), Err(Error::CannotEquip(3710)));
+        assert_eq!(inventory_move(&mut inventory, 30, 31), Err(Error::EmptySlot(30)));
+        assert_eq!(inventory_move(&mut inventory, 13, 58), Err(Error::InvalidSlot(58)));
+
+        assert_eq!(slots(&character.items), vec![
+            (1, 3640, 1), (6, 3700, 1), (15, 3643, 1), (16, 3650, 1), (17, 10730, 1), (18, 3633, 1), (19, 3710, 1),
+        ]);
+    }
+
+    #[test]
+    fn stacks_items() {
+        let registry = registry();
+        let mut character = character(1, "Rusty");
+        let potion = Item::new(0, registry.items.get(62).unwrap(), 120);
+        let mut inventory = Inventory::new(&mut character, &registry);
+        assert_eq!(inventory.add(potion.clone()), Ok(vec![13, 14, 15]));
+        let inventory_move = |inventory: &mut Inventory, from, to, quantity| {
+            inventory.move_item((Container::Inventory, from), (Container::Inventory, to), quantity)
+        };
+        inventory_move(&mut inventory, 14, 20, 10).unwrap();
+        inventory_move(&mut inventory, 20, 15, 0).unwrap();
+        inventory_move(&mut inventory, 13, 14, 0).unwrap();
+        assert_eq!(inventory_move(&mut inventory, 15, 16, 31), Err(Error::InvalidQuantity(31)));
+        assert_eq!(inventory_move(&mut inventory, 15, 6, 0), Err(Error::CannotEquip(62)));
+        assert_eq!(inventory.remove(6, 0), Err(Error::InvalidSlot(6)));
+        assert_eq!(inventory.remove(13, 15).map(|item| item.quantity), Ok(15));
+        assert_eq!(slots(&character.items), vec![(6, 3633, 1), (13, 62, 25), (14, 62, 50), (15, 62, 30)]);
+
+        let mut inventory = Inventory::new(&mut character, &registry);
+        assert_eq!(inventory.add(Item { quantity: 45 + 42 * 50 + 1, ..potion.clone() }), Err(Error::InventoryFull));
+        assert_eq!(inventory.get(Container::Inventory, 13).unwrap().quantity, 25);
+        assert_eq!(inventory.add(Item { quantity: 45, ..potion }), Ok(vec![13, 15]));
+    }
+}
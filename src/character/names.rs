@@ -0,0 +1,62 @@
+use crate::character::errors::Error;
+
+/// Rules for character names: their length, allowed characters and names reserved for staff.
+#[derive(Debug, Clone)]
+pub struct NameRules {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Lowercase names which can't be used, also not decorated with digits or underscores,
+    /// e.g. `GM_01` is rejected by `gm`.
+    pub reserved: Vec<String>,
+}
+
+impl Default for NameRules {
+    fn default() -> Self {
+        let reserved = ["gm", "admin", "administrator", "joymax", "system", "server", "notice", "support"];
+        NameRules { min_length: 3, max_length: 12, reserved: reserved.iter().map(|name| name.to_string()).collect() }
+    }
+}
+
+impl NameRules {
+    /// Checks a name, which has to start with a letter and may only contain ASCII letters,
+    /// digits and underscores.
+    pub fn validate(&self, name: &str) -> Result<(), Error> {
+        let length = name.chars().count();
+        if length < self.min_length {
+            return Err(Error::InvalidName("too short"));
+        }
+        if length > self.max_length {
+            return Err(Error::InvalidName("too long"));
+        }
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(Error::InvalidName("contains invalid characters"));
+        }
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Err(Error::InvalidName("doesn't start with a letter"));
+        }
+        let letters: String = name.chars().filter(char::is_ascii_alphabetic).map(|c| c.to_ascii_lowercase()).collect();
+        if self.reserved.contains(&letters) {
+            return Err(Error::InvalidName("reserved"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_names() {
+        let rules = NameRules::default();
+        assert_eq!(rules.validate("Rusty_01"), Ok(()));
+        assert_eq!(rules.validate("Jo"), Err(Error::InvalidName("too short")));
+        assert_eq!(rules.validate("ThisNameIsTooLong"), Err(Error::InvalidName("too long")));
+        assert_eq!(rules.validate("Rüsty"), Err(Error::InvalidName("contains invalid characters")));
+        assert_eq!(rules.validate("Rusty Road"), Err(Error::InvalidName("contains invalid characters")));
+        assert_eq!(rules.validate("1Rusty"), Err(Error::InvalidName("doesn't start with a letter")));
+        assert_eq!(rules.validate("GM_01"), Err(Error::InvalidName("reserved")));
+        assert_eq!(rules.validate("Admin"), Err(Error::InvalidName("reserved")));
+        assert_eq!(rules.validate("Sigma"), Ok(()));
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::{Duration, SystemTime};
+
+use crate::textdata::object::RefObjItem;
+use crate::world::position::Position;
+
+/// Equipment slot of the chest armor.
+pub const SLOT_CHEST: u8 = 1;
+/// Equipment slot of the leg armor.
+pub const SLOT_LEGS: u8 = 4;
+/// Equipment slot of the foot armor.
+pub const SLOT_FOOT: u8 = 5;
+/// Equipment slot of the weapon.
+pub const SLOT_WEAPON: u8 = 6;
+/// Number of equipment slots, the inventory starts after them.
+pub const EQUIPMENT_SLOTS: u8 = 13;
+
+/// An item in a slot of a character's inventory, avatar inventory or storage.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Item {
+    pub slot: u8,
+    pub ref_item_id: u32,
+    pub plus: u8,
+    /// The white stats of equipment, 5 bits per stat.
+    pub variance: u64,
+    pub durability: u32,
+    /// Number of items in the stack, `1` for items which don't stack.
+    pub quantity: u16,
+    /// The blue stats of equipment as type and value.
+    pub magic_options: Vec<(u32, u32)>,
+}
+
+impl Item {
+    /// Creates a plain item of given reference with full durability.
+    pub fn new(slot: u8, reference: &RefObjItem, quantity: u16) -> Item {
+        Item {
+            slot,
+            ref_item_id: reference.common.id,
+            durability: reference.durability.1 as u32,
+            quantity,
+            ..Item::default()
+        }
+    }
+}
+
+/// The level of a weapon or magic mastery.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mastery {
+    pub id: u32,
+    pub level: u8,
+}
+
+/// A character of an account as persisted in a [CharacterStore](crate::character::store::CharacterStore).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Character {
+    /// Assigned by the store, `0` until the character was created.
+    pub id: u32,
+    pub account: u32,
+    pub name: String,
+    /// The model of the character in `characterdata`.
+    pub ref_obj_id: u32,
+    /// Height in the upper and volume in the lower 4 bits.
+    pub scale: u8,
+    pub level: u8,
+    pub exp: u64,
+    pub gold: u64,
+    pub skill_points: u32,
+    pub strength: u16,
+    pub intelligence: u16,
+    pub stat_points: u16,
+    pub hp: u32,
+    pub mp: u32,
+    pub position: Position,
+    /// When the character gets deleted for good, if deletion was requested.
+    pub deletion: Option<SystemTime>,
+    /// Items in the equipment slots and the inventory following them.
+    pub items: Vec<Item>,
+    pub avatars: Vec<Item>,
+    pub storage: Vec<Item>,
+    pub masteries: Vec<Mastery>,
+    /// IDs of the learned skills.
+    pub skills: Vec<u32>,
+}
+
+impl Character {
+    /// Returns the time left until the character gets deleted, if it is being deleted.
+    pub fn deletion_remaining(&self, now: SystemTime) -> Option<Duration> {
+        self.deletion.map(|deletion| deletion.duration_since(now).unwrap_or_default())
+    }
+
+    /// Returns the equipped items.
+    pub fn equipment(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter().filter(|item| item.slot < EQUIPMENT_SLOTS)
+    }
+}
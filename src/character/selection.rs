@@ -0,0 +1,291 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::character::errors::Error;
+use crate::character::names::NameRules;
+use crate::character::record::{Character, Item, SLOT_CHEST, SLOT_FOOT, SLOT_LEGS, SLOT_WEAPON};
+use crate::character::store::CharacterStore;
+use crate::textdata::object::{RefObjChar, GENDER_ANY};
+use crate::textdata::registry::Registry;
+use crate::world::position::{Position, RegionId};
+
+/// Maximum number of characters per account, including the ones being deleted.
+pub const MAX_CHARACTERS: usize = 4;
+/// Time until a character is deleted for good after deletion was requested. It can be
+/// restored in the meantime.
+pub const DELETION_DELAY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// `Country` of chinese models and items.
+const COUNTRY_CHINESE: u8 = 0;
+/// `TypeID3` of weapons.
+const TYPE_WEAPON: u8 = 6;
+/// `TypeID3` of chinese and european armor, from garment/robe to armor/heavy armor.
+const TYPES_ARMOR: [u8; 6] = [1, 2, 3, 9, 10, 11];
+
+/// A character requested on the selection screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewCharacter {
+    pub name: String,
+    pub ref_obj_id: u32,
+    pub scale: u8,
+    pub chest: u32,
+    pub legs: u32,
+    pub foot: u32,
+    pub weapon: u32,
+}
+
+/// The actions of the character selection screen, validated against the reference data.
+pub struct CharacterSelection {
+    store: Arc<dyn CharacterStore>,
+    registry: Arc<Registry>,
+    rules: NameRules,
+}
+
+impl CharacterSelection {
+    pub fn new(store: Arc<dyn CharacterStore>, registry: Arc<Registry>, rules: NameRules) -> CharacterSelection {
+        CharacterSelection { store, registry, rules }
+    }
+
+    /// Returns the underlying store.
+    pub fn store(&self) -> &Arc<dyn CharacterStore> {
+        &self.store
+    }
+
+    /// Returns the reference data characters are validated against.
+    pub fn registry(&self) -> &Arc<Registry> {
+        &self.registry
+    }
+
+    /// Returns the characters of an account, after deleting the ones whose deletion delay
+    /// passed.
+    pub fn list(&self, account: u32, now: SystemTime) -> Result<Vec<Character>, Error> {
+        let (expired, characters): (Vec<_>, Vec<_>) = self.store.characters(account)?
+            .into_iter()
+            .partition(|character| character.deletion.is_some_and(|deletion| deletion <= now));
+        for character in expired {
+            info!("deleting character {} of account {}", character.name, account);
+            self.store.delete(character.id)?;
+        }
+        Ok(characters)
+    }
+
+    /// Checks whether a name follows the [NameRules] and isn't used yet.
+    pub fn check_name(&self, name: &str) -> Result<(), Error> {
+        self.rules.validate(name)?;
+        match self.store.find(name)? {
+            Some(_) => Err(Error::NameTaken(name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Creates a character at the starting town of its race, wearing the chosen equipment.
+    pub fn create(&self, account: u32, new: NewCharacter, now: SystemTime) -> Result<Character, Error> {
+        self.check_name(&new.name)?;
+        let model = self.registry.characters.get(new.ref_obj_id)
+            .filter(|model| model.common.type_id[..2] == [1, 1])
+            .ok_or(Error::InvalidModel(new.ref_obj_id))?;
+        let items = vec![
+            self.starting_item(model, SLOT_CHEST, new.chest, |t| TYPES_ARMOR.contains(&t[2]) && t[3] == 3)?,
+            self.starting_item(model, SLOT_LEGS, new.legs, |t| TYPES_ARMOR.contains(&t[2]) && t[3] == 4)?,
+            self.starting_item(model, SLOT_FOOT, new.foot, |t| TYPES_ARMOR.contains(&t[2]) && t[3] == 6)?,
+            self.starting_item(model, SLOT_WEAPON, new.weapon, |t| t[2] == TYPE_WEAPON)?,
+        ];
+        if self.list(account, now)?.len() >= MAX_CHARACTERS {
+            return Err(Error::CharacterLimit);
+        }
+        let position = if model.common.country == COUNTRY_CHINESE {
+            // Jangan
+            Position::new(RegionId(0x61A8), 980.0, 0.0, 1330.0)
+        } else {
+            // Constantinople
+            Position::new(RegionId(0x694F), 1000.0, 0.0, 1000.0)
+        };
+        let character = self.store.create(Character {
+            id: 0,
+            account,
+            name: new.name,
+            ref_obj_id: new.ref_obj_id,
+            scale: new.scale,
+            level: 1,
+            exp: 0,
+            gold: 0,
+            skill_points: 0,
+            strength: 20,
+            intelligence: 20,
+            stat_points: 0,
+            hp: 200,
+            mp: 200,
+            position,
+            deletion: None,
+            items,
+            avatars: Vec::new(),
+            storage: Vec::new(),
+            masteries: Vec::new(),
+            skills: model.default_skills.iter().copied().filter(|skill| *skill != 0).collect(),
+        })?;
+        info!("created character {} of account {}", character.name, account);
+        Ok(character)
+    }
+
+    /// Starts the deletion timer of a character (see [DELETION_DELAY]).
+    pub fn delete(&self, account: u32, name: &str, now: SystemTime) -> Result<(), Error> {
+        let mut character = self.owned(account, name)?;
+        if character.deletion.is_none() {
+            character.deletion = Some(now + DELETION_DELAY);
+            self.store.save(&character)?;
+        }
+        Ok(())
+    }
+
+    /// Stops the deletion timer of a character.
+    pub fn restore(&self, account: u32, name: &str) -> Result<(), Error> {
+        let mut character = self.owned(account, name)?;
+        if character.deletion.take().is_some() {
+            self.store.save(&character)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the character of an account which joins the game. Characters being deleted have
+    /// to be restored first.
+    pub fn select(&self, account: u32, name: &str) -> Result<Character, Error> {
+        let character = self.owned(account, name)?;
+        match character.deletion {
+            Some(_) => Err(Error::BeingDeleted(character.name)),
+            None => Ok(character),
+        }
+    }
+
+    fn owned(&self, account: u32, name: &str) -> Result<Character, Error> {
+        self.store.find(name)?
+            .filter(|character| character.account == account)
+            .ok_or_else(|| Error::NotFound(name.to_string()))
+    }
+
+    /// Checks that an item is starting equipment of given kind which the model can wear: an
+    /// equipment (`TypeID` 3, 1) of its race and gender without level requirement.
+    fn starting_item(&self, model: &RefObjChar, slot: u8, id: u32, kind: impl Fn(&[u8; 4]) -> bool) -> Result<Item, Error> {
+        let item = self.registry.items.get(id).ok_or(Error::InvalidItem(id))?;
+        let common = &item.common;
+        let valid = common.type_id[..2] == [3, 1]
+            && kind(&common.type_id)
+            && common.country == model.common.country
+            && (item.req_gender == GENDER_ANY || item.req_gender == model.gender)
+            && common.req_levels[0].1 <= 1;
+        if !valid {
+            return Err(Error::InvalidItem(id));
+        }
+        Ok(Item::new(slot, item, 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::character::store::MemoryStore;
+    use crate::textdata::object;
+    use crate::textdata::object::RefObjItem;
+
+    use super::*;
+
+    fn model(id: &str, code_name: &str, type_id: [&str; 4], country: &str) -> RefObjChar {
+        object::tests::character(&[
+            (0, "1"), (1, id), (2, code_name), (9, type_id[0]), (10, type_id[1]), (11, type_id[2]), (12, type_id[3]),
+            (14, country), (58, "1"),
+        ])
+    }
+
+    fn item(id: &str, code_name: &str, type_id: [&str; 4], country: &str, gender: &str) -> RefObjItem {
+        object::tests::item(&[
+            (0, "1"), (1, id), (2, code_name), (9, type_id[0]), (10, type_id[1]), (11, type_id[2]), (12, type_id[3]),
+            (14, country), (58, gender),
+        ])
+    }
+
+    fn selection() -> CharacterSelection {
+        let registry = Registry::new(
+            vec![
+                model("1907", "CHAR_CH_MAN_ADVENTURER", ["1", "1", "0", "0"], "0"),
+                model("14875", "CHAR_EU_MAN_NOBLE", ["1", "1", "0", "0"], "1"),
+                model("1954", "MOB_CH_MANGNYANG", ["1", "2", "1", "1"], "0"),
+            ],
+            vec![
+                item("3633", "ITEM_CH_SWORD_01_A_DEF", ["3", "1", "6", "2"], "0", "2"),
+                item("3640", "ITEM_CH_M_CLOTHES_01_BA_A_DEF", ["3", "1", "1", "3"], "0", "1"),
+                item("3641", "ITEM_CH_M_CLOTHES_01_LA_A_DEF", ["3", "1", "1", "4"], "0", "1"),
+                item("3642", "ITEM_CH_M_CLOTHES_01_FA_A_DEF", ["3", "1", "1", "6"], "0", "1"),
+                item("3643", "ITEM_CH_W_CLOTHES_01_BA_A_DEF", ["3", "1", "1", "3"], "0", "0"),
+                item("10730", "ITEM_EU_SWORD_01_A_DEF", ["3", "1", "6", "7"], "1", "2"),
+            ],
+            vec![],
+        ).unwrap();
+        CharacterSelection::new(Arc::new(MemoryStore::new()), Arc::new(registry), NameRules::default())
+    }
+
+    fn new_character(name: &str) -> NewCharacter {
+        NewCharacter { name: name.to_string(), ref_obj_id: 1907, scale: 0x44, chest: 3640, legs: 3641, foot: 3642, weapon: 3633 }
+    }
+
+    #[test]
+    fn creates_characters() {
+        let selection = selection();
+        let now = SystemTime::now();
+        let character = selection.create(1, new_character("Rusty"), now).unwrap();
+        assert_eq!(character.id, 1);
+        assert_eq!(character.position.region, RegionId(0x61A8));
+        assert_eq!(character.equipment().map(|item| (item.slot, item.ref_item_id)).collect::<Vec<_>>(), vec![
+            (SLOT_CHEST, 3640), (SLOT_LEGS, 3641), (SLOT_FOOT, 3642), (SLOT_WEAPON, 3633),
+        ]);
+        assert_eq!(selection.list(1, now).unwrap(), vec![character]);
+        assert!(selection.list(2, now).unwrap().is_empty());
+
+        assert_eq!(selection.check_name("RUSTY"), Err(Error::NameTaken("RUSTY".to_string())));
+        assert_eq!(selection.create(2, new_character("rusty"), now), Err(Error::NameTaken("rusty".to_string())));
+        assert_eq!(selection.create(1, new_character("Admin"), now), Err(Error::InvalidName("reserved")));
+        for name in ["Road", "Silk", "Way"] {
+            selection.create(1, new_character(name), now).unwrap();
+        }
+        assert_eq!(selection.create(1, new_character("Fifth"), now), Err(Error::CharacterLimit));
+    }
+
+    #[test]
+    fn validates_models_and_equipment() {
+        let selection = selection();
+        let now = SystemTime::now();
+        let create = |change: fn(&mut NewCharacter)| {
+            let mut new = new_character("Rusty");
+            change(&mut new);
+            selection.create(1, new, now).err()
+        };
+        assert_eq!(create(|new| new.ref_obj_id = 1954), Some(Error::InvalidModel(1954)));
+        assert_eq!(create(|new| new.ref_obj_id = 1), Some(Error::InvalidModel(1)));
+        // a european model can't wear chinese clothes
+        assert_eq!(create(|new| new.ref_obj_id = 14875), Some(Error::InvalidItem(3640)));
+        // female clothes
+        assert_eq!(create(|new| new.chest = 3643), Some(Error::InvalidItem(3643)));
+        // legs worn as chest
+        assert_eq!(create(|new| new.chest = 3641), Some(Error::InvalidItem(3641)));
+        assert_eq!(create(|new| new.weapon = 10730), Some(Error::InvalidItem(10730)));
+        assert_eq!(create(|new| new.weapon = 3640), Some(Error::InvalidItem(3640)));
+        assert_eq!(create(|_| {}), None);
+    }
+
+    #[test]
+    fn deletes_and_restores_characters() {
+        let selection = selection();
+        let now = SystemTime::now();
+        selection.create(1, new_character("Rusty"), now).unwrap();
+        assert_eq!(selection.delete(2, "Rusty", now), Err(Error::NotFound("Rusty".to_string())));
+
+        assert_eq!(selection.select(1, "rusty").unwrap().name, "Rusty");
+        selection.delete(1, "rusty", now).unwrap();
+        assert_eq!(selection.select(1, "Rusty"), Err(Error::BeingDeleted("Rusty".to_string())));
+        let characters = selection.list(1, now).unwrap();
+        assert_eq!(characters[0].deletion_remaining(now), Some(DELETION_DELAY));
+        selection.restore(1, "Rusty").unwrap();
+        assert_eq!(selection.list(1, now).unwrap()[0].deletion, None);
+
+        selection.delete(1, "Rusty", now).unwrap();
+        assert_eq!(selection.list(1, now + DELETION_DELAY).unwrap(), vec![]);
+        assert_eq!(selection.check_name("Rusty"), Ok(()));
+    }
+}
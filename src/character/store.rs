@@ -0,0 +1,154 @@
+pub mod sqlite;
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::character::errors::Error;
+use crate::character::record::Character;
+
+/// Persists the characters of all accounts. Names are unique regardless of their case.
+pub trait CharacterStore: Send + Sync {
+    /// Returns the characters of given account ordered by their ID, including the ones being
+    /// deleted.
+    fn characters(&self, account: u32) -> Result<Vec<Character>, Error>;
+
+    /// Returns the character with given name of any account.
+    fn find(&self, name: &str) -> Result<Option<Character>, Error>;
+
+    /// Stores a new character and returns it with its assigned ID.
+    fn create(&self, character: Character) -> Result<Character, Error>;
+
+    /// Updates an existing character.
+    fn save(&self, character: &Character) -> Result<(), Error>;
+
+    /// Removes a character for good.
+    fn delete(&self, id: u32) -> Result<(), Error>;
+}
+
+/// A [CharacterStore] which keeps the characters in memory only, e.g. for tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    characters: Mutex<BTreeMap<u32, Character>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl CharacterStore for MemoryStore {
+    fn characters(&self, account: u32) -> Result<Vec<Character>, Error> {
+        let characters = self.characters.lock().unwrap();
+        Ok(characters.values().filter(|character| character.account == account).cloned().collect())
+    }
+
+    fn find(&self, name: &str) -> Result<Option<Character>, Error> {
+        let characters = self.characters.lock().unwrap();
+        Ok(characters.values().find(|character| character.name.eq_ignore_ascii_case(name)).cloned())
+    }
+
+    fn create(&self, mut character: Character) -> Result<Character, Error> {
+        let mut characters = self.characters.lock().unwrap();
+        if characters.values().any(|existing| existing.name.eq_ignore_ascii_case(&character.name)) {
+            return Err(Error::NameTaken(character.name));
+        }
+        character.id = characters.keys().next_back().map_or(1, |id| id + 1);
+        characters.insert(character.id, character.clone());
+        Ok(character)
+    }
+
+    fn save(&self, character: &Character) -> Result<(), Error> {
+        match self.characters.lock().unwrap().get_mut(&character.id) {
+            Some(existing) => {
+                *existing = character.clone();
+                Ok(())
+            }
+            None => Err(Error::NotFound(character.name.clone())),
+        }
+    }
+
+    fn delete(&self, id: u32) -> Result<(), Error> {
+        self.characters.lock().unwrap().remove(&id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::character::record::{Item, Mastery};
+    use crate::world::position::{Position, RegionId};
+
+    use super::*;
+
+    pub(crate) fn character(account: u32, name: &str) -> Character {
+        Character {
+            id: 0,
+            account,
+            name: name.to_string(),
+            ref_obj_id: 1907,
+            scale: 0x44,
+            level: 1,
+            exp: 0,
+            gold: 0,
+            skill_points: 0,
+            strength: 20,
+            intelligence: 20,
+            stat_points: 0,
+            hp: 200,
+            mp: 200,
+            position: Position::new(RegionId(0x61A8), 980.0, 0.0, 1330.0),
+            deletion: None,
+            items: vec![Item { slot: 6, ref_item_id: 3633, plus: 0, quantity: 1, ..Item::default() }],
+            avatars: Vec::new(),
+            storage: Vec::new(),
+            masteries: Vec::new(),
+            skills: vec![1, 2],
+        }
+    }
+
+    /// Checks the behaviour every [CharacterStore] has to share.
+    pub(crate) fn exercise(store: &dyn CharacterStore) {
+        let rusty = store.create(character(1, "Rusty")).unwrap();
+        let road = store.create(character(1, "Road")).unwrap();
+        store.create(character(2, "Other")).unwrap();
+        assert!(rusty.id != 0 && rusty.id < road.id);
+        assert_eq!(store.create(character(2, "RUSTY")), Err(Error::NameTaken("RUSTY".to_string())));
+        assert_eq!(store.characters(1).unwrap(), vec![rusty.clone(), road.clone()]);
+        assert_eq!(store.find("rusty").unwrap(), Some(rusty.clone()));
+        assert_eq!(store.find("Nobody").unwrap(), None);
+
+        let mut changed = rusty.clone();
+        changed.level = 12;
+        changed.exp = u64::MAX / 4;
+        changed.gold = 1_000_000_000_000;
+        changed.position = Position::new(RegionId(0x61A9), 10.5, -3.25, 1900.75);
+        changed.deletion = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        changed.items = vec![
+            Item { slot: 6, ref_item_id: 3634, plus: 3, variance: 0x3FFF_FFFF, durability: 30, quantity: 1, magic_options: vec![(1, 2), (3, 4)] },
+            Item { slot: 13, ref_item_id: 62, quantity: 50, ..Item::default() },
+        ];
+        changed.avatars = vec![Item { slot: 0, ref_item_id: 23000, quantity: 1, ..Item::default() }];
+        changed.storage = vec![Item { slot: 0, ref_item_id: 62, quantity: 20, ..Item::default() }];
+        changed.masteries = vec![Mastery { id: 257, level: 12 }];
+        changed.skills = vec![1, 3];
+        store.save(&changed).unwrap();
+        assert_eq!(store.find("Rusty").unwrap(), Some(changed.clone()));
+
+        let mut missing = character(1, "Missing");
+        missing.id = 999;
+        assert_eq!(store.save(&missing), Err(Error::NotFound("Missing".to_string())));
+
+        store.delete(changed.id).unwrap();
+        assert_eq!(store.characters(1).unwrap(), vec![road]);
+        assert_eq!(store.find("Rusty").unwrap(), None);
+        store.create(character(1, "Rusty")).unwrap();
+    }
+
+    #[test]
+    fn stores_characters_in_memory() {
+        exercise(&MemoryStore::new());
+    }
+}
@@ -0,0 +1,314 @@
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+use byteorder::{ByteOrder, LE};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+
+use crate::character::errors::Error;
+use crate::character::record::{Character, Item, Mastery};
+use crate::character::store::CharacterStore;
+use crate::world::position::{Position, RegionId};
+
+/// Tables of the database, created if they don't exist yet. Names are unique regardless of
+/// their case; items, masteries and skills are deleted together with their character.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS characters (
+        id INTEGER PRIMARY KEY,
+        account INTEGER NOT NULL,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        ref_obj_id INTEGER NOT NULL,
+        scale INTEGER NOT NULL,
+        level INTEGER NOT NULL,
+        exp INTEGER NOT NULL,
+        gold INTEGER NOT NULL,
+        skill_points INTEGER NOT NULL,
+        strength INTEGER NOT NULL,
+        intelligence INTEGER NOT NULL,
+        stat_points INTEGER NOT NULL,
+        hp INTEGER NOT NULL,
+        mp INTEGER NOT NULL,
+        region INTEGER NOT NULL,
+        x REAL NOT NULL,
+        y REAL NOT NULL,
+        z REAL NOT NULL,
+        deletion INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS characters_account ON characters (account);
+    CREATE TABLE IF NOT EXISTS items (
+        character_id INTEGER NOT NULL REFERENCES characters (id) ON DELETE CASCADE,
+        container INTEGER NOT NULL,
+        slot INTEGER NOT NULL,
+        ref_item_id INTEGER NOT NULL,
+        plus INTEGER NOT NULL,
+        variance INTEGER NOT NULL,
+        durability INTEGER NOT NULL,
+        quantity INTEGER NOT NULL,
+        magic_options BLOB NOT NULL,
+        PRIMARY KEY (character_id, container, slot)
+    );
+    CREATE TABLE IF NOT EXISTS masteries (
+        character_id INTEGER NOT NULL REFERENCES characters (id) ON DELETE CASCADE,
+        id INTEGER NOT NULL,
+        level INTEGER NOT NULL,
+        PRIMARY KEY (character_id, id)
+    );
+    CREATE TABLE IF NOT EXISTS skills (
+        character_id INTEGER NOT NULL REFERENCES characters (id) ON DELETE CASCADE,
+        id INTEGER NOT NULL,
+        PRIMARY KEY (character_id, id)
+    );
+";
+
+const COLUMNS: &str = "id, account, name, ref_obj_id, scale, level, exp, gold, skill_points, strength, intelligence, \
+    stat_points, hp, mp, region, x, y, z, deletion";
+
+/// Values of `items.container`.
+const CONTAINER_INVENTORY: u8 = 0;
+const CONTAINER_AVATAR: u8 = 1;
+const CONTAINER_STORAGE: u8 = 2;
+
+/// Parameters of the columns following the ID in [COLUMNS], which [write_character] binds.
+const VALUES: &str = "?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18";
+
+/// A [CharacterStore] in an embedded SQLite database. Each character is saved as a whole
+/// within a transaction.
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens or creates the database at given path.
+    pub fn open(path: &Path) -> Result<SqliteStore, Error> {
+        SqliteStore::new(Connection::open(path)?)
+    }
+
+    /// Creates a database which only lives in memory, e.g. for tests.
+    pub fn in_memory() -> Result<SqliteStore, Error> {
+        SqliteStore::new(Connection::open_in_memory()?)
+    }
+
+    fn new(connection: Connection) -> Result<SqliteStore, Error> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteStore { connection: Mutex::new(connection) })
+    }
+}
+
+impl CharacterStore for SqliteStore {
+    fn characters(&self, account: u32) -> Result<Vec<Character>, Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let characters = {
+            let mut statement = transaction.prepare(&format!("SELECT {} FROM characters WHERE account = ?1 ORDER BY id", COLUMNS))?;
+            let rows = statement.query_map([account], read_character)?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        characters.into_iter().map(|character| load_children(&transaction, character)).collect()
+    }
+
+    fn find(&self, name: &str) -> Result<Option<Character>, Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let character = transaction
+            .query_row(&format!("SELECT {} FROM characters WHERE name = ?1", COLUMNS), [name], read_character)
+            .optional()?;
+        character.map(|character| load_children(&transaction, character)).transpose()
+    }
+
+    fn create(&self, mut character: Character) -> Result<Character, Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let taken: bool = transaction.query_row("SELECT EXISTS (SELECT 1 FROM characters WHERE name = ?1)", [&character.name], |row| row.get(0))?;
+        if taken {
+            return Err(Error::NameTaken(character.name));
+        }
+        character.id = 0;
+        write_character(&transaction, &format!("INSERT INTO characters ({}) VALUES (NULLIF(?19, 0), {})", COLUMNS, VALUES), &character)?;
+        character.id = transaction.last_insert_rowid() as u32;
+        write_children(&transaction, &character)?;
+        transaction.commit()?;
+        Ok(character)
+    }
+
+    fn save(&self, character: &Character) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let updated = write_character(&transaction, &format!("UPDATE characters SET ({}) = (?19, {}) WHERE id = ?19", COLUMNS, VALUES), character)?;
+        if updated == 0 {
+            return Err(Error::NotFound(character.name.clone()));
+        }
+        write_children(&transaction, character)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, id: u32) -> Result<(), Error> {
+        self.connection.lock().unwrap().execute("DELETE FROM characters WHERE id = ?1", [id])?;
+        Ok(())
+    }
+}
+
+/// Reads the columns of [COLUMNS], without the items, masteries and skills.
+fn read_character(row: &Row<'_>) -> rusqlite::Result<Character> {
+    let deletion: Option<i64> = row.get(18)?;
+    Ok(Character {
+        id: row.get(0)?,
+        account: row.get(1)?,
+        name: row.get(2)?,
+        ref_obj_id: row.get(3)?,
+        scale: row.get(4)?,
+        level: row.get(5)?,
+        exp: row.get::<_, i64>(6)? as u64,
+        gold: row.get::<_, i64>(7)? as u64,
+        skill_points: row.get(8)?,
+        strength: row.get(9)?,
+        intelligence: row.get(10)?,
+        stat_points: row.get(11)?,
+        hp: row.get(12)?,
+        mp: row.get(13)?,
+        position: Position::new(
+            RegionId(row.get(14)?),
+            row.get::<_, f64>(15)? as f32,
+            row.get::<_, f64>(16)? as f32,
+            row.get::<_, f64>(17)? as f32,
+        ),
+        deletion: deletion.map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds as u64)),
+        items: Vec::new(),
+        avatars: Vec::new(),
+        storage: Vec::new(),
+        masteries: Vec::new(),
+        skills: Vec::new(),
+    })
+}
+
+/// Executes an insert or update of a character, binding its ID to `?19` and the other columns
+/// to [VALUES]. Returns the number of changed rows.
+fn write_character(transaction: &Transaction<'_>, sql: &str, character: &Character) -> Result<usize, Error> {
+    let position = character.position;
+    let deletion = character.deletion
+        .map(|deletion| deletion.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64);
+    let changed = transaction.execute(sql, params![
+        character.account,
+        character.name,
+        character.ref_obj_id,
+        character.scale,
+        character.level,
+        character.exp as i64,
+        character.gold as i64,
+        character.skill_points,
+        character.strength,
+        character.intelligence,
+        character.stat_points,
+        character.hp,
+        character.mp,
+        position.region.0,
+        position.x as f64,
+        position.y as f64,
+        position.z as f64,
+        deletion,
+        character.id,
+    ])?;
+    Ok(changed)
+}
+
+fn load_children(transaction: &Transaction<'_>, mut character: Character) -> Result<Character, Error> {
+    let mut statement = transaction.prepare(
+        "SELECT container, slot, ref_item_id, plus, variance, durability, quantity, magic_options FROM items \
+        WHERE character_id = ?1 ORDER BY container, slot"
+    )?;
+    let items = statement.query_map([character.id], |row| {
+        let options: Vec<u8> = row.get(7)?;
+        let item = Item {
+            slot: row.get(1)?,
+            ref_item_id: row.get(2)?,
+            plus: row.get(3)?,
+            variance: row.get::<_, i64>(4)? as u64,
+            durability: row.get(5)?,
+            quantity: row.get(6)?,
+            magic_options: options.chunks_exact(8)
+                .map(|option| (LE::read_u32(&option[..4]), LE::read_u32(&option[4..])))
+                .collect(),
+        };
+        Ok((row.get::<_, u8>(0)?, item))
+    })?;
+    for item in items {
+        let (container, item) = item?;
+        match container {
+            CONTAINER_AVATAR => character.avatars.push(item),
+            CONTAINER_STORAGE => character.storage.push(item),
+            _ => character.items.push(item),
+        }
+    }
+    let mut statement = transaction.prepare("SELECT id, level FROM masteries WHERE character_id = ?1 ORDER BY id")?;
+    character.masteries = statement
+        .query_map([character.id], |row| Ok(Mastery { id: row.get(0)?, level: row.get(1)? }))?
+        .collect::<Result<_, _>>()?;
+    let mut statement = transaction.prepare("SELECT id FROM skills WHERE character_id = ?1 ORDER BY id")?;
+    character.skills = statement.query_map([character.id], |row| row.get(0))?.collect::<Result<_, _>>()?;
+    Ok(character)
+}
+
+/// Replaces the items, masteries and skills of a character.
+fn write_children(transaction: &Transaction<'_>, character: &Character) -> Result<(), Error> {
+    for table in ["items", "masteries", "skills"] {
+        transaction.execute(&format!("DELETE FROM {} WHERE character_id = ?1", table), [character.id])?;
+    }
+    let mut statement = transaction.prepare(
+        "INSERT INTO items (character_id, container, slot, ref_item_id, plus, variance, durability, quantity, magic_options) \
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+    )?;
+    let containers = [
+        (CONTAINER_INVENTORY, &character.items),
+        (CONTAINER_AVATAR, &character.avatars),
+        (CONTAINER_STORAGE, &character.storage),
+    ];
+    for (container, items) in containers {
+        for item in items {
+            let mut options = vec![0; item.magic_options.len() * 8];
+            for (option, (kind, value)) in options.chunks_exact_mut(8).zip(&item.magic_options) {
+                LE::write_u32(&mut option[..4], *kind);
+                LE::write_u32(&mut option[4..], *value);
+            }
+            statement.execute(params![
+                character.id, container, item.slot, item.ref_item_id, item.plus, item.variance as i64, item.durability,
+                item.quantity, options,
+            ])?;
+        }
+    }
+    let mut statement = transaction.prepare("INSERT INTO masteries (character_id, id, level) VALUES (?1, ?2, ?3)")?;
+    for mastery in &character.masteries {
+        statement.execute(params![character.id, mastery.id, mastery.level])?;
+    }
+    let mut statement = transaction.prepare("INSERT INTO skills (character_id, id) VALUES (?1, ?2)")?;
+    for skill in &character.skills {
+        statement.execute(params![character.id, skill])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::character::store::tests::{character, exercise};
+
+    use super::*;
+
+    #[test]
+    fn stores_characters_in_sqlite() {
+        exercise(&SqliteStore::in_memory().unwrap());
+    }
+
+    #[test]
+    fn persists_characters() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("characters.db");
+        let created = SqliteStore::open(&path).unwrap().create(character(1, "Rusty")).unwrap();
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.characters(1).unwrap(), vec![created.clone()]);
+
+        store.delete(created.id).unwrap();
+        let connection = store.connection.lock().unwrap();
+        let items: i64 = connection.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0)).unwrap();
+        assert_eq!(items, 0);
+    }
+}
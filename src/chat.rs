@@ -0,0 +1,4 @@
+pub mod errors;
+pub mod filter;
+pub mod groups;
+pub mod state;
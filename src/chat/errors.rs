@@ -0,0 +1,34 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(std::fmt::Debug, PartialEq)]
+pub enum Error {
+    /// The message is empty.
+    EmptyMessage,
+    /// The sender sent too many messages in a short time.
+    RateLimited,
+    /// No character with given name is in the game.
+    NotFound(String),
+    /// The sender isn't in a party.
+    NoParty,
+    /// The sender isn't in a guild.
+    NoGuild,
+    /// The inventory slot holds no global chatting item.
+    NoChatItem(u8),
+    /// Only game masters may use the channel.
+    NotAllowed,
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::EmptyMessage => write!(f, "message is empty"),
+            Error::RateLimited => write!(f, "too many messages"),
+            Error::NotFound(name) => write!(f, "character {} isn't in the game", name),
+            Error::NoParty => write!(f, "not in a party"),
+            Error::NoGuild => write!(f, "not in a guild"),
+            Error::NoChatItem(slot) => write!(f, "slot {} holds no global chatting item", slot),
+            Error::NotAllowed => write!(f, "channel is reserved to game masters"),
+        }
+    }
+}
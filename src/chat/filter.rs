@@ -0,0 +1,146 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::chat::errors::Error;
+
+/// Checks chat messages before they're delivered, e.g. to censor profanity or to stop spam.
+pub trait ChatFilter: Send + Sync {
+    /// Checks a message a session sends at given time. Returns the message to deliver, which
+    /// may differ from the sent one, or why it's rejected.
+    fn filter(&self, sender: &Uuid, message: &str, now: Instant) -> Result<String, Error>;
+
+    /// Forgets the state kept for a session, e.g. once it disconnected.
+    fn forget(&self, _session: &Uuid) {}
+}
+
+/// Applies several filters in order, each to the output of the previous one.
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn ChatFilter>>,
+}
+
+impl FilterChain {
+    pub fn new(filters: Vec<Box<dyn ChatFilter>>) -> FilterChain {
+        FilterChain { filters }
+    }
+}
+
+impl ChatFilter for FilterChain {
+    fn filter(&self, sender: &Uuid, message: &str, now: Instant) -> Result<String, Error> {
+        self.filters.iter().try_fold(message.to_string(), |message, filter| filter.filter(sender, &message, now))
+    }
+
+    fn forget(&self, session: &Uuid) {
+        self.filters.iter().for_each(|filter| filter.forget(session));
+    }
+}
+
+/// Replaces each character of forbidden words with `*`, regardless of their case.
+pub struct WordFilter {
+    /// The forbidden words in lowercase.
+    words: Vec<String>,
+}
+
+impl WordFilter {
+    pub fn new(words: impl IntoIterator<Item = String>) -> WordFilter {
+        let words = words.into_iter()
+            .map(|word| word.trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        WordFilter { words }
+    }
+
+    /// Reads the forbidden words from a list with one word per line.
+    pub fn parse(list: &str) -> WordFilter {
+        WordFilter::new(list.lines().map(String::from))
+    }
+}
+
+impl ChatFilter for WordFilter {
+    fn filter(&self, _sender: &Uuid, message: &str, _now: Instant) -> Result<String, Error> {
+        let mut chars: Vec<char> = message.chars().collect();
+        let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+        for word in &self.words {
+            let word: Vec<char> = word.chars().collect();
+            let mut start = 0;
+            while start + word.len() <= lower.len() {
+                if lower[start..start + word.len()] == word[..] {
+                    chars[start..start + word.len()].iter_mut().for_each(|c| *c = '*');
+                    start += word.len();
+                } else {
+                    start += 1;
+                }
+            }
+        }
+        Ok(chars.into_iter().collect())
+    }
+}
+
+/// Rejects messages of sessions which already sent a number of messages within a time window.
+pub struct RateLimit {
+    messages: usize,
+    window: Duration,
+    sent: Mutex<HashMap<Uuid, VecDeque<Instant>>>,
+}
+
+impl RateLimit {
+    pub fn new(messages: usize, window: Duration) -> RateLimit {
+        RateLimit { messages, window, sent: Mutex::default() }
+    }
+}
+
+impl Default for RateLimit {
+    /// Allows 5 messages within 5 seconds.
+    fn default() -> Self {
+        RateLimit::new(5, Duration::from_secs(5))
+    }
+}
+
+impl ChatFilter for RateLimit {
+    fn filter(&self, sender: &Uuid, message: &str, now: Instant) -> Result<String, Error> {
+        let mut sent = self.sent.lock().unwrap();
+        let times = sent.entry(*sender).or_default();
+        while times.front().is_some_and(|time| now.duration_since(*time) >= self.window) {
+            times.pop_front();
+        }
+        if times.len() >= self.messages {
+            return Err(Error::RateLimited);
+        }
+        times.push_back(now);
+        Ok(message.to_string())
+    }
+
+    fn forget(&self, session: &Uuid) {
+        self.sent.lock().unwrap().remove(session);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn censors_words() {
+        let filter = WordFilter::parse("darn\n\n Heck \n");
+        let sender = Uuid::new_v4();
+        assert_eq!(filter.filter(&sender, "DARN it, what the heck", Instant::now()), Ok("**** it, what the ****".to_string()));
+        assert_eq!(filter.filter(&sender, "hello", Instant::now()), Ok("hello".to_string()));
+    }
+
+    #[test]
+    fn limits_message_rate() {
+        let chain = FilterChain::new(vec![Box::new(RateLimit::new(2, Duration::from_secs(5))), Box::new(WordFilter::parse("darn"))]);
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+        assert_eq!(chain.filter(&a, "darn", now), Ok("****".to_string()));
+        assert!(chain.filter(&a, "hi", now + Duration::from_secs(1)).is_ok());
+        assert_eq!(chain.filter(&a, "hi", now + Duration::from_secs(2)), Err(Error::RateLimited));
+        assert!(chain.filter(&b, "hi", now + Duration::from_secs(2)).is_ok());
+        assert!(chain.filter(&a, "hi", now + Duration::from_secs(5)).is_ok());
+        chain.forget(&a);
+        assert!(chain.filter(&a, "hi", now + Duration::from_secs(5)).is_ok());
+    }
+}
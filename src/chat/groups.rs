@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+/// Memberships of sessions in groups sharing a chat channel, like parties or guilds. Each
+/// session is a member of at most one group.
+#[derive(Debug, Default)]
+pub struct Groups {
+    groups: HashMap<Uuid, u32>,
+    members: HashMap<u32, Vec<Uuid>>,
+}
+
+impl Groups {
+    pub fn new() -> Groups {
+        Groups::default()
+    }
+
+    /// Adds a session to a group, leaving its previous one.
+    pub fn join(&mut self, group: u32, session: Uuid) {
+        self.leave(&session);
+        self.groups.insert(session, group);
+        self.members.entry(group).or_default().push(session);
+    }
+
+    /// Removes a session from its group. Returns the group it left.
+    pub fn leave(&mut self, session: &Uuid) -> Option<u32> {
+        let group = self.groups.remove(session)?;
+        if let Some(members) = self.members.get_mut(&group) {
+            members.retain(|member| member != session);
+            if members.is_empty() {
+                self.members.remove(&group);
+            }
+        }
+        Some(group)
+    }
+
+    /// Returns the group of a session.
+    pub fn group(&self, session: &Uuid) -> Option<u32> {
+        self.groups.get(session).copied()
+    }
+
+    /// Returns the sessions in a group in the order they joined.
+    pub fn members(&self, group: u32) -> &[Uuid] {
+        self.members.get(&group).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_members() {
+        let mut groups = Groups::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        groups.join(1, a);
+        groups.join(1, b);
+        assert_eq!(groups.members(1), &[a, b]);
+        groups.join(2, a);
+        assert_eq!((groups.group(&a), groups.members(1)), (Some(2), &[b][..]));
+        assert_eq!(groups.leave(&b), Some(1));
+        assert!(groups.members(1).is_empty());
+        assert_eq!(groups.leave(&b), None);
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use uuid::Uuid;
+
+use crate::chat::filter::{ChatFilter, FilterChain, RateLimit};
+use crate::chat::groups::Groups;
+
+/// State of the chat shared by all sessions: the filter messages pass, the parties and guilds
+/// with their channels, and the game masters.
+pub struct Chat {
+    filter: Box<dyn ChatFilter>,
+    parties: Mutex<Groups>,
+    guilds: Mutex<Groups>,
+    game_masters: Mutex<HashSet<Uuid>>,
+}
+
+impl Chat {
+    pub fn new(filter: Box<dyn ChatFilter>) -> Chat {
+        Chat {
+            filter,
+            parties: Mutex::default(),
+            guilds: Mutex::default(),
+            game_masters: Mutex::default(),
+        }
+    }
+
+    /// Returns the filter of messages sent by players. Messages of game masters aren't filtered.
+    pub fn filter(&self) -> &dyn ChatFilter {
+        self.filter.as_ref()
+    }
+
+    pub fn parties(&self) -> &Mutex<Groups> {
+        &self.parties
+    }
+
+    pub fn guilds(&self) -> &Mutex<Groups> {
+        &self.guilds
+    }
+
+    /// Grants or revokes a session the game master channels, i.e. notices.
+    pub fn set_game_master(&self, session: Uuid, game_master: bool) {
+        let mut game_masters = self.game_masters.lock().unwrap();
+        if game_master {
+            game_masters.insert(session);
+        } else {
+            game_masters.remove(&session);
+        }
+    }
+
+    pub fn is_game_master(&self, session: &Uuid) -> bool {
+        self.game_masters.lock().unwrap().contains(session)
+    }
+
+    /// Removes a closed session from its groups and forgets its state.
+    pub fn disconnected(&self, session: &Uuid) {
+        self.parties.lock().unwrap().leave(session);
+        self.guilds.lock().unwrap().leave(session);
+        self.game_masters.lock().unwrap().remove(session);
+        self.filter.forget(session);
+    }
+}
+
+impl Default for Chat {
+    /// Creates a chat which only limits the message rate (see [RateLimit::default]).
+    fn default() -> Self {
+        Chat::new(Box::new(FilterChain::new(vec![Box::new(RateLimit::default())])))
+    }
+}
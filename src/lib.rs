@@ -0,0 +1,14 @@
+#[macro_use]
+extern crate log;
+
+pub mod account;
+pub mod agent;
+pub mod blowfish;
+pub mod character;
+pub mod chat;
+pub mod navmesh;
+pub mod net;
+pub mod pk2;
+pub mod textdata;
+pub mod vfs;
+pub mod world;
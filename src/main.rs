@@ -3,7 +3,10 @@ extern crate core;
 extern crate log;
 
 use std::convert::Infallible;
+use std::env;
 use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
 
 use env_logger::{Target, WriteStyle};
 use hyper::{Body, Method, Request, Response, StatusCode};
@@ -13,11 +16,31 @@ use prometheus::{Encoder, TextEncoder};
 use tokio_stream::StreamExt;
 use tokio_stream::wrappers::ReceiverStream;
 
-use net::server::{Engine, ServerSignal};
+use rustyroad::account::TrustedLogins;
+use rustyroad::agent::handler::{Agent, AUTOSAVE_INTERVAL, SIGHT_UPDATE_INTERVAL};
+use rustyroad::character::names::NameRules;
+use rustyroad::character::selection::CharacterSelection;
+use rustyroad::character::store::{CharacterStore, MemoryStore};
+use rustyroad::character::store::sqlite::SqliteStore;
+use rustyroad::chat::filter::{ChatFilter, FilterChain, RateLimit, WordFilter};
+use rustyroad::chat::state::Chat;
+use rustyroad::navmesh::loader::Loader;
+use rustyroad::net::server::{Engine, ServerSignal};
+use rustyroad::pk2::archive::Archive;
+use rustyroad::textdata::registry::Registry;
+use rustyroad::world::navigation::NavMap;
+use rustyroad::world::state::World;
 
-mod net;
-mod blowfish;
-mod pk2;
+/// Environment variable containing the path to the client's `Data.pk2`.
+const DATA_PK2_VAR: &str = "RUSTYROAD_DATA_PK2";
+/// Environment variable containing the path to the client's `Media.pk2`.
+const MEDIA_PK2_VAR: &str = "RUSTYROAD_MEDIA_PK2";
+/// Environment variable containing the path to the SQLite database of the characters.
+const DATABASE_VAR: &str = "RUSTYROAD_DATABASE";
+/// Environment variable containing the path to a list of words censored in the chat.
+const CHAT_WORDS_VAR: &str = "RUSTYROAD_CHAT_WORDS";
+/// Environment variable which, if set to `1`, accepts every login without checking it.
+const TRUST_LOGINS_VAR: &str = "RUSTYROAD_TRUST_LOGINS";
 
 
 #[tokio::main]
@@ -30,18 +53,43 @@ async fn main() {
         .init();
 
     let server = Engine::new(Vec::new()).await;
+    let characters = CharacterSelection::new(open_store(), Arc::new(load_registry()), NameRules::default());
+    let mut agent = Agent::new(server.sessions(), World::new(load_navigation()), characters, load_chat());
+    if env::var(TRUST_LOGINS_VAR).as_deref() == Ok("1") {
+        warn!("{} is set, every login is accepted without checking it", TRUST_LOGINS_VAR);
+        agent = agent.with_authenticator(TrustedLogins);
+    } else {
+        warn!("{} isn't set, all logins are rejected until a gateway server exists", TRUST_LOGINS_VAR);
+    }
     let (mut server_signal_receiver, packet_receiver) = server.start().await.unwrap();
     tokio::spawn(serve_metrics());
+    let sight_agent = agent.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SIGHT_UPDATE_INTERVAL);
+        loop {
+            interval.tick().await;
+            sight_agent.update_sight().await;
+        }
+    });
+    let autosave_agent = agent.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(AUTOSAVE_INTERVAL);
+        loop {
+            interval.tick().await;
+            autosave_agent.autosave().await;
+        }
+    });
+    let packet_agent = agent.clone();
     tokio::spawn(async move {
         // TODO: build a packet stream that does following in order
         //  1) decrypt packet
         //  2) verify checksum
         //  3) unwrap massive packet
-        //  4) handle packet
-        // TODO: remove this example
         let mut receiver_stream = ReceiverStream::new(packet_receiver);
-        while let Some((uuid, data)) = receiver_stream.next().await {
-            debug!("session {} received: {:02X?}", uuid.to_string(), data);
+        while let Some((uuid, packet)) = receiver_stream.next().await {
+            if let Err(err) = packet_agent.handle(uuid, packet).await {
+                warn!("failed to handle packet of session {}: {}", uuid, err);
+            }
         }
     });
     loop {
@@ -50,10 +98,14 @@ async fn main() {
             match signal {
                 ServerSignal::Shutdown(msg) => {
                     info!("shutting down server: {}", msg);
+                    agent.autosave().await;
                     return;
                 }
-                ServerSignal::NewConnection(msg) => debug!("new session: {}", msg.to_string()),
-                ServerSignal::ClosedConnection(msg) => debug!("closed session: {}", msg),
+                ServerSignal::NewConnection(msg) => debug!("new session: {}", msg),
+                ServerSignal::ClosedConnection(msg) => {
+                    debug!("closed session: {}", msg);
+                    agent.disconnected(msg).await;
+                }
                 ServerSignal::Started => {}
             }
         }
@@ -61,6 +113,75 @@ async fn main() {
     // TODO: add a hook to handle system signals e.g. for graceful shutdown
 }
 
+/// Loads the navigation meshes from the `Data.pk2` configured by [DATA_PK2_VAR]. Without it,
+/// movements aren't validated against the terrain.
+fn load_navigation() -> NavMap {
+    let path = match env::var(DATA_PK2_VAR) {
+        Ok(path) => path,
+        Err(_) => {
+            warn!("{} isn't set, movements won't be validated against the terrain", DATA_PK2_VAR);
+            return NavMap::new();
+        }
+    };
+    let navigation = Archive::open(Path::new(&path))
+        .map_err(|err| err.to_string())
+        .and_then(|archive| NavMap::load(&Loader::new(&archive)).map_err(|err| err.to_string()));
+    match navigation {
+        Ok(navigation) => {
+            info!("loaded the navigation meshes of {} regions", navigation.len());
+            navigation
+        }
+        Err(err) => panic!("failed to load navigation meshes from {}: {}", path, err),
+    }
+}
+
+/// Opens the character database configured by [DATABASE_VAR]. Without it, characters are only
+/// kept in memory.
+fn open_store() -> Arc<dyn CharacterStore> {
+    match env::var(DATABASE_VAR) {
+        Ok(path) => match SqliteStore::open(Path::new(&path)) {
+            Ok(store) => Arc::new(store),
+            Err(err) => panic!("failed to open character database {}: {}", path, err),
+        },
+        Err(_) => {
+            warn!("{} isn't set, characters won't be persisted", DATABASE_VAR);
+            Arc::new(MemoryStore::new())
+        }
+    }
+}
+
+/// Loads the reference data from the `Media.pk2` configured by [MEDIA_PK2_VAR]. Without it,
+/// no characters can be created.
+fn load_registry() -> Registry {
+    let path = match env::var(MEDIA_PK2_VAR) {
+        Ok(path) => path,
+        Err(_) => {
+            warn!("{} isn't set, characters can't be created", MEDIA_PK2_VAR);
+            return Registry::new(Vec::new(), Vec::new(), Vec::new()).unwrap();
+        }
+    };
+    let registry = Archive::open(Path::new(&path))
+        .map_err(|err| err.to_string())
+        .and_then(|archive| Registry::load(&archive).map_err(|err| err.to_string()));
+    match registry {
+        Ok(registry) => registry,
+        Err(err) => panic!("failed to load reference data from {}: {}", path, err),
+    }
+}
+
+/// Creates the chat, which censors the words listed in the file configured by
+/// [CHAT_WORDS_VAR] and limits the message rate.
+fn load_chat() -> Chat {
+    let mut filters: Vec<Box<dyn ChatFilter>> = vec![Box::new(RateLimit::default())];
+    if let Ok(path) = env::var(CHAT_WORDS_VAR) {
+        match std::fs::read_to_string(&path) {
+            Ok(list) => filters.push(Box::new(WordFilter::parse(&list))),
+            Err(err) => panic!("failed to read chat words from {}: {}", path, err),
+        }
+    }
+    Chat::new(Box::new(FilterChain::new(filters)))
+}
+
 async fn serve_metrics() {
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
 
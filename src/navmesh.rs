@@ -0,0 +1,7 @@
+pub mod bms;
+pub mod errors;
+pub mod geometry;
+pub mod loader;
+pub mod mapinfo;
+pub mod nvm;
+mod util;
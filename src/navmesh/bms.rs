@@ -0,0 +1,197 @@
+use crate::navmesh::errors::Error;
+use crate::navmesh::errors::Error::InvalidData;
+use crate::navmesh::geometry::Vec3;
+use crate::navmesh::util::Reader;
+
+const SIGNATURE: &str = "JMXVBMS 0110";
+/// Navigation flag telling that outline and inline edges carry an event zone index.
+const NAV_FLAG_EDGE: u32 = 0x01;
+/// Navigation flag telling that cells carry an event zone index.
+const NAV_FLAG_CELL: u32 = 0x02;
+
+/// Collision data of an object mesh (`.bms` files referenced by `navmesh/object.ifo`).
+///
+/// Only the header and the navigation mesh of the object are parsed, which is the part needed
+/// for collision checks. Positions are relative to the object and have to be transformed with
+/// the [ObjectInstance](crate::navmesh::nvm::ObjectInstance) placing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectMesh {
+    pub name: String,
+    pub material: String,
+    /// `None` if the object has no collision.
+    pub navmesh: Option<ObjectNavMesh>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectNavMesh {
+    pub vertices: Vec<Vec3>,
+    pub cells: Vec<ObjectCell>,
+    /// Edges on the outline of the mesh, where the object's mesh connects to the terrain.
+    pub outline_edges: Vec<ObjectEdge>,
+    pub inline_edges: Vec<ObjectEdge>,
+}
+
+/// A triangle of the object's navigation mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectCell {
+    pub vertices: [u16; 3],
+    pub flag: u16,
+    pub event_zone: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectEdge {
+    pub vertices: [u16; 2],
+    /// Cells on both sides of the edge, `0xFFFF` outside of the mesh.
+    pub cells: [u16; 2],
+    pub flag: u8,
+    pub event_zone: Option<u8>,
+}
+
+impl ObjectMesh {
+    /// Parses the content of a `.bms` file.
+    pub fn parse(data: &[u8]) -> Result<ObjectMesh, Error> {
+        let mut reader = Reader::new(data);
+        reader.signature(SIGNATURE)?;
+        // offsets of the vertices, skin, faces, cloth vertices, cloth edges, bounding box and
+        // occlusion portals, which aren't needed for collisions
+        reader.bytes(7 * 4)?;
+        let navmesh_offset = reader.u32()?;
+        // offsets of the skinned navigation mesh and two unknown sections
+        reader.bytes(3 * 4)?;
+        let nav_flag = reader.u32()?;
+        // sub primitive count, vertex flag and an unknown value
+        reader.bytes(3 * 4)?;
+        let name = reader.string()?;
+        let material = reader.string()?;
+
+        let navmesh = match navmesh_offset {
+            0 => None,
+            offset if offset as usize >= data.len() => return Err(InvalidData("navigation mesh offset out of bounds")),
+            offset => {
+                reader.seek(offset as u64);
+                Some(ObjectNavMesh::read(&mut reader, nav_flag)?)
+            }
+        };
+        Ok(ObjectMesh { name, material, navmesh })
+    }
+}
+
+impl ObjectNavMesh {
+    fn read(reader: &mut Reader, nav_flag: u32) -> Result<ObjectNavMesh, Error> {
+        let vertex_count = reader.u32()? as usize;
+        let vertices = reader.repeat(vertex_count, |r| {
+            let vertex = r.vec3()?;
+            r.u8()?;
+            Ok(vertex)
+        })?;
+        let cell_count = reader.u32()? as usize;
+        let cells = reader.repeat(cell_count, |r| {
+            Ok(ObjectCell {
+                vertices: [r.u16()?, r.u16()?, r.u16()?],
+                flag: r.u16()?,
+                event_zone: if nav_flag & NAV_FLAG_CELL != 0 { Some(r.u8()?) } else { None },
+            })
+        })?;
+        let read_edge = |r: &mut Reader| Ok(ObjectEdge {
+            vertices: [r.u16()?, r.u16()?],
+            cells: [r.u16()?, r.u16()?],
+            flag: r.u8()?,
+            event_zone: if nav_flag & NAV_FLAG_EDGE != 0 { Some(r.u8()?) } else { None },
+        });
+        let outline_count = reader.u32()? as usize;
+        let outline_edges = reader.repeat(outline_count, read_edge)?;
+        let inline_count = reader.u32()? as usize;
+        let inline_edges = reader.repeat(inline_count, read_edge)?;
+
+        let navmesh = ObjectNavMesh { vertices, cells, outline_edges, inline_edges };
+        navmesh.check()?;
+        Ok(navmesh)
+    }
+
+    /// Makes sure all indices refer to existing vertices and cells.
+    fn check(&self) -> Result<(), Error> {
+        let vertex = |index: &u16| (*index as usize) < self.vertices.len();
+        let cell = |index: &u16| *index == 0xFFFF || (*index as usize) < self.cells.len();
+        if !self.cells.iter().all(|c| c.vertices.iter().all(vertex)) {
+            return Err(InvalidData("cell refers to a missing vertex"));
+        }
+        if !self.outline_edges.iter().chain(self.inline_edges.iter())
+            .all(|e| e.vertices.iter().all(vertex) && e.cells.iter().all(cell)) {
+            return Err(InvalidData("edge refers to a missing vertex or cell"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{WriteBytesExt, LE};
+
+    use super::*;
+
+    /// Builds an object whose navigation mesh is a square of two triangles.
+    fn synthetic_bms(nav_flag: u32) -> Vec<u8> {
+        let mut data = SIGNATURE.as_bytes().to_vec();
+        let u16s = |data: &mut Vec<u8>, values: &[u16]| values.iter().for_each(|v| data.write_u16::<LE>(*v).unwrap());
+        data.extend([0; 7 * 4]);
+        let navmesh_offset = data.len();
+        data.extend([0; 4 + 3 * 4]);
+        data.write_u32::<LE>(nav_flag).unwrap();
+        data.extend([0; 3 * 4]);
+        for string in ["door", "stone"] {
+            data.write_u32::<LE>(string.len() as u32).unwrap();
+            data.extend(string.as_bytes());
+        }
+        let offset = data.len() as u32;
+        data[navmesh_offset..navmesh_offset + 4].copy_from_slice(&offset.to_le_bytes());
+
+        data.write_u32::<LE>(4).unwrap();
+        for (x, z) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)] {
+            [x, 1.0, z].iter().for_each(|v| data.write_f32::<LE>(*v).unwrap());
+            data.push(0);
+        }
+        data.write_u32::<LE>(2).unwrap();
+        for cell in [[0, 1, 2], [0, 2, 3]] {
+            u16s(&mut data, &cell);
+            u16s(&mut data, &[0]);
+            if nav_flag & NAV_FLAG_CELL != 0 {
+                data.push(5);
+            }
+        }
+        data.write_u32::<LE>(1).unwrap();
+        u16s(&mut data, &[0, 1, 0, 0xFFFF]);
+        data.extend(if nav_flag & NAV_FLAG_EDGE != 0 { &[3, 7][..] } else { &[3][..] });
+        data.write_u32::<LE>(1).unwrap();
+        u16s(&mut data, &[0, 2, 0, 1]);
+        data.extend(if nav_flag & NAV_FLAG_EDGE != 0 { &[0, 7][..] } else { &[0][..] });
+        data
+    }
+
+    #[test]
+    fn parses_object_navmesh() {
+        let mesh = ObjectMesh::parse(&synthetic_bms(0)).unwrap();
+        assert_eq!((mesh.name.as_str(), mesh.material.as_str()), ("door", "stone"));
+        let navmesh = mesh.navmesh.unwrap();
+        assert_eq!(navmesh.vertices[2], Vec3::new(10.0, 1.0, 10.0));
+        assert_eq!(navmesh.cells[1], ObjectCell { vertices: [0, 2, 3], flag: 0, event_zone: None });
+        assert_eq!(navmesh.outline_edges[0].cells, [0, 0xFFFF]);
+        assert_eq!(navmesh.inline_edges[0].cells, [0, 1]);
+    }
+
+    #[test]
+    fn reads_event_zones() {
+        let navmesh = ObjectMesh::parse(&synthetic_bms(NAV_FLAG_EDGE | NAV_FLAG_CELL)).unwrap().navmesh.unwrap();
+        assert_eq!(navmesh.cells[0].event_zone, Some(5));
+        assert_eq!(navmesh.outline_edges[0].flag, 3);
+        assert_eq!(navmesh.inline_edges[0].event_zone, Some(7));
+    }
+
+    #[test]
+    fn rejects_dangling_indices() {
+        let mut data = synthetic_bms(0);
+        let len = data.len();
+        data[len - 3] = 9;
+        assert!(matches!(ObjectMesh::parse(&data), Err(InvalidData(_))));
+    }
+}
@@ -0,0 +1,25 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use crate::vfs;
+
+#[derive(std::fmt::Debug)]
+pub enum Error {
+    Vfs(vfs::errors::Error),
+    /// The file ended before all of its data could be read.
+    IO(io::Error),
+    InvalidSignature(&'static str),
+    InvalidData(&'static str),
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Vfs(err) => write!(f, "{}", err),
+            Error::IO(err) => write!(f, "{}", err),
+            Error::InvalidSignature(expected) => write!(f, "invalid signature, expected {}", expected),
+            Error::InvalidData(msg) => write!(f, "invalid data: {}", msg),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+/// A point on the ground plane. Like in the client, `z` is the second horizontal axis.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub z: f32,
+}
+
+/// A point in space, `y` being the height.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// An axis aligned rectangle on the ground plane.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Vec2 {
+    pub fn new(x: f32, z: f32) -> Vec2 {
+        Vec2 { x, z }
+    }
+}
+
+impl Vec3 {
+    pub fn new(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+}
+
+impl Rect {
+    /// Returns whether the point lies within the rectangle, including its borders.
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.z >= self.min.z && point.z <= self.max.z
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::navmesh::bms::ObjectMesh;
+use crate::navmesh::errors::Error;
+use crate::navmesh::mapinfo::MapInfo;
+use crate::navmesh::nvm::RegionMesh;
+use crate::vfs::Vfs;
+use crate::world::position::RegionId;
+
+/// Directory of the navigation meshes in the client's `Data.pk2`.
+pub const NAVMESH_DIR: &str = "navmesh";
+
+/// Loads navigation data from a [Vfs], e.g. an opened `Data.pk2`.
+pub struct Loader<'v> {
+    vfs: &'v dyn Vfs,
+    dir: PathBuf,
+}
+
+impl<'v> Loader<'v> {
+    /// Creates a loader reading from [NAVMESH_DIR].
+    pub fn new(vfs: &'v dyn Vfs) -> Loader<'v> {
+        Loader { vfs, dir: PathBuf::from(NAVMESH_DIR) }
+    }
+
+    /// Loads `mapinfo.mfo`.
+    pub fn map_info(&self) -> Result<MapInfo, Error> {
+        MapInfo::parse(&self.read(&self.dir.join("mapinfo.mfo"))?)
+    }
+
+    /// Loads the navigation mesh of given region from `nv_XXYY.nvm`, `XXYY` being the region ID
+    /// in hex.
+    pub fn region(&self, region: RegionId) -> Result<RegionMesh, Error> {
+        RegionMesh::parse(&self.read(&self.dir.join(format!("nv_{:04x}.nvm", region.0)))?)
+    }
+
+    /// Loads the navigation meshes of all regions enabled in `mapinfo.mfo`.
+    pub fn regions(&self) -> Result<BTreeMap<RegionId, RegionMesh>, Error> {
+        self.map_info()?.enabled_regions()
+            .map(|id| Ok((RegionId(id), self.region(RegionId(id))?)))
+            .collect()
+    }
+
+    /// Loads an object mesh, given its path relative to the root of the [Vfs].
+    pub fn object(&self, path: &Path) -> Result<ObjectMesh, Error> {
+        ObjectMesh::parse(&self.read(path)?)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        self.vfs.read(path).map_err(Error::Vfs)
+    }
+}
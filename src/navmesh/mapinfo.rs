@@ -0,0 +1,63 @@
+use crate::navmesh::errors::Error;
+use crate::navmesh::util::Reader;
+
+const SIGNATURE: &str = "JMXVMFO 1000";
+/// Size of the region bitmap, one bit for each of the 256 × 256 possible regions.
+const REGION_BITMAP_SIZE: usize = 256 * 256 / 8;
+
+/// Map information (`navmesh/mapinfo.mfo` in `Data.pk2`), telling which regions of the world
+/// have a navigation mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapInfo {
+    pub width: u16,
+    pub height: u16,
+    regions: Vec<u8>,
+}
+
+impl MapInfo {
+    /// Parses the content of a `mapinfo.mfo` file.
+    pub fn parse(data: &[u8]) -> Result<MapInfo, Error> {
+        let mut reader = Reader::new(data);
+        reader.signature(SIGNATURE)?;
+        let width = reader.u16()?;
+        let height = reader.u16()?;
+        reader.bytes(8)?;
+        let regions = reader.bytes(REGION_BITMAP_SIZE)?;
+        Ok(MapInfo { width, height, regions })
+    }
+
+    /// Returns whether the region with given ID is enabled. Regions are stored as one bit per
+    /// region, starting with the least significant bit.
+    pub fn is_enabled(&self, region_id: u16) -> bool {
+        self.regions[region_id as usize >> 3] & (1 << (region_id & 7)) != 0
+    }
+
+    /// Returns the IDs of all enabled regions in ascending order.
+    pub fn enabled_regions(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..=u16::MAX).filter(move |id| self.is_enabled(*id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_region_bitmap() {
+        let mut data = SIGNATURE.as_bytes().to_vec();
+        data.extend([0, 1, 0, 1]);
+        data.extend([0; 8]);
+        let mut regions = vec![0u8; REGION_BITMAP_SIZE];
+        regions[0x61A8 >> 3] = 0b0000_0001;
+        regions[0x61AF >> 3] |= 0b1000_0000;
+        regions[REGION_BITMAP_SIZE - 1] = 0b1000_0000;
+        data.extend(&regions);
+
+        let map_info = MapInfo::parse(&data).unwrap();
+        assert_eq!((map_info.width, map_info.height), (256, 256));
+        assert!(map_info.is_enabled(0x61A8));
+        assert!(!map_info.is_enabled(0x61A9));
+        assert_eq!(map_info.enabled_regions().collect::<Vec<_>>(), vec![0x61A8, 0x61AF, 0xFFFF]);
+        assert!(MapInfo::parse(&data[..data.len() - 1]).is_err());
+    }
+}
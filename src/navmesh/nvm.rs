@@ -0,0 +1,274 @@
+use crate::navmesh::errors::Error;
+use crate::navmesh::errors::Error::InvalidData;
+use crate::navmesh::geometry::{Rect, Vec2, Vec3};
+use crate::navmesh::util::Reader;
+
+const SIGNATURE: &str = "JMXVNVM 1000";
+/// Width and depth of a region in world units.
+pub const REGION_SIZE: f32 = 1920.0;
+/// Number of tiles along each side of a region.
+pub const TILES_PER_SIDE: usize = 96;
+/// Width and depth of a tile in world units.
+pub const TILE_SIZE: f32 = REGION_SIZE / TILES_PER_SIDE as f32;
+/// Number of height samples along each side of a region, one per tile corner.
+pub const HEIGHTS_PER_SIDE: usize = TILES_PER_SIDE + 1;
+
+/// Edge flag blocking movement from the first to the second cell.
+pub const EDGE_BLOCKED_SRC_TO_DST: u8 = 0x01;
+/// Edge flag blocking movement from the second to the first cell.
+pub const EDGE_BLOCKED_DST_TO_SRC: u8 = 0x02;
+
+/// Navigation mesh of a terrain region (`navmesh/nv_XXYY.nvm` in `Data.pk2`).
+///
+/// The walkable terrain is divided into rectangular cells connected by edges. Internal edges
+/// connect cells of the region, global edges connect them with cells of neighbouring regions.
+/// Only the data up to the height map is parsed, the surface data following it is ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionMesh {
+    pub objects: Vec<ObjectInstance>,
+    pub cells: Vec<Cell>,
+    /// Number of cells which aren't covered by an object.
+    pub open_cells: u32,
+    pub global_edges: Vec<GlobalEdge>,
+    pub internal_edges: Vec<Edge>,
+    /// [TILES_PER_SIDE]² tiles, row by row along the z axis.
+    pub tiles: Vec<Tile>,
+    /// [HEIGHTS_PER_SIDE]² heights, row by row along the z axis.
+    pub heights: Vec<f32>,
+}
+
+/// An object placed in a region, e.g. a building. Its collision mesh is stored in a `.bms` file
+/// (see [ObjectMesh](crate::navmesh::bms::ObjectMesh)).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectInstance {
+    /// Index of the object in `navmesh/object.ifo`.
+    pub id: u32,
+    pub position: Vec3,
+    pub collision: u16,
+    pub yaw: f32,
+    pub local_uid: u16,
+    pub is_big: bool,
+    pub is_struct: bool,
+    pub region_id: u16,
+    pub edge_links: Vec<EdgeLink>,
+}
+
+/// Connects an outline edge of an object with an edge of another object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeLink {
+    pub linked_object: u16,
+    pub linked_edge: u16,
+    pub edge: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    pub rect: Rect,
+    /// Indices of the objects in [RegionMesh::objects] overlapping the cell.
+    pub objects: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub flag: u8,
+    pub directions: [u8; 2],
+    /// Indices of the cells on both sides of the edge.
+    pub cells: [u16; 2],
+}
+
+/// An edge on the border of a region, connecting a cell with one of a neighbouring region.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalEdge {
+    pub edge: Edge,
+    /// IDs of the regions of [Edge::cells].
+    pub regions: [u16; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    /// Index of the cell covering the tile.
+    pub cell: u32,
+    pub flag: u16,
+    pub texture: u16,
+}
+
+impl Edge {
+    /// Returns whether the edge can't be crossed in any direction.
+    pub fn is_blocked(&self) -> bool {
+        self.flag & (EDGE_BLOCKED_SRC_TO_DST | EDGE_BLOCKED_DST_TO_SRC) != 0
+    }
+
+    fn read(reader: &mut Reader) -> Result<Edge, Error> {
+        Ok(Edge {
+            start: reader.vec2()?,
+            end: reader.vec2()?,
+            flag: reader.u8()?,
+            directions: [reader.u8()?, reader.u8()?],
+            cells: [reader.u16()?, reader.u16()?],
+        })
+    }
+}
+
+impl RegionMesh {
+    /// Parses the content of an `.nvm` file.
+    pub fn parse(data: &[u8]) -> Result<RegionMesh, Error> {
+        let mut reader = Reader::new(data);
+        reader.signature(SIGNATURE)?;
+
+        let object_count = reader.u16()? as usize;
+        let objects = reader.repeat(object_count, |r| {
+            let id = r.u32()?;
+            let position = r.vec3()?;
+            let collision = r.u16()?;
+            let yaw = r.f32()?;
+            let local_uid = r.u16()?;
+            r.u16()?;
+            let (is_big, is_struct) = (r.u8()? != 0, r.u8()? != 0);
+            let region_id = r.u16()?;
+            let link_count = r.u16()? as usize;
+            let edge_links = r.repeat(link_count, |r| Ok(EdgeLink { linked_object: r.u16()?, linked_edge: r.u16()?, edge: r.u16()? }))?;
+            Ok(ObjectInstance { id, position, collision, yaw, local_uid, is_big, is_struct, region_id, edge_links })
+        })?;
+
+        let cell_count = reader.u32()? as usize;
+        let open_cells = reader.u32()?;
+        let cells = reader.repeat(cell_count, |r| {
+            let rect = r.rect()?;
+            let object_count = r.u8()? as usize;
+            Ok(Cell { rect, objects: r.repeat(object_count, Reader::u16)? })
+        })?;
+
+        let global_edge_count = reader.u32()? as usize;
+        let global_edges = reader.repeat(global_edge_count, |r| {
+            Ok(GlobalEdge { edge: Edge::read(r)?, regions: [r.u16()?, r.u16()?] })
+        })?;
+        let internal_edge_count = reader.u32()? as usize;
+        let internal_edges = reader.repeat(internal_edge_count, Edge::read)?;
+
+        let tiles = reader.repeat(TILES_PER_SIDE * TILES_PER_SIDE, |r| {
+            Ok(Tile { cell: r.u32()?, flag: r.u16()?, texture: r.u16()? })
+        })?;
+        if tiles.iter().any(|tile| tile.cell as usize >= cells.len()) {
+            return Err(InvalidData("tile refers to a missing cell"));
+        }
+        let heights = reader.repeat(HEIGHTS_PER_SIDE * HEIGHTS_PER_SIDE, Reader::f32)?;
+
+        Ok(RegionMesh { objects, cells, open_cells, global_edges, internal_edges, tiles, heights })
+    }
+
+    /// Returns the tile at given region-local position, or `None` if it lies outside the region.
+    pub fn tile_at(&self, x: f32, z: f32) -> Option<&Tile> {
+        if !(0.0..REGION_SIZE).contains(&x) || !(0.0..REGION_SIZE).contains(&z) {
+            return None;
+        }
+        let (column, row) = ((x / TILE_SIZE) as usize, (z / TILE_SIZE) as usize);
+        self.tiles.get(row * TILES_PER_SIDE + column)
+    }
+
+    /// Returns the cell at given region-local position.
+    pub fn cell_at(&self, x: f32, z: f32) -> Option<&Cell> {
+        self.tile_at(x, z).and_then(|tile| self.cells.get(tile.cell as usize))
+    }
+
+    /// Returns the terrain height at given region-local position, interpolated between the height
+    /// samples at the corners of its tile.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        if !(0.0..=REGION_SIZE).contains(&x) || !(0.0..=REGION_SIZE).contains(&z) {
+            return None;
+        }
+        let (fx, fz) = (x / TILE_SIZE, z / TILE_SIZE);
+        let column = (fx as usize).min(TILES_PER_SIDE - 1);
+        let row = (fz as usize).min(TILES_PER_SIDE - 1);
+        let (tx, tz) = (fx - column as f32, fz - row as f32);
+        let height = |column: usize, row: usize| self.heights[row * HEIGHTS_PER_SIDE + column];
+        let near = height(column, row) * (1.0 - tx) + height(column + 1, row) * tx;
+        let far = height(column, row + 1) * (1.0 - tx) + height(column + 1, row + 1) * tx;
+        Some(near * (1.0 - tz) + far * tz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{WriteBytesExt, LE};
+
+    use super::*;
+
+    /// Builds a region with one object, two cells split at x = 960 and a height rising along x.
+    fn synthetic_nvm() -> Vec<u8> {
+        let mut data = SIGNATURE.as_bytes().to_vec();
+        let floats = |data: &mut Vec<u8>, values: &[f32]| values.iter().for_each(|v| data.write_f32::<LE>(*v).unwrap());
+        data.write_u16::<LE>(1).unwrap();
+        data.write_u32::<LE>(7).unwrap();
+        floats(&mut data, &[100.0, 5.0, 200.0]);
+        data.write_u16::<LE>(0xFFFF).unwrap();
+        floats(&mut data, &[1.5]);
+        data.extend([1, 0, 0, 0, 1, 0]);
+        data.write_u16::<LE>(0x61A8).unwrap();
+        data.write_u16::<LE>(1).unwrap();
+        [2u16, 3, 4].iter().for_each(|v| data.write_u16::<LE>(*v).unwrap());
+
+        data.write_u32::<LE>(2).unwrap();
+        data.write_u32::<LE>(1).unwrap();
+        floats(&mut data, &[0.0, 0.0, 960.0, 1920.0]);
+        data.extend([1, 0, 0]);
+        floats(&mut data, &[960.0, 0.0, 1920.0, 1920.0]);
+        data.push(0);
+
+        data.write_u32::<LE>(1).unwrap();
+        floats(&mut data, &[1920.0, 0.0, 1920.0, 1920.0]);
+        data.extend([0, 1, 3, 1, 0, 0, 0, 0xA8, 0x61, 0xA9, 0x61]);
+        data.write_u32::<LE>(1).unwrap();
+        floats(&mut data, &[960.0, 0.0, 960.0, 1920.0]);
+        data.extend([EDGE_BLOCKED_SRC_TO_DST, 1, 3, 0, 0, 1, 0]);
+
+        for _ in 0..TILES_PER_SIDE {
+            for column in 0..TILES_PER_SIDE {
+                data.write_u32::<LE>((column >= TILES_PER_SIDE / 2) as u32).unwrap();
+                data.extend([0, 0, 0, 0]);
+            }
+        }
+        for _ in 0..HEIGHTS_PER_SIDE {
+            for column in 0..HEIGHTS_PER_SIDE {
+                data.write_f32::<LE>(column as f32).unwrap();
+            }
+        }
+        data.extend([0; 16]);
+        data
+    }
+
+    #[test]
+    fn parses_region_mesh() {
+        let mesh = RegionMesh::parse(&synthetic_nvm()).unwrap();
+        assert_eq!(mesh.objects.len(), 1);
+        assert_eq!(mesh.objects[0].position, Vec3::new(100.0, 5.0, 200.0));
+        assert_eq!(mesh.objects[0].region_id, 0x61A8);
+        assert_eq!(mesh.objects[0].edge_links, vec![EdgeLink { linked_object: 2, linked_edge: 3, edge: 4 }]);
+        assert!(mesh.objects[0].is_big && !mesh.objects[0].is_struct);
+        assert_eq!(mesh.cells[0].objects, vec![0]);
+        assert_eq!(mesh.open_cells, 1);
+        assert_eq!(mesh.global_edges[0].regions, [0x61A8, 0x61A9]);
+        assert!(!mesh.global_edges[0].edge.is_blocked());
+        assert!(mesh.internal_edges[0].is_blocked());
+        assert_eq!(mesh.internal_edges[0].cells, [0, 1]);
+    }
+
+    #[test]
+    fn looks_up_cells_and_heights() {
+        let mesh = RegionMesh::parse(&synthetic_nvm()).unwrap();
+        assert_eq!(mesh.cell_at(10.0, 1000.0).unwrap().rect.max.x, 960.0);
+        assert_eq!(mesh.cell_at(1000.0, 10.0).unwrap().rect.min.x, 960.0);
+        assert!(mesh.cell_at(1920.0, 0.0).is_none());
+        assert_eq!(mesh.height_at(30.0, 500.0), Some(1.5));
+        assert_eq!(mesh.height_at(1920.0, 1920.0), Some(96.0));
+        assert_eq!(mesh.height_at(-1.0, 0.0), None);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let data = synthetic_nvm();
+        assert!(matches!(RegionMesh::parse(b"JMXVNVM 1001"), Err(Error::InvalidSignature(_))));
+        assert!(matches!(RegionMesh::parse(&data[..data.len() / 2]), Err(Error::IO(_))));
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::{Cursor, Read};
+
+use byteorder::{ReadBytesExt, LE};
+
+use crate::navmesh::errors::Error;
+use crate::navmesh::errors::Error::{InvalidSignature, IO};
+use crate::navmesh::geometry::{Rect, Vec2, Vec3};
+
+/// Little endian reader over the content of a file, mapping all read errors to [Error::IO].
+pub struct Reader<'a> {
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { cursor: Cursor::new(data) }
+    }
+
+    /// Reads the signature at the start of a file, e.g. `JMXVNVM 1000`.
+    pub fn signature(&mut self, expected: &'static str) -> Result<(), Error> {
+        let mut signature = vec![0; expected.len()];
+        self.cursor.read_exact(&mut signature).map_err(|_| InvalidSignature(expected))?;
+        if signature != expected.as_bytes() {
+            return Err(InvalidSignature(expected));
+        }
+        Ok(())
+    }
+
+    pub fn seek(&mut self, position: u64) {
+        self.cursor.set_position(position);
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        self.cursor.read_u8().map_err(IO)
+    }
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        self.cursor.read_u16::<LE>().map_err(IO)
+    }
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        self.cursor.read_u32::<LE>().map_err(IO)
+    }
+    pub fn f32(&mut self) -> Result<f32, Error> {
+        self.cursor.read_f32::<LE>().map_err(IO)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        (&mut self.cursor).take(len as u64).read_to_end(&mut bytes).map_err(IO)?;
+        if bytes.len() != len {
+            return Err(IO(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(bytes)
+    }
+
+    /// Reads a string prefixed with its length as u32.
+    pub fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(&self.bytes(len)?).into_owned())
+    }
+
+    pub fn vec2(&mut self) -> Result<Vec2, Error> {
+        Ok(Vec2::new(self.f32()?, self.f32()?))
+    }
+    pub fn vec3(&mut self) -> Result<Vec3, Error> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+    pub fn rect(&mut self) -> Result<Rect, Error> {
+        Ok(Rect { min: self.vec2()?, max: self.vec2()? })
+    }
+
+    /// Reads `count` elements. The vector grows while reading, so a corrupted count fails with
+    /// [Error::IO] instead of allocating huge amounts of memory.
+    pub fn repeat<T>(&mut self, count: usize, mut read: impl FnMut(&mut Self) -> Result<T, Error>) -> Result<Vec<T>, Error> {
+        let mut elements = Vec::new();
+        for _ in 0..count {
+            elements.push(read(self)?);
+        }
+        Ok(elements)
+    }
+}
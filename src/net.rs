@@ -1,2 +1,3 @@
+pub mod errors;
 pub mod server;
-pub mod packet;
\ No newline at end of file
+pub mod packet;
@@ -0,0 +1,27 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(std::fmt::Debug, PartialEq)]
+pub enum Error {
+    /// The packet ended before a value of given type could be read.
+    Truncated { opcode: u16, value: &'static str },
+    InvalidString(u16),
+    /// The packet is encrypted, which isn't supported yet.
+    Encrypted(u16),
+    /// The data doesn't fit into a single packet.
+    TooLarge(usize),
+    /// A packet is malformed, e.g. it contains an invalid enum value.
+    InvalidPacket { opcode: u16, message: &'static str },
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Truncated { opcode, value } => write!(f, "packet {:04X} ends before {}", opcode, value),
+            Error::InvalidString(opcode) => write!(f, "packet {:04X} contains an invalid string", opcode),
+            Error::Encrypted(opcode) => write!(f, "packet {:04X} is encrypted", opcode),
+            Error::TooLarge(size) => write!(f, "{} bytes exceed the maximum packet size", size),
+            Error::InvalidPacket { opcode, message } => write!(f, "invalid packet {:04X}: {}", opcode, message),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::convert::TryInto;
+
+use crate::net::errors::Error;
+use crate::net::errors::Error::{Encrypted, InvalidString, TooLarge, Truncated};
+
+/// Size of a packet's header: data size, opcode, security count and CRC.
+pub const HEADER_SIZE: usize = 6;
+/// Maximum size of a packet's data, the highest bit of the size field flags encrypted packets.
+pub const MAX_DATA_SIZE: usize = 0x7FFF;
+const ENCRYPTED_FLAG: u16 = 0x8000;
+
+/// A packet of the SRO protocol: an opcode identifying the message and its data.
+///
+/// On the wire every packet starts with a [HEADER_SIZE] byte header. The security bytes of client
+/// packets are ignored and zero in server packets, since the security handshake isn't implemented.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    pub opcode: u16,
+    pub data: Vec<u8>,
+}
+
+impl Packet {
+    pub fn new(opcode: u16, data: Vec<u8>) -> Packet {
+        Packet { opcode, data }
+    }
+
+    /// Returns a reader over the packet's data.
+    pub fn reader(&self) -> PacketReader<'_> {
+        PacketReader { opcode: self.opcode, data: &self.data, position: 0 }
+    }
+
+    /// Serializes the packet including its header.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        if self.data.len() > MAX_DATA_SIZE {
+            return Err(TooLarge(self.data.len()));
+        }
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.data.len());
+        bytes.extend((self.data.len() as u16).to_le_bytes());
+        bytes.extend(self.opcode.to_le_bytes());
+        bytes.extend([0, 0]);
+        bytes.extend(&self.data);
+        Ok(bytes)
+    }
+
+    /// Removes the first complete packet from the start of given buffer, which collects the bytes
+    /// received from a connection. Returns `None` if the buffer doesn't contain a whole packet yet.
+    pub fn decode(buffer: &mut Vec<u8>) -> Result<Option<Packet>, Error> {
+        if buffer.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+        let size = u16::from_le_bytes([buffer[0], buffer[1]]);
+        let opcode = u16::from_le_bytes([buffer[2], buffer[3]]);
+        if size & ENCRYPTED_FLAG != 0 {
+            return Err(Encrypted(opcode));
+        }
+        let end = HEADER_SIZE + size as usize;
+        if buffer.len() < end {
+            return Ok(None);
+        }
+        let data = buffer[HEADER_SIZE..end].to_vec();
+        buffer.drain(..end);
+        Ok(Some(Packet { opcode, data }))
+    }
+}
+
+/// Reads the little endian values of a packet's data one after another.
+pub struct PacketReader<'p> {
+    opcode: u16,
+    data: &'p [u8],
+    position: usize,
+}
+
+impl<'p> PacketReader<'p> {
+    /// Returns the opcode of the packet, e.g. for error messages.
+    pub fn opcode(&self) -> u16 {
+        self.opcode
+    }
+
+    /// Returns the number of bytes which haven't been read yet.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    pub fn bytes(&mut self, len: usize, value: &'static str) -> Result<&'p [u8], Error> {
+        if self.remaining() < len {
+            return Err(Truncated { opcode: self.opcode, value });
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self, value: &'static str) -> Result<[u8; N], Error> {
+        Ok(self.bytes(N, value)?.try_into().expect("slice has the requested length"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>("u8")?[0])
+    }
+    pub fn bool(&mut self) -> Result<bool, Error> {
+        Ok(self.u8()? != 0)
+    }
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array("u16")?))
+    }
+    pub fn i16(&mut self) -> Result<i16, Error> {
+        Ok(i16::from_le_bytes(self.array("i16")?))
+    }
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array("u32")?))
+    }
+    pub fn i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.array("i32")?))
+    }
+    pub fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.array("u64")?))
+    }
+    pub fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.array("f32")?))
+    }
+
+    /// Reads a string prefixed with its length in bytes as u16.
+    pub fn string(&mut self) -> Result<String, Error> {
+        let len = self.u16()? as usize;
+        let bytes = self.bytes(len, "string")?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| InvalidString(self.opcode))
+    }
+
+    /// Reads a UTF-16LE string prefixed with its length in code units as u16.
+    pub fn utf16_string(&mut self) -> Result<String, Error> {
+        let len = self.u16()? as usize;
+        let bytes = self.bytes(len * 2, "string")?;
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        String::from_utf16(&units).map_err(|_| InvalidString(self.opcode))
+    }
+}
+
+/// Builds a packet by appending little endian values.
+pub struct PacketWriter {
+    opcode: u16,
+    data: Vec<u8>,
+}
+
+impl PacketWriter {
+    pub fn new(opcode: u16) -> PacketWriter {
+        PacketWriter { opcode, data: Vec::new() }
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.data.extend_from_slice(bytes);
+        self
+    }
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.bytes(&[value])
+    }
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.u8(value as u8)
+    }
+    pub fn u16(&mut self, value: u16) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+    pub fn i16(&mut self, value: i16) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+    pub fn i32(&mut self, value: i32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+    pub fn f32(&mut self, value: f32) -> &mut Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    /// Writes a string prefixed with its length in bytes as u16 (see [PacketReader::string]).
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.u16(value.len() as u16).bytes(value.as_bytes())
+    }
+
+    /// Writes a UTF-16LE string prefixed with its length in code units as u16.
+    pub fn utf16_string(&mut self, value: &str) -> &mut Self {
+        let units: Vec<u16> = value.encode_utf16().collect();
+        self.u16(units.len() as u16);
+        units.iter().for_each(|unit| { self.u16(*unit); });
+        self
+    }
+
+    /// Returns the packet written so far.
+    pub fn build(&self) -> Packet {
+        Packet::new(self.opcode, self.data.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_and_reads_values() {
+        let packet = PacketWriter::new(0xB021)
+            .u8(1).u16(0x61A8).i16(-5).u32(7).i32(-7).u64(u64::MAX).f32(1.5).bool(true)
+            .string("ITEM").utf16_string("한글")
+            .build();
+        let mut reader = packet.reader();
+        assert_eq!(reader.u8().unwrap(), 1);
+        assert_eq!(reader.u16().unwrap(), 0x61A8);
+        assert_eq!(reader.i16().unwrap(), -5);
+        assert_eq!(reader.u32().unwrap(), 7);
+        assert_eq!(reader.i32().unwrap(), -7);
+        assert_eq!(reader.u64().unwrap(), u64::MAX);
+        assert_eq!(reader.f32().unwrap(), 1.5);
+        assert!(reader.bool().unwrap());
+        assert_eq!(reader.string().unwrap(), "ITEM");
+        assert_eq!(reader.utf16_string().unwrap(), "한글");
+        assert_eq!(reader.remaining(), 0);
+        assert_eq!(reader.u8(), Err(Truncated { opcode: 0xB021, value: "u8" }));
+    }
+
+    #[test]
+    fn frames_packets() {
+        let packet = Packet::new(0x7021, vec![1, 2, 3]);
+        let encoded = packet.encode().unwrap();
+        assert_eq!(encoded, vec![3, 0, 0x21, 0x70, 0, 0, 1, 2, 3]);
+
+        let mut buffer = encoded[..5].to_vec();
+        assert_eq!(Packet::decode(&mut buffer), Ok(None));
+        buffer.extend(&encoded[5..]);
+        buffer.extend(&encoded[..2]);
+        assert_eq!(Packet::decode(&mut buffer), Ok(Some(packet)));
+        assert_eq!(buffer, encoded[..2].to_vec());
+
+        assert_eq!(Packet::decode(&mut vec![3, 0x80, 0x21, 0x70, 0, 0]), Err(Encrypted(0x7021)));
+        assert_eq!(Packet::new(0, vec![0; MAX_DATA_SIZE + 1]).encode(), Err(TooLarge(MAX_DATA_SIZE + 1)));
+    }
+}
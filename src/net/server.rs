@@ -1,12 +1,11 @@
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
 use tokio::sync::mpsc::{Sender};
 use uuid::Uuid;
 
-use crate::net::server::session::BUFFER_SIZE;
+use crate::net::packet::Packet;
 
-/// General type definition for an SRO packet
-type Packet = [u8; BUFFER_SIZE];
 /// A type definition holding a session's channels as triplet. Used for code simplification
 type SessionChannels = (Sender<()>, Sender<Packet>);
 
@@ -14,7 +13,14 @@ type SessionChannels = (Sender<()>, Sender<Packet>);
 pub struct Engine {
     bind_host: &'static str,
     bind_port: u16,
-    sessions: HashMap<Uuid, SessionChannels>,
+    sessions: Sessions,
+}
+
+/// Shared handle to the channels of all connected sessions, used to send packets to clients.
+/// Clones refer to the same sessions.
+#[derive(Clone, Default)]
+pub struct Sessions {
+    channels: Arc<RwLock<HashMap<Uuid, SessionChannels>>>,
 }
 
 
@@ -30,4 +36,5 @@ pub enum ServerSignal {
 
 mod session;
 mod engine;
-mod options;
\ No newline at end of file
+mod options;
+mod sessions;
\ No newline at end of file
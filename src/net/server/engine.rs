@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use prometheus::{register_int_counter, register_int_gauge};
 use tokio::net::TcpListener;
 use tokio::select;
@@ -8,9 +6,11 @@ use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::Receiver;
 use uuid::Uuid;
 
-use crate::{Engine, ServerSignal};
-use crate::net::server::{Packet};
-use crate::net::server::session::{BUFFER_SIZE, Session};
+use crate::net::packet::Packet;
+use crate::net::server::{Engine, ServerSignal, Sessions};
+use crate::net::server::session::Session;
+
+const MESSAGE_CHANNEL_SIZE: usize = 4096;
 
 impl Engine {
     /// Creates a new server instance for given address. Fails if binding is not successful
@@ -18,20 +18,25 @@ impl Engine {
         let mut engine = Engine {
             bind_host: "0.0.0.0",
             bind_port: 8080,
-            sessions: HashMap::new()
+            sessions: Sessions::default()
         };
 
         for opt in opts.iter() {
             opt(&mut engine)
         }
 
-        return engine;
+        engine
+    }
+
+    /// Returns the handle to the connected sessions, e.g. to send packets from packet handlers.
+    pub fn sessions(&self) -> Sessions {
+        self.sessions.clone()
     }
 
     /// Starts the handling of incoming connections.
     /// Returns a [Receiver] to inform about certain events.
-    pub async fn start(mut self) -> Result<(Receiver<ServerSignal>, Receiver<(Uuid, Packet)>), std::io::Error> {
-        let bind_result = TcpListener::bind(format!("{}:{}", self.bind_host, self.bind_port.to_string())).await;
+    pub async fn start(self) -> Result<(Receiver<ServerSignal>, Receiver<(Uuid, Packet)>), std::io::Error> {
+        let bind_result = TcpListener::bind(format!("{}:{}", self.bind_host, self.bind_port)).await;
 
         if let Err(err) = bind_result {
             return Err(err)
@@ -42,7 +47,7 @@ impl Engine {
         info!("server started listening on {}:{}", self.bind_host, self.bind_port);
 
         let (server_signal_sender, server_signal_receiver) = mpsc::channel::<ServerSignal>(2);
-        let (message_sender, message_receiver) = mpsc::channel::<(Uuid, Packet)>(BUFFER_SIZE);
+        let (message_sender, message_receiver) = mpsc::channel::<(Uuid, Packet)>(MESSAGE_CHANNEL_SIZE);
         tokio::spawn(async move {
             let (disconnected_session_sender, mut disconnected_session_receiver) = mpsc::channel::<Uuid>(32);
             handle_signal_result(server_signal_sender.send(ServerSignal::Started).await);
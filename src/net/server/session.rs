@@ -9,11 +9,13 @@ use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
 use uuid::Uuid;
 
-use crate::net::server::{Packet, SessionChannels};
+use crate::net::packet::Packet;
+use crate::net::server::SessionChannels;
 
 pub const BUFFER_SIZE: usize = 4096;
-const INCOMING_CHANNEL_SIZE: usize = 32;
-const OUTGOING_CHANNEL_SIZE: usize = 32;
+/// Packets queued for a session before it's disconnected for not keeping up. A single sight
+/// update or joining the game sends a burst of packets, so the queue has to hold plenty of them.
+const OUTGOING_CHANNEL_SIZE: usize = 1024;
 
 lazy_static! {
     static ref RECEIVED_BYTES_COUNTER: GenericCounter<AtomicU64> = register_int_counter!("net_server_received_bytes", "amount of received bytes").expect("failed to register counter net_server_received_bytes");
@@ -41,11 +43,12 @@ impl Session {
     /// Be aware that it's a multi-producer-single-consumer channel.
     pub async fn start(self, stream: TcpStream, dc_sender: Sender<Uuid>, message_sender: Sender<(Uuid, Packet)>) -> SessionChannels {
         let (interrupt_sender, mut interrupt_receiver) = mpsc::channel::<()>(1);
-        let (_, _incoming_receiver) = mpsc::channel::<[u8; BUFFER_SIZE]>(INCOMING_CHANNEL_SIZE);
-        let (outgoing_sender, mut outgoing_receiver) = mpsc::channel::<[u8; BUFFER_SIZE]>(OUTGOING_CHANNEL_SIZE);
+        let (outgoing_sender, mut outgoing_receiver) = mpsc::channel::<Packet>(OUTGOING_CHANNEL_SIZE);
         let sid = self.id;
         let (mut read_half, mut write_half) = tokio::io::split(stream);
         tokio::spawn(async move {
+            // received bytes which don't form a complete packet yet
+            let mut pending = Vec::new();
             loop {
                 let mut read_buf: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
                 select! {
@@ -58,19 +61,17 @@ impl Session {
                    },
                    read_result = read_half.read(&mut read_buf) => {
                        let _read_bytes = match read_result {
-                           Ok(n) if n == 0 => {
+                           Ok(0) => {
                                debug!("client terminated connection");
                                break;
                            },
                            Ok(n) => {
-                               let send_result = message_sender.send((self.id, read_buf)).await;
-                               match send_result {
-                                   Err(err) => {
-                                       error!("failed to send client {} incoming data ({} bytes) to channel", n, err);
-                                       break;
-                                   },
-                                   Ok(_) => n
+                               pending.extend_from_slice(&read_buf[..n]);
+                               if let Err(err) = forward_packets(sid, &mut pending, &message_sender).await {
+                                   warn!("closing session {}: {}", sid, err);
+                                   break;
                                }
+                               n
                            },
                            Err(e) => {
                                warn!("session {} failed to read from socket: {:?}", sid, e);
@@ -81,7 +82,14 @@ impl Session {
                    },
                    out_channel_result = outgoing_receiver.recv() => {
                        match out_channel_result {
-                           Some(out_data) => {
+                           Some(packet) => {
+                               let out_data = match packet.encode() {
+                                   Ok(out_data) => out_data,
+                                   Err(err) => {
+                                       warn!("session {} dropped outgoing packet {:04X}: {}", sid, packet.opcode, err);
+                                       continue;
+                                   }
+                               };
                                match write_half.write_all(&out_data).await {
                                    Ok(_) => {},
                                    Err(e) => {
@@ -110,3 +118,52 @@ impl Session {
     }
 }
 
+
+/// Decodes all complete packets of the received bytes and sends them to the message channel.
+async fn forward_packets(sid: Uuid, pending: &mut Vec<u8>, message_sender: &Sender<(Uuid, Packet)>) -> Result<(), String> {
+    while let Some(packet) = Packet::decode(pending).map_err(|err| err.to_string())? {
+        message_sender.send((sid, packet)).await
+            .map_err(|err| format!("failed to send incoming packet to channel: {}", err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc::channel;
+
+    use crate::net::server::Sessions;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_sessions_receiving_bursts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (dc_sender, _dc_receiver) = channel(1);
+        let (message_sender, _message_receiver) = channel(1);
+        let id = Uuid::new_v4();
+        let sessions = Sessions::default();
+        sessions.insert(id, Session::new(id).start(stream, dc_sender, message_sender).await);
+
+        let burst = 200;
+        for i in 0..burst {
+            assert!(sessions.send(id, Packet::new(0x3019, vec![i as u8])), "packet {} was dropped", i);
+        }
+        let mut received = Vec::new();
+        let mut pending = Vec::new();
+        while received.len() < burst {
+            let mut buffer = [0; BUFFER_SIZE];
+            let n = client.read(&mut buffer).await.unwrap();
+            assert_ne!(n, 0, "session closed after {} packets", received.len());
+            pending.extend_from_slice(&buffer[..n]);
+            while let Some(packet) = Packet::decode(&mut pending).unwrap() {
+                received.push(packet.data[0]);
+            }
+        }
+        assert!(received.iter().enumerate().all(|(i, data)| *data == i as u8));
+    }
+}
+
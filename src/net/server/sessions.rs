@@ -0,0 +1,105 @@
+use log::warn;
+use tokio::sync::mpsc::error::TrySendError;
+use uuid::Uuid;
+
+use crate::net::packet::Packet;
+use crate::net::server::{SessionChannels, Sessions};
+
+impl Sessions {
+    pub(crate) fn insert(&self, id: Uuid, channels: SessionChannels) {
+        self.channels.write().unwrap().insert(id, channels);
+    }
+
+    pub(crate) fn remove(&self, id: &Uuid) {
+        self.channels.write().unwrap().remove(id);
+    }
+
+    /// Returns the number of connected sessions.
+    pub fn len(&self) -> usize {
+        self.channels.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.channels.read().unwrap().contains_key(id)
+    }
+
+    /// Queues a packet to be sent to given session. Returns false if the session is gone.
+    ///
+    /// Never waits for the session: a client which doesn't read its packets fast enough to keep
+    /// its outgoing queue from filling up is disconnected, so it can't stall other sessions.
+    pub fn send(&self, id: Uuid, packet: Packet) -> bool {
+        let channels = self.channels.read().unwrap();
+        match channels.get(&id) {
+            Some(channels) => queue(id, channels, packet),
+            None => false,
+        }
+    }
+
+    /// Queues a packet to be sent to all given sessions, skipping those which are gone and
+    /// disconnecting those which are too slow (see [Sessions::send]).
+    pub fn broadcast(&self, ids: impl IntoIterator<Item = Uuid>, packet: &Packet) {
+        let channels = self.channels.read().unwrap();
+        for id in ids {
+            if let Some(channels) = channels.get(&id) {
+                queue(id, channels, packet.clone());
+            }
+        }
+    }
+
+    /// Closes the connection of given session.
+    pub fn disconnect(&self, id: Uuid) {
+        if let Some((interrupt, _)) = self.channels.read().unwrap().get(&id) {
+            // a full interrupt channel means the session is already closing
+            let _ = interrupt.try_send(());
+        }
+    }
+}
+
+/// Queues a packet without waiting. Interrupts the session if its outgoing queue is full.
+fn queue(id: Uuid, (interrupt, sender): &SessionChannels, packet: Packet) -> bool {
+    match sender.try_send(packet) {
+        Ok(()) => true,
+        Err(TrySendError::Full(packet)) => {
+            warn!("disconnecting session {}: outgoing queue is full, dropped packet {:04X}", id, packet.opcode);
+            let _ = interrupt.try_send(());
+            false
+        }
+        // a closed session is removed once its disconnect is handled
+        Err(TrySendError::Closed(_)) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::channel;
+
+    use super::*;
+
+    #[test]
+    fn disconnects_slow_sessions() {
+        let sessions = Sessions::default();
+        let (slow, fast) = (Uuid::new_v4(), Uuid::new_v4());
+        let (slow_interrupt, mut slow_interrupted) = channel(1);
+        let (slow_sender, _slow_receiver) = channel(1);
+        sessions.insert(slow, (slow_interrupt, slow_sender));
+        let (fast_interrupt, mut fast_interrupted) = channel(1);
+        let (fast_sender, mut fast_receiver) = channel(4);
+        sessions.insert(fast, (fast_interrupt, fast_sender));
+
+        let packet = Packet::new(0x3026, vec![1]);
+        sessions.broadcast(vec![slow, fast], &packet);
+        assert!(slow_interrupted.try_recv().is_err());
+        sessions.broadcast(vec![slow, fast], &packet);
+        assert!(slow_interrupted.try_recv().is_ok());
+        assert!(!sessions.send(slow, packet.clone()));
+
+        assert!(sessions.send(fast, packet.clone()));
+        assert!(fast_interrupted.try_recv().is_err());
+        assert_eq!(std::iter::from_fn(|| fast_receiver.try_recv().ok()).count(), 3);
+        assert!(!sessions.send(Uuid::new_v4(), packet));
+    }
+}
@@ -1,7 +1,20 @@
 pub mod archive;
+pub mod async_archive;
+pub mod compaction;
 pub mod constants;
+pub mod diff;
+pub mod editing;
 pub mod entry;
 pub mod errors;
-mod directory;
+pub mod extraction;
+pub mod name;
+pub mod packing;
+pub mod patch;
+pub mod verification;
+mod cache;
+pub(crate) mod directory;
+#[cfg(test)]
+pub(crate) mod fixture;
 mod header;
 mod util;
+mod writer;
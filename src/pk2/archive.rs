@@ -1,64 +1,166 @@
 use std::fs::File;
-use std::io::{Read};
+use std::io::ErrorKind;
+use std::os::unix::fs::FileExt;
 use std::path::Path;
 
-use crate::pk2::constants::{HEADER_SIZE};
+use crate::blowfish::Blowfish;
+use crate::pk2::constants::{HEADER_SIZE, KEY_BYTES, SALT};
 use crate::pk2::directory::Directory;
 use crate::pk2::errors::Error;
-use crate::pk2::errors::Error::{IO};
+use crate::pk2::extraction::{ExtractionSummary, Extractor};
+use crate::pk2::entry::Entry;
+use crate::pk2::errors::Error::{InvalidEntry, InvalidHeader, InvalidKey, IO, NotFound};
 use crate::pk2::header::Header;
+use crate::pk2::name::EntryName;
 use crate::pk2::util::read_block;
 
 /// A structure to access an SRO PK2 archive.
+///
+/// Contents are read with positional reads, which don't move a shared file cursor. Hence an archive
+/// can be shared between threads (e.g. in an [Arc](std::sync::Arc)) and read concurrently without locking.
 pub struct Archive {
-    file: File,
+    pub(crate) file: File,
+    pub(crate) header: Header,
+    pub(crate) blowfish: Blowfish,
     pub root: Directory
 }
 
-impl From<File> for Archive {
-    fn from(mut file: File) -> Self {
-        let mut header_buf: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
-        match file.read(&mut header_buf) {
-            Ok(bytes_read) => {
-                if bytes_read != HEADER_SIZE {
-                    panic!("Header length too short: {}", bytes_read);
-                }
-                let header = Header::from(header_buf);
-                header.verify().unwrap();
-                let root = Archive::index(&file).expect("failed to index archive file");
-                Archive{file, root}
-            }
-            Err(err) => panic!("{}", err)
-        }
-    }
+/// The outcome of [Archive::guess_key].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyGuess<'k> {
+    /// The archive isn't encrypted, so it opens with any key.
+    Unencrypted,
+    /// The first candidate matching the header's checksum.
+    Key(&'k [u8]),
+    /// None of the candidates matches.
+    Unknown,
 }
 
 impl Archive {
     /// Opens the PK2 archive file at given path and creates an accessible instance
     pub fn open(file_path: &Path) -> Result<Archive, Error> {
-        let file_result = File::open(file_path);
+        Archive::open_with_key(file_path, KEY_BYTES)
+    }
+
+    /// Opens the PK2 archive file at given path, which is encrypted with given blowfish key
+    pub fn open_with_key(file_path: &Path, key: &[u8]) -> Result<Archive, Error> {
+        let file = File::open(file_path).map_err(IO)?;
+        Archive::with_key(file, key)
+    }
+
+    /// Creates an accessible instance from given file, which is encrypted with given blowfish key
+    pub fn with_key(file: File, key: &[u8]) -> Result<Archive, Error> {
+        let blowfish = Blowfish::new(key, SALT).map_err(|_| InvalidKey("Key must be between 4 and 56 bytes long"))?;
+        let header = read_header(&file)?;
+        header.verify(&blowfish)?;
+        let cipher = if header.is_encrypted() { Some(&blowfish) } else { None };
+        let root = Archive::index(&file, cipher)?;
+        Ok(Archive{file, header, blowfish, root})
+    }
 
-        return match file_result {
-            Ok(file) => {
-                Ok(Archive::from(file))
-            },
-            Err(err) => Err(IO(err))
+    /// Tries the candidate keys on the archive at given path and returns the first one matching
+    /// the header's checksum. Unencrypted archives have no checksum, so no key is tried on them.
+    pub fn guess_key<'k>(file_path: &Path, candidates: &[&'k [u8]]) -> Result<KeyGuess<'k>, Error> {
+        let file = File::open(file_path).map_err(IO)?;
+        let header = read_header(&file)?;
+        if !header.is_encrypted() {
+            return Ok(KeyGuess::Unencrypted);
+        }
+        let guess = candidates.iter()
+            .filter_map(|key| Blowfish::new(key, SALT).ok().map(|blowfish| (*key, blowfish)))
+            .find(|(_, blowfish)| header.verify_checksum(blowfish).is_ok())
+            .map_or(KeyGuess::Unknown, |(key, _)| KeyGuess::Key(key));
+        Ok(guess)
+    }
+
+    /// Returns the blowfish instance used for the archive's blocks, if the archive is encrypted
+    pub(crate) fn cipher(&self) -> Option<&Blowfish> {
+        if self.header.is_encrypted() {
+            Some(&self.blowfish)
+        } else {
+            None
         }
     }
 
     /// Indexes all archive entries recursively
-    fn index(file: &File) -> Result<Directory, Error> {
-        let entries = read_block(file, HEADER_SIZE as u64)?;
-        // 0x2E -> "."
-        let mut root_dir_entry = *entries.iter().find(|e| e.is_dir() && e.name[0] == 0x2e).unwrap();
-        root_dir_entry.name[0] = 0;
+    pub(crate) fn index(file: &File, blowfish: Option<&Blowfish>) -> Result<Directory, Error> {
+        let entries = read_block(file, HEADER_SIZE as u64, blowfish)?;
+        let mut root_dir_entry = *entries.iter()
+            .find(|e| e.is_dir() && e.name.bytes() == b".")
+            .ok_or(InvalidEntry("root directory has no \".\" entry"))?;
+        root_dir_entry.name = EntryName::default();
         let mut root_dir = Directory::from(root_dir_entry);
-        root_dir.expand(file)?;
+        root_dir.expand(file, blowfish)?;
         Ok(root_dir)
     }
 
-    /// Extracts the archive at given location
-    pub fn extract(&mut self, location: &Path) {
-        self.root.extract(location, &mut self.file);
+    /// Returns the entry at given path, e.g. `server_dep/silkroad/textdata/itemdata.txt`
+    /// (see [Directory::find]).
+    pub fn entry(&self, path: &Path) -> Option<&Entry> {
+        self.root.find(path)
+    }
+
+    /// Reads the whole content of the file at given path.
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self.entry(path) {
+            Some(entry) if entry.is_file() => self.read_entry(entry),
+            _ => Err(NotFound(path.to_path_buf()))
+        }
+    }
+
+    /// Reads the whole content of given file entry.
+    pub fn read_entry(&self, entry: &Entry) -> Result<Vec<u8>, Error> {
+        let mut data_buf = vec![0u8; entry.size as usize];
+        self.file.read_exact_at(&mut data_buf, entry.position).map_err(IO)?;
+        Ok(data_buf)
     }
-}
\ No newline at end of file
+
+    /// Extracts all files of the archive at given location (see [Extractor] for more options)
+    pub fn extract(&self, location: &Path) -> ExtractionSummary {
+        Extractor::new(self).extract(location)
+    }
+}
+
+/// Reads the header at the beginning of the archive file
+pub(crate) fn read_header(file: &File) -> Result<Header, Error> {
+    let mut header_buf: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
+    match file.read_exact_at(&mut header_buf, 0) {
+        Ok(_) => Ok(Header::from(header_buf)),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Err(InvalidHeader("Header length too short")),
+        Err(err) => Err(IO(err))
+    }
+}
+
+// archives are meant to be shared between threads, which must not break unnoticed
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Archive>();
+};
+
+#[cfg(test)]
+mod tests {
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    #[test]
+    fn guesses_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let (custom, unencrypted) = (dir.path().join("custom.pk2"), dir.path().join("unencrypted.pk2"));
+        Fixture::with_key(Some(b"secret")).save(&custom);
+        Fixture::with_key(None).save(&unencrypted);
+
+        let candidates: &[&[u8]] = &[KEY_BYTES, b"key", b"secret"];
+        assert_eq!(Archive::guess_key(&custom, candidates).unwrap(), KeyGuess::Key(b"secret"));
+        assert_eq!(Archive::guess_key(&custom, &candidates[..2]).unwrap(), KeyGuess::Unknown);
+        assert_eq!(Archive::guess_key(&unencrypted, candidates).unwrap(), KeyGuess::Unencrypted);
+        assert!(Archive::guess_key(&dir.path().join("missing.pk2"), candidates).is_err());
+    }
+
+    #[test]
+    fn rejects_archives_without_root_directory() {
+        let mut fixture = Fixture::new();
+        fixture.push_block(&[raw_entry(2, b"readme.txt", 0, 0, 0)]);
+        assert!(matches!(Archive::with_key(fixture.file(), KEY_BYTES), Err(InvalidEntry(_))));
+    }
+}
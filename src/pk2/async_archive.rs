@@ -0,0 +1,195 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+
+use tokio::task::spawn_blocking;
+
+use crate::pk2::archive::Archive;
+use crate::pk2::cache::{ContentKey, EntryCache};
+use crate::pk2::entry::Entry;
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::{IO, NotFound};
+
+/// Reads an [Archive] from async code without blocking the runtime's workers. The archive is read
+/// on tokio's blocking thread pool, and the contents of recently read files are kept in a cache
+/// with a configurable byte budget. Clones share the archive and the cache.
+///
+/// An archive opened for editing (see [Archive::open_for_edit]) can be modified through
+/// [AsyncArchive::add_file] and [AsyncArchive::remove], which drop the replaced contents from the
+/// cache.
+#[derive(Clone)]
+pub struct AsyncArchive {
+    archive: Arc<RwLock<Archive>>,
+    cache: Arc<Mutex<EntryCache>>,
+}
+
+impl AsyncArchive {
+    /// Wraps an already opened archive. Up to `cache_budget` bytes of file contents are cached.
+    pub fn new(archive: Archive, cache_budget: usize) -> AsyncArchive {
+        AsyncArchive {
+            archive: Arc::new(RwLock::new(archive)),
+            cache: Arc::new(Mutex::new(EntryCache::new(cache_budget))),
+        }
+    }
+
+    /// Opens and indexes the archive at given path on the blocking thread pool
+    /// (see [Archive::open_with_key]).
+    pub async fn open(file_path: &Path, key: &[u8], cache_budget: usize) -> Result<AsyncArchive, Error> {
+        let (file_path, key) = (file_path.to_path_buf(), key.to_vec());
+        let archive = spawn_blocking(move || Archive::open_with_key(&file_path, &key)).await
+            .map_err(|err| IO(io::Error::other(err)))??;
+        Ok(AsyncArchive::new(archive, cache_budget))
+    }
+
+    /// Returns the wrapped archive, e.g. to look up entries. Its blocking read methods must not
+    /// be called from async code, and the returned guard must not be held across an await.
+    pub fn archive(&self) -> RwLockReadGuard<'_, Archive> {
+        self.archive.read().unwrap()
+    }
+
+    /// Reads the whole content of the file at given path.
+    pub async fn read(&self, path: &Path) -> Result<Arc<[u8]>, Error> {
+        let entry = self.archive().entry(path).copied();
+        match entry {
+            Some(entry) if entry.is_file() => self.read_entry(entry).await,
+            _ => Err(NotFound(PathBuf::from(path)))
+        }
+    }
+
+    /// Reads the whole content of given file entry, from the cache if possible.
+    pub async fn read_entry(&self, entry: Entry) -> Result<Arc<[u8]>, Error> {
+        let key = (entry.position, entry.size);
+        if let Some(data) = self.cache.lock().unwrap().get(key) {
+            return Ok(data);
+        }
+        let archive = self.archive.clone();
+        let data: Arc<[u8]> = spawn_blocking(move || archive.read().unwrap().read_entry(&entry)).await
+            .map_err(|err| IO(io::Error::other(err)))??
+            .into();
+        self.cache.lock().unwrap().insert(key, data.clone());
+        Ok(data)
+    }
+
+    /// Adds or replaces a file on the blocking thread pool (see [Archive::add_file]). The content
+    /// of a replaced file is dropped from the cache.
+    pub async fn add_file(&self, path: &Path, content: Vec<u8>) -> Result<(), Error> {
+        let path = path.to_path_buf();
+        self.edit(move |archive| {
+            let replaced = stale_contents(archive, &path);
+            archive.add_file(&path, &content)?;
+            Ok(replaced)
+        }).await
+    }
+
+    /// Removes a file or directory on the blocking thread pool (see [Archive::remove]). The
+    /// contents of the removed files are dropped from the cache.
+    pub async fn remove(&self, path: &Path) -> Result<(), Error> {
+        let path = path.to_path_buf();
+        self.edit(move |archive| {
+            let removed = stale_contents(archive, &path);
+            archive.remove(&path)?;
+            Ok(removed)
+        }).await
+    }
+
+    /// Modifies the archive on the blocking thread pool and invalidates the cached contents the
+    /// modification returns.
+    async fn edit<F>(&self, modify: F) -> Result<(), Error>
+        where F: FnOnce(&mut Archive) -> Result<Vec<ContentKey>, Error> + Send + 'static {
+        let (archive, cache) = (self.archive.clone(), self.cache.clone());
+        spawn_blocking(move || {
+            let stale = modify(&mut archive.write().unwrap())?;
+            let mut cache = cache.lock().unwrap();
+            stale.into_iter().for_each(|key| cache.invalidate(key));
+            Ok(())
+        }).await.map_err(|err| IO(io::Error::other(err)))?
+    }
+
+    /// Returns the amount of bytes currently cached.
+    pub fn cached_bytes(&self) -> usize {
+        self.cache.lock().unwrap().size()
+    }
+}
+
+/// Returns the contents of the file at given path, or of all files below the directory at given
+/// path, which are no longer read once it's replaced or removed.
+fn stale_contents(archive: &Archive, path: &Path) -> Vec<ContentKey> {
+    let files = match archive.entry(path) {
+        Some(entry) if entry.is_file() => vec![entry],
+        Some(_) => archive.root.find_directory(path)
+            .map(|dir| dir.files().into_iter().map(|(_, entry)| entry).collect())
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    files.into_iter().map(|entry| (entry.position, entry.size)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pk2::constants::{BLOCK_SIZE, KEY_BYTES};
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    /// Saves an archive with the files `a.txt` and `b.txt` and the directory `sub`.
+    fn save(location: &Path) {
+        let mut fixture = Fixture::new();
+        let root = fixture.offset();
+        let (sub, data) = (root + BLOCK_SIZE as u64, root + 2 * BLOCK_SIZE as u64);
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(2, b"a.txt", data, 5, 0),
+            raw_entry(2, b"b.txt", data + 5, 6, 0),
+            raw_entry(1, b"sub", sub, 0, 0),
+        ]);
+        fixture.push_block(&[raw_entry(1, b".", sub, 0, 0), raw_entry(1, b"..", root, 0, 0)]);
+        fixture.push_data(b"helloworld!");
+        fixture.save(location);
+    }
+
+    #[tokio::test]
+    async fn reads_through_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("async.pk2");
+        save(&location);
+        let archive = AsyncArchive::open(&location, KEY_BYTES, 8).await.unwrap();
+
+        let first = archive.read(Path::new("A.TXT")).await.unwrap();
+        assert_eq!(&first[..], b"hello");
+        assert_eq!(archive.cached_bytes(), 5);
+        assert!(Arc::ptr_eq(&first, &archive.clone().read(Path::new("a.txt")).await.unwrap()));
+
+        // "b.txt" doesn't fit next to "a.txt", which is evicted
+        assert_eq!(&archive.read(Path::new("b.txt")).await.unwrap()[..], b"world!");
+        assert_eq!(archive.cached_bytes(), 6);
+        assert!(!Arc::ptr_eq(&first, &archive.read(Path::new("a.txt")).await.unwrap()));
+
+        assert!(matches!(archive.read(Path::new("sub")).await, Err(NotFound(_))));
+        assert!(matches!(archive.read(Path::new("c.txt")).await, Err(NotFound(_))));
+        assert!(AsyncArchive::open(&dir.path().join("missing.pk2"), KEY_BYTES, 8).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn invalidates_edited_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("async.pk2");
+        save(&location);
+        let archive = AsyncArchive::new(Archive::open_for_edit(&location, KEY_BYTES).unwrap(), 16);
+        archive.read(Path::new("a.txt")).await.unwrap();
+        archive.read(Path::new("b.txt")).await.unwrap();
+        assert_eq!(archive.cached_bytes(), 11);
+
+        archive.add_file(Path::new("a.txt"), b"replaced".to_vec()).await.unwrap();
+        assert_eq!(archive.cached_bytes(), 6);
+        assert_eq!(&archive.read(Path::new("a.txt")).await.unwrap()[..], b"replaced");
+        archive.add_file(Path::new("sub/c.txt"), b"c".to_vec()).await.unwrap();
+        archive.read(Path::new("sub/c.txt")).await.unwrap();
+        assert_eq!(archive.cached_bytes(), 15);
+
+        archive.remove(Path::new("sub")).await.unwrap();
+        archive.remove(Path::new("b.txt")).await.unwrap();
+        assert_eq!(archive.cached_bytes(), 8);
+        assert!(matches!(archive.read(Path::new("sub/c.txt")).await, Err(NotFound(_))));
+        assert!(AsyncArchive::new(Archive::open(&location).unwrap(), 16).add_file(Path::new("d.txt"), Vec::new()).await.is_err());
+    }
+}
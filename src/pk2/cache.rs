@@ -0,0 +1,151 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter, register_int_gauge, IntCounter, IntGauge};
+
+lazy_static! {
+    static ref CACHE_HITS_COUNTER: IntCounter = register_int_counter!("pk2_cache_hits", "number of reads served from the entry cache").expect("failed to register counter pk2_cache_hits");
+    static ref CACHE_MISSES_COUNTER: IntCounter = register_int_counter!("pk2_cache_misses", "number of reads which had to access the archive").expect("failed to register counter pk2_cache_misses");
+    static ref CACHE_EVICTIONS_COUNTER: IntCounter = register_int_counter!("pk2_cache_evictions", "number of entries evicted from the entry cache").expect("failed to register counter pk2_cache_evictions");
+    static ref CACHE_BYTES_GAUGE: IntGauge = register_int_gauge!("pk2_cache_bytes", "current amount of cached bytes").expect("failed to register gauge pk2_cache_bytes");
+}
+
+/// Identifies a file content by its position and size in the archive, so a content replaced at
+/// the same position isn't mistaken for the cached one.
+pub(crate) type ContentKey = (u64, u32);
+
+/// Least recently used cache of file contents (see [ContentKey]).
+/// The total size of the cached contents never exceeds the byte budget.
+pub(crate) struct EntryCache {
+    budget: usize,
+    size: usize,
+    tick: u64,
+    /// Cached contents and the tick they were last used at.
+    entries: HashMap<ContentKey, (Arc<[u8]>, u64)>,
+    /// Keys of the cached contents by the tick they were last used at.
+    recency: BTreeMap<u64, ContentKey>,
+}
+
+impl EntryCache {
+    pub(crate) fn new(budget: usize) -> EntryCache {
+        EntryCache { budget, size: 0, tick: 0, entries: HashMap::new(), recency: BTreeMap::new() }
+    }
+
+    /// Returns the cached content and marks it as recently used.
+    pub(crate) fn get(&mut self, key: ContentKey) -> Option<Arc<[u8]>> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(&key) {
+            Some((data, last_used)) => {
+                self.recency.remove(last_used);
+                self.recency.insert(tick, key);
+                *last_used = tick;
+                CACHE_HITS_COUNTER.inc();
+                Some(data.clone())
+            }
+            None => {
+                CACHE_MISSES_COUNTER.inc();
+                None
+            }
+        }
+    }
+
+    /// Caches a content, evicting the least recently used contents until it fits into the budget.
+    /// Contents larger than the whole budget aren't cached.
+    pub(crate) fn insert(&mut self, key: ContentKey, data: Arc<[u8]>) {
+        if data.len() > self.budget || self.entries.contains_key(&key) {
+            return;
+        }
+        while self.size + data.len() > self.budget {
+            self.evict();
+        }
+        self.tick += 1;
+        self.size += data.len();
+        CACHE_BYTES_GAUGE.add(data.len() as i64);
+        self.recency.insert(self.tick, key);
+        self.entries.insert(key, (data, self.tick));
+    }
+
+    /// Drops a content which is no longer part of the archive, e.g. because its file was replaced.
+    pub(crate) fn invalidate(&mut self, key: ContentKey) {
+        if let Some((data, last_used)) = self.entries.remove(&key) {
+            self.recency.remove(&last_used);
+            self.size -= data.len();
+            CACHE_BYTES_GAUGE.sub(data.len() as i64);
+        }
+    }
+
+    /// Returns the amount of cached bytes.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Removes the least recently used content.
+    fn evict(&mut self) {
+        if let Some((_, key)) = self.recency.pop_first() {
+            if let Some((data, _)) = self.entries.remove(&key) {
+                self.size -= data.len();
+                CACHE_BYTES_GAUGE.sub(data.len() as i64);
+                CACHE_EVICTIONS_COUNTER.inc();
+            }
+        }
+    }
+}
+
+impl Drop for EntryCache {
+    fn drop(&mut self) {
+        CACHE_BYTES_GAUGE.sub(self.size as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(len: usize) -> Arc<[u8]> {
+        vec![0; len].into()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_contents() {
+        let mut cache = EntryCache::new(10);
+        cache.insert((1, 4), data(4));
+        cache.insert((2, 4), data(4));
+        assert!(cache.get((1, 4)).is_some());
+        cache.insert((3, 4), data(4));
+        assert_eq!(cache.size(), 8);
+        assert!(cache.get((2, 4)).is_none());
+        assert!(cache.get((1, 4)).is_some());
+        assert!(cache.get((3, 4)).is_some());
+    }
+
+    #[test]
+    fn stays_within_its_budget() {
+        let mut cache = EntryCache::new(10);
+        cache.insert((1, 11), data(11));
+        assert!(cache.get((1, 11)).is_none());
+        cache.insert((2, 6), data(6));
+        cache.insert((2, 6), data(6));
+        assert_eq!(cache.size(), 6);
+        cache.insert((3, 3), data(3));
+        cache.insert((4, 10), data(10));
+        assert_eq!(cache.size(), 10);
+        assert!(cache.get((2, 6)).is_none() && cache.get((3, 3)).is_none());
+        assert!(EntryCache::new(0).get((4, 10)).is_none());
+    }
+
+    #[test]
+    fn tells_contents_at_the_same_position_apart() {
+        let mut cache = EntryCache::new(10);
+        cache.insert((1, 4), data(4));
+        assert!(cache.get((1, 5)).is_none());
+        cache.insert((1, 5), data(5));
+        cache.invalidate((1, 4));
+        assert_eq!(cache.size(), 5);
+        assert!(cache.get((1, 4)).is_none());
+        assert!(cache.get((1, 5)).is_some());
+        cache.insert((2, 5), data(5));
+        assert_eq!(cache.size(), 10);
+    }
+}
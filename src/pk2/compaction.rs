@@ -0,0 +1,170 @@
+use std::fs::{File, rename};
+use std::path::Path;
+
+use crate::pk2::archive::Archive;
+use crate::pk2::directory::Directory;
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::IO;
+use crate::pk2::constants::{BLOCK_SIZE, HEADER_SIZE};
+use crate::pk2::util::{block_chain, copy_content};
+use crate::pk2::writer::Writer;
+
+/// Describes how the bytes of an archive are used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpaceUsage {
+    /// Size of the archive file.
+    pub archive_size: u64,
+    /// Bytes occupied by the block chains of all directories.
+    pub block_bytes: u64,
+    /// Bytes occupied by the contents of all files.
+    pub data_bytes: u64,
+    /// Size of the archive after compaction.
+    pub compacted_size: u64,
+}
+
+impl SpaceUsage {
+    /// Bytes which are reclaimed by compacting the archive.
+    pub fn wasted_bytes(&self) -> u64 {
+        self.archive_size.saturating_sub(self.compacted_size)
+    }
+
+    /// Bytes which are referenced by neither a directory nor a file, e.g. orphaned file data.
+    pub fn unreferenced_bytes(&self) -> u64 {
+        self.archive_size.saturating_sub(HEADER_SIZE as u64 + self.block_bytes + self.data_bytes)
+    }
+}
+
+impl Archive {
+    /// Determines how the space of the archive is used and how much of it is wasted.
+    pub fn space_usage(&self) -> Result<SpaceUsage, Error> {
+        let archive_size = self.file.metadata().map_err(IO)?.len();
+        let (block_bytes, data_bytes) = self.directory_usage(&self.root)?;
+        let compacted_size = Writer::new(&self.root)?.size();
+        Ok(SpaceUsage { archive_size, block_bytes, data_bytes, compacted_size })
+    }
+
+    fn directory_usage(&self, dir: &Directory) -> Result<(u64, u64), Error> {
+        let mut block_bytes = (block_chain(&self.file, dir.entry.position, self.cipher())?.len() * BLOCK_SIZE) as u64;
+        let mut data_bytes = dir.entries.values()
+            .filter(|e| e.is_file())
+            .map(|e| e.size as u64)
+            .sum();
+        for sub_dir in dir.directories.values() {
+            let (sub_block_bytes, sub_data_bytes) = self.directory_usage(sub_dir)?;
+            block_bytes += sub_block_bytes;
+            data_bytes += sub_data_bytes;
+        }
+        Ok((block_bytes, data_bytes))
+    }
+
+    /// Writes a compacted copy of the archive to given location. The copy has no unreferenced
+    /// bytes and every directory only uses as many blocks as it needs, while all entries keep their
+    /// paths, timestamps and contents.
+    ///
+    /// Returns the space usage of the archive before compaction.
+    pub fn compact_to(&self, location: &Path) -> Result<SpaceUsage, Error> {
+        let usage = self.space_usage()?;
+        let target = File::create(location).map_err(IO)?;
+        Writer::new(&self.root)?.write(&self.header, &self.blowfish, &target, |_, entry, out| copy_content(&self.file, entry, out))?;
+        target.sync_all().map_err(IO)?;
+        Ok(usage)
+    }
+
+    /// Compacts the archive at given path, encrypted with given key, in place (see
+    /// [Archive::compact_to]). The compacted archive is written next to the original one, which
+    /// is replaced afterwards.
+    pub fn compact(file_path: &Path, key: &[u8]) -> Result<SpaceUsage, Error> {
+        let mut temp_path = file_path.as_os_str().to_owned();
+        temp_path.push(".compact");
+        let usage = Archive::open_with_key(file_path, key)?.compact_to(Path::new(&temp_path))?;
+        rename(&temp_path, file_path).map_err(IO)?;
+        Ok(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pk2::constants::{ENTRIES_PER_BLOCK, ENTRY_SIZE, KEY_BYTES};
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    const KEY: &[u8] = b"ABCDEFGH";
+
+    /// Builds an unencrypted archive whose root directory has a needless second block, and whose
+    /// files `a.txt` and `sub/b.txt` are followed by 4 orphaned bytes.
+    fn wasteful() -> Fixture {
+        let mut fixture = Fixture::with_key(None);
+        let root = fixture.offset();
+        let (sub, chained, data) = (root + BLOCK_SIZE as u64, root + 2 * BLOCK_SIZE as u64, root + 3 * BLOCK_SIZE as u64);
+        let mut root_block = vec![
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(1, b"sub", sub, 0, 0),
+            raw_entry(2, b"a.txt", data, 5, 0),
+        ];
+        root_block.resize(ENTRIES_PER_BLOCK - 1, [0; ENTRY_SIZE]);
+        root_block.push(raw_entry(0, b"", 0, 0, chained));
+        fixture.push_block(&root_block);
+        fixture.push_block(&[
+            raw_entry(1, b".", sub, 0, 0),
+            raw_entry(1, b"..", root, 0, 0),
+            raw_entry(2, b"b.txt", data + 5, 3, 0),
+        ]);
+        fixture.push_block(&[]);
+        fixture.push_data(b"helloabcxxxx");
+        fixture
+    }
+
+    #[test]
+    fn determines_space_usage() {
+        let archive = Archive::with_key(wasteful().file(), KEY_BYTES).unwrap();
+        let usage = archive.space_usage().unwrap();
+        assert_eq!(usage, SpaceUsage {
+            archive_size: (HEADER_SIZE + 3 * BLOCK_SIZE + 12) as u64,
+            block_bytes: 3 * BLOCK_SIZE as u64,
+            data_bytes: 8,
+            compacted_size: (HEADER_SIZE + 2 * BLOCK_SIZE + 8) as u64,
+        });
+        assert_eq!(usage.wasted_bytes(), BLOCK_SIZE as u64 + 4);
+        assert_eq!(usage.unreferenced_bytes(), 4);
+    }
+
+    #[test]
+    fn compacts_to_a_copy() {
+        let archive = Archive::with_key(wasteful().file(), KEY_BYTES).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("compacted.pk2");
+        assert_eq!(archive.compact_to(&location).unwrap(), archive.space_usage().unwrap());
+
+        let compacted = Archive::open(&location).unwrap();
+        assert!(!compacted.header.is_encrypted());
+        assert_eq!(compacted.file.metadata().unwrap().len(), (HEADER_SIZE + 2 * BLOCK_SIZE + 8) as u64);
+        assert_eq!(compacted.read(Path::new("a.txt")).unwrap(), b"hello");
+        assert_eq!(compacted.read(Path::new("sub/b.txt")).unwrap(), b"abc");
+        let (original, copied) = (archive.entry(Path::new("sub/b.txt")).unwrap(), compacted.entry(Path::new("sub/b.txt")).unwrap());
+        assert_eq!((copied.created(), copied.modified()), (original.created(), original.modified()));
+        assert!(Archive::verify(&location, KEY_BYTES).unwrap().issues.is_empty());
+    }
+
+    #[test]
+    fn compacts_archives_encrypted_with_any_key() {
+        let mut fixture = Fixture::with_key(Some(KEY));
+        let root = fixture.offset();
+        let data = root + BLOCK_SIZE as u64;
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(2, b"a.txt", data + 16, 5, 0),
+        ]);
+        // the first 16 bytes are orphaned
+        fixture.push_data(b"0123456789abcdefhello");
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("k.pk2");
+        fixture.save(&location);
+
+        let usage = Archive::compact(&location, KEY).unwrap();
+        assert_eq!(usage.wasted_bytes(), 16);
+        let archive = Archive::open_with_key(&location, KEY).unwrap();
+        assert_eq!(archive.read(Path::new("a.txt")).unwrap(), b"hello");
+        assert_eq!(archive.space_usage().unwrap().wasted_bytes(), 0);
+    }
+}
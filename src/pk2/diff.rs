@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::pk2::archive::Archive;
+use crate::pk2::entry::Entry;
+use crate::pk2::errors::Error;
+use crate::pk2::util::copy_content;
+
+/// SHA-256 hash of the content of a file.
+pub type ContentHash = [u8; 32];
+
+/// A difference of a file between two archives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The file only exists in the newer archive.
+    Added(PathBuf),
+    /// The file only exists in the older archive.
+    Removed(PathBuf),
+    /// The file exists in both archives with different contents.
+    Modified(PathBuf),
+}
+
+impl Change {
+    /// Returns the path of the changed file.
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added(path) | Change::Removed(path) | Change::Modified(path) => path,
+        }
+    }
+}
+
+impl Archive {
+    /// Computes the hash of the content of given file entry.
+    pub fn content_hash(&self, entry: &Entry) -> Result<ContentHash, Error> {
+        let mut hasher = Sha256::new();
+        copy_content(&self.file, entry, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Compares the files of this archive with the ones of a newer archive by path and content hash.
+    /// Paths are compared case-insensitively, like the client looks them up. The changes are
+    /// sorted by path.
+    pub fn diff(&self, newer: &Archive) -> Result<Vec<Change>, Error> {
+        let older_files = files_by_key(self);
+        let newer_files = files_by_key(newer);
+        let mut changes = Vec::new();
+
+        for (key, (path, entry)) in older_files.iter() {
+            match newer_files.get(key) {
+                None => changes.push(Change::Removed(path.clone())),
+                Some((newer_path, newer_entry)) => {
+                    if entry.size != newer_entry.size || self.content_hash(entry)? != newer.content_hash(newer_entry)? {
+                        changes.push(Change::Modified(newer_path.clone()));
+                    }
+                }
+            }
+        }
+        changes.extend(newer_files.iter()
+            .filter(|(key, _)| !older_files.contains_key(*key))
+            .map(|(_, (path, _))| Change::Added(path.clone())));
+
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(changes)
+    }
+}
+
+/// Returns all files of an archive keyed by their path in ASCII lower case. The raw bytes of the
+/// path are used, so names which can't be decoded don't collide.
+fn files_by_key(archive: &Archive) -> BTreeMap<Vec<u8>, (PathBuf, &Entry)> {
+    archive.root.files().into_iter()
+        .map(|(path, entry)| (path.as_os_str().as_bytes().to_ascii_lowercase(), (path, entry)))
+        .collect()
+}
@@ -1,16 +1,17 @@
 use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::fs::File;
 use std::path::{Path, PathBuf};
 
+use crate::blowfish::Blowfish;
 use crate::pk2::entry::Entry;
 use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::InvalidBlock;
 use crate::pk2::util::read_block;
 
 /// Represents a directory entry in the PK2 archive.
 #[derive(Clone)]
 pub struct Directory {
-    entry: Entry,
+    pub entry: Entry,
     pub entries: HashMap<PathBuf, Entry>,
     pub directories: HashMap<PathBuf, Directory>,
 }
@@ -30,13 +31,22 @@ impl From<Entry> for Directory {
 }
 
 impl Directory {
-    /// Expands a directory recursively. Used for indexing.
-    pub fn expand(&mut self, file: &File) -> Result<(), Error> {
-        let entries = read_block(file, self.entry.position)?;
+    /// Expands a directory recursively. Used for indexing. Fails if a directory points back to
+    /// its own block or the block of one of its parents, which would never end.
+    pub fn expand(&mut self, file: &File, blowfish: Option<&Blowfish>) -> Result<(), Error> {
+        self.expand_within(file, blowfish, &mut Vec::new())
+    }
+
+    /// Expands a directory recursively, below the directories at given block positions.
+    fn expand_within(&mut self, file: &File, blowfish: Option<&Blowfish>, ancestors: &mut Vec<u64>) -> Result<(), Error> {
+        if ancestors.contains(&self.entry.position) {
+            return Err(InvalidBlock("cyclic directory"));
+        }
+        let entries = read_block(file, self.entry.position, blowfish)?;
         let path = self.entry.path_buf().clone();
         let mapped_entries: HashMap<PathBuf, Entry> = entries.iter()
             .filter(|e| !e.is_empty())
-            .filter(|e| e.name[0] != 0x2E)
+            .filter(|e| !e.name.is_link())
             .map(|entry| {
                 let mut cloned_path = path.clone();
                 cloned_path.push(entry.path_buf());
@@ -47,21 +57,98 @@ impl Directory {
 
         let mut dirs: HashMap<PathBuf, Directory> = entries.iter()
             .filter(|e| e.is_dir())
-            .filter(|e| e.name[0] != 0x2E) // 0x2E -> "."
+            .filter(|e| !e.name.is_link())
             .map(|e| Directory::from(*e))
             .map(|d| (d.entry.path_buf(), d))
             .collect();
 
+        ancestors.push(self.entry.position);
         dirs.iter_mut()
-            .for_each(|(_, d)| {
-                d.expand(file).unwrap()
-            });
+            .try_for_each(|(_, d)| d.expand_within(file, blowfish, ancestors))?;
+        ancestors.pop();
 
         self.directories.extend(dirs);
 
         Ok(())
     }
 
+    /// Adds a file entry to the directory.
+    pub fn add_file(&mut self, entry: Entry) {
+        self.entries.insert(self.entry.path_buf().join(entry.path_buf()), entry);
+    }
+
+    /// Adds a sub directory and its entry to the directory.
+    pub fn add_directory(&mut self, directory: Directory) {
+        self.entries.insert(self.entry.path_buf().join(directory.entry.path_buf()), directory.entry);
+        self.directories.insert(directory.entry.path_buf(), directory);
+    }
+
+    /// Removes the entry with given name, ignoring ASCII case, together with its sub directory if
+    /// it's a directory. Returns the removed entry.
+    pub fn remove(&mut self, name: &str) -> Option<Entry> {
+        let path = self.entries.keys().find(|p| name_matches(p, name))?.clone();
+        self.directories.retain(|p, _| !name_matches(p, name));
+        self.entries.remove(&path)
+    }
+
+    /// Looks up the entry at given path relative to this directory. Path components may be
+    /// separated by `/` or `\` and are compared case-insensitively, like the client does.
+    pub fn find(&self, path: &Path) -> Option<&Entry> {
+        let mut components = path_components(path);
+        let name = components.pop()?;
+        let dir = components.iter()
+            .try_fold(self, |dir, component| dir.child_directory(component))?;
+        dir.child_entry(&name)
+    }
+
+    /// Looks up the directory at given path relative to this directory (see [Directory::find]).
+    /// An empty path refers to this directory.
+    pub fn find_directory(&self, path: &Path) -> Option<&Directory> {
+        path_components(path).iter()
+            .try_fold(self, |dir, component| dir.child_directory(component))
+    }
+
+    /// Returns the entry with given name in this directory, ignoring ASCII case.
+    pub fn child_entry(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(&self.entry.path_buf().join(name))
+            .or_else(|| self.entries.iter()
+                .find(|(p, _)| name_matches(p, name))
+                .map(|(_, e)| e))
+    }
+
+    /// Returns the sub directory with given name, ignoring ASCII case.
+    pub fn child_directory(&self, name: &str) -> Option<&Directory> {
+        self.directories.get(Path::new(name))
+            .or_else(|| self.directories.iter()
+                .find(|(p, _)| name_matches(p, name))
+                .map(|(_, d)| d))
+    }
+
+    /// Returns the sub directory with given name for modification, ignoring ASCII case.
+    pub fn child_directory_mut(&mut self, name: &str) -> Option<&mut Directory> {
+        match self.directories.keys().find(|p| name_matches(p, name)).cloned() {
+            Some(path) => self.directories.get_mut(&path),
+            None => None,
+        }
+    }
+
+    /// Returns all files of the directory and its sub directories together with their paths
+    /// relative to this directory, sorted by path.
+    pub fn files(&self) -> Vec<(PathBuf, &Entry)> {
+        let mut files = Vec::new();
+        self.collect_files(Path::new(""), &mut files);
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        files
+    }
+
+    fn collect_files<'a>(&'a self, path: &Path, files: &mut Vec<(PathBuf, &'a Entry)>) {
+        files.extend(self.entries.values()
+            .filter(|e| e.is_file())
+            .map(|e| (path.join(e.path_buf()), e)));
+        self.directories.iter()
+            .for_each(|(name, dir)| dir.collect_files(&path.join(name), files));
+    }
+
     /// Prints out all entries of a directory recursively.
     pub fn print_entries(&self) {
         self.directories.iter()
@@ -75,36 +162,112 @@ impl Directory {
                 info!("File: {:?}", p.as_path());
             });
     }
+}
 
-    /// Extracts the directory at given location. Requires the file to read it.
-    pub fn extract(&self, location: &Path, file: &mut File) {
-        if !location.exists() {
-            create_dir_all(location).expect(format!("failed to create target directory {:?}", location).as_str());
-        }
+/// Returns whether the last component of given path equals the name ignoring ASCII case.
+fn name_matches(path: &Path, name: &str) -> bool {
+    path.file_name()
+        .map(|file_name| file_name.to_string_lossy().eq_ignore_ascii_case(name))
+        .unwrap_or(false)
+}
 
-        self.entries.iter()
-            .filter(|(_p, e)| e.is_file())
-            .for_each(|(p, e)| {
-                file.seek(SeekFrom::Start(e.position)).expect("failed to move to entry's file offset");
-                let mut data_buf= vec![0u8; e.size as usize];
-                let bytes_read = file.read(&mut data_buf).expect("failed to read file content");
-                if bytes_read != e.size as usize {
-                    warn!("read {} bytes for entry although it has size {}", bytes_read, e.size);
-                }
-                let file_name = p.file_name().expect("failed to extract file name from its path");
-                let f_loc = location.join(file_name);
-                let mut f = File::create(&f_loc).expect(format!("failed to create file {:?}", f_loc).as_str());
-                f.write_all(&data_buf).expect("failed to write file data");
-            });
+/// Splits a path in an archive into its components, which may be separated by `/` or `\`.
+pub fn path_components(path: &Path) -> Vec<String> {
+    path.to_string_lossy()
+        .split(['/', '\\'])
+        .filter(|c| !c.is_empty())
+        .map(String::from)
+        .collect()
+}
 
-        self.directories.iter()
-            .for_each(|(p, dir)| {
-                let new_loc = location.join(p);
-                if !new_loc.exists() {
-                    create_dir_all(&new_loc).expect(format!("failed to create target directory {:?}", &new_loc).as_str());
-                }
-                dir.extract(&new_loc, file);
-            });
+#[cfg(test)]
+mod tests {
+    use crate::pk2::constants::{BLOCK_SIZE, ENTRIES_PER_BLOCK, HEADER_SIZE, KEY_BYTES};
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    /// "한글" encoded as EUC-KR
+    const KOREAN_NAME: &[u8] = &[0xC7, 0xD1, 0xB1, 0xDB];
+
+    /// Builds an archive with a root directory containing `readme.txt`, a directory `Data` whose
+    /// 25 files span two chained blocks, and a directory `한글` containing `a.txt`.
+    fn fixture(key: Option<&[u8]>) -> Fixture {
+        let root = HEADER_SIZE as u64;
+        let (data, data_chained, korean) = (root + BLOCK_SIZE as u64, root + 2 * BLOCK_SIZE as u64, root + 3 * BLOCK_SIZE as u64);
+        let mut fixture = Fixture::with_key(key);
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(1, b"Data", data, 0, 0),
+            [0u8; 128],
+            raw_entry(2, b"readme.txt", 0, 0, 0),
+            raw_entry(1, KOREAN_NAME, korean, 0, 0),
+        ]);
+        let mut data_entries = vec![raw_entry(1, b".", data, 0, 0), raw_entry(1, b"..", root, 0, 0)];
+        data_entries.extend((0..25).map(|i| raw_entry(2, format!("file{:02}.dat", i).as_bytes(), 0, 0, 0)));
+        data_entries[ENTRIES_PER_BLOCK - 1][118..126].copy_from_slice(&data_chained.to_le_bytes());
+        fixture.push_block(&data_entries[..ENTRIES_PER_BLOCK]);
+        fixture.push_block(&data_entries[ENTRIES_PER_BLOCK..]);
+        fixture.push_block(&[
+            raw_entry(1, b".", korean, 0, 0),
+            raw_entry(1, b"..", root, 0, 0),
+            raw_entry(2, b"a.txt", 0, 0, 0),
+        ]);
+        fixture
     }
-}
 
+    fn expand(fixture: &Fixture) -> Directory {
+        let mut root = Directory::from(Entry::from(&raw_entry(1, b"", HEADER_SIZE as u64, 0, 0)[..]));
+        root.expand(&fixture.file(), fixture.cipher()).unwrap();
+        root
+    }
+
+    #[test]
+    fn expands_nested_directories() {
+        let root = expand(&fixture(Some(KEY_BYTES)));
+        assert_eq!(root.entries.len(), 3);
+        assert_eq!(root.directories.len(), 2);
+        assert_eq!(root.find_directory(Path::new("data")).unwrap().entries.len(), 25);
+        assert!(root.find(Path::new("DATA\\file24.dat")).is_some());
+        assert!(root.find(Path::new("한글/A.TXT")).is_some());
+        assert!(root.find(Path::new("Data/.")).is_none());
+        assert!(root.find(Path::new("Data/..")).is_none());
+    }
+
+    #[test]
+    fn expands_unencrypted_archive() {
+        let root = expand(&fixture(None));
+        assert_eq!(root.files().len(), 27);
+    }
+
+    #[test]
+    fn lists_files_sorted() {
+        let root = expand(&fixture(Some(KEY_BYTES)));
+        let files: Vec<PathBuf> = root.files().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(files.first().unwrap(), Path::new("Data/file00.dat"));
+        assert_eq!(files[25], Path::new("readme.txt"));
+        assert_eq!(files[26], Path::new("한글/a.txt"));
+    }
+
+    #[test]
+    fn rejects_cyclic_directories() {
+        let root = HEADER_SIZE as u64;
+        let data = root + BLOCK_SIZE as u64;
+        let mut fixture = Fixture::with_key(Some(KEY_BYTES));
+        fixture.push_block(&[raw_entry(1, b".", root, 0, 0), raw_entry(1, b"Data", data, 0, 0)]);
+        fixture.push_block(&[raw_entry(1, b".", data, 0, 0), raw_entry(1, b"Loop", root, 0, 0)]);
+        let mut directory = Directory::from(Entry::from(&raw_entry(1, b"", root, 0, 0)[..]));
+        assert!(matches!(directory.expand(&fixture.file(), fixture.cipher()), Err(InvalidBlock(_))));
+
+        let mut fixture = Fixture::with_key(Some(KEY_BYTES));
+        fixture.push_block(&[raw_entry(1, b".", root, 0, 0), raw_entry(1, b"Self", root, 0, 0)]);
+        let mut directory = Directory::from(Entry::from(&raw_entry(1, b"", root, 0, 0)[..]));
+        assert!(matches!(directory.expand(&fixture.file(), fixture.cipher()), Err(InvalidBlock(_))));
+    }
+
+    #[test]
+    fn splits_path_components() {
+        assert_eq!(path_components(Path::new("a\\b//c/")), vec!["a", "b", "c"]);
+        assert!(path_components(Path::new("")).is_empty());
+    }
+}
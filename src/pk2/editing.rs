@@ -0,0 +1,289 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::pk2::archive::Archive;
+use crate::pk2::constants::{BLOCK_SIZE, ENTRIES_PER_BLOCK, ENTRY_SIZE};
+use crate::pk2::directory::{Directory, path_components};
+use crate::pk2::entry::{Entry, EntryType};
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::{InvalidEntry, IO, NotFound};
+use crate::pk2::util::{block_chain, read_single_block, write_block, write_entry};
+
+impl Archive {
+    /// Opens the PK2 archive file at given path for in-place modifications
+    /// (see [Archive::add_file] and [Archive::remove]).
+    pub fn open_for_edit(file_path: &Path, key: &[u8]) -> Result<Archive, Error> {
+        let file = File::options().read(true).write(true).open(file_path).map_err(IO)?;
+        Archive::with_key(file, key)
+    }
+
+    /// Adds a file with given content at given path or replaces the content of an existing one.
+    /// Missing parent directories are created.
+    ///
+    /// The content and any new blocks are appended to the archive. Replaced content remains in the
+    /// archive as unreferenced bytes until it is compacted (see [Archive::compact]). The content is
+    /// only appended once the path turned out to be valid.
+    pub fn add_file(&mut self, path: &Path, content: &[u8]) -> Result<(), Error> {
+        let mut components = path_components(path);
+        reject_links(&components)?;
+        let name = components.pop().ok_or_else(|| NotFound(path.to_path_buf()))?;
+        let size = u32::try_from(content.len()).map_err(|_| InvalidEntry("content is too large"))?;
+        let now = SystemTime::now();
+        let mut entry = Entry::new(EntryType::File, &name, size, now, now)?;
+
+        let mut dir_position = self.root.entry.position;
+        for depth in 0..components.len() {
+            dir_position = self.ensure_directory(dir_position, &components[..=depth])?;
+        }
+
+        let slot = self.find_slot(dir_position, &name)?;
+        if matches!(slot, Some((_, existing)) if existing.is_dir()) {
+            return Err(InvalidEntry("path is a directory"));
+        }
+        let position = self.append(content)?;
+        let entry = match slot {
+            Some((offset, mut existing)) => {
+                existing.position = position;
+                existing.size = size;
+                existing.set_modified(now);
+                write_entry(&self.file, offset, &existing, self.cipher())?;
+                existing
+            }
+            None => {
+                entry.position = position;
+                self.insert_entry(dir_position, entry)?
+            }
+        };
+
+        self.indexed_directory(&components)?.add_file(entry);
+        Ok(())
+    }
+
+    /// Removes the file or directory at given path. The content of removed files and the blocks of
+    /// removed directories remain in the archive as unreferenced bytes until it is compacted.
+    pub fn remove(&mut self, path: &Path) -> Result<(), Error> {
+        let mut components = path_components(path);
+        reject_links(&components)?;
+        let name = components.pop().ok_or_else(|| NotFound(path.to_path_buf()))?;
+        let dir = self.root.find_directory(Path::new(&components.join("/")))
+            .ok_or_else(|| NotFound(path.to_path_buf()))?;
+
+        let (offset, existing) = self.find_slot(dir.entry.position, &name)?
+            .ok_or_else(|| NotFound(path.to_path_buf()))?;
+        // the link to the next block must be kept in the block's last entry
+        let mut empty = Entry::empty();
+        empty.next_chain = existing.next_chain;
+        write_entry(&self.file, offset, &empty, self.cipher())?;
+
+        self.indexed_directory(&components)?.remove(&name);
+        Ok(())
+    }
+
+    /// Returns the position of the directory at given path, whose parent is at given position.
+    /// The directory is created if it doesn't exist.
+    fn ensure_directory(&mut self, dir_position: u64, path: &[String]) -> Result<u64, Error> {
+        let (name, parent) = path.split_last().ok_or(InvalidEntry("path is the root directory"))?;
+        match self.find_slot(dir_position, name)? {
+            Some((_, existing)) if existing.is_dir() => Ok(existing.position),
+            Some(_) => Err(InvalidEntry("path is a file")),
+            None => {
+                let now = SystemTime::now();
+                let mut entry = Entry::new(EntryType::Dir, name, 0, now, now)?;
+                // the new block is appended at the current end of the archive
+                entry.position = self.append_block(vec![
+                    Entry::link(&entry, b".", self.file_size()?),
+                    Entry::link(&entry, b"..", dir_position),
+                ])?;
+                let entry = self.insert_entry(dir_position, entry)?;
+                self.indexed_directory(parent)?.add_directory(Directory::from(entry));
+                Ok(entry.position)
+            }
+        }
+    }
+
+    /// Looks up the entry with given name in the block chain of the directory at given position.
+    /// Returns the entry and its offset in the archive. The `.` and `..` links are never matched.
+    fn find_slot(&self, dir_position: u64, name: &str) -> Result<Option<(u64, Entry)>, Error> {
+        for block in block_chain(&self.file, dir_position, self.cipher())? {
+            let entries = read_single_block(&self.file, block, self.cipher())?;
+            let slot = entries.into_iter()
+                .enumerate()
+                .find(|(_, e)| !e.is_empty() && !e.name.is_link() && e.path_buf().to_string_lossy().eq_ignore_ascii_case(name));
+            if let Some((i, entry)) = slot {
+                return Ok(Some((block + (i * ENTRY_SIZE) as u64, entry)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Stores an entry in the first empty slot of the directory at given position.
+    /// If all slots are used, a new block is appended to the directory's block chain. Returns the
+    /// entry as it was stored.
+    fn insert_entry(&mut self, dir_position: u64, mut entry: Entry) -> Result<Entry, Error> {
+        let chain = block_chain(&self.file, dir_position, self.cipher())?;
+        for block in chain.iter() {
+            let entries = read_single_block(&self.file, *block, self.cipher())?;
+            if let Some(i) = entries.iter().position(|e| e.is_empty()) {
+                entry.next_chain = entries[i].next_chain;
+                write_entry(&self.file, block + (i * ENTRY_SIZE) as u64, &entry, self.cipher())?;
+                return Ok(entry);
+            }
+        }
+
+        let last_block = *chain.last().unwrap_or(&dir_position);
+        let last_offset = last_block + ((ENTRIES_PER_BLOCK - 1) * ENTRY_SIZE) as u64;
+        let mut last_entry = read_single_block(&self.file, last_block, self.cipher())?[ENTRIES_PER_BLOCK - 1];
+        last_entry.next_chain = self.append_block(vec![entry])?;
+        write_entry(&self.file, last_offset, &last_entry, self.cipher())?;
+        Ok(entry)
+    }
+
+    /// Appends a block with given entries to the archive and returns its position.
+    fn append_block(&mut self, mut entries: Vec<Entry>) -> Result<u64, Error> {
+        entries.resize(ENTRIES_PER_BLOCK, Entry::empty());
+        let mut block = Vec::with_capacity(BLOCK_SIZE);
+        write_block(&mut block, &entries, self.cipher())?;
+        self.append(&block)
+    }
+
+    /// Appends given data to the archive and returns its position.
+    fn append(&mut self, data: &[u8]) -> Result<u64, Error> {
+        let position = self.file_size()?;
+        self.file.write_all_at(data, position).map_err(IO)?;
+        Ok(position)
+    }
+
+    fn file_size(&self) -> Result<u64, Error> {
+        Ok(self.file.metadata().map_err(IO)?.len())
+    }
+
+    /// Returns the indexed directory at given path, which is updated along with the blocks, so the
+    /// archive doesn't have to be indexed again after each modification.
+    fn indexed_directory(&mut self, path: &[String]) -> Result<&mut Directory, Error> {
+        path.iter()
+            .try_fold(&mut self.root, |dir, component| dir.child_directory_mut(component))
+            .ok_or(InvalidEntry("directory is missing in the index"))
+    }
+}
+
+/// Rejects paths through the `.` and `..` links, which must not be replaced or removed.
+fn reject_links(components: &[String]) -> Result<(), Error> {
+    if components.iter().any(|c| c == "." || c == "..") {
+        return Err(InvalidEntry("path contains a . or .. link"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::pk2::constants::KEY_BYTES;
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    /// Saves an archive with a root directory containing `a.txt` and an empty directory `sub`.
+    fn archive(dir: &Path) -> Archive {
+        let mut fixture = Fixture::new();
+        let root = fixture.offset();
+        let sub = root + BLOCK_SIZE as u64;
+        let data = sub + BLOCK_SIZE as u64;
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(2, b"a.txt", data, 5, 0),
+            raw_entry(1, b"sub", sub, 0, 0),
+        ]);
+        fixture.push_block(&[raw_entry(1, b".", sub, 0, 0), raw_entry(1, b"..", root, 0, 0)]);
+        fixture.push_data(b"hello");
+        let location = dir.join("edit.pk2");
+        fixture.save(&location);
+        Archive::open_for_edit(&location, KEY_BYTES).unwrap()
+    }
+
+    fn is_sound(dir: &Path) -> bool {
+        Archive::verify(&dir.join("edit.pk2"), KEY_BYTES).unwrap().issues.is_empty()
+    }
+
+    #[test]
+    fn adds_and_replaces_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = archive(dir.path());
+        archive.add_file(Path::new("a.txt"), b"replaced").unwrap();
+        archive.add_file(Path::new("sub/new/b.txt"), b"new").unwrap();
+        // fills the root block, so the directory gets a second one
+        for i in 0..ENTRIES_PER_BLOCK {
+            archive.add_file(Path::new(&format!("{}.txt", i)), b"x").unwrap();
+        }
+        assert!(matches!(archive.add_file(Path::new("sub"), b"x"), Err(InvalidEntry(_))));
+
+        let archive = Archive::open(&dir.path().join("edit.pk2")).unwrap();
+        assert_eq!(archive.read(Path::new("a.txt")).unwrap(), b"replaced");
+        assert_eq!(archive.read(Path::new("SUB\\new\\b.txt")).unwrap(), b"new");
+        assert_eq!(archive.read(Path::new("19.txt")).unwrap(), b"x");
+        assert_eq!(archive.root.files().len(), 2 + ENTRIES_PER_BLOCK);
+        assert!(is_sound(dir.path()));
+    }
+
+    #[test]
+    fn validates_paths_before_appending() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = archive(dir.path());
+        let size = archive.file_size().unwrap();
+        assert!(matches!(archive.add_file(Path::new("sub"), b"x"), Err(InvalidEntry(_))));
+        assert!(matches!(archive.add_file(Path::new("new/\u{1F600}.txt"), b"x"), Err(InvalidEntry(_))));
+        assert_eq!(archive.file_size().unwrap(), size);
+        assert!(archive.entry(Path::new("new")).is_none());
+    }
+
+    #[test]
+    fn keeps_the_index_up_to_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = archive(dir.path());
+        archive.add_file(Path::new("A.TXT"), b"replaced").unwrap();
+        archive.add_file(Path::new("sub/new/b.txt"), b"b").unwrap();
+        archive.add_file(Path::new("Sub/c.txt"), b"c").unwrap();
+        archive.remove(Path::new("sub/new")).unwrap();
+        for i in 0..ENTRIES_PER_BLOCK {
+            archive.add_file(Path::new(&format!("{}.txt", i)), b"x").unwrap();
+        }
+
+        let files = |archive: &Archive| -> Vec<(PathBuf, u64, u32)> {
+            archive.root.files().into_iter().map(|(path, entry)| (path, entry.position, entry.size)).collect()
+        };
+        let reopened = Archive::open(&dir.path().join("edit.pk2")).unwrap();
+        assert_eq!(files(&archive), files(&reopened));
+        assert_eq!(archive.read(Path::new("a.txt")).unwrap(), b"replaced");
+        assert!(archive.entry(Path::new("sub/new")).is_none());
+        assert_eq!(archive.root.find_directory(Path::new("sub")).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn removes_files_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = archive(dir.path());
+        archive.add_file(Path::new("sub/b.txt"), b"b").unwrap();
+        archive.remove(Path::new("a.txt")).unwrap();
+        assert!(archive.entry(Path::new("a.txt")).is_none());
+        assert!(archive.entry(Path::new("sub/b.txt")).is_some());
+        archive.remove(Path::new("sub")).unwrap();
+        assert!(archive.entry(Path::new("sub/b.txt")).is_none());
+        assert!(matches!(archive.remove(Path::new("missing.txt")), Err(NotFound(_))));
+        assert!(is_sound(dir.path()));
+    }
+
+    #[test]
+    fn rejects_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = archive(dir.path());
+        assert!(matches!(archive.remove(Path::new("sub/..")), Err(InvalidEntry(_))));
+        assert!(matches!(archive.remove(Path::new("sub/.")), Err(InvalidEntry(_))));
+        assert!(matches!(archive.add_file(Path::new("sub/../a.txt"), b"x"), Err(InvalidEntry(_))));
+        assert!(matches!(archive.add_file(Path::new("./b.txt"), b"x"), Err(InvalidEntry(_))));
+        assert!(archive.find_slot(archive.root.entry.position, ".").unwrap().is_none());
+        assert!(is_sound(dir.path()));
+    }
+}
@@ -1,11 +1,13 @@
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use std::time::SystemTime;
 
-use encoding::DecoderTrap;
-use encoding::label::encoding_from_whatwg_label;
+use byteorder::{ByteOrder, LE};
 
 use crate::pk2::constants::ENTRY_SIZE;
-use crate::pk2::util::{as_u32_le, as_u64_le};
+use crate::pk2::errors::Error;
+use crate::pk2::name::{EntryName, NAME_SIZE};
+use crate::pk2::util::{as_u32_le, as_u64_le, filetime_to_system_time, system_time_to_filetime};
 
 /// Byte representation of an [Entry]'s type.
 #[repr(u8)]
@@ -28,7 +30,7 @@ impl Display for EntryType {
 
 impl From<u8> for EntryType {
     fn from(val: u8) -> Self {
-        return match val {
+        match val {
             0 => EntryType::Empty,
             1 => EntryType::Dir,
             2 => EntryType::File,
@@ -41,7 +43,7 @@ impl From<u8> for EntryType {
 #[derive(Copy, Clone)]
 pub struct Entry {
     pub typ: u8,
-    pub name: [u8; 89],
+    pub name: EntryName,
     create_time: u64,
     modify_time: u64,
     pub position: u64,
@@ -56,9 +58,12 @@ impl From<&[u8]> for Entry {
             panic!("invalid buffer size: {}", buf.len());
         }
 
+        let mut name = [0; NAME_SIZE];
+        name.copy_from_slice(&buf[1..90]);
+
         let mut entry = Entry {
             typ: buf[0],
-            name: [0; 89],
+            name: EntryName::from(name),
             create_time: as_u64_le(&buf[90..98]),
             modify_time: as_u64_le(&buf[98..106]),
             position: as_u64_le(&buf[106..114]),
@@ -67,14 +72,68 @@ impl From<&[u8]> for Entry {
             padding: [0; 2]
         };
 
-        entry.name.copy_from_slice(&buf[1..90]);
         entry.padding.copy_from_slice(&buf[126..128]);
 
-        return entry;
+        entry
+    }
+}
+
+impl From<&Entry> for [u8; ENTRY_SIZE] {
+    /// Serializes an [Entry] into its raw (unencrypted) representation.
+    fn from(entry: &Entry) -> Self {
+        let mut buf = [0; ENTRY_SIZE];
+        buf[0] = entry.typ;
+        buf[1..90].copy_from_slice(entry.name.raw());
+        LE::write_u64(&mut buf[90..98], entry.create_time);
+        LE::write_u64(&mut buf[98..106], entry.modify_time);
+        LE::write_u64(&mut buf[106..114], entry.position);
+        LE::write_u32(&mut buf[114..118], entry.size);
+        LE::write_u64(&mut buf[118..126], entry.next_chain);
+        buf[126..128].copy_from_slice(&entry.padding);
+        buf
     }
 }
 
 impl Entry {
+    /// Creates an empty entry, which is used to fill up unused slots of a block.
+    pub fn empty() -> Entry {
+        Entry::from(&[0u8; ENTRY_SIZE][..])
+    }
+
+    /// Creates a file or directory entry with given name, which is encoded as EUC-KR like the
+    /// client expects. The position is set once the entry is written to an archive.
+    pub fn new(typ: EntryType, name: &str, size: u32, created: SystemTime, modified: SystemTime) -> Result<Entry, Error> {
+        Ok(Entry::named(typ, EntryName::encode(name)?, size, created, modified))
+    }
+
+    /// Creates a file or directory entry with given already encoded name (see [Entry::new]).
+    pub fn named(typ: EntryType, name: EntryName, size: u32, created: SystemTime, modified: SystemTime) -> Entry {
+        let mut entry = Entry::empty();
+        entry.typ = typ as u8;
+        entry.name = name;
+        entry.create_time = system_time_to_filetime(created);
+        entry.modify_time = system_time_to_filetime(modified);
+        entry.size = size;
+        entry
+    }
+
+    /// Sets the time the entry was last modified.
+    pub fn set_modified(&mut self, modified: SystemTime) {
+        self.modify_time = system_time_to_filetime(modified);
+    }
+
+    /// Creates a directory entry with given name linking to the block at given position.
+    /// The timestamps are taken over from `template`. Used for the `.` and `..` entries of a directory.
+    pub fn link(template: &Entry, name: &[u8], position: u64) -> Entry {
+        let mut entry = Entry::empty();
+        entry.typ = EntryType::Dir as u8;
+        entry.name = EntryName::from_bytes(name).expect("invalid link name");
+        entry.create_time = template.create_time;
+        entry.modify_time = template.modify_time;
+        entry.position = position;
+        entry
+    }
+
     pub fn is_dir(&self) -> bool {
         self.typ == 1
     }
@@ -85,10 +144,103 @@ impl Entry {
         self.typ == 0
     }
 
+    /// Returns the time the entry was created.
+    pub fn created(&self) -> SystemTime {
+        filetime_to_system_time(self.create_time)
+    }
+
+    /// Returns the time the entry was last modified.
+    pub fn modified(&self) -> SystemTime {
+        filetime_to_system_time(self.modify_time)
+    }
+
+    /// Returns the name of the entry as a path (see [EntryName::to_os_string]).
     pub fn path_buf(&self) -> PathBuf {
-        let korean = encoding_from_whatwg_label("euc-kr").unwrap();
-        let name = korean.decode(&self.name, DecoderTrap::Replace).unwrap();
-        let p = PathBuf::from(name.trim_end_matches("\x00"));
-        return p;
+        PathBuf::from(self.name.to_os_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use proptest::prelude::*;
+
+    use crate::pk2::fixture::raw_entry;
+
+    use super::*;
+
+    /// "한글.txt" encoded as EUC-KR
+    const KOREAN_NAME: &[u8] = &[0xC7, 0xD1, 0xB1, 0xDB, b'.', b't', b'x', b't'];
+
+    #[test]
+    fn parses_raw_entry() {
+        let entry = Entry::from(&raw_entry(2, b"itemdata.txt", 0x1234_5678_9ABC, 42, 0x2800)[..]);
+        assert!(entry.is_file());
+        assert_eq!(entry.path_buf(), PathBuf::from("itemdata.txt"));
+        assert_eq!(entry.position, 0x1234_5678_9ABC);
+        assert_eq!(entry.size, 42);
+        assert_eq!(entry.next_chain, 0x2800);
+        assert_eq!(entry.modified().duration_since(entry.created()).unwrap(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn decodes_euc_kr_names() {
+        let entry = Entry::from(&raw_entry(1, KOREAN_NAME, 0, 0, 0)[..]);
+        assert!(entry.is_dir());
+        assert_eq!(entry.path_buf(), PathBuf::from("한글.txt"));
+    }
+
+    #[test]
+    fn parses_empty_entry() {
+        let entry = Entry::from(&[0u8; ENTRY_SIZE][..]);
+        assert!(entry.is_empty());
+        assert_eq!(entry.path_buf(), PathBuf::new());
+    }
+
+    #[test]
+    fn new_entry_encodes_euc_kr() {
+        let entry = Entry::new(EntryType::File, "한글.txt", 3, UNIX_EPOCH, UNIX_EPOCH).unwrap();
+        assert_eq!(&entry.name.raw()[..KOREAN_NAME.len()], KOREAN_NAME);
+        assert!(entry.name.raw()[KOREAN_NAME.len()..].iter().all(|b| *b == 0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn new_entry_rejects_invalid_names() {
+        let longest = "a".repeat(88);
+        assert!(Entry::new(EntryType::File, &longest, 0, UNIX_EPOCH, UNIX_EPOCH).is_ok());
+        let too_long = "a".repeat(89);
+        assert!(Entry::new(EntryType::File, &too_long, 0, UNIX_EPOCH, UNIX_EPOCH).is_err());
+        assert!(Entry::new(EntryType::File, "\u{1F600}.txt", 0, UNIX_EPOCH, UNIX_EPOCH).is_err());
+    }
+
+    #[test]
+    fn link_takes_over_timestamps() {
+        let template = Entry::from(&raw_entry(1, b"data", 0, 0, 0)[..]);
+        let link = Entry::link(&template, b"..", 256);
+        assert!(link.is_dir());
+        assert_eq!(link.path_buf(), PathBuf::from(".."));
+        assert_eq!(link.position, 256);
+        assert_eq!(link.created(), template.created());
+        assert_eq!(link.modified(), template.modified());
+    }
+
+    proptest! {
+        #[test]
+        fn serialization_round_trips(typ in 0u8..=2, rest in prop::collection::vec(any::<u8>(), ENTRY_SIZE - 1)) {
+            let mut bytes = vec![typ];
+            bytes.extend(rest);
+            let entry = Entry::from(&bytes[..]);
+            prop_assert_eq!(<[u8; ENTRY_SIZE]>::from(&entry).to_vec(), bytes);
+        }
+
+        #[test]
+        fn timestamps_round_trip(secs in 0u64..10_000_000_000, hundred_nanos in 0u32..10_000_000) {
+            let time = UNIX_EPOCH + Duration::new(secs, hundred_nanos * 100);
+            let mut entry = Entry::new(EntryType::File, "a", 0, time, time).unwrap();
+            prop_assert_eq!(entry.created(), time);
+            entry.set_modified(time + Duration::from_secs(1));
+            prop_assert_eq!(entry.modified(), time + Duration::from_secs(1));
+        }
+    }
+}
@@ -1,8 +1,35 @@
+use std::fmt::{Display, Formatter};
 use std::io;
+use std::path::PathBuf;
+
+use glob::PatternError;
 
 #[derive(std::fmt::Debug)]
 pub enum Error {
     InvalidHeader(&'static str),
     IO(io::Error),
-    InvalidBlock(&'static str)
-}
\ No newline at end of file
+    InvalidBlock(&'static str),
+    InvalidEntry(&'static str),
+    InvalidKey(&'static str),
+    NotFound(PathBuf),
+    InvalidPattern(PatternError),
+    InvalidPatch(&'static str),
+    Conflict(PathBuf)
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidHeader(msg) => write!(f, "invalid header: {}", msg),
+            Error::IO(err) => write!(f, "{}", err),
+            Error::InvalidBlock(msg) => write!(f, "invalid block: {}", msg),
+            Error::InvalidEntry(msg) => write!(f, "invalid entry: {}", msg),
+            Error::InvalidKey(msg) => write!(f, "invalid key: {}", msg),
+            Error::NotFound(path) => write!(f, "{:?} not found", path),
+            Error::InvalidPattern(err) => write!(f, "invalid pattern: {}", err),
+            Error::InvalidPatch(msg) => write!(f, "invalid patch: {}", msg),
+            Error::Conflict(path) => write!(f, "{:?} doesn't match the patched version", path),
+        }
+    }
+}
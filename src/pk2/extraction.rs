@@ -0,0 +1,244 @@
+use std::fs::{create_dir_all, File, FileTimes};
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use glob::{MatchOptions, Pattern};
+
+use crate::pk2::archive::Archive;
+use crate::pk2::entry::Entry;
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::{InvalidEntry, InvalidPattern, IO};
+use crate::pk2::util::copy_content;
+
+/// Paths are matched like the client looks them up, ignoring case.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// A callback receiving the progress of an extraction.
+type ProgressCallback<'a> = Box<dyn Fn(&Progress) + Sync + 'a>;
+
+/// Progress of an extraction, which is reported after each file.
+pub struct Progress<'p> {
+    /// Path of the file which has just been extracted or failed.
+    pub path: &'p Path,
+    /// Number of files handled so far, including failed ones.
+    pub completed: usize,
+    /// Number of files to extract.
+    pub total: usize,
+    /// Number of bytes extracted so far.
+    pub bytes: u64,
+}
+
+/// The outcome of an extraction.
+#[derive(Debug, Default)]
+pub struct ExtractionSummary {
+    /// Number of extracted files.
+    pub extracted: usize,
+    /// Number of extracted bytes.
+    pub bytes: u64,
+    /// Number of files which didn't match the filters.
+    pub skipped: usize,
+    /// Files which failed to extract and why.
+    pub failures: Vec<(PathBuf, Error)>,
+}
+
+/// Extracts files of an archive in parallel, streaming each file's content to disk.
+///
+/// ```ignore
+/// let summary = Extractor::new(&archive)
+///     .include("server_dep/silkroad/textdata/*.txt")?
+///     .exclude("**/*_old.txt")?
+///     .on_progress(|p| debug!("{}/{} {:?}", p.completed, p.total, p.path))
+///     .extract(Path::new("media"));
+/// ```
+pub struct Extractor<'a> {
+    archive: &'a Archive,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    threads: usize,
+    progress: Option<ProgressCallback<'a>>,
+}
+
+impl<'a> Extractor<'a> {
+    /// Creates an extractor for all files of given archive, which uses as many threads as there are CPUs.
+    pub fn new(archive: &'a Archive) -> Extractor<'a> {
+        Extractor {
+            archive,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            progress: None,
+        }
+    }
+
+    /// Only extracts files whose path matches one of the included glob patterns.
+    pub fn include(mut self, pattern: &str) -> Result<Extractor<'a>, Error> {
+        self.include.push(Pattern::new(pattern).map_err(InvalidPattern)?);
+        Ok(self)
+    }
+
+    /// Doesn't extract files whose path matches given glob pattern.
+    pub fn exclude(mut self, pattern: &str) -> Result<Extractor<'a>, Error> {
+        self.exclude.push(Pattern::new(pattern).map_err(InvalidPattern)?);
+        Ok(self)
+    }
+
+    /// Sets the number of threads extracting files.
+    pub fn threads(mut self, threads: usize) -> Extractor<'a> {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets a callback which is called after each file. It is called from the extracting threads.
+    pub fn on_progress<F>(mut self, progress: F) -> Extractor<'a>
+        where F: Fn(&Progress) + Sync + 'a {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Returns whether a file at given path is extracted.
+    pub fn matches(&self, path: &Path) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| p.matches_path_with(path, MATCH_OPTIONS));
+        included && !self.exclude.iter().any(|p| p.matches_path_with(path, MATCH_OPTIONS))
+    }
+
+    /// Extracts all matching files to given location, keeping the archive's directory structure
+    /// and the files' modification times. Failures don't stop the extraction of other files but
+    /// are collected in the summary.
+    pub fn extract(&self, location: &Path) -> ExtractionSummary {
+        let all_files = self.archive.root.files();
+        let files: Vec<(PathBuf, &Entry)> = all_files.iter()
+            .filter(|(path, _)| self.matches(path))
+            .map(|(path, entry)| (path.clone(), *entry))
+            .collect();
+
+        let next = AtomicUsize::new(0);
+        let completed = AtomicUsize::new(0);
+        let bytes = AtomicU64::new(0);
+        let failures = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(files.len()) {
+                scope.spawn(|| {
+                    while let Some((path, entry)) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        match self.extract_file(location, path, entry) {
+                            Ok(_) => { bytes.fetch_add(entry.size as u64, Ordering::Relaxed); }
+                            Err(err) => failures.lock().unwrap().push((path.clone(), err)),
+                        }
+                        let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                        if let Some(progress) = &self.progress {
+                            progress(&Progress { path, completed, total: files.len(), bytes: bytes.load(Ordering::Relaxed) });
+                        }
+                    }
+                });
+            }
+        });
+
+        let failures = failures.into_inner().unwrap();
+        ExtractionSummary {
+            extracted: files.len() - failures.len(),
+            bytes: bytes.into_inner(),
+            skipped: all_files.len() - files.len(),
+            failures,
+        }
+    }
+
+    fn extract_file(&self, location: &Path, path: &Path, entry: &Entry) -> Result<(), Error> {
+        // entry names must not lead outside of the target location
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(InvalidEntry("path leaves the extraction location"));
+        }
+
+        let target_path = location.join(path);
+        if let Some(parent) = target_path.parent() {
+            create_dir_all(parent).map_err(IO)?;
+        }
+        let target = File::create(&target_path).map_err(IO)?;
+        let mut writer = BufWriter::new(&target);
+        copy_content(&self.archive.file, entry, &mut writer)?;
+        writer.flush().map_err(IO)?;
+        drop(writer);
+        target.set_times(FileTimes::new()
+            .set_accessed(entry.modified())
+            .set_modified(entry.modified()))
+            .map_err(IO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{metadata, read};
+
+    use crate::pk2::constants::{BLOCK_SIZE, KEY_BYTES};
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    /// Builds an archive with `readme.txt` and the directory `Data` containing `a.txt`,
+    /// `b_old.txt`, `image.ddj` and `broken.txt`, whose content lies beyond the end of the archive.
+    fn archive() -> Archive {
+        let mut fixture = Fixture::new();
+        let root = fixture.offset();
+        let (data_dir, data) = (root + BLOCK_SIZE as u64, root + 2 * BLOCK_SIZE as u64);
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(2, b"readme.txt", data, 6, 0),
+            raw_entry(1, b"Data", data_dir, 0, 0),
+        ]);
+        fixture.push_block(&[
+            raw_entry(1, b".", data_dir, 0, 0),
+            raw_entry(1, b"..", root, 0, 0),
+            raw_entry(2, b"a.txt", data + 6, 1, 0),
+            raw_entry(2, b"b_old.txt", data + 7, 2, 0),
+            raw_entry(2, b"image.ddj", data + 9, 3, 0),
+            raw_entry(2, b"broken.txt", data + 100, 5, 0),
+        ]);
+        fixture.push_data(b"readmeabbccc");
+        Archive::with_key(fixture.file(), KEY_BYTES).unwrap()
+    }
+
+    #[test]
+    fn filters_files() {
+        let archive = archive();
+        let extractor = Extractor::new(&archive)
+            .include("data/*.TXT").unwrap()
+            .exclude("**/*_old.txt").unwrap();
+        assert!(extractor.matches(Path::new("Data/a.txt")));
+        assert!(!extractor.matches(Path::new("Data/b_old.txt")));
+        assert!(!extractor.matches(Path::new("Data/image.ddj")));
+        assert!(!extractor.matches(Path::new("readme.txt")));
+        assert!(Extractor::new(&archive).matches(Path::new("readme.txt")));
+        assert!(matches!(Extractor::new(&archive).include("[a"), Err(InvalidPattern(_))));
+    }
+
+    #[test]
+    fn extracts_files_and_reports_progress() {
+        let archive = archive();
+        let dir = tempfile::tempdir().unwrap();
+        let progress = Mutex::new(Vec::new());
+        let summary = Extractor::new(&archive)
+            .exclude("**/*_old.txt").unwrap()
+            .threads(2)
+            .on_progress(|p| progress.lock().unwrap().push((p.completed, p.total)))
+            .extract(dir.path());
+
+        assert_eq!((summary.extracted, summary.bytes, summary.skipped), (3, 10, 1));
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].0, Path::new("Data/broken.txt"));
+        let mut progress = progress.into_inner().unwrap();
+        progress.sort();
+        assert_eq!(progress, vec![(1, 4), (2, 4), (3, 4), (4, 4)]);
+
+        assert_eq!(read(dir.path().join("readme.txt")).unwrap(), b"readme");
+        assert_eq!(read(dir.path().join("Data/image.ddj")).unwrap(), b"ccc");
+        assert!(!dir.path().join("Data/b_old.txt").exists());
+        let modified = archive.entry(Path::new("Data/a.txt")).unwrap().modified();
+        assert_eq!(metadata(dir.path().join("Data/a.txt")).unwrap().modified().unwrap(), modified);
+    }
+}
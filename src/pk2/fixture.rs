@@ -0,0 +1,101 @@
+//! Builds small synthetic archives for tests. The raw bytes are assembled by hand instead of using
+//! the crate's own serialization, so that symmetric bugs in parsing and writing are caught.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::blowfish::Blowfish;
+use crate::pk2::constants::{BLOCK_SIZE, CHECKSUM, ENTRIES_PER_BLOCK, ENTRY_SIZE, HEADER_SIZE, KEY_BYTES, SALT, SIGNATURE, VERSION};
+
+/// Returns the raw bytes of an entry.
+pub fn raw_entry(typ: u8, name: &[u8], position: u64, size: u32, next_chain: u64) -> [u8; ENTRY_SIZE] {
+    let mut buf = [0u8; ENTRY_SIZE];
+    buf[0] = typ;
+    buf[1..1 + name.len()].copy_from_slice(name);
+    buf[90..98].copy_from_slice(&130_000_000_000_000_000u64.to_le_bytes());
+    buf[98..106].copy_from_slice(&130_000_000_010_000_000u64.to_le_bytes());
+    buf[106..114].copy_from_slice(&position.to_le_bytes());
+    buf[114..118].copy_from_slice(&size.to_le_bytes());
+    buf[118..126].copy_from_slice(&next_chain.to_le_bytes());
+    buf
+}
+
+/// Returns the raw header of an archive encrypted with given key, or an unencrypted one.
+pub fn raw_header(key: Option<&[u8]>) -> [u8; HEADER_SIZE] {
+    let mut buf = [0u8; HEADER_SIZE];
+    buf[0..30].copy_from_slice(SIGNATURE);
+    buf[30..34].copy_from_slice(&VERSION.to_le_bytes());
+    if let Some(key) = key {
+        buf[34] = 1;
+        let mut checksum = *CHECKSUM;
+        Blowfish::new(key, SALT).unwrap().encrypt(&mut checksum).unwrap();
+        buf[35..38].copy_from_slice(&checksum[..3]);
+    }
+    buf
+}
+
+/// An archive assembled block by block. Blocks and data are appended in the order they are pushed,
+/// starting with the root block right after the header.
+pub struct Fixture {
+    blowfish: Option<Blowfish>,
+    bytes: Vec<u8>,
+}
+
+impl Fixture {
+    /// Starts an archive encrypted with the default key.
+    pub fn new() -> Fixture {
+        Fixture::with_key(Some(KEY_BYTES))
+    }
+
+    /// Starts an archive encrypted with given key, or an unencrypted one.
+    pub fn with_key(key: Option<&[u8]>) -> Fixture {
+        Fixture {
+            blowfish: key.map(|key| Blowfish::new(key, SALT).unwrap()),
+            bytes: raw_header(key).to_vec(),
+        }
+    }
+
+    /// Returns the position the next pushed block or data will be placed at.
+    pub fn offset(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    /// Appends a block with given entries, filling up the remaining slots with empty entries.
+    /// Returns the position of the block.
+    pub fn push_block(&mut self, entries: &[[u8; ENTRY_SIZE]]) -> u64 {
+        assert!(entries.len() <= ENTRIES_PER_BLOCK);
+        let mut block = vec![0u8; BLOCK_SIZE];
+        block.chunks_exact_mut(ENTRY_SIZE)
+            .zip(entries.iter())
+            .for_each(|(buf, entry)| buf.copy_from_slice(entry));
+        if let Some(blowfish) = &self.blowfish {
+            blowfish.encrypt(&mut block).unwrap();
+        }
+        self.push_data(&block)
+    }
+
+    /// Appends raw data, e.g. the content of a file. Returns its position.
+    pub fn push_data(&mut self, data: &[u8]) -> u64 {
+        let position = self.offset();
+        self.bytes.extend_from_slice(data);
+        position
+    }
+
+    /// Writes the archive to a temporary file.
+    pub fn file(&self) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&self.bytes).unwrap();
+        file
+    }
+
+    /// Writes the archive to given location, for the APIs which take a path.
+    pub fn save(&self, location: &Path) {
+        std::fs::write(location, &self.bytes).unwrap();
+    }
+
+    /// Returns the blowfish instance the blocks are encrypted with.
+    pub fn cipher(&self) -> Option<&Blowfish> {
+        self.blowfish.as_ref()
+    }
+}
@@ -1,9 +1,13 @@
-use crate::pk2::constants::{BLOWFISH, CHECKSUM, HEADER_SIZE, SIGNATURE, VERSION};
+use byteorder::{ByteOrder, LE};
+
+use crate::blowfish::Blowfish;
+use crate::pk2::constants::{CHECKSUM, HEADER_SIZE, SIGNATURE, VERSION};
 use crate::pk2::errors::Error;
 use crate::pk2::errors::Error::InvalidHeader;
 use crate::pk2::util::as_u32_le;
 
 /// Represents the raw header of a PK2 archive, which is used to verify the integrity of an archive.
+#[derive(Clone)]
 pub struct Header {
     signature: [u8; 30],
     version: u32,
@@ -27,7 +31,20 @@ impl From<[u8; HEADER_SIZE]> for Header {
         header.checksum.copy_from_slice(&buf[35..51]);
         header.reserved.copy_from_slice(&buf[51..]);
 
-        return header;
+        header
+    }
+}
+
+impl From<&Header> for [u8; HEADER_SIZE] {
+    /// Serializes a [Header] into its raw representation.
+    fn from(header: &Header) -> Self {
+        let mut buf = [0; HEADER_SIZE];
+        buf[0..30].copy_from_slice(&header.signature);
+        LE::write_u32(&mut buf[30..34], header.version);
+        buf[34] = header.encrypted as u8;
+        buf[35..51].copy_from_slice(&header.checksum);
+        buf[51..].copy_from_slice(&header.reserved);
+        buf
     }
 }
 
@@ -39,29 +56,53 @@ impl From<&[u8]> for Header {
 
         let mut buf = [0; HEADER_SIZE];
         buf.copy_from_slice(header_buf);
-        return Header::from(buf);
+        Header::from(buf)
     }
 }
 
 impl Header {
-    fn verify_checksum(&self) -> Result<(), Error> {
+    /// Creates a valid header for an archive whose blocks are encrypted with given blowfish
+    /// instance, unless `encrypted` is false.
+    pub fn new(encrypted: bool, blowfish: &Blowfish) -> Header {
+        let mut header = Header {
+            signature: *SIGNATURE,
+            version: VERSION,
+            encrypted,
+            checksum: [0; 16],
+            reserved: [0; 205]
+        };
+
+        if encrypted {
+            let mut encrypted_checksum = *CHECKSUM;
+            blowfish.encrypt(&mut encrypted_checksum).expect("checksum is a multiple of the blowfish block size");
+            header.checksum[..3].copy_from_slice(&encrypted_checksum[..3]);
+        }
+
+        header
+    }
+
+    /// Returns whether the blocks of the archive are encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Verifies that the archive's blocks are encrypted with the key of given blowfish instance
+    pub fn verify_checksum(&self, blowfish: &Blowfish) -> Result<(), Error> {
         if !self.encrypted {
             return Ok(());
         }
 
-        let mut encrypted_checksum = CHECKSUM.clone();
-        BLOWFISH.encrypt(&mut encrypted_checksum);
+        let mut encrypted_checksum = *CHECKSUM;
+        blowfish.encrypt(&mut encrypted_checksum).expect("checksum is a multiple of the blowfish block size");
 
-        for i in 0..3 {
-            if encrypted_checksum[i] != self.checksum[i] {
-                return Err(InvalidHeader("Checksum is invalid"));
-            }
+        if encrypted_checksum[..3] != self.checksum[..3] {
+            return Err(InvalidHeader("Checksum is invalid"));
         }
 
-        return Ok(());
+        Ok(())
     }
 
-    fn verify_signature(&self) -> Result<(), Error> {
+    pub fn verify_signature(&self) -> Result<(), Error> {
         if &self.signature != SIGNATURE {
             Err(InvalidHeader("Invalid signature"))
         } else if self.version != VERSION {
@@ -71,8 +112,79 @@ impl Header {
         }
     }
 
-    pub fn verify(&self) -> Result<(), Error> {
+    pub fn verify(&self, blowfish: &Blowfish) -> Result<(), Error> {
         self.verify_signature()?;
-        self.verify_checksum()
+        self.verify_checksum(blowfish)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::pk2::constants::KEY_BYTES;
+    use crate::pk2::fixture::raw_header;
+
+    use super::*;
+
+    fn blowfish(key: &[u8]) -> Blowfish {
+        Blowfish::new(key, crate::pk2::constants::SALT).unwrap()
+    }
+
+    #[test]
+    fn parses_encrypted_header() {
+        let header = Header::from(raw_header(Some(KEY_BYTES)));
+        assert!(header.is_encrypted());
+        assert_eq!(header.version, VERSION);
+        assert!(header.verify(&blowfish(KEY_BYTES)).is_ok());
+    }
+
+    #[test]
+    fn rejects_wrong_key() {
+        let header = Header::from(raw_header(Some(KEY_BYTES)));
+        assert!(header.verify_checksum(&blowfish(b"abcdef")).is_err());
+    }
+
+    #[test]
+    fn unencrypted_header_accepts_any_key() {
+        let header = Header::from(raw_header(None));
+        assert!(!header.is_encrypted());
+        assert!(header.verify(&blowfish(b"abcdef")).is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rejects_wrong_signature_and_version() {
+        let mut buf = raw_header(Some(KEY_BYTES));
+        buf[0] = b'X';
+        assert!(Header::from(buf).verify_signature().is_err());
+
+        let mut buf = raw_header(Some(KEY_BYTES));
+        buf[30] = 0x03;
+        assert!(Header::from(buf).verify_signature().is_err());
+    }
+
+    #[test]
+    fn new_header_matches_raw_header() {
+        let header = Header::new(true, &blowfish(KEY_BYTES));
+        assert_eq!(<[u8; HEADER_SIZE]>::from(&header), raw_header(Some(KEY_BYTES)));
+        let header = Header::new(false, &blowfish(KEY_BYTES));
+        assert_eq!(<[u8; HEADER_SIZE]>::from(&header), raw_header(None));
+    }
+
+    #[test]
+    #[should_panic]
+    fn short_buffer_panics() {
+        let _ = Header::from(&[0u8; HEADER_SIZE - 1][..]);
+    }
+
+    proptest! {
+        #[test]
+        fn serialization_round_trips(bytes in prop::collection::vec(any::<u8>(), HEADER_SIZE)) {
+            let header = Header::from(&bytes[..]);
+            // the encrypted flag is normalized to 0 or 1
+            let mut expected = bytes.clone();
+            expected[34] = (bytes[34] == 1) as u8;
+            prop_assert_eq!(<[u8; HEADER_SIZE]>::from(&header).to_vec(), expected);
+        }
+    }
+}
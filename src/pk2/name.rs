@@ -0,0 +1,245 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Debug, Display, Formatter};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+use encoding::{DecoderTrap, EncoderTrap, EncodingRef};
+use encoding::label::encoding_from_whatwg_label;
+
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::InvalidEntry;
+
+/// Size of the name field of an entry, including the terminating NUL byte.
+pub const NAME_SIZE: usize = 89;
+
+/// Marks file names holding the raw bytes of a name which isn't valid EUC-KR (see
+/// [EntryName::to_os_string]). It can't be encoded as EUC-KR, so decoded names never start with it.
+pub const RAW_MARKER: char = '\u{FFFD}';
+
+/// Name of an entry as it is stored in the archive: EUC-KR encoded and terminated by a NUL byte.
+///
+/// The raw bytes are kept as they are, including any garbage after the terminating NUL, so that
+/// names are written back unchanged. Names which aren't valid EUC-KR, e.g. the ones of some Chinese
+/// client builds, are represented by their raw bytes instead of being mangled when decoding.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntryName([u8; NAME_SIZE]);
+
+impl From<[u8; NAME_SIZE]> for EntryName {
+    fn from(raw: [u8; NAME_SIZE]) -> Self {
+        EntryName(raw)
+    }
+}
+
+impl Default for EntryName {
+    /// Creates an empty name, e.g. the one of the root directory.
+    fn default() -> Self {
+        EntryName([0; NAME_SIZE])
+    }
+}
+
+impl EntryName {
+    /// Encodes given name as EUC-KR like the client expects.
+    pub fn encode(name: &str) -> Result<EntryName, Error> {
+        let encoded = korean().encode(name, EncoderTrap::Strict)
+            .map_err(|_| InvalidEntry("name can't be encoded as EUC-KR"))?;
+        EntryName::from_bytes(&encoded)
+    }
+
+    /// Creates a name from its already encoded bytes, which must not contain a NUL byte.
+    pub fn from_bytes(bytes: &[u8]) -> Result<EntryName, Error> {
+        // the name is terminated by at least one NUL byte
+        if bytes.len() >= NAME_SIZE {
+            return Err(InvalidEntry("name is too long"));
+        }
+        if bytes.contains(&0) {
+            return Err(InvalidEntry("name contains a NUL byte"));
+        }
+        let mut raw = [0; NAME_SIZE];
+        raw[..bytes.len()].copy_from_slice(bytes);
+        Ok(EntryName(raw))
+    }
+
+    /// Creates a name from a file name, e.g. when packing a directory. This is the inverse of
+    /// [EntryName::to_os_string]: the bytes following [RAW_MARKER] are taken over as they are,
+    /// other file names are encoded as EUC-KR. File names which aren't valid unicode or can't be
+    /// encoded are rejected, so they never end up as garbled raw bytes in the archive.
+    pub fn from_os_str(name: &OsStr) -> Result<EntryName, Error> {
+        if let Some(raw) = name.as_bytes().strip_prefix(RAW_MARKER.to_string().as_bytes()) {
+            return EntryName::from_bytes(raw);
+        }
+        let name = name.to_str().ok_or(InvalidEntry("file name isn't valid unicode"))?;
+        EntryName::encode(name)
+    }
+
+    /// Returns all bytes of the name field, including the ones after the terminating NUL.
+    pub fn raw(&self) -> &[u8; NAME_SIZE] {
+        &self.0
+    }
+
+    /// Returns the bytes of the name up to the first NUL byte.
+    pub fn bytes(&self) -> &[u8] {
+        let len = self.0.iter().position(|b| *b == 0).unwrap_or(NAME_SIZE);
+        &self.0[..len]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0[0] == 0
+    }
+
+    /// Returns whether this is the name of a `.` or `..` entry, which link a directory to itself
+    /// and its parent.
+    pub fn is_link(&self) -> bool {
+        matches!(self.bytes(), b"." | b"..")
+    }
+
+    /// Decodes the name. Returns `None` if it isn't valid EUC-KR or encoding the decoded name
+    /// doesn't yield the same bytes.
+    pub fn decode(&self) -> Option<String> {
+        let decoded = korean().decode(self.bytes(), DecoderTrap::Strict).ok()?;
+        let encoded = korean().encode(&decoded, EncoderTrap::Strict).ok()?;
+        if encoded == self.bytes() {
+            Some(decoded)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether decoding and encoding the name again yields exactly the raw bytes, i.e.
+    /// the name is valid EUC-KR and has no garbage after its terminating NUL.
+    pub fn round_trips(&self) -> bool {
+        self.decode().is_some() && self.0[self.bytes().len()..].iter().all(|b| *b == 0)
+    }
+
+    /// Returns the decoded name, or its raw bytes prefixed with [RAW_MARKER] if it can't be
+    /// decoded. Unlike a lossy decoding, different names never result in the same file name, and
+    /// [EntryName::from_os_str] restores the exact bytes.
+    pub fn to_os_string(&self) -> OsString {
+        match self.decode() {
+            Some(decoded) => OsString::from(decoded),
+            None => {
+                let mut escaped = RAW_MARKER.to_string().into_bytes();
+                escaped.extend_from_slice(self.bytes());
+                OsString::from_vec(escaped)
+            }
+        }
+    }
+
+    /// Returns the decoded name, in which undecodable bytes are replaced by U+FFFD.
+    pub fn to_string_lossy(&self) -> String {
+        korean().decode(self.bytes(), DecoderTrap::Replace).unwrap_or_default()
+    }
+}
+
+impl Display for EntryName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+impl Debug for EntryName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.decode() {
+            Some(decoded) => write!(f, "EntryName({:?})", decoded),
+            None => write!(f, "EntryName({:02x?})", self.bytes()),
+        }
+    }
+}
+
+fn korean() -> EncodingRef {
+    encoding_from_whatwg_label("euc-kr").unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// "한글" encoded as EUC-KR
+    const KOREAN: &[u8] = &[0xC7, 0xD1, 0xB1, 0xDB];
+
+    fn raw(bytes: &[u8]) -> EntryName {
+        let mut raw = [0; NAME_SIZE];
+        raw[..bytes.len()].copy_from_slice(bytes);
+        EntryName::from(raw)
+    }
+
+    #[test]
+    fn decodes_euc_kr() {
+        let name = raw(KOREAN);
+        assert_eq!(name.decode().as_deref(), Some("한글"));
+        assert!(name.round_trips());
+        assert_eq!(EntryName::encode("한글").unwrap(), name);
+    }
+
+    #[test]
+    fn stops_at_first_nul() {
+        let name = raw(b"a.txt\0garbage");
+        assert_eq!(name.bytes(), b"a.txt");
+        assert_eq!(name.decode().as_deref(), Some("a.txt"));
+        assert!(!name.round_trips());
+        assert_eq!(&name.raw()[6..13], b"garbage");
+    }
+
+    #[test]
+    fn keeps_undecodable_names_distinct() {
+        let first = raw(&[b'a', 0xFF, 0x81]);
+        let second = raw(&[b'a', 0xFF, 0x82]);
+        assert!(first.decode().is_none());
+        assert!(!first.round_trips());
+        assert_eq!(first.to_string_lossy(), second.to_string_lossy());
+        assert_ne!(first.to_os_string(), second.to_os_string());
+        assert!(first.to_os_string().as_bytes().starts_with(RAW_MARKER.to_string().as_bytes()));
+        assert_eq!(EntryName::from_os_str(&first.to_os_string()).unwrap(), first);
+    }
+
+    #[test]
+    fn escapes_raw_utf8_names() {
+        // valid UTF-8 ("ƀ" and "한"), but not EUC-KR
+        for bytes in [&[0xC6, 0x80][..], &[0xED, 0x95, 0x9C][..]] {
+            let name = raw(bytes);
+            assert!(name.decode().is_none());
+            assert!(name.to_os_string().to_str().unwrap().starts_with(RAW_MARKER));
+            assert_eq!(EntryName::from_os_str(&name.to_os_string()).unwrap(), name);
+        }
+        assert_ne!(raw(&[0xED, 0x95, 0x9C]).to_os_string(), raw(KOREAN[..2].as_ref()).to_os_string());
+        assert_eq!(EntryName::from_os_str(OsStr::new("한")).unwrap(), raw(&KOREAN[..2]));
+    }
+
+    #[test]
+    fn rejects_unescaped_raw_file_names() {
+        assert!(EntryName::from_os_str(OsStr::new("ƀ")).is_err());
+        assert!(EntryName::from_os_str(OsStr::from_bytes(&[b'a', 0xFF, 0x81])).is_err());
+        assert_eq!(EntryName::from_os_str(OsStr::new("\u{FFFD}ƀ")).unwrap(), raw(&[0xC6, 0x80]));
+    }
+
+    #[test]
+    fn detects_links() {
+        assert!(raw(b".").is_link());
+        assert!(raw(b"..").is_link());
+        assert!(!raw(b".hidden").is_link());
+        assert!(EntryName::default().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        assert!(EntryName::from_bytes(&[b'a'; NAME_SIZE - 1]).is_ok());
+        assert!(EntryName::from_bytes(&[b'a'; NAME_SIZE]).is_err());
+        assert!(EntryName::from_bytes(b"a\0b").is_err());
+        assert!(EntryName::encode("\u{1F600}").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn encoded_names_round_trip(name in "[a-zA-Z0-9_. 가-힣]{1,40}") {
+            let encoded = EntryName::encode(&name).unwrap();
+            prop_assert!(encoded.round_trips());
+            prop_assert_eq!(encoded.decode(), Some(name));
+        }
+
+        #[test]
+        fn os_strings_round_trip(bytes in prop::collection::vec(1u8.., 1..NAME_SIZE)) {
+            let name = EntryName::from_bytes(&bytes).unwrap();
+            prop_assert_eq!(EntryName::from_os_str(&name.to_os_string()).unwrap(), name);
+        }
+    }
+}
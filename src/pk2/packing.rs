@@ -0,0 +1,70 @@
+use std::convert::TryFrom;
+use std::fs::{File, Metadata, read_dir};
+use std::io::{copy, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::blowfish::Blowfish;
+use crate::pk2::archive::Archive;
+use crate::pk2::constants::{HEADER_SIZE, SALT};
+use crate::pk2::directory::Directory;
+use crate::pk2::entry::{Entry, EntryType};
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::{InvalidEntry, InvalidKey, IO};
+use crate::pk2::header::Header;
+use crate::pk2::name::EntryName;
+use crate::pk2::writer::Writer;
+
+impl Archive {
+    /// Packs the contents of the source directory into a new archive at given location, which is
+    /// encrypted with given blowfish key. Files and directories keep their names and timestamps.
+    ///
+    /// Returns the number of packed files.
+    pub fn pack(source: &Path, location: &Path, key: &[u8]) -> Result<usize, Error> {
+        let blowfish = Blowfish::new(key, SALT).map_err(|_| InvalidKey("Key must be between 4 and 56 bytes long"))?;
+        let root_metadata = source.metadata().map_err(IO)?;
+        let mut root_entry = Entry::new(EntryType::Dir, "", 0, created(&root_metadata), modified(&root_metadata))?;
+        root_entry.position = HEADER_SIZE as u64;
+        let mut root = Directory::from(root_entry);
+        pack_directory(source, &mut root)?;
+
+        let target = File::create(location).map_err(IO)?;
+        Writer::new(&root)?.write(&Header::new(true, &blowfish), &blowfish, &target, |path, entry, out| {
+            let mut file = File::open(source.join(path)).map_err(IO)?.take(entry.size as u64);
+            copy(&mut file, out).map_err(IO)?;
+            Ok(())
+        })?;
+        target.sync_all().map_err(IO)?;
+        Ok(root.files().len())
+    }
+}
+
+/// Adds the files and sub directories of given source directory to the archive's directory.
+fn pack_directory(source: &Path, directory: &mut Directory) -> Result<(), Error> {
+    for dir_entry in read_dir(source).map_err(IO)? {
+        let dir_entry = dir_entry.map_err(IO)?;
+        let metadata = dir_entry.metadata().map_err(IO)?;
+        let name = EntryName::from_os_str(&dir_entry.file_name())?;
+
+        if metadata.is_dir() {
+            let entry = Entry::named(EntryType::Dir, name, 0, created(&metadata), modified(&metadata));
+            let mut sub_dir = Directory::from(entry);
+            pack_directory(&dir_entry.path(), &mut sub_dir)?;
+            directory.add_directory(sub_dir);
+        } else if metadata.is_file() {
+            let size = u32::try_from(metadata.len()).map_err(|_| InvalidEntry("file is too large"))?;
+            directory.add_file(Entry::named(EntryType::File, name, size, created(&metadata), modified(&metadata)));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the creation time of a file, falling back to its modification time where the file
+/// system doesn't record it.
+fn created(metadata: &Metadata) -> SystemTime {
+    metadata.created().or_else(|_| metadata.modified()).unwrap_or_else(|_| SystemTime::now())
+}
+
+fn modified(metadata: &Metadata) -> SystemTime {
+    metadata.modified().unwrap_or_else(|_| SystemTime::now())
+}
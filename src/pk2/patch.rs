@@ -0,0 +1,397 @@
+use std::fs::{create_dir_all, read, read_to_string, remove_dir_all, rename, write, File};
+use std::io::BufWriter;
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::pk2::archive::Archive;
+use crate::pk2::diff::{Change, ContentHash};
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::{Conflict, InvalidPatch, IO, NotFound};
+use crate::pk2::util::copy_content;
+
+/// First line of a patch manifest, identifying the format and its version.
+const FORMAT: &str = "rustyroad-patch 1";
+/// Name of the manifest file in a patch bundle.
+const MANIFEST: &str = "manifest";
+/// Name of the directory containing the contents of added and modified files in a patch bundle.
+const CONTENT_DIR: &str = "files";
+
+/// A file change of a [Patch]. The base hash is the hash of the file's content in the archive
+/// the patch applies to, the hash the one of its content after applying the patch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchEntry {
+    Added { path: PathBuf, hash: ContentHash, size: u32 },
+    Modified { path: PathBuf, base_hash: ContentHash, hash: ContentHash, size: u32 },
+    Removed { path: PathBuf, base_hash: ContentHash },
+}
+
+impl PatchEntry {
+    /// Returns the path of the changed file in the archive.
+    pub fn path(&self) -> &Path {
+        match self {
+            PatchEntry::Added { path, .. } | PatchEntry::Modified { path, .. } | PatchEntry::Removed { path, .. } => path,
+        }
+    }
+}
+
+/// A patch bundle containing all changes between two versions of an archive.
+///
+/// The bundle is a directory with a `manifest` listing the changes and a `files` directory
+/// holding the contents of all added and modified files at their path in the archive, so they
+/// can be served to clients as they are.
+pub struct Patch {
+    location: PathBuf,
+    pub entries: Vec<PatchEntry>,
+}
+
+impl Patch {
+    /// Creates a patch bundle at given location containing the changes from the `older` to the
+    /// `newer` archive (see [Archive::diff]).
+    ///
+    /// The bundle is written next to given location first and only moved there once it's
+    /// complete, so a failed creation leaves nothing behind.
+    pub fn create(older: &Archive, newer: &Archive, location: &Path) -> Result<Patch, Error> {
+        let mut temp_location = location.as_os_str().to_owned();
+        temp_location.push(".partial");
+        let patch = Patch { location: PathBuf::from(temp_location), entries: Vec::new() };
+        let patch = match patch.write_bundle(older, newer) {
+            Ok(patch) => patch,
+            Err(err) => {
+                let _ = remove_dir_all(&patch.location);
+                return Err(err);
+            }
+        };
+        rename(&patch.location, location).map_err(IO)?;
+        Ok(Patch { location: location.to_path_buf(), ..patch })
+    }
+
+    /// Writes the contents of all changes and the manifest into the bundle.
+    fn write_bundle(&self, older: &Archive, newer: &Archive) -> Result<Patch, Error> {
+        create_dir_all(self.location.join(CONTENT_DIR)).map_err(IO)?;
+
+        let entries = older.diff(newer)?.into_iter()
+            .map(|change| match change {
+                Change::Added(path) => {
+                    let entry = newer.entry(&path).ok_or_else(|| NotFound(path.clone()))?;
+                    let hash = newer.content_hash(entry)?;
+                    let added = PatchEntry::Added { path, hash, size: entry.size };
+                    self.write_content(newer, &added)?;
+                    Ok(added)
+                }
+                Change::Modified(path) => {
+                    let base = older.entry(&path).ok_or_else(|| NotFound(path.clone()))?;
+                    let entry = newer.entry(&path).ok_or_else(|| NotFound(path.clone()))?;
+                    let modified = PatchEntry::Modified {
+                        base_hash: older.content_hash(base)?,
+                        hash: newer.content_hash(entry)?,
+                        size: entry.size,
+                        path,
+                    };
+                    self.write_content(newer, &modified)?;
+                    Ok(modified)
+                }
+                Change::Removed(path) => {
+                    check_path(&path)?;
+                    let base = older.entry(&path).ok_or_else(|| NotFound(path.clone()))?;
+                    Ok(PatchEntry::Removed { base_hash: older.content_hash(base)?, path })
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let patch = Patch { location: self.location.clone(), entries };
+        write(patch.location.join(MANIFEST), patch.manifest()).map_err(IO)?;
+        Ok(patch)
+    }
+
+    /// Opens the patch bundle at given location.
+    pub fn open(location: &Path) -> Result<Patch, Error> {
+        let manifest = read_to_string(location.join(MANIFEST)).map_err(IO)?;
+        let mut lines = manifest.lines();
+        if lines.next() != Some(FORMAT) {
+            return Err(InvalidPatch("unknown manifest format"));
+        }
+        let entries = lines
+            .filter(|line| !line.is_empty())
+            .map(parse_entry)
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Patch { location: location.to_path_buf(), entries })
+    }
+
+    /// Returns the location of the bundle.
+    pub fn location(&self) -> &Path {
+        &self.location
+    }
+
+    /// Returns the path of the file holding the new content of an added or modified file.
+    pub fn content_path(&self, entry: &PatchEntry) -> Option<PathBuf> {
+        match entry {
+            PatchEntry::Removed { .. } => None,
+            _ => Some(self.location.join(CONTENT_DIR).join(entry.path())),
+        }
+    }
+
+    /// Applies the patch to an archive opened for editing (see [Archive::open_for_edit]).
+    /// Returns the number of applied changes.
+    ///
+    /// Before anything is modified, every file of the patch is checked to be in either its base or
+    /// its patched version, otherwise [Error::Conflict] is returned. Files which are already in
+    /// their patched version are skipped, so an interrupted patch can be applied again.
+    pub fn apply(&self, archive: &mut Archive) -> Result<usize, Error> {
+        let mut pending = Vec::new();
+        for entry in self.entries.iter() {
+            let current = match archive.entry(entry.path()) {
+                Some(file) if file.is_file() => Some(archive.content_hash(file)?),
+                Some(_) => return Err(Conflict(entry.path().to_path_buf())),
+                None => None,
+            };
+            let applied = match entry {
+                PatchEntry::Added { hash, .. } => match current {
+                    None => false,
+                    Some(current) if current == *hash => true,
+                    Some(_) => return Err(Conflict(entry.path().to_path_buf())),
+                },
+                PatchEntry::Modified { base_hash, hash, .. } => match current {
+                    Some(current) if current == *base_hash => false,
+                    Some(current) if current == *hash => true,
+                    _ => return Err(Conflict(entry.path().to_path_buf())),
+                },
+                PatchEntry::Removed { base_hash, .. } => match current {
+                    Some(current) if current == *base_hash => false,
+                    None => true,
+                    Some(_) => return Err(Conflict(entry.path().to_path_buf())),
+                },
+            };
+            if !applied {
+                pending.push(entry);
+            }
+        }
+
+        for entry in pending.iter() {
+            match entry {
+                PatchEntry::Removed { path, .. } => archive.remove(path)?,
+                PatchEntry::Added { path, .. } | PatchEntry::Modified { path, .. } => {
+                    archive.add_file(path, &self.read_content(entry)?)?;
+                }
+            }
+        }
+        Ok(pending.len())
+    }
+
+    /// Reads the new content of an added or modified file and checks it against its hash.
+    pub fn read_content(&self, entry: &PatchEntry) -> Result<Vec<u8>, Error> {
+        let (hash, size) = match entry {
+            PatchEntry::Added { hash, size, .. } | PatchEntry::Modified { hash, size, .. } => (hash, *size),
+            PatchEntry::Removed { path, .. } => return Err(NotFound(path.clone())),
+        };
+        let content_path = self.content_path(entry).ok_or_else(|| NotFound(entry.path().to_path_buf()))?;
+        let content = read(content_path).map_err(IO)?;
+        if content.len() != size as usize || Sha256::digest(&content)[..] != hash[..] {
+            return Err(InvalidPatch("content doesn't match the manifest"));
+        }
+        Ok(content)
+    }
+
+    /// Copies the new content of an added or modified file from given archive into the bundle.
+    fn write_content(&self, archive: &Archive, entry: &PatchEntry) -> Result<(), Error> {
+        let path = entry.path();
+        check_path(path)?;
+        let file = archive.entry(path).ok_or_else(|| NotFound(path.to_path_buf()))?;
+        let content_path = self.content_path(entry).ok_or_else(|| NotFound(path.to_path_buf()))?;
+        if let Some(parent) = content_path.parent() {
+            create_dir_all(parent).map_err(IO)?;
+        }
+        let target = File::create(content_path).map_err(IO)?;
+        let mut writer = BufWriter::new(&target);
+        copy_content(&archive.file, file, &mut writer)?;
+        writer.into_inner().map_err(|err| IO(err.into_error()))?;
+        Ok(())
+    }
+
+    /// Serializes the entries into the manifest, one line per entry:
+    /// `A <hash> <size> <path>`, `M <base hash> <hash> <size> <path>` or `R <base hash> <path>`.
+    fn manifest(&self) -> String {
+        let mut manifest = format!("{}\n", FORMAT);
+        for entry in self.entries.iter() {
+            let line = match entry {
+                PatchEntry::Added { path, hash, size } =>
+                    format!("A {} {} {}", to_hex(hash), size, manifest_path(path)),
+                PatchEntry::Modified { path, base_hash, hash, size } =>
+                    format!("M {} {} {} {}", to_hex(base_hash), to_hex(hash), size, manifest_path(path)),
+                PatchEntry::Removed { path, base_hash } =>
+                    format!("R {} {}", to_hex(base_hash), manifest_path(path)),
+            };
+            manifest.push_str(&line);
+            manifest.push('\n');
+        }
+        manifest
+    }
+}
+
+/// Parses a line of the manifest (see [Patch::manifest]).
+fn parse_entry(line: &str) -> Result<PatchEntry, Error> {
+    let hash = |field: Option<&str>| field.and_then(from_hex).ok_or(InvalidPatch("invalid hash"));
+    let size = |field: Option<&str>| field.and_then(|s| s.parse().ok()).ok_or(InvalidPatch("invalid size"));
+    let path = |field: Option<&str>| {
+        let path = PathBuf::from(field.filter(|p| !p.is_empty()).ok_or(InvalidPatch("missing path"))?);
+        check_path(&path)?;
+        Ok(path)
+    };
+
+    let mut fields = line.splitn(2, ' ');
+    match fields.next() {
+        Some("A") => {
+            let mut fields = fields.next().unwrap_or_default().splitn(3, ' ');
+            Ok(PatchEntry::Added { hash: hash(fields.next())?, size: size(fields.next())?, path: path(fields.next())? })
+        }
+        Some("M") => {
+            let mut fields = fields.next().unwrap_or_default().splitn(4, ' ');
+            Ok(PatchEntry::Modified {
+                base_hash: hash(fields.next())?,
+                hash: hash(fields.next())?,
+                size: size(fields.next())?,
+                path: path(fields.next())?,
+            })
+        }
+        Some("R") => {
+            let mut fields = fields.next().unwrap_or_default().splitn(2, ' ');
+            Ok(PatchEntry::Removed { base_hash: hash(fields.next())?, path: path(fields.next())? })
+        }
+        _ => Err(InvalidPatch("unknown change")),
+    }
+}
+
+/// Makes sure a path stays within the bundle and can be written into a single manifest line.
+fn check_path(path: &Path) -> Result<(), Error> {
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(InvalidPatch("path leaves the bundle"));
+    }
+    match path.to_str() {
+        None => return Err(InvalidPatch("path isn't valid unicode")),
+        Some(path) if path.contains(['\n', '\r']) => return Err(InvalidPatch("path contains a line break")),
+        Some(_) => {}
+    }
+    Ok(())
+}
+
+/// Formats a path of the archive with `/` separators.
+fn manifest_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn to_hex(hash: &ContentHash) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<ContentHash> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0u8; 32];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_dir;
+
+    use crate::pk2::constants::KEY_BYTES;
+
+    use super::*;
+
+    /// Packs an archive with given files into the temporary directory and returns its path.
+    fn pack(dir: &Path, name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let source = dir.join(format!("{}-source", name));
+        for (path, content) in files {
+            let path = source.join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, content).unwrap();
+        }
+        let location = dir.join(format!("{}.pk2", name));
+        Archive::pack(&source, &location, KEY_BYTES).unwrap();
+        location
+    }
+
+    fn older(dir: &Path) -> PathBuf {
+        pack(dir, "older", &[("a.txt", b"a"), ("b.txt", b"b"), ("sub/c.txt", b"c")])
+    }
+
+    fn newer(dir: &Path) -> Archive {
+        Archive::open(&pack(dir, "newer", &[("a.txt", b"a"), ("b.txt", b"b2"), ("sub/d.txt", b"d")])).unwrap()
+    }
+
+    #[test]
+    fn creates_and_opens_patches() {
+        let dir = tempfile::tempdir().unwrap();
+        let (older, newer) = (Archive::open(&older(dir.path())).unwrap(), newer(dir.path()));
+        let patch = Patch::create(&older, &newer, &dir.path().join("patch")).unwrap();
+        let hash = |archive: &Archive, path: &str| archive.content_hash(archive.entry(Path::new(path)).unwrap()).unwrap();
+        assert_eq!(patch.entries, vec![
+            PatchEntry::Modified { path: PathBuf::from("b.txt"), base_hash: hash(&older, "b.txt"), hash: hash(&newer, "b.txt"), size: 2 },
+            PatchEntry::Removed { path: PathBuf::from("sub/c.txt"), base_hash: hash(&older, "sub/c.txt") },
+            PatchEntry::Added { path: PathBuf::from("sub/d.txt"), hash: hash(&newer, "sub/d.txt"), size: 1 },
+        ]);
+        assert_eq!(read(dir.path().join("patch/files/sub/d.txt")).unwrap(), b"d");
+
+        let opened = Patch::open(patch.location()).unwrap();
+        assert_eq!(opened.entries, patch.entries);
+        assert_eq!(opened.read_content(&opened.entries[0]).unwrap(), b"b2");
+        write(dir.path().join("patch/files/b.txt"), b"xx").unwrap();
+        assert!(matches!(opened.read_content(&opened.entries[0]), Err(InvalidPatch(_))));
+    }
+
+    #[test]
+    fn applies_patches_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let older_path = older(dir.path());
+        let newer = newer(dir.path());
+        let patch = Patch::create(&Archive::open(&older_path).unwrap(), &newer, &dir.path().join("patch")).unwrap();
+
+        let mut archive = Archive::open_for_edit(&older_path, KEY_BYTES).unwrap();
+        assert_eq!(patch.apply(&mut archive).unwrap(), 3);
+        assert!(archive.diff(&newer).unwrap().is_empty());
+        assert_eq!(patch.apply(&mut archive).unwrap(), 0);
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let older_path = older(dir.path());
+        let patch = Patch::create(&Archive::open(&older_path).unwrap(), &newer(dir.path()), &dir.path().join("patch")).unwrap();
+
+        let mut archive = Archive::open_for_edit(&older_path, KEY_BYTES).unwrap();
+        archive.add_file(Path::new("b.txt"), b"local").unwrap();
+        archive.remove(Path::new("sub/c.txt")).unwrap();
+        assert!(matches!(patch.apply(&mut archive), Err(Conflict(path)) if path == Path::new("b.txt")));
+        // nothing is modified if there's a conflict
+        assert!(archive.entry(Path::new("sub/d.txt")).is_none());
+    }
+
+    #[test]
+    fn leaves_nothing_behind_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let older = Archive::open(&pack(dir.path(), "older", &[("a.txt", b"a"), ("line\nbreak.txt", b"b")])).unwrap();
+        let newer = newer(dir.path());
+        let location = dir.path().join("patch");
+        assert!(matches!(Patch::create(&older, &newer, &location), Err(InvalidPatch(_))));
+        let mut files: Vec<_> = read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        files.sort();
+        assert_eq!(files, ["newer-source", "newer.pk2", "older-source", "older.pk2"]);
+    }
+
+    #[test]
+    fn rejects_invalid_manifests() {
+        assert!(matches!(parse_entry("X foo"), Err(InvalidPatch(_))));
+        assert!(matches!(parse_entry("R 00 a.txt"), Err(InvalidPatch(_))));
+        assert!(matches!(parse_entry(&format!("R {} ../a.txt", "00".repeat(32))), Err(InvalidPatch(_))));
+        assert!(matches!(parse_entry(&format!("A {} many a.txt", "00".repeat(32))), Err(InvalidPatch(_))));
+        assert_eq!(parse_entry(&format!("R {} sub/a b.txt", "ab".repeat(32))).unwrap(),
+                   PatchEntry::Removed { path: PathBuf::from("sub/a b.txt"), base_hash: [0xAB; 32] });
+    }
+}
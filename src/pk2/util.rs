@@ -1,14 +1,22 @@
+use std::collections::HashSet;
 use std::fs::File;
+use std::io::Write;
 use std::os::unix::fs::FileExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::pk2::constants::{BLOCK_SIZE, BLOWFISH, ENTRY_SIZE};
+use crate::blowfish::Blowfish;
+use crate::pk2::constants::{BLOCK_SIZE, ENTRIES_PER_BLOCK, ENTRY_SIZE};
 use crate::pk2::entry::Entry;
 use crate::pk2::errors::Error;
 use crate::pk2::errors::Error::{InvalidBlock, IO};
 
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+/// Number of 100 ns intervals between the FILETIME epoch (1601-01-01) and the unix epoch
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
 /// Converts a byte slice in little endian to an u32 number.
 pub fn as_u32_le(array: &[u8]) -> u32 {
-    ((array[0] as u32) << 0) +
+    (array[0] as u32) +
     ((array[1] as u32) << 8) +
     ((array[2] as u32) << 16) +
     ((array[3] as u32) << 24)
@@ -16,7 +24,7 @@ pub fn as_u32_le(array: &[u8]) -> u32 {
 
 /// Converts a byte slice in little endian to an u64 number.
 pub fn as_u64_le(array: &[u8]) -> u64 {
-    ((array[0] as u64) << 0) +
+    (array[0] as u64) +
     ((array[1] as u64) << 8) +
     ((array[2] as u64) << 16) +
     ((array[3] as u64) << 24) +
@@ -26,8 +34,30 @@ pub fn as_u64_le(array: &[u8]) -> u64 {
     ((array[7] as u64) << 56)
 }
 
-/// Reads a block (see [BLOCK_SIZE]) in a PK2 archive and returns the entries.
-pub fn read_block(file: &File, offset: u64) -> Result<Vec<Entry>, Error> {
+/// Reads a block (see [BLOCK_SIZE]) and all blocks chained to it in a PK2 archive and returns the
+/// non-empty entries. The blocks are decrypted with given blowfish instance, unless the archive is
+/// unencrypted.
+pub fn read_block(file: &File, offset: u64, blowfish: Option<&Blowfish>) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    let mut current = offset;
+    loop {
+        if !visited.insert(current) {
+            return Err(InvalidBlock("cyclic block chain"));
+        }
+        let block = read_single_block(file, current, blowfish)?;
+        let next = block[ENTRIES_PER_BLOCK - 1].next_chain;
+        entries.extend(block.into_iter().filter(|e| !e.is_empty()));
+        if next == 0 {
+            return Ok(entries);
+        }
+        current = next;
+    }
+}
+
+/// Reads a single block (see [BLOCK_SIZE]) without following its chain and returns all of its
+/// entries, including empty ones.
+pub fn read_single_block(file: &File, offset: u64, blowfish: Option<&Blowfish>) -> Result<Vec<Entry>, Error> {
     let mut entry_buf: [u8; BLOCK_SIZE] = [0; BLOCK_SIZE];
     match file.read_at(&mut entry_buf, offset) {
         Err(err) => return Err(IO(err)),
@@ -36,20 +66,200 @@ pub fn read_block(file: &File, offset: u64) -> Result<Vec<Entry>, Error> {
         }
     }
 
-    BLOWFISH.decrypt(&mut entry_buf);
+    if let Some(blowfish) = blowfish {
+        blowfish.decrypt(&mut entry_buf).map_err(|_| InvalidBlock("misaligned block"))?;
+    }
 
-    let mut entries: Vec<Entry> = entry_buf.chunks_exact(ENTRY_SIZE)
-        .map(|buf| Entry::from(buf))
+    let entries: Vec<Entry> = entry_buf.chunks_exact(ENTRY_SIZE)
+        .map(Entry::from)
         .collect();
 
-    if entries[19].next_chain > 0 {
-        entries.extend(read_block(file, entries[19].next_chain)?.iter());
+    Ok(entries)
+}
+
+
+/// Returns the offsets of all blocks chained together starting with the block at given offset.
+pub fn block_chain(file: &File, offset: u64, blowfish: Option<&Blowfish>) -> Result<Vec<u64>, Error> {
+    let mut chain = vec![offset];
+    let mut entry_buf: [u8; ENTRY_SIZE] = [0; ENTRY_SIZE];
+    let mut current = offset;
+    loop {
+        let last_entry_offset = current + ((ENTRIES_PER_BLOCK - 1) * ENTRY_SIZE) as u64;
+        file.read_exact_at(&mut entry_buf, last_entry_offset).map_err(IO)?;
+        if let Some(blowfish) = blowfish {
+            blowfish.decrypt(&mut entry_buf).map_err(|_| InvalidBlock("misaligned block"))?;
+        }
+        let next = Entry::from(&entry_buf[..]).next_chain;
+        if next == 0 {
+            return Ok(chain);
+        }
+        if chain.contains(&next) {
+            return Err(InvalidBlock("cyclic block chain"));
+        }
+        chain.push(next);
+        current = next;
     }
+}
 
-    entries = entries.iter()
-        .filter(|e| !e.is_empty())
-        .map(|e| *e)
-        .collect();
+/// Writes the entries of a single block (see [BLOCK_SIZE]), which is encrypted with given blowfish
+/// instance unless the archive is unencrypted.
+pub fn write_block(target: &mut dyn Write, entries: &[Entry], blowfish: Option<&Blowfish>) -> Result<(), Error> {
+    if entries.len() != ENTRIES_PER_BLOCK {
+        return Err(InvalidBlock("wrong block size"));
+    }
 
-    Ok(entries)
+    let mut block_buf: [u8; BLOCK_SIZE] = [0; BLOCK_SIZE];
+    block_buf.chunks_exact_mut(ENTRY_SIZE)
+        .zip(entries.iter())
+        .for_each(|(buf, entry)| buf.copy_from_slice(&<[u8; ENTRY_SIZE]>::from(entry)));
+
+    if let Some(blowfish) = blowfish {
+        blowfish.encrypt(&mut block_buf).map_err(|_| InvalidBlock("misaligned block"))?;
+    }
+    target.write_all(&block_buf).map_err(IO)
+}
+
+/// Writes a single entry at given offset, e.g. into an existing block. The entry is encrypted with
+/// given blowfish instance unless the archive is unencrypted.
+pub fn write_entry(file: &File, offset: u64, entry: &Entry, blowfish: Option<&Blowfish>) -> Result<(), Error> {
+    let mut entry_buf = <[u8; ENTRY_SIZE]>::from(entry);
+    if let Some(blowfish) = blowfish {
+        blowfish.encrypt(&mut entry_buf).map_err(|_| InvalidBlock("misaligned block"))?;
+    }
+    file.write_all_at(&entry_buf, offset).map_err(IO)
+}
+
+/// Copies the content of an entry from the archive file.
+pub fn copy_content(file: &File, entry: &Entry, target: &mut dyn Write) -> Result<(), Error> {
+    let mut buf = vec![0u8; COPY_BUFFER_SIZE];
+    let mut position = entry.position;
+    let mut remaining = entry.size as usize;
+    while remaining > 0 {
+        let len = remaining.min(COPY_BUFFER_SIZE);
+        file.read_exact_at(&mut buf[..len], position).map_err(IO)?;
+        target.write_all(&buf[..len]).map_err(IO)?;
+        position += len as u64;
+        remaining -= len;
+    }
+    Ok(())
+}
+
+/// Converts a [SystemTime] to a Windows FILETIME (see [filetime_to_system_time]).
+pub fn system_time_to_filetime(time: SystemTime) -> u64 {
+    let to_intervals = |duration: Duration| duration.as_secs() * 10_000_000 + (duration.subsec_nanos() / 100) as u64;
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => FILETIME_UNIX_EPOCH + to_intervals(duration),
+        Err(err) => FILETIME_UNIX_EPOCH.saturating_sub(to_intervals(err.duration())),
+    }
+}
+
+/// Converts a Windows FILETIME, which PK2 entries use for their timestamps, to a [SystemTime].
+pub fn filetime_to_system_time(filetime: u64) -> SystemTime {
+    let to_duration = |intervals: u64| Duration::new(intervals / 10_000_000, (intervals % 10_000_000) as u32 * 100);
+    if filetime >= FILETIME_UNIX_EPOCH {
+        UNIX_EPOCH + to_duration(filetime - FILETIME_UNIX_EPOCH)
+    } else {
+        UNIX_EPOCH - to_duration(FILETIME_UNIX_EPOCH - filetime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    fn names(entries: &[Entry]) -> Vec<String> {
+        entries.iter().map(|e| e.path_buf().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn reads_single_block_without_empty_entries() {
+        let mut fixture = Fixture::new();
+        let root = fixture.push_block(&[
+            raw_entry(1, b".", 256, 0, 0),
+            [0u8; ENTRY_SIZE],
+            raw_entry(2, b"a.txt", 0, 1, 0),
+        ]);
+        let entries = read_block(&fixture.file(), root, fixture.cipher()).unwrap();
+        assert_eq!(names(&entries), vec![".", "a.txt"]);
+        assert_eq!(read_single_block(&fixture.file(), root, fixture.cipher()).unwrap().len(), ENTRIES_PER_BLOCK);
+    }
+
+    #[test]
+    fn follows_block_chain() {
+        let mut fixture = Fixture::with_key(None);
+        let root = fixture.offset();
+        let mut first: Vec<_> = (0..ENTRIES_PER_BLOCK).map(|i| raw_entry(2, format!("{}", i).as_bytes(), 0, 0, 0)).collect();
+        first[ENTRIES_PER_BLOCK - 1] = raw_entry(2, b"19", 0, 0, root + 2 * BLOCK_SIZE as u64);
+        fixture.push_block(&first);
+        // an unreferenced block between the chained ones
+        fixture.push_block(&[raw_entry(2, b"orphan", 0, 0, 0)]);
+        fixture.push_block(&[[0u8; ENTRY_SIZE], raw_entry(2, b"20", 0, 0, 0)]);
+
+        let entries = read_block(&fixture.file(), root, None).unwrap();
+        assert_eq!(entries.len(), ENTRIES_PER_BLOCK + 1);
+        assert_eq!(names(&entries).last().unwrap(), "20");
+        assert_eq!(block_chain(&fixture.file(), root, None).unwrap(), vec![root, root + 2 * BLOCK_SIZE as u64]);
+    }
+
+    #[test]
+    fn rejects_cyclic_chain() {
+        let mut fixture = Fixture::new();
+        let root = fixture.offset();
+        let mut block = vec![[0u8; ENTRY_SIZE]; ENTRIES_PER_BLOCK];
+        block[ENTRIES_PER_BLOCK - 1] = raw_entry(2, b"loop", 0, 0, root);
+        fixture.push_block(&block);
+        assert!(read_block(&fixture.file(), root, fixture.cipher()).is_err());
+        assert!(block_chain(&fixture.file(), root, fixture.cipher()).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_block() {
+        let mut fixture = Fixture::with_key(None);
+        let root = fixture.push_data(&[0u8; ENTRY_SIZE + 1]);
+        assert!(read_block(&fixture.file(), root, None).is_err());
+    }
+
+    #[test]
+    fn write_block_matches_fixture() {
+        let raw = [raw_entry(1, b".", 256, 0, 0), raw_entry(2, b"a.txt", 5000, 7, 0)];
+        let mut fixture = Fixture::new();
+        fixture.push_block(&raw);
+
+        let mut entries: Vec<Entry> = raw.iter().map(|e| Entry::from(&e[..])).collect();
+        entries.resize(ENTRIES_PER_BLOCK, Entry::empty());
+        let mut written = Vec::new();
+        write_block(&mut written, &entries, fixture.cipher()).unwrap();
+
+        let mut expected = vec![0u8; BLOCK_SIZE];
+        fixture.file().read_exact_at(&mut expected, 256).unwrap();
+        assert_eq!(written, expected);
+        assert!(write_block(&mut Vec::new(), &entries[1..], fixture.cipher()).is_err());
+    }
+
+    #[test]
+    fn write_entry_replaces_single_entry() {
+        let mut fixture = Fixture::new();
+        let root = fixture.push_block(&[raw_entry(1, b".", 256, 0, 0), raw_entry(2, b"a.txt", 0, 1, 0)]);
+        let file = fixture.file();
+        let replacement = Entry::from(&raw_entry(2, b"b.txt", 0, 2, 0)[..]);
+        write_entry(&file, root + ENTRY_SIZE as u64, &replacement, fixture.cipher()).unwrap();
+        assert_eq!(names(&read_block(&file, root, fixture.cipher()).unwrap()), vec![".", "b.txt"]);
+    }
+
+    proptest! {
+        #[test]
+        fn little_endian_conversions(value: u64) {
+            prop_assert_eq!(as_u64_le(&value.to_le_bytes()), value);
+            prop_assert_eq!(as_u32_le(&(value as u32).to_le_bytes()), value as u32);
+        }
+
+        #[test]
+        fn filetime_round_trips(filetime in 0u64..(1 << 62)) {
+            prop_assert_eq!(system_time_to_filetime(filetime_to_system_time(filetime)), filetime);
+        }
+    }
 }
@@ -0,0 +1,360 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::blowfish::Blowfish;
+use crate::pk2::archive::{Archive, read_header};
+use crate::pk2::constants::{BLOCK_SIZE, ENTRIES_PER_BLOCK, HEADER_SIZE, SALT};
+use crate::pk2::directory::Directory;
+use crate::pk2::entry::Entry;
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::{InvalidHeader, InvalidKey, IO};
+use crate::pk2::header::Header;
+use crate::pk2::util::{copy_content, read_single_block};
+use crate::pk2::writer::Writer;
+
+/// A problem found while verifying an archive.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// The header is invalid, e.g. due to a wrong signature or key.
+    InvalidHeader(&'static str),
+    /// A block lies (partially) beyond the end of the archive.
+    BlockOutOfBounds { path: PathBuf, offset: u64 },
+    /// A block chain leads back to one of its own blocks.
+    CyclicChain { path: PathBuf, offset: u64 },
+    /// A block already belongs to another directory, e.g. because a directory links to one of its ancestors.
+    SharedBlock { path: PathBuf, offset: u64, owner: PathBuf },
+    /// An entry has a type other than empty, directory or file.
+    InvalidType { path: PathBuf, typ: u8 },
+    /// A directory lacks a `.` entry linking to the directory itself.
+    MissingDot { path: PathBuf },
+    /// A directory lacks a `..` entry linking to its parent directory.
+    MissingDotDot { path: PathBuf },
+    /// The content of a file lies (partially) beyond the end of the archive.
+    DataOutOfBounds { path: PathBuf, position: u64, size: u32 },
+    /// The content of a file or a block overlaps with another one.
+    Overlap { path: PathBuf, other: PathBuf },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::InvalidHeader(msg) => write!(f, "invalid header: {}", msg),
+            Issue::BlockOutOfBounds { path, offset } => write!(f, "{:?}: block at {:#x} lies beyond the end of the archive", path, offset),
+            Issue::CyclicChain { path, offset } => write!(f, "{:?}: block chain leads back to block at {:#x}", path, offset),
+            Issue::SharedBlock { path, offset, owner } => write!(f, "{:?}: block at {:#x} already belongs to {:?}", path, offset, owner),
+            Issue::InvalidType { path, typ } => write!(f, "{:?}: invalid entry type {}", path, typ),
+            Issue::MissingDot { path } => write!(f, "{:?}: missing \".\" entry", path),
+            Issue::MissingDotDot { path } => write!(f, "{:?}: missing \"..\" entry", path),
+            Issue::DataOutOfBounds { path, position, size } => write!(f, "{:?}: content at {:#x} with {} bytes lies beyond the end of the archive", path, position, size),
+            Issue::Overlap { path, other } => write!(f, "{:?}: overlaps with {:?}", path, other),
+        }
+    }
+}
+
+/// The result of verifying an archive.
+#[derive(Debug, Default)]
+pub struct VerificationReport {
+    /// Number of blocks visited.
+    pub blocks: usize,
+    /// Number of directories visited, including the root directory.
+    pub directories: usize,
+    /// Number of files visited.
+    pub files: usize,
+    /// All problems found.
+    pub issues: Vec<Issue>,
+}
+
+impl VerificationReport {
+    /// Returns whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Archive {
+    /// Verifies the archive at given path, which is encrypted with given blowfish key, by walking
+    /// through every block and entry. Unlike [Archive::open] this also works for damaged archives.
+    pub fn verify(file_path: &Path, key: &[u8]) -> Result<VerificationReport, Error> {
+        let file = File::open(file_path).map_err(IO)?;
+        let blowfish = new_blowfish(key)?;
+        let (report, _, _) = Verifier::run(&file, &blowfish)?;
+        Ok(report)
+    }
+
+    /// Verifies the archive at given path (see [Archive::verify]) and writes a repaired copy to
+    /// given location. The copy contains every directory and file whose blocks and content could be
+    /// read, laid out like a compacted archive (see [Archive::compact_to]).
+    ///
+    /// Returns the report of the damaged archive.
+    pub fn repair(file_path: &Path, key: &[u8], location: &Path) -> Result<VerificationReport, Error> {
+        let file = File::open(file_path).map_err(IO)?;
+        let blowfish = new_blowfish(key)?;
+        let (report, header, root) = Verifier::run(&file, &blowfish)?;
+        let header = if report.issues.iter().any(|i| matches!(i, Issue::InvalidHeader(_))) {
+            Header::new(header.is_encrypted(), &blowfish)
+        } else {
+            header
+        };
+
+        let target = File::create(location).map_err(IO)?;
+        Writer::new(&root)?.write(&header, &blowfish, &target, |_, entry, out| copy_content(&file, entry, out))?;
+        target.sync_all().map_err(IO)?;
+        Ok(report)
+    }
+}
+
+fn new_blowfish(key: &[u8]) -> Result<Blowfish, Error> {
+    Blowfish::new(key, SALT).map_err(|_| InvalidKey("Key must be between 4 and 56 bytes long"))
+}
+
+/// Walks through all blocks of an archive, collects issues and salvages the readable directories.
+struct Verifier<'a> {
+    file: &'a File,
+    blowfish: Option<&'a Blowfish>,
+    archive_size: u64,
+    owners: HashMap<u64, PathBuf>,
+    ranges: Vec<(u64, u64, PathBuf)>,
+    report: VerificationReport,
+}
+
+impl<'a> Verifier<'a> {
+    fn run(file: &'a File, blowfish: &'a Blowfish) -> Result<(VerificationReport, Header, Directory), Error> {
+        let header = read_header(file)?;
+        let mut verifier = Verifier {
+            file,
+            blowfish: if header.is_encrypted() { Some(blowfish) } else { None },
+            archive_size: file.metadata().map_err(IO)?.len(),
+            owners: HashMap::new(),
+            ranges: vec![(0, HEADER_SIZE as u64, PathBuf::new())],
+            report: VerificationReport::default(),
+        };
+
+        for result in [header.verify_signature(), header.verify_checksum(blowfish)] {
+            if let Err(InvalidHeader(msg)) = result {
+                verifier.report.issues.push(Issue::InvalidHeader(msg));
+            }
+        }
+
+        let root_entry = Entry::link(&Entry::empty(), b"", HEADER_SIZE as u64);
+        let root = verifier.verify_directory(Path::new(""), root_entry, None)?
+            .unwrap_or_else(|| Directory::from(root_entry));
+        verifier.verify_ranges();
+        Ok((verifier.report, header, root))
+    }
+
+    /// Verifies the directory of given entry and all of its sub directories.
+    /// Returns the salvaged directory or `None` if not even its first block could be read.
+    fn verify_directory(&mut self, path: &Path, entry: Entry, parent: Option<u64>) -> Result<Option<Directory>, Error> {
+        let start = entry.position;
+        let mut directory = Directory::from(entry);
+        let mut sub_dirs = Vec::new();
+        let mut has_dot = false;
+        let mut has_dot_dot = false;
+        let mut chain = HashSet::new();
+        let mut offset = start;
+
+        loop {
+            // corrupt offsets may be close to u64::MAX
+            let end = match offset.checked_add(BLOCK_SIZE as u64) {
+                Some(end) if end <= self.archive_size => end,
+                _ => {
+                    self.report.issues.push(Issue::BlockOutOfBounds { path: path.to_path_buf(), offset });
+                    break;
+                }
+            };
+            if !chain.insert(offset) {
+                self.report.issues.push(Issue::CyclicChain { path: path.to_path_buf(), offset });
+                break;
+            }
+            if let Some(owner) = self.owners.get(&offset) {
+                self.report.issues.push(Issue::SharedBlock { path: path.to_path_buf(), offset, owner: owner.clone() });
+                break;
+            }
+            self.owners.insert(offset, path.to_path_buf());
+            self.ranges.push((offset, end, path.to_path_buf()));
+            self.report.blocks += 1;
+
+            let entries = read_single_block(self.file, offset, self.blowfish)?;
+            for e in entries.iter() {
+                match e.typ {
+                    0 => {}
+                    1 if e.name.bytes() == b"." => {
+                        has_dot |= e.position == start;
+                        if parent.is_none() {
+                            // the root directory has no entry of its own, so it takes over the timestamps of "."
+                            directory.entry = Entry::link(e, b"", start);
+                        }
+                    }
+                    1 if e.name.bytes() == b".." => has_dot_dot |= Some(e.position) == parent,
+                    1 => sub_dirs.push(*e),
+                    2 => self.verify_file(path, e, &mut directory),
+                    typ => self.report.issues.push(Issue::InvalidType { path: path.join(e.path_buf()), typ }),
+                }
+            }
+
+            let next = entries[ENTRIES_PER_BLOCK - 1].next_chain;
+            if next == 0 {
+                break;
+            }
+            offset = next;
+        }
+
+        if chain.is_empty() {
+            return Ok(None);
+        }
+        self.report.directories += 1;
+        if !has_dot {
+            self.report.issues.push(Issue::MissingDot { path: path.to_path_buf() });
+        }
+        if parent.is_some() && !has_dot_dot {
+            self.report.issues.push(Issue::MissingDotDot { path: path.to_path_buf() });
+        }
+
+        for sub_dir_entry in sub_dirs {
+            let sub_path = path.join(sub_dir_entry.path_buf());
+            if let Some(sub_dir) = self.verify_directory(&sub_path, sub_dir_entry, Some(start))? {
+                directory.add_directory(sub_dir);
+            }
+        }
+
+        Ok(Some(directory))
+    }
+
+    fn verify_file(&mut self, path: &Path, entry: &Entry, directory: &mut Directory) {
+        let file_path = path.join(entry.path_buf());
+        let end = match entry.position.checked_add(entry.size as u64) {
+            Some(end) if end <= self.archive_size => end,
+            _ => {
+                self.report.issues.push(Issue::DataOutOfBounds { path: file_path, position: entry.position, size: entry.size });
+                return;
+            }
+        };
+        if entry.size > 0 {
+            self.ranges.push((entry.position, end, file_path));
+        }
+        self.report.files += 1;
+        directory.add_file(*entry);
+    }
+
+    /// Looks for overlaps between the header, blocks and file contents.
+    fn verify_ranges(&mut self) {
+        self.ranges.sort_by_key(|(start, end, _)| (*start, *end));
+        let mut furthest: Option<&(u64, u64, PathBuf)> = None;
+        for range in self.ranges.iter() {
+            match furthest {
+                Some(previous) if range.0 < previous.1 => {
+                    self.report.issues.push(Issue::Overlap { path: range.2.clone(), other: previous.2.clone() });
+                    if range.1 > previous.1 {
+                        furthest = Some(range);
+                    }
+                }
+                _ => furthest = Some(range),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pk2::constants::{ENTRY_SIZE, KEY_BYTES};
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    fn verify(fixture: &Fixture) -> VerificationReport {
+        let blowfish = new_blowfish(KEY_BYTES).unwrap();
+        Verifier::run(&fixture.file(), &blowfish).unwrap().0
+    }
+
+    /// Builds an archive whose root directory contains the files `a.txt` and `b.txt`, which overlap,
+    /// `lost.txt`, whose content is missing, an entry of an invalid type, and the directory `sub`
+    /// lacking its ".." entry with the file `c.txt`. The root's block chain leads back to itself.
+    fn damaged() -> Fixture {
+        let mut fixture = Fixture::new();
+        let root = fixture.offset();
+        let (sub, data) = (root + BLOCK_SIZE as u64, root + 2 * BLOCK_SIZE as u64);
+        let mut root_block = vec![
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(1, b"sub", sub, 0, 0),
+            raw_entry(2, b"a.txt", data, 5, 0),
+            raw_entry(7, b"bad", 0, 0, 0),
+            raw_entry(2, b"b.txt", data + 3, 5, 0),
+            raw_entry(2, b"lost.txt", data, 1000, 0),
+        ];
+        root_block.resize(ENTRIES_PER_BLOCK - 1, [0; ENTRY_SIZE]);
+        root_block.push(raw_entry(0, b"", 0, 0, root));
+        fixture.push_block(&root_block);
+        fixture.push_block(&[raw_entry(1, b".", sub, 0, 0), raw_entry(2, b"c.txt", data + 8, 2, 0)]);
+        fixture.push_data(b"helloworld");
+        fixture
+    }
+
+    #[test]
+    fn accepts_sound_archives() {
+        let mut fixture = Fixture::new();
+        let root = fixture.offset();
+        let sub = root + BLOCK_SIZE as u64;
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(1, b"sub", sub, 0, 0),
+            raw_entry(2, b"a.txt", sub + BLOCK_SIZE as u64, 5, 0),
+            raw_entry(2, b"empty.txt", 0, 0, 0),
+        ]);
+        fixture.push_block(&[raw_entry(1, b".", sub, 0, 0), raw_entry(1, b"..", root, 0, 0)]);
+        fixture.push_data(b"hello");
+        let report = verify(&fixture);
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert_eq!((report.blocks, report.directories, report.files), (2, 2, 2));
+    }
+
+    #[test]
+    fn reports_damages() {
+        let report = verify(&damaged());
+        let root = HEADER_SIZE as u64;
+        let data = root + 2 * BLOCK_SIZE as u64;
+        assert_eq!(report.issues, vec![
+            Issue::InvalidType { path: PathBuf::from("bad"), typ: 7 },
+            Issue::DataOutOfBounds { path: PathBuf::from("lost.txt"), position: data, size: 1000 },
+            Issue::CyclicChain { path: PathBuf::new(), offset: root },
+            Issue::MissingDotDot { path: PathBuf::from("sub") },
+            Issue::Overlap { path: PathBuf::from("b.txt"), other: PathBuf::from("a.txt") },
+        ]);
+        assert_eq!((report.blocks, report.directories, report.files), (2, 2, 3));
+    }
+
+    #[test]
+    fn repairs_damaged_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let (damaged_path, repaired_path) = (dir.path().join("damaged.pk2"), dir.path().join("repaired.pk2"));
+        damaged().save(&damaged_path);
+        let report = Archive::repair(&damaged_path, KEY_BYTES, &repaired_path).unwrap();
+        assert_eq!(report.issues.len(), 5);
+
+        assert!(Archive::verify(&repaired_path, KEY_BYTES).unwrap().is_ok());
+        let repaired = Archive::open(&repaired_path).unwrap();
+        assert_eq!(repaired.read(Path::new("a.txt")).unwrap(), b"hello");
+        assert_eq!(repaired.read(Path::new("b.txt")).unwrap(), b"lowor");
+        assert_eq!(repaired.read(Path::new("sub/c.txt")).unwrap(), b"ld");
+        assert!(repaired.entry(Path::new("lost.txt")).is_none());
+        assert!(repaired.entry(Path::new("bad")).is_none());
+    }
+
+    #[test]
+    fn reports_positions_beyond_the_address_space() {
+        let mut fixture = Fixture::with_key(None);
+        let root = fixture.offset();
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(2, b"huge.txt", 0xFFFF_FFFF_FFFF_FFF0, 0x100, 0),
+            raw_entry(1, b"far", u64::MAX - 8, 0, 0),
+        ]);
+        let report = verify(&fixture);
+        assert_eq!(report.issues, vec![
+            Issue::DataOutOfBounds { path: PathBuf::from("huge.txt"), position: 0xFFFF_FFFF_FFFF_FFF0, size: 0x100 },
+            Issue::BlockOutOfBounds { path: PathBuf::from("far"), offset: u64::MAX - 8 },
+        ]);
+        assert_eq!((report.blocks, report.directories, report.files), (1, 1, 0));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::blowfish::Blowfish;
+use crate::pk2::constants::{BLOCK_SIZE, ENTRIES_PER_BLOCK, HEADER_SIZE};
+use crate::pk2::directory::Directory;
+use crate::pk2::entry::Entry;
+use crate::pk2::errors::Error;
+use crate::pk2::errors::Error::{InvalidEntry, IO};
+use crate::pk2::header::Header;
+use crate::pk2::util::write_block;
+
+/// A directory with the position of its first block in the planned layout.
+struct PlannedDirectory<'a> {
+    path: PathBuf,
+    directory: &'a Directory,
+    children: Vec<&'a Entry>,
+    position: u64,
+    parent_position: Option<u64>,
+}
+
+/// Writes a PK2 archive without any gaps: the header is followed by the blocks of all directories,
+/// which are followed by the contents of all files. Every directory only occupies as many blocks as
+/// it needs for its entries.
+pub struct Writer<'a> {
+    directories: Vec<PlannedDirectory<'a>>,
+    positions: HashMap<PathBuf, u64>,
+    files: Vec<(PathBuf, &'a Entry)>,
+    size: u64,
+}
+
+impl<'a> Writer<'a> {
+    /// Plans the layout of an archive containing the given root directory and everything below it.
+    pub fn new(root: &'a Directory) -> Result<Writer<'a>, Error> {
+        let mut directories = Vec::new();
+        let mut positions = HashMap::new();
+        let mut files = Vec::new();
+        let mut offset = HEADER_SIZE as u64;
+
+        let mut queue = VecDeque::from(vec![(PathBuf::new(), root, None)]);
+        while let Some((path, directory, parent_position)) = queue.pop_front() {
+            let mut children: Vec<&Entry> = directory.entries.values().collect();
+            children.sort_by_key(|e| e.name);
+
+            // the root directory only has a "." entry, all others also have a ".." entry
+            let slots = children.len() + if parent_position.is_some() { 2 } else { 1 };
+            let position = offset;
+            offset += (block_count(slots) * BLOCK_SIZE) as u64;
+
+            for child in children.iter() {
+                let child_path = path.join(child.path_buf());
+                if child.is_dir() {
+                    let child_dir = directory.directories.get(&child.path_buf())
+                        .ok_or(InvalidEntry("directory entry without indexed directory"))?;
+                    queue.push_back((child_path, child_dir, Some(position)));
+                } else {
+                    files.push((child_path, *child));
+                }
+            }
+
+            positions.insert(path.clone(), position);
+            directories.push(PlannedDirectory { path, directory, children, position, parent_position });
+        }
+
+        for (path, entry) in files.iter() {
+            positions.insert(path.clone(), offset);
+            offset += entry.size as u64;
+        }
+
+        Ok(Writer { directories, positions, files, size: offset })
+    }
+
+    /// Returns the size in bytes of the planned archive.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Writes the planned archive, whose blocks are encrypted with given blowfish instance unless
+    /// the header marks the archive as unencrypted. The content of each file is written by
+    /// `write_content`, which receives the file's path and its original entry and must write
+    /// exactly as many bytes as the entry's size.
+    pub fn write<W, F>(&self, header: &Header, blowfish: &Blowfish, target: W, mut write_content: F) -> Result<(), Error>
+        where W: Write,
+              F: FnMut(&Path, &Entry, &mut dyn Write) -> Result<(), Error> {
+        let mut target = BufWriter::new(target);
+        target.write_all(&<[u8; HEADER_SIZE]>::from(header)).map_err(IO)?;
+
+        let cipher = if header.is_encrypted() { Some(blowfish) } else { None };
+        for dir in self.directories.iter() {
+            self.write_directory(dir, cipher, &mut target)?;
+        }
+
+        for (path, entry) in self.files.iter() {
+            let mut counter = CountingWriter { inner: &mut target, count: 0 };
+            write_content(path, entry, &mut counter)?;
+            if counter.count != entry.size as u64 {
+                return Err(InvalidEntry("written content size differs from entry size"));
+            }
+        }
+
+        target.flush().map_err(IO)
+    }
+
+    fn write_directory(&self, dir: &PlannedDirectory, blowfish: Option<&Blowfish>, target: &mut dyn Write) -> Result<(), Error> {
+        let mut entries = vec![Entry::link(&dir.directory.entry, b".", dir.position)];
+        if let Some(parent_position) = dir.parent_position {
+            entries.push(Entry::link(&dir.directory.entry, b"..", parent_position));
+        }
+        for child in dir.children.iter() {
+            let mut entry = **child;
+            entry.position = self.positions[&dir.path.join(child.path_buf())];
+            entry.next_chain = 0;
+            entries.push(entry);
+        }
+
+        let blocks = block_count(entries.len());
+        entries.resize(blocks * ENTRIES_PER_BLOCK, Entry::empty());
+        for (i, block) in entries.chunks_mut(ENTRIES_PER_BLOCK).enumerate() {
+            if i + 1 < blocks {
+                block[ENTRIES_PER_BLOCK - 1].next_chain = dir.position + ((i + 1) * BLOCK_SIZE) as u64;
+            }
+            write_block(target, block, blowfish)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the number of blocks needed to hold given number of entries.
+fn block_count(entries: usize) -> usize {
+    entries.div_ceil(ENTRIES_PER_BLOCK).max(1)
+}
+
+/// Counts the bytes written to the inner writer.
+struct CountingWriter<'w> {
+    inner: &'w mut dyn Write,
+    count: u64,
+}
+
+impl Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use proptest::prelude::*;
+
+    use crate::pk2::archive::Archive;
+    use crate::pk2::constants::{KEY_BYTES, SALT};
+    use crate::pk2::entry::EntryType;
+
+    use super::*;
+
+    /// Directory and file names, including ones which are encoded as multi byte EUC-KR.
+    fn name() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[a-z0-9_]{1,12}",
+            prop::sample::select(vec!["한글", "몬스터", "아이템", "가나다"]).prop_map(String::from),
+        ]
+    }
+
+    /// Paths of up to three components mapped to their contents. Directories and files are
+    /// prefixed differently, so a path never refers to both.
+    fn tree() -> impl Strategy<Value = BTreeMap<Vec<String>, Vec<u8>>> {
+        let path = (prop::collection::vec(name().prop_map(|n| format!("d_{}", n)), 0..3), name())
+            .prop_map(|(mut dirs, file)| {
+                dirs.push(format!("f_{}.bin", file));
+                dirs
+            });
+        prop::collection::btree_map(path, prop::collection::vec(any::<u8>(), 0..300), 0..60)
+    }
+
+    fn build(tree: &BTreeMap<Vec<String>, Vec<u8>>) -> Directory {
+        let time = UNIX_EPOCH + Duration::from_secs(1_300_000_000);
+        let mut root_entry = Entry::new(EntryType::Dir, "", 0, time, time).unwrap();
+        root_entry.position = HEADER_SIZE as u64;
+        let mut root = Directory::from(root_entry);
+        for (path, content) in tree.iter() {
+            let (file, dirs) = path.split_last().unwrap();
+            let dir = dirs.iter().fold(&mut root, |dir, name| {
+                if !dir.directories.contains_key(Path::new(name)) {
+                    dir.add_directory(Directory::from(Entry::new(EntryType::Dir, name, 0, time, time).unwrap()));
+                }
+                dir.directories.get_mut(Path::new(name)).unwrap()
+            });
+            dir.add_file(Entry::new(EntryType::File, file, content.len() as u32, time, time).unwrap());
+        }
+        root
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn written_archive_round_trips(tree in tree(), encrypted: bool) {
+            let root = build(&tree);
+            let blowfish = Blowfish::new(KEY_BYTES, SALT).unwrap();
+            let writer = Writer::new(&root).unwrap();
+            let file = tempfile::tempfile().unwrap();
+            writer.write(&Header::new(encrypted, &blowfish), &blowfish, &file, |path, _, out| {
+                let components: Vec<String> = path.iter().map(|c| c.to_string_lossy().into_owned()).collect();
+                out.write_all(&tree[&components]).map_err(IO)
+            }).unwrap();
+            prop_assert_eq!(file.metadata().unwrap().len(), writer.size());
+
+            let archive = Archive::with_key(file, KEY_BYTES).unwrap();
+            prop_assert_eq!(archive.header.is_encrypted(), encrypted);
+            let files = archive.root.files();
+            prop_assert_eq!(files.len(), tree.len());
+            for (path, entry) in files {
+                let components: Vec<String> = path.iter().map(|c| c.to_string_lossy().into_owned()).collect();
+                prop_assert_eq!(&archive.read_entry(entry).unwrap(), &tree[&components]);
+            }
+        }
+    }
+}
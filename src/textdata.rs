@@ -0,0 +1,6 @@
+pub mod errors;
+pub mod loader;
+pub mod object;
+pub mod registry;
+pub mod skill;
+pub mod table;
@@ -0,0 +1,28 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use crate::vfs;
+
+#[derive(std::fmt::Debug)]
+pub enum Error {
+    Vfs(vfs::errors::Error),
+    /// The file is neither valid UTF-16LE nor UTF-8.
+    Encoding(PathBuf),
+    /// A value of a row couldn't be parsed. Lines and columns start at 1.
+    Parse { file: PathBuf, line: usize, column: usize, message: String },
+    /// Two records of a table share an ID or code name.
+    Duplicate { table: &'static str, key: String },
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Vfs(err) => write!(f, "{}", err),
+            Error::Encoding(file) => write!(f, "{:?} is neither UTF-16LE nor UTF-8", file),
+            Error::Parse { file, line, column, message } =>
+                write!(f, "{}:{}:{}: {}", file.display(), line, column, message),
+            Error::Duplicate { table, key } => write!(f, "duplicate {} {}", table, key),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+use crate::textdata::errors::Error;
+use crate::textdata::object::{RefObjChar, RefObjItem};
+use crate::textdata::skill::RefSkill;
+use crate::textdata::table::{Row, Table};
+use crate::vfs::Vfs;
+
+/// Directory of the text data in the client's `Media.pk2`.
+pub const TEXTDATA_DIR: &str = "server_dep/silkroad/textdata";
+
+/// Loads the reference tables from the text data of a [Vfs], e.g. an opened `Media.pk2`.
+///
+/// Each table has an index file (e.g. `characterdata.txt`) listing its part files
+/// (e.g. `characterdata_5000.txt`), whose rows are concatenated. Disabled rows are skipped.
+/// The encrypted `skilldataenc` parts of newer clients are not supported.
+pub struct Loader<'v> {
+    vfs: &'v dyn Vfs,
+    dir: PathBuf,
+}
+
+impl<'v> Loader<'v> {
+    /// Creates a loader reading from [TEXTDATA_DIR].
+    pub fn new(vfs: &'v dyn Vfs) -> Loader<'v> {
+        Loader::with_dir(vfs, Path::new(TEXTDATA_DIR))
+    }
+
+    /// Creates a loader reading from given directory of the [Vfs].
+    pub fn with_dir(vfs: &'v dyn Vfs, dir: &Path) -> Loader<'v> {
+        Loader { vfs, dir: dir.to_path_buf() }
+    }
+
+    /// Loads all characters, monsters and NPCs listed by `characterdata.txt`.
+    pub fn characters(&self) -> Result<Vec<RefObjChar>, Error> {
+        self.load("characterdata.txt")
+    }
+
+    /// Loads all items listed by `itemdata.txt`.
+    pub fn items(&self) -> Result<Vec<RefObjItem>, Error> {
+        self.load("itemdata.txt")
+    }
+
+    /// Loads all skills listed by `skilldata.txt`.
+    pub fn skills(&self) -> Result<Vec<RefSkill>, Error> {
+        self.load("skilldata.txt")
+    }
+
+    /// Parses the rows of all part files listed by given index file.
+    pub fn load<T>(&self, index: &str) -> Result<Vec<T>, Error>
+        where T: for<'r, 't> TryFrom<&'r Row<'t>, Error = Error> {
+        let index = self.table(index)?;
+        let mut records = Vec::new();
+        for part in index.names() {
+            let table = self.table(part)?;
+            for row in table.rows(false) {
+                records.push(T::try_from(&row)?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Reads and decodes the table with given file name.
+    pub fn table(&self, name: &str) -> Result<Table, Error> {
+        let path = self.dir.join(name);
+        let content = self.vfs.read(&path).map_err(Error::Vfs)?;
+        Table::decode(&path, &content)
+    }
+}
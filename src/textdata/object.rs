@@ -0,0 +1,271 @@
+use std::convert::TryFrom;
+
+use crate::textdata::errors::Error;
+use crate::textdata::table::Row;
+
+/// [RefObjItem::req_gender] of items usable by both genders.
+pub const GENDER_ANY: u8 = 2;
+
+/// Columns shared by all reference objects (`_RefObjCommon`), in the order of the vSRO 1.188
+/// text data files. Characters and items continue with their own columns after these.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefObjCommon {
+    pub service: bool,
+    pub id: u32,
+    pub code_name: String,
+    pub obj_name: Option<String>,
+    pub org_obj_code_name: Option<String>,
+    pub name_str_id: Option<String>,
+    pub desc_str_id: Option<String>,
+    pub cash_item: bool,
+    pub bionic: bool,
+    /// `TypeID1` to `TypeID4`, which classify the object, e.g. `[1, 1, 1, 0]` for player characters.
+    pub type_id: [u8; 4],
+    pub decay_time: u32,
+    pub country: u8,
+    pub rarity: u8,
+    pub can_trade: bool,
+    pub can_sell: bool,
+    pub can_buy: bool,
+    pub can_borrow: bool,
+    pub can_drop: bool,
+    pub can_pick: bool,
+    pub can_repair: bool,
+    pub can_revive: bool,
+    pub can_use: bool,
+    pub can_throw: bool,
+    pub price: u64,
+    pub cost_repair: u32,
+    pub cost_revive: u32,
+    pub cost_borrow: u32,
+    pub keep_cost: u32,
+    pub sell_price: u64,
+    /// `ReqLevelType1..4` paired with `ReqLevel1..4`.
+    pub req_levels: [(i32, u8); 4],
+    pub max_contain: u32,
+    pub region_id: i16,
+    pub dir: i16,
+    pub offset: [i16; 3],
+    pub speed: [i32; 2],
+    pub scale: i32,
+    pub bc_height: i32,
+    pub bc_radius: i32,
+    pub event_id: i32,
+    pub asset_file: Option<String>,
+    /// `AssocFileDrop128`, the model shown when the object lies on the ground.
+    pub asset_file_gfx: Option<String>,
+    /// `AssocFileIcon128`.
+    pub asset_file_icon: Option<String>,
+}
+
+impl RefObjCommon {
+    /// Number of columns of the common part.
+    pub const COLUMNS: usize = 57;
+}
+
+impl TryFrom<&Row<'_>> for RefObjCommon {
+    type Error = Error;
+
+    fn try_from(row: &Row<'_>) -> Result<Self, Error> {
+        let name = |column| row.name(column).map(|name| name.map(String::from));
+        let mut req_levels = [(0, 0); 4];
+        for (i, level) in req_levels.iter_mut().enumerate() {
+            *level = (row.parse(32 + 2 * i)?, row.parse(33 + 2 * i)?);
+        }
+        Ok(RefObjCommon {
+            service: row.flag(0)?,
+            id: row.parse(1)?,
+            code_name: row.str(2)?.to_string(),
+            obj_name: name(3)?,
+            org_obj_code_name: name(4)?,
+            name_str_id: name(5)?,
+            desc_str_id: name(6)?,
+            cash_item: row.flag(7)?,
+            bionic: row.flag(8)?,
+            type_id: row.parse_array(9)?,
+            decay_time: row.parse(13)?,
+            country: row.parse(14)?,
+            rarity: row.parse(15)?,
+            can_trade: row.flag(16)?,
+            can_sell: row.flag(17)?,
+            can_buy: row.flag(18)?,
+            can_borrow: row.flag(19)?,
+            can_drop: row.flag(20)?,
+            can_pick: row.flag(21)?,
+            can_repair: row.flag(22)?,
+            can_revive: row.flag(23)?,
+            can_use: row.flag(24)?,
+            can_throw: row.flag(25)?,
+            price: row.parse(26)?,
+            cost_repair: row.parse(27)?,
+            cost_revive: row.parse(28)?,
+            cost_borrow: row.parse(29)?,
+            keep_cost: row.parse(30)?,
+            sell_price: row.parse(31)?,
+            req_levels,
+            max_contain: row.parse(40)?,
+            region_id: row.parse(41)?,
+            dir: row.parse(42)?,
+            offset: row.parse_array(43)?,
+            speed: row.parse_array(46)?,
+            scale: row.parse(48)?,
+            bc_height: row.parse(49)?,
+            bc_radius: row.parse(50)?,
+            event_id: row.parse(51)?,
+            asset_file: name(52)?,
+            asset_file_gfx: name(53)?,
+            asset_file_icon: name(54)?,
+        })
+    }
+}
+
+/// A character, monster or NPC (`_RefObjChar`) from `characterdata_*.txt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefObjChar {
+    pub common: RefObjCommon,
+    pub level: u8,
+    pub gender: u8,
+    pub max_hp: u32,
+    pub max_mp: u32,
+    pub inventory_size: u8,
+    pub physical_defence: u32,
+    pub magical_defence: u32,
+    pub physical_absorb_ratio: u32,
+    pub magical_absorb_ratio: u32,
+    pub evasion_ratio: u32,
+    pub block_ratio: u32,
+    pub hit_ratio: u32,
+    pub critical_ratio: u32,
+    pub exp_to_give: u32,
+    /// `DefaultSkill_1..10`, zero for unset slots.
+    pub default_skills: [u32; 10],
+}
+
+impl TryFrom<&Row<'_>> for RefObjChar {
+    type Error = Error;
+
+    fn try_from(row: &Row<'_>) -> Result<Self, Error> {
+        Ok(RefObjChar {
+            common: RefObjCommon::try_from(row)?,
+            level: row.parse(57)?,
+            gender: row.parse(58)?,
+            max_hp: row.parse(59)?,
+            max_mp: row.parse(60)?,
+            inventory_size: row.parse(93)?,
+            physical_defence: row.parse(103)?,
+            magical_defence: row.parse(104)?,
+            physical_absorb_ratio: row.parse(105)?,
+            magical_absorb_ratio: row.parse(106)?,
+            evasion_ratio: row.parse(107)?,
+            block_ratio: row.parse(108)?,
+            hit_ratio: row.parse(109)?,
+            critical_ratio: row.parse(110)?,
+            exp_to_give: row.parse(111)?,
+            default_skills: row.parse_array(115)?,
+        })
+    }
+}
+
+/// An item (`_RefObjItem`) from `itemdata_*.txt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefObjItem {
+    pub common: RefObjCommon,
+    pub max_stack: u32,
+    /// Gender of the characters who can use the item, or [GENDER_ANY].
+    pub req_gender: u8,
+    pub req_str: u16,
+    pub req_int: u16,
+    pub item_class: u8,
+    pub set_id: u32,
+    /// Lower and upper bound of the durability.
+    pub durability: (f32, f32),
+    pub two_handed: bool,
+    pub range: u32,
+}
+
+impl TryFrom<&Row<'_>> for RefObjItem {
+    type Error = Error;
+
+    fn try_from(row: &Row<'_>) -> Result<Self, Error> {
+        Ok(RefObjItem {
+            common: RefObjCommon::try_from(row)?,
+            max_stack: row.parse(57)?,
+            req_gender: row.parse(58)?,
+            req_str: row.parse(59)?,
+            req_int: row.parse(60)?,
+            item_class: row.parse(61)?,
+            set_id: row.parse(62)?,
+            durability: (row.parse(63)?, row.parse(64)?),
+            two_handed: row.flag(93)?,
+            range: row.parse(94)?,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::Path;
+
+    use crate::textdata::table::Table;
+    use crate::textdata::table::tests::{parse_row, row};
+
+    use super::*;
+
+    /// Builds a character with given values at their columns of `characterdata_*.txt`.
+    pub(crate) fn character(values: &[(usize, &str)]) -> RefObjChar {
+        parse_row(125, values)
+    }
+
+    /// Builds an item with given values at their columns of `itemdata_*.txt`.
+    pub(crate) fn item(values: &[(usize, &str)]) -> RefObjItem {
+        parse_row(95, values)
+    }
+
+    #[test]
+    fn parses_character() {
+        let character = character(&[
+            (0, "1"), (1, "1907"), (2, "CHAR_CH_MAN_ADVENTURER"), (3, "xxx"), (9, "1"), (10, "1"), (11, "1"),
+            (46, "16.000000"), (47, "50"), (52, "prim\\ch_man.bsr"), (57, "1"), (59, "200"), (93, "45"), (115, "1"),
+        ]);
+        assert_eq!(character.common.id, 1907);
+        assert_eq!(character.common.code_name, "CHAR_CH_MAN_ADVENTURER");
+        assert_eq!(character.common.obj_name, None);
+        assert_eq!(character.common.type_id, [1, 1, 1, 0]);
+        assert_eq!(character.common.speed, [16, 50]);
+        assert_eq!(character.common.asset_file.as_deref(), Some("prim\\ch_man.bsr"));
+        assert_eq!((character.level, character.max_hp, character.inventory_size), (1, 200, 45));
+        assert_eq!(character.default_skills[..2], [1, 0]);
+    }
+
+    #[test]
+    fn parses_stats() {
+        let character = character(&[
+            (0, "1"), (1, "1907"), (2, "CHAR_CH_MAN_ADVENTURER"), (103, "1"), (104, "2"), (105, "3"), (106, "4"),
+            (107, "5"), (108, "6"), (109, "7"), (110, "8"), (111, "9"),
+        ]);
+        assert_eq!((character.physical_defence, character.magical_defence), (1, 2));
+        assert_eq!((character.physical_absorb_ratio, character.magical_absorb_ratio), (3, 4));
+        assert_eq!((character.evasion_ratio, character.block_ratio), (5, 6));
+        assert_eq!((character.hit_ratio, character.critical_ratio), (7, 8));
+        assert_eq!(character.exp_to_give, 9);
+    }
+
+    #[test]
+    fn parses_asset_files() {
+        let item = item(&[
+            (0, "1"), (1, "62"), (2, "ITEM_ETC_HP_POTION_01"), (52, "item\\etc\\hp_potion.bsr"),
+            (53, "item\\etc\\drop_hp_potion.bsr"), (54, "item\\etc\\hp_potion_01.ddj"), (55, "xxx"), (56, "xxx"),
+        ]);
+        assert_eq!(item.common.asset_file.as_deref(), Some("item\\etc\\hp_potion.bsr"));
+        assert_eq!(item.common.asset_file_gfx.as_deref(), Some("item\\etc\\drop_hp_potion.bsr"));
+        assert_eq!(item.common.asset_file_icon.as_deref(), Some("item\\etc\\hp_potion_01.ddj"));
+    }
+
+    #[test]
+    fn reports_invalid_item_column() {
+        let text = format!("// comment\n{}\n", row(95, &[(0, "1"), (2, "ITEM_ETC_GOLD_01"), (93, "2")]));
+        let table = Table::decode(Path::new("itemdata_5000.txt"), text.as_bytes()).unwrap();
+        let err = RefObjItem::try_from(&table.rows(false).next().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "itemdata_5000.txt:2:94: invalid flag '2'");
+    }
+}
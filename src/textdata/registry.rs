@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use lazy_static::lazy_static;
+use prometheus::{register_int_gauge, register_int_gauge_vec, IntGauge, IntGaugeVec};
+
+use crate::textdata::errors::Error;
+use crate::textdata::loader::Loader;
+use crate::textdata::object::{RefObjChar, RefObjItem};
+use crate::textdata::skill::RefSkill;
+use crate::vfs::Vfs;
+
+lazy_static! {
+    static ref RECORDS_GAUGE: IntGaugeVec = register_int_gauge_vec!("textdata_records", "number of loaded reference records per table", &["table"]).expect("failed to register gauge textdata_records");
+    static ref MISSING_REFERENCES_GAUGE: IntGauge = register_int_gauge!("textdata_missing_references", "number of references to records which don't exist").expect("failed to register gauge textdata_missing_references");
+}
+
+/// First three type IDs of weapons, whose last type ID is the weapon type skills refer to.
+const TYPE_ID_WEAPON: [u8; 3] = [3, 1, 6];
+/// `ReqCast_Weapon` of skills which can be cast with any weapon.
+const ANY_WEAPON: u8 = 255;
+
+/// A reference record which can be looked up by its ID and code name.
+pub trait Record {
+    fn id(&self) -> u32;
+    fn code_name(&self) -> &str;
+}
+
+impl Record for RefObjChar {
+    fn id(&self) -> u32 {
+        self.common.id
+    }
+    fn code_name(&self) -> &str {
+        &self.common.code_name
+    }
+}
+
+impl Record for RefObjItem {
+    fn id(&self) -> u32 {
+        self.common.id
+    }
+    fn code_name(&self) -> &str {
+        &self.common.code_name
+    }
+}
+
+impl Record for RefSkill {
+    fn id(&self) -> u32 {
+        self.id
+    }
+    fn code_name(&self) -> &str {
+        &self.code
+    }
+}
+
+/// Records of a reference table indexed by ID and code name.
+pub struct RefTable<T> {
+    name: &'static str,
+    records: Vec<T>,
+    by_id: HashMap<u32, usize>,
+    by_code_name: HashMap<String, usize>,
+}
+
+impl<T: Record> RefTable<T> {
+    /// Indexes the records. Fails if two records share an ID or code name.
+    pub fn new(name: &'static str, records: Vec<T>) -> Result<RefTable<T>, Error> {
+        let mut by_id = HashMap::with_capacity(records.len());
+        let mut by_code_name = HashMap::with_capacity(records.len());
+        for (i, record) in records.iter().enumerate() {
+            if by_id.insert(record.id(), i).is_some() {
+                return Err(Error::Duplicate { table: name, key: record.id().to_string() });
+            }
+            if by_code_name.insert(record.code_name().to_string(), i).is_some() {
+                return Err(Error::Duplicate { table: name, key: record.code_name().to_string() });
+            }
+        }
+        Ok(RefTable { name, records, by_id, by_code_name })
+    }
+
+    /// Returns the name of the table.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the record with given ID.
+    pub fn get(&self, id: u32) -> Option<&T> {
+        self.by_id.get(&id).map(|i| &self.records[*i])
+    }
+
+    /// Returns the record with given code name, e.g. `ITEM_CH_SWORD_01_A`.
+    pub fn by_code_name(&self, code_name: &str) -> Option<&T> {
+        self.by_code_name.get(code_name).map(|i| &self.records[*i])
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.by_id.contains_key(&id)
+    }
+
+    /// Returns all records in the order they were loaded.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// A reference from one record to another which doesn't exist, e.g. a default skill of a
+/// character missing in the skill table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingReference {
+    pub table: &'static str,
+    pub id: u32,
+    /// Name of the referencing column.
+    pub column: &'static str,
+    /// ID or code name of the missing record.
+    pub target: String,
+}
+
+impl Display for MissingReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} references missing {} in {}", self.table, self.id, self.target, self.column)
+    }
+}
+
+/// Immutable reference data of the game, loaded once at startup and shared between tasks
+/// behind an [Arc](std::sync::Arc).
+pub struct Registry {
+    pub characters: RefTable<RefObjChar>,
+    pub items: RefTable<RefObjItem>,
+    pub skills: RefTable<RefSkill>,
+}
+
+impl Registry {
+    /// Loads the text data of given [Vfs], usually the client's `Media.pk2` opened as
+    /// [Archive](crate::pk2::archive::Archive) (see [Loader]).
+    ///
+    /// Missing references between the tables are logged, since the client files contain some,
+    /// and counted by the `textdata_missing_references` gauge (see [Registry::validate]).
+    pub fn load(vfs: &dyn Vfs) -> Result<Registry, Error> {
+        let loader = Loader::new(vfs);
+        let registry = Registry::new(loader.characters()?, loader.items()?, loader.skills()?)?;
+        let missing = registry.validate();
+        missing.iter().for_each(|reference| warn!("{}", reference));
+        MISSING_REFERENCES_GAUGE.set(missing.len() as i64);
+        info!("loaded {} characters, {} items and {} skills", registry.characters.len(), registry.items.len(), registry.skills.len());
+        Ok(registry)
+    }
+
+    /// Indexes already loaded records and reports the size of each table.
+    pub fn new(characters: Vec<RefObjChar>, items: Vec<RefObjItem>, skills: Vec<RefSkill>) -> Result<Registry, Error> {
+        let registry = Registry {
+            characters: RefTable::new("character", characters)?,
+            items: RefTable::new("item", items)?,
+            skills: RefTable::new("skill", skills)?,
+        };
+        RECORDS_GAUGE.with_label_values(&["characters"]).set(registry.characters.len() as i64);
+        RECORDS_GAUGE.with_label_values(&["items"]).set(registry.items.len() as i64);
+        RECORDS_GAUGE.with_label_values(&["skills"]).set(registry.skills.len() as i64);
+        Ok(registry)
+    }
+
+    /// Returns all references between records whose target doesn't exist: default skills of
+    /// characters, skills required to learn a skill or following in its chain, weapon types a
+    /// skill has to be cast with, and the original items of items, e.g. the one a rare item is
+    /// based on.
+    pub fn validate(&self) -> Vec<MissingReference> {
+        let weapon_types: HashSet<u8> = self.items.iter()
+            .filter(|item| item.common.type_id[..3] == TYPE_ID_WEAPON)
+            .map(|item| item.common.type_id[3])
+            .collect();
+        let mut missing = Vec::new();
+        let mut check = |table, id, column, target: u32, exists: bool| {
+            if target != 0 && !exists {
+                missing.push(MissingReference { table, id, column, target: target.to_string() });
+            }
+        };
+        for character in self.characters.iter() {
+            for skill in character.default_skills.iter() {
+                check(self.characters.name, character.common.id, "DefaultSkill", *skill, self.skills.contains(*skill));
+            }
+        }
+        for skill in self.skills.iter() {
+            check(self.skills.name, skill.id, "Basic_ChainCode", skill.chain_code, self.skills.contains(skill.chain_code));
+            for required in skill.required_skills.iter() {
+                check(self.skills.name, skill.id, "ReqLearn_Skill", *required, self.skills.contains(*required));
+            }
+            for weapon in skill.required_weapons.iter() {
+                let exists = *weapon == ANY_WEAPON || weapon_types.contains(weapon);
+                check(self.skills.name, skill.id, "ReqCast_Weapon", *weapon as u32, exists);
+            }
+        }
+        for item in self.items.iter() {
+            if let Some(original) = &item.common.org_obj_code_name {
+                if self.items.by_code_name(original).is_none() {
+                    missing.push(MissingReference { table: self.items.name, id: item.common.id, column: "OrgObjCodeName128", target: original.clone() });
+                }
+            }
+        }
+        missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+    use std::path::Path;
+
+    use crate::textdata::loader::TEXTDATA_DIR;
+    use crate::textdata::table::tests::{row, utf16};
+    use crate::vfs::directory::DirectoryVfs;
+
+    use super::*;
+
+    /// Writes a table consisting of an index file and a single part as UTF-16LE.
+    fn write_table(dir: &Path, name: &str, rows: &[String]) {
+        write(dir.join(format!("{}.txt", name)), utf16(&format!("{}_5000.txt\r\n", name))).unwrap();
+        write(dir.join(format!("{}_5000.txt", name)), utf16(&rows.join("\r\n"))).unwrap();
+    }
+
+    fn textdata(skill_rows: &[String]) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join(TEXTDATA_DIR);
+        create_dir_all(&dir).unwrap();
+        write_table(&dir, "characterdata", &[
+            row(125, &[(0, "1"), (1, "1907"), (2, "CHAR_CH_MAN_ADVENTURER"), (115, "1"), (116, "3")]),
+            row(125, &[(0, "0"), (1, "1908"), (2, "CHAR_DISABLED")]),
+        ]);
+        write_table(&dir, "itemdata", &[
+            "// Service\tID\tCodeName128".to_string(),
+            row(95, &[(0, "1"), (1, "3633"), (2, "ITEM_CH_SWORD_01_A"), (4, "xxx"), (9, "3"), (10, "1"), (11, "6"), (12, "2")]),
+            row(95, &[(0, "1"), (1, "3634"), (2, "ITEM_CH_SWORD_01_A_RARE"), (4, "ITEM_CH_SWORD_01_A")]),
+            row(95, &[(0, "1"), (1, "3635"), (2, "ITEM_CH_SWORD_01_B_RARE"), (4, "ITEM_CH_SWORD_01_B")]),
+        ]);
+        write_table(&dir, "skilldata", skill_rows);
+        root
+    }
+
+    #[test]
+    fn looks_up_records_by_id_and_code_name() {
+        let root = textdata(&[
+            row(118, &[(0, "1"), (1, "1"), (3, "SKILL_CH_SWORD_BASE_01"), (9, "2"), (50, "2"), (51, "255")]),
+            row(118, &[(0, "1"), (1, "2"), (3, "SKILL_CH_SWORD_BASE_02"), (40, "5"), (50, "3")]),
+        ]);
+        let registry = Registry::load(&DirectoryVfs::new(root.path()).unwrap()).unwrap();
+        assert_eq!(registry.characters.len(), 1);
+        assert_eq!(registry.characters.get(1907).unwrap().common.code_name, "CHAR_CH_MAN_ADVENTURER");
+        assert!(registry.characters.by_code_name("CHAR_DISABLED").is_none());
+        assert_eq!(registry.items.by_code_name("ITEM_CH_SWORD_01_A").unwrap().common.id, 3633);
+        assert_eq!(registry.skills.get(1).unwrap().chain_code, 2);
+        assert_eq!(registry.validate(), vec![
+            MissingReference { table: "character", id: 1907, column: "DefaultSkill", target: "3".to_string() },
+            MissingReference { table: "skill", id: 2, column: "ReqLearn_Skill", target: "5".to_string() },
+            MissingReference { table: "skill", id: 2, column: "ReqCast_Weapon", target: "3".to_string() },
+            MissingReference { table: "item", id: 3635, column: "OrgObjCodeName128", target: "ITEM_CH_SWORD_01_B".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let root = textdata(&[
+            row(118, &[(0, "1"), (1, "1"), (3, "SKILL_A")]),
+            row(118, &[(0, "1"), (1, "2"), (3, "SKILL_A")]),
+        ]);
+        let err = Registry::load(&DirectoryVfs::new(root.path()).unwrap()).err().unwrap();
+        assert_eq!(err.to_string(), "duplicate skill SKILL_A");
+    }
+}
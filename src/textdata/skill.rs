@@ -0,0 +1,70 @@
+use std::convert::TryFrom;
+
+use crate::textdata::errors::Error;
+use crate::textdata::table::Row;
+
+/// A skill (`_RefSkill`) from `skilldata_*.txt`, in the column order of vSRO 1.188.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefSkill {
+    pub service: bool,
+    pub id: u32,
+    pub group_id: u32,
+    pub code: String,
+    pub name: Option<String>,
+    pub level: u8,
+    /// Code of the skill following in a chain, e.g. the next hit of a combo.
+    pub chain_code: u32,
+    pub preparing_time: i32,
+    pub casting_time: i32,
+    pub action_duration: i32,
+    pub reuse_delay: i32,
+    pub cool_time: i32,
+    pub flying_speed: i32,
+    pub interruptable: bool,
+    pub overlap: i32,
+    pub auto_attack_type: u8,
+    pub in_town: bool,
+    pub range: i16,
+    /// `ReqLearn_Skill1..3`, zero for unset requirements.
+    pub required_skills: [u32; 3],
+    /// `ReqCast_Weapon1` and `ReqCast_Weapon2`, item classes of weapons the skill needs.
+    pub required_weapons: [u8; 2],
+    pub consume_hp: u32,
+    pub consume_mp: u32,
+    pub icon: Option<String>,
+    /// `Param1..50`, whose meaning depends on the skill's effects.
+    pub params: [i32; 50],
+}
+
+impl TryFrom<&Row<'_>> for RefSkill {
+    type Error = Error;
+
+    fn try_from(row: &Row<'_>) -> Result<Self, Error> {
+        Ok(RefSkill {
+            service: row.flag(0)?,
+            id: row.parse(1)?,
+            group_id: row.parse(2)?,
+            code: row.str(3)?.to_string(),
+            name: row.name(4)?.map(String::from),
+            level: row.parse(7)?,
+            chain_code: row.parse(9)?,
+            preparing_time: row.parse(11)?,
+            casting_time: row.parse(12)?,
+            action_duration: row.parse(13)?,
+            reuse_delay: row.parse(14)?,
+            cool_time: row.parse(15)?,
+            flying_speed: row.parse(16)?,
+            interruptable: row.flag(17)?,
+            overlap: row.parse(18)?,
+            auto_attack_type: row.parse(19)?,
+            in_town: row.flag(20)?,
+            range: row.parse(21)?,
+            required_skills: row.parse_array(40)?,
+            required_weapons: row.parse_array(50)?,
+            consume_hp: row.parse(52)?,
+            consume_mp: row.parse(53)?,
+            icon: row.name(61)?.map(String::from),
+            params: row.parse_array(68)?,
+        })
+    }
+}
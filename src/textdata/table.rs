@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::textdata::errors::Error;
+use crate::textdata::errors::Error::{Encoding, Parse};
+
+/// UTF-16LE byte order mark
+const BOM: [u8; 2] = [0xFF, 0xFE];
+
+/// A text data file: tab separated rows, usually encoded as UTF-16LE.
+pub struct Table {
+    file: PathBuf,
+    content: String,
+}
+
+impl Table {
+    /// Decodes the content of given text data file. Files starting with a byte order mark or looking
+    /// like UTF-16 are decoded as UTF-16LE, all others as UTF-8.
+    pub fn decode(file: &Path, bytes: &[u8]) -> Result<Table, Error> {
+        let content = if bytes.starts_with(&BOM) || (bytes.len() >= 2 && bytes[1] == 0) {
+            let bytes = bytes.strip_prefix(&BOM[..]).unwrap_or(bytes);
+            if !bytes.len().is_multiple_of(2) {
+                return Err(Encoding(file.to_path_buf()));
+            }
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16(&units).map_err(|_| Encoding(file.to_path_buf()))?
+        } else {
+            String::from_utf8(bytes.to_vec()).map_err(|_| Encoding(file.to_path_buf()))?
+        };
+        Ok(Table { file: file.to_path_buf(), content })
+    }
+
+    /// Returns the path of the file.
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Returns the rows of the table, skipping empty lines and `//` comments. Rows whose service
+    /// column is `0` are disabled and skipped as well, unless `disabled` is set.
+    pub fn rows(&self, disabled: bool) -> impl Iterator<Item = Row<'_>> {
+        self.lines()
+            .filter(move |row| disabled || row.columns.first() != Some(&"0"))
+    }
+
+    /// Returns the names listed by an index file, e.g. the part files of a table.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.lines().map(|row| row.columns[0])
+    }
+
+    fn lines(&self) -> impl Iterator<Item = Row<'_>> {
+        self.content.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end_matches(['\r', '\0'])))
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with("//"))
+            .map(move |(line, content)| Row {
+                file: &self.file,
+                line,
+                columns: content.split('\t').map(str::trim).collect(),
+            })
+    }
+}
+
+/// A row of a [Table] whose columns are parsed by index. Errors name the file, line and column.
+pub struct Row<'t> {
+    file: &'t Path,
+    line: usize,
+    columns: Vec<&'t str>,
+}
+
+impl<'t> Row<'t> {
+    /// Returns the line of the row in its file, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the number of columns.
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Returns the text of the column at given index, starting at 0.
+    pub fn str(&self, column: usize) -> Result<&'t str, Error> {
+        self.columns.get(column).copied()
+            .ok_or_else(|| self.error(column, format!("missing column, the row has {}", self.columns.len())))
+    }
+
+    /// Returns the text of the column, or `None` for the `xxx` placeholder the client files use
+    /// for unset names.
+    pub fn name(&self, column: usize) -> Result<Option<&'t str>, Error> {
+        self.str(column).map(|value| Some(value).filter(|v| !v.is_empty() && *v != "xxx"))
+    }
+
+    /// Parses the column as a number. Integers written with a zero fraction, e.g. `50.000000`,
+    /// are accepted as well.
+    pub fn parse<T: FromStr>(&self, column: usize) -> Result<T, Error> {
+        let value = self.str(column)?;
+        value.parse()
+            .or_else(|_| value.parse::<f64>().ok()
+                .filter(|f| f.fract() == 0.0)
+                .and_then(|f| format!("{}", f).parse().ok())
+                .ok_or(()))
+            .map_err(|_| self.error(column, format!("invalid {} '{}'", short_type_name::<T>(), value)))
+    }
+
+    /// Parses the column as a flag, which is `0` or `1`.
+    pub fn flag(&self, column: usize) -> Result<bool, Error> {
+        match self.str(column)? {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            value => Err(self.error(column, format!("invalid flag '{}'", value))),
+        }
+    }
+
+    /// Parses `N` consecutive columns starting with the given one.
+    pub fn parse_array<T: FromStr + Default + Copy, const N: usize>(&self, column: usize) -> Result<[T; N], Error> {
+        let mut values = [T::default(); N];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self.parse(column + i)?;
+        }
+        Ok(values)
+    }
+
+    fn error(&self, column: usize, message: String) -> Error {
+        Parse { file: self.file.to_path_buf(), line: self.line, column: column + 1, message }
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    /// Builds a row with given values at their columns and `0` in all others.
+    pub(crate) fn row(columns: usize, values: &[(usize, &str)]) -> String {
+        let mut row = vec!["0"; columns];
+        values.iter().for_each(|(column, value)| row[*column] = value);
+        row.join("\t")
+    }
+
+    /// Parses a record from a row built by [row].
+    pub(crate) fn parse_row<T>(columns: usize, values: &[(usize, &str)]) -> T
+        where T: for<'a, 'r> TryFrom<&'a Row<'r>, Error = Error> {
+        let table = Table::decode(Path::new("test.txt"), row(columns, values).as_bytes()).unwrap();
+        let row = table.rows(true).next().unwrap();
+        T::try_from(&row).unwrap()
+    }
+
+    /// Encodes a table as UTF-16LE with BOM, like the client's files.
+    pub(crate) fn utf16(text: &str) -> Vec<u8> {
+        let mut bytes = BOM.to_vec();
+        bytes.extend(text.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn decodes_utf16_and_utf8() {
+        let text = "1\t1907\tCHAR_CH_MAN_ADVENTURER\t캐릭터\r\n";
+        for bytes in [utf16(text), utf16(text)[2..].to_vec(), text.as_bytes().to_vec()] {
+            let table = Table::decode(Path::new("characterdata_5000.txt"), &bytes).unwrap();
+            let row = table.rows(false).next().unwrap();
+            assert_eq!(row.parse::<u32>(1).unwrap(), 1907);
+            assert_eq!(row.str(3).unwrap(), "캐릭터");
+        }
+        assert!(Table::decode(Path::new("a.txt"), &[0xFF, 0xFE, 0x00]).is_err());
+        assert!(Table::decode(Path::new("a.txt"), &[b'a', 0xFF]).is_err());
+    }
+
+    #[test]
+    fn skips_comments_and_disabled_rows() {
+        let table = Table::decode(Path::new("a.txt"), &utf16("// header\r\n\r\n1\ta\r\n0\tb\r\n  // indented\r\n1\tc\t\r\n")).unwrap();
+        let rows: Vec<(usize, &str)> = table.rows(false).map(|r| (r.line(), r.str(1).unwrap())).collect();
+        assert_eq!(rows, vec![(3, "a"), (6, "c")]);
+        assert_eq!(table.rows(true).count(), 3);
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        let table = Table::decode(Path::new("itemdata_5000.txt"), b"1\t2\n1\tx\t3.5\t2.000\n").unwrap();
+        let row = table.rows(false).nth(1).unwrap();
+        assert_eq!(row.parse::<u32>(3).unwrap(), 2);
+        assert_eq!(row.str(4).unwrap_err().to_string(), "itemdata_5000.txt:2:5: missing column, the row has 4");
+        assert_eq!(row.parse::<u32>(1).unwrap_err().to_string(), "itemdata_5000.txt:2:2: invalid u32 'x'");
+        assert!(row.parse::<u32>(2).is_err());
+        assert_eq!(row.parse::<f32>(2).unwrap(), 3.5);
+        assert!(row.flag(1).is_err());
+        assert_eq!(row.name(1).unwrap(), Some("x"));
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+
+use crate::vfs::errors::Error;
+
+pub mod archive;
+pub mod directory;
+pub mod errors;
+pub mod overlay;
+
+/// Read-only view of a file system containing the client's data, e.g. a PK2 archive or a folder
+/// it has been extracted to.
+///
+/// Paths are relative to the root of the file system. Their components may be separated by `/` or
+/// `\` and are compared case-insensitively, like the client does. An empty path refers to the root.
+pub trait Vfs: Send + Sync {
+    /// Reads the whole content of the file at given path.
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error>;
+
+    /// Returns the metadata of the file or directory at given path.
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error>;
+
+    /// Lists the files and directories of the directory at given path, sorted by name.
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, Error>;
+
+    /// Returns whether a file or directory exists at given path.
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+}
+
+/// Metadata of a file or directory in a [Vfs].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metadata {
+    pub is_dir: bool,
+    /// Size of the file's content, zero for directories.
+    pub size: u64,
+}
+
+impl Metadata {
+    pub(crate) fn directory() -> Metadata {
+        Metadata { is_dir: true, size: 0 }
+    }
+
+    pub(crate) fn file(size: u64) -> Metadata {
+        Metadata { is_dir: false, size }
+    }
+}
+
+/// A file or directory listed by [Vfs::read_dir].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirEntry {
+    pub name: String,
+    pub metadata: Metadata,
+}
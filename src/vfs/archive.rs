@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use crate::pk2::archive::Archive;
+use crate::pk2::directory::path_components;
+use crate::vfs::{DirEntry, Metadata, Vfs};
+use crate::vfs::errors::Error;
+use crate::vfs::errors::Error::{Archive as ArchiveError, NotADirectory, NotAFile, NotFound};
+
+impl Vfs for Archive {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self.entry(path) {
+            Some(entry) if entry.is_file() => self.read_entry(entry).map_err(ArchiveError),
+            Some(_) => Err(NotAFile(path.to_path_buf())),
+            None => Err(NotFound(path.to_path_buf())),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        if path_components(path).is_empty() {
+            return Ok(Metadata::directory());
+        }
+        match self.entry(path) {
+            Some(entry) if entry.is_dir() => Ok(Metadata::directory()),
+            Some(entry) => Ok(Metadata::file(entry.size as u64)),
+            None => Err(NotFound(path.to_path_buf())),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, Error> {
+        let dir = match self.root.find_directory(path) {
+            Some(dir) => dir,
+            None if self.entry(path).is_some() => return Err(NotADirectory(path.to_path_buf())),
+            None => return Err(NotFound(path.to_path_buf())),
+        };
+        let mut entries: Vec<DirEntry> = dir.entries.values()
+            .map(|entry| DirEntry {
+                name: entry.path_buf().to_string_lossy().into_owned(),
+                metadata: if entry.is_dir() { Metadata::directory() } else { Metadata::file(entry.size as u64) },
+            })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::pk2::constants::{BLOCK_SIZE, KEY_BYTES};
+    use crate::pk2::fixture::{Fixture, raw_entry};
+
+    use super::*;
+
+    /// Returns an archive with `readme.txt` and the directory `Media` containing `a.txt`.
+    pub(crate) fn archive() -> Archive {
+        let mut fixture = Fixture::new();
+        let root = fixture.offset();
+        let (media, data) = (root + BLOCK_SIZE as u64, root + 2 * BLOCK_SIZE as u64);
+        fixture.push_block(&[
+            raw_entry(1, b".", root, 0, 0),
+            raw_entry(2, b"readme.txt", data, 6, 0),
+            raw_entry(1, b"Media", media, 0, 0),
+        ]);
+        fixture.push_block(&[
+            raw_entry(1, b".", media, 0, 0),
+            raw_entry(1, b"..", root, 0, 0),
+            raw_entry(2, b"a.txt", data + 6, 1, 0),
+        ]);
+        fixture.push_data(b"readmea");
+        Archive::with_key(fixture.file(), KEY_BYTES).unwrap()
+    }
+
+    #[test]
+    fn reads_archives() {
+        let archive = archive();
+        // the archive's own methods take precedence over the ones of the trait
+        let archive: &dyn Vfs = &archive;
+        assert_eq!(archive.read(Path::new("MEDIA\\A.TXT")).unwrap(), b"a");
+        assert!(matches!(archive.read(Path::new("Media")), Err(NotAFile(_))));
+        assert!(matches!(archive.read(Path::new("b.txt")), Err(NotFound(_))));
+        assert_eq!(archive.metadata(Path::new("")).unwrap(), Metadata::directory());
+        assert_eq!(archive.metadata(Path::new("media")).unwrap(), Metadata::directory());
+        assert_eq!(archive.metadata(Path::new("readme.txt")).unwrap(), Metadata::file(6));
+        assert!(archive.exists(Path::new("/Media/a.txt")));
+        assert!(!archive.exists(Path::new("Media/b.txt")));
+    }
+
+    #[test]
+    fn lists_directories() {
+        let archive = archive();
+        let archive: &dyn Vfs = &archive;
+        assert_eq!(archive.read_dir(Path::new("")).unwrap(), vec![
+            DirEntry { name: "Media".to_string(), metadata: Metadata::directory() },
+            DirEntry { name: "readme.txt".to_string(), metadata: Metadata::file(6) },
+        ]);
+        assert_eq!(archive.read_dir(Path::new("media")).unwrap(), vec![
+            DirEntry { name: "a.txt".to_string(), metadata: Metadata::file(1) },
+        ]);
+        assert!(matches!(archive.read_dir(Path::new("readme.txt")), Err(NotADirectory(_))));
+        assert!(matches!(archive.read_dir(Path::new("missing")), Err(NotFound(_))));
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs::{read, read_dir};
+use std::path::{Path, PathBuf};
+
+use crate::pk2::directory::path_components;
+use crate::vfs::{DirEntry, Metadata, Vfs};
+use crate::vfs::errors::Error;
+use crate::vfs::errors::Error::{IO, NotADirectory, NotAFile, NotFound};
+
+/// A [Vfs] backed by a plain directory, e.g. the extracted contents of a PK2 archive.
+pub struct DirectoryVfs {
+    root: PathBuf,
+}
+
+impl DirectoryVfs {
+    /// Creates a file system with given directory as its root.
+    pub fn new(root: &Path) -> Result<DirectoryVfs, Error> {
+        if !root.is_dir() {
+            return Err(NotADirectory(root.to_path_buf()));
+        }
+        Ok(DirectoryVfs { root: root.to_path_buf() })
+    }
+
+    /// Returns the root directory of the file system.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves a path of the file system to a path on disk. Components which don't exist with
+    /// the exact case are looked up case-insensitively. Paths leaving the root aren't resolved.
+    fn resolve(&self, path: &Path) -> Result<PathBuf, Error> {
+        let not_found = || NotFound(path.to_path_buf());
+        path_components(path).iter()
+            .try_fold(self.root.clone(), |resolved, component| {
+                if component == "." || component == ".." {
+                    return Err(not_found());
+                }
+                let exact = resolved.join(component);
+                if exact.symlink_metadata().is_ok() {
+                    return Ok(exact);
+                }
+                read_dir(&resolved).map_err(|_| not_found())?
+                    .filter_map(|entry| entry.ok())
+                    .find(|entry| entry.file_name().to_string_lossy().eq_ignore_ascii_case(component))
+                    .map(|entry| entry.path())
+                    .ok_or_else(not_found)
+            })
+    }
+}
+
+impl Vfs for DirectoryVfs {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let resolved = self.resolve(path)?;
+        if resolved.is_dir() {
+            return Err(NotAFile(path.to_path_buf()));
+        }
+        read(resolved).map_err(IO)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        let metadata = self.resolve(path)?.metadata().map_err(IO)?;
+        Ok(if metadata.is_dir() { Metadata::directory() } else { Metadata::file(metadata.len()) })
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, Error> {
+        let resolved = self.resolve(path)?;
+        if !resolved.is_dir() {
+            return Err(NotADirectory(path.to_path_buf()));
+        }
+        let mut entries = read_dir(resolved).map_err(IO)?
+            .map(|entry| {
+                let entry = entry.map_err(IO)?;
+                let metadata = entry.metadata().map_err(IO)?;
+                Ok(DirEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    metadata: if metadata.is_dir() { Metadata::directory() } else { Metadata::file(metadata.len()) },
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Returns a directory with `Media/a.txt`, `Media/b.txt` and `Media/Sub/c.txt`.
+    pub(crate) fn directory() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        create_dir_all(dir.path().join("Media/Sub")).unwrap();
+        write(dir.path().join("Media/a.txt"), b"edited").unwrap();
+        write(dir.path().join("Media/b.txt"), b"b").unwrap();
+        write(dir.path().join("Media/Sub/c.txt"), b"c").unwrap();
+        dir
+    }
+
+    #[test]
+    fn reads_files_ignoring_case() {
+        let dir = directory();
+        let vfs = DirectoryVfs::new(dir.path()).unwrap();
+        assert_eq!(vfs.read(Path::new("media\\SUB/C.txt")).unwrap(), b"c");
+        assert_eq!(vfs.metadata(Path::new("Media/a.txt")).unwrap(), Metadata::file(6));
+        assert_eq!(vfs.metadata(Path::new("")).unwrap(), Metadata::directory());
+        assert!(matches!(vfs.read(Path::new("Media")), Err(NotAFile(_))));
+        assert!(matches!(vfs.read(Path::new("Media/d.txt")), Err(NotFound(_))));
+        assert!(matches!(DirectoryVfs::new(&dir.path().join("Media/a.txt")), Err(NotADirectory(_))));
+    }
+
+    #[test]
+    fn stays_within_its_root() {
+        let dir = directory();
+        let vfs = DirectoryVfs::new(&dir.path().join("Media/Sub")).unwrap();
+        assert!(matches!(vfs.read(Path::new("../a.txt")), Err(NotFound(_))));
+        assert!(matches!(vfs.metadata(Path::new("./c.txt")), Err(NotFound(_))));
+        assert!(vfs.exists(Path::new("c.txt")));
+    }
+
+    #[test]
+    fn lists_directories() {
+        let dir = directory();
+        let vfs = DirectoryVfs::new(dir.path()).unwrap();
+        assert_eq!(vfs.read_dir(Path::new("MEDIA")).unwrap(), vec![
+            DirEntry { name: "Sub".to_string(), metadata: Metadata::directory() },
+            DirEntry { name: "a.txt".to_string(), metadata: Metadata::file(6) },
+            DirEntry { name: "b.txt".to_string(), metadata: Metadata::file(1) },
+        ]);
+        assert!(matches!(vfs.read_dir(Path::new("Media/a.txt")), Err(NotADirectory(_))));
+    }
+}
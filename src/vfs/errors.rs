@@ -0,0 +1,27 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+
+use crate::pk2;
+
+#[derive(std::fmt::Debug)]
+pub enum Error {
+    NotFound(PathBuf),
+    NotAFile(PathBuf),
+    NotADirectory(PathBuf),
+    IO(io::Error),
+    Archive(pk2::errors::Error),
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "{:?} not found", path),
+            Error::NotAFile(path) => write!(f, "{:?} is not a file", path),
+            Error::NotADirectory(path) => write!(f, "{:?} is not a directory", path),
+            Error::IO(err) => write!(f, "{}", err),
+            Error::Archive(err) => write!(f, "{}", err),
+        }
+    }
+}
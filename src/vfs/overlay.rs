@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::vfs::{DirEntry, Metadata, Vfs};
+use crate::vfs::errors::Error;
+use crate::vfs::errors::Error::NotFound;
+
+/// A [Vfs] layering file systems on top of each other, e.g. a directory of edited files on top of
+/// a PK2 archive. Files of upper layers shadow the ones with the same path in lower layers.
+pub struct Overlay {
+    /// The layers from top to bottom.
+    layers: Vec<Box<dyn Vfs>>,
+}
+
+impl Overlay {
+    /// Creates an overlay with given file system as its bottom layer.
+    pub fn new(base: impl Vfs + 'static) -> Overlay {
+        Overlay { layers: vec![Box::new(base)] }
+    }
+
+    /// Adds a layer on top of the existing ones.
+    pub fn layer(mut self, layer: impl Vfs + 'static) -> Overlay {
+        self.layers.insert(0, Box::new(layer));
+        self
+    }
+
+    /// Returns the result of the topmost layer which contains given path.
+    fn first<T>(&self, path: &Path, f: impl Fn(&dyn Vfs) -> Result<T, Error>) -> Result<T, Error> {
+        for layer in self.layers.iter() {
+            match f(layer.as_ref()) {
+                Err(NotFound(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(NotFound(path.to_path_buf()))
+    }
+}
+
+impl Vfs for Overlay {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        self.first(path, |layer| layer.read(path))
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        self.first(path, |layer| layer.metadata(path))
+    }
+
+    /// Merges the entries of all layers containing the directory. Names are compared
+    /// case-insensitively and entries of upper layers take precedence.
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, Error> {
+        let mut merged = BTreeMap::new();
+        let mut found = false;
+        for layer in self.layers.iter().rev() {
+            match layer.read_dir(path) {
+                Ok(entries) => {
+                    found = true;
+                    merged.extend(entries.into_iter().map(|entry| (entry.name.to_lowercase(), entry)));
+                }
+                Err(NotFound(_)) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        if !found {
+            return Err(NotFound(path.to_path_buf()));
+        }
+        let mut entries: Vec<DirEntry> = merged.into_values().collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vfs::archive::tests::archive;
+    use crate::vfs::directory::DirectoryVfs;
+    use crate::vfs::directory::tests::directory;
+    use crate::vfs::errors::Error::NotAFile;
+
+    use super::*;
+
+    #[test]
+    fn shadows_lower_layers() {
+        let dir = directory();
+        let overlay = Overlay::new(archive()).layer(DirectoryVfs::new(dir.path()).unwrap());
+        assert_eq!(overlay.read(Path::new("media/A.TXT")).unwrap(), b"edited");
+        assert_eq!(overlay.read(Path::new("readme.txt")).unwrap(), b"readme");
+        assert_eq!(overlay.metadata(Path::new("Media/a.txt")).unwrap(), Metadata::file(6));
+        assert!(matches!(overlay.read(Path::new("Media")), Err(NotAFile(_))));
+        assert!(matches!(overlay.read(Path::new("missing.txt")), Err(NotFound(_))));
+    }
+
+    #[test]
+    fn merges_directories() {
+        let dir = directory();
+        let overlay = Overlay::new(archive()).layer(DirectoryVfs::new(dir.path()).unwrap());
+        assert_eq!(overlay.read_dir(Path::new("media")).unwrap(), vec![
+            DirEntry { name: "Sub".to_string(), metadata: Metadata::directory() },
+            DirEntry { name: "a.txt".to_string(), metadata: Metadata::file(6) },
+            DirEntry { name: "b.txt".to_string(), metadata: Metadata::file(1) },
+        ]);
+        let names: Vec<String> = overlay.read_dir(Path::new("")).unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["Media", "readme.txt"]);
+        assert!(matches!(overlay.read_dir(Path::new("missing")), Err(NotFound(_))));
+    }
+}
@@ -0,0 +1,12 @@
+pub mod errors;
+pub mod ground;
+pub mod interest;
+pub mod movement;
+pub mod navigation;
+pub mod npc;
+pub mod pathfinding;
+pub mod player;
+pub mod position;
+pub mod state;
+
+pub use position::Position;
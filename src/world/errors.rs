@@ -0,0 +1,27 @@
+use std::fmt::{Display, Formatter};
+
+use crate::world::position::Position;
+
+#[derive(std::fmt::Debug, PartialEq)]
+pub enum Error {
+    /// The position doesn't lie within a cell of the navigation mesh.
+    NotWalkable(Position),
+    /// No path connects both positions.
+    Unreachable,
+    /// The search visited given number of cells without reaching the destination.
+    BudgetExceeded(usize),
+    /// The blocking search task was cancelled.
+    Cancelled,
+}
+
+impl std::error::Error for Error {}
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotWalkable(position) => write!(f, "{} isn't walkable", position),
+            Error::Unreachable => write!(f, "destination is unreachable"),
+            Error::BudgetExceeded(cells) => write!(f, "no path found within {} cells", cells),
+            Error::Cancelled => write!(f, "path search was cancelled"),
+        }
+    }
+}
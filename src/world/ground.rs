@@ -0,0 +1,11 @@
+use crate::character::record::Item;
+use crate::world::position::Position;
+
+/// An item lying on the ground, which any player nearby can pick up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundItem {
+    /// ID of the item's entity, which clients use to pick it up.
+    pub unique_id: u32,
+    pub item: Item,
+    pub position: Position,
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::world::position::RegionId;
+
+/// Spatial index of the entities in the world by region, deciding which entities see each other.
+///
+/// An entity sees all entities in its own and the eight neighbouring regions (see [in_sight]).
+/// Dungeons only see themselves. Visibility is symmetric, so every change is reported from the
+/// view of the moved entity.
+#[derive(Default)]
+pub struct InterestGrid {
+    regions: HashMap<RegionId, BTreeSet<u32>>,
+    entities: HashMap<u32, RegionId>,
+}
+
+/// Entities which came into or went out of sight of an entity after it moved to another region.
+#[derive(Debug, Default, PartialEq)]
+pub struct SightChange {
+    pub entered: Vec<u32>,
+    pub left: Vec<u32>,
+}
+
+impl InterestGrid {
+    pub fn new() -> InterestGrid {
+        InterestGrid::default()
+    }
+
+    /// Adds an entity to given region and returns the entities it sees, i.e. which see it.
+    pub fn insert(&mut self, entity: u32, region: RegionId) -> Vec<u32> {
+        if self.entities.contains_key(&entity) {
+            self.remove(entity);
+        }
+        let visible = self.visible_from(region, entity);
+        self.entities.insert(entity, region);
+        self.regions.entry(region).or_default().insert(entity);
+        visible
+    }
+
+    /// Removes an entity and returns the entities which saw it.
+    pub fn remove(&mut self, entity: u32) -> Vec<u32> {
+        let region = match self.entities.remove(&entity) {
+            Some(region) => region,
+            None => return Vec::new(),
+        };
+        if let Some(entities) = self.regions.get_mut(&region) {
+            entities.remove(&entity);
+            if entities.is_empty() {
+                self.regions.remove(&region);
+            }
+        }
+        self.visible_from(region, entity)
+    }
+
+    /// Moves an entity to given region, adding it if it isn't known yet.
+    pub fn update(&mut self, entity: u32, region: RegionId) -> SightChange {
+        let previous = match self.entities.get(&entity) {
+            Some(previous) if *previous == region => return SightChange::default(),
+            Some(previous) => *previous,
+            None => return SightChange { entered: self.insert(entity, region), left: Vec::new() },
+        };
+        let before = self.visible_from(previous, entity);
+        self.insert(entity, region);
+        let after = self.visible_from(region, entity);
+        SightChange {
+            entered: after.iter().filter(|e| !before.contains(e)).copied().collect(),
+            left: before.iter().filter(|e| !after.contains(e)).copied().collect(),
+        }
+    }
+
+    /// Returns the region of an entity.
+    pub fn region(&self, entity: u32) -> Option<RegionId> {
+        self.entities.get(&entity).copied()
+    }
+
+    /// Returns all entities seen from given region.
+    pub fn in_sight_of(&self, region: RegionId) -> Vec<u32> {
+        sight_regions(region).iter()
+            .filter_map(|region| self.regions.get(region))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn visible_from(&self, region: RegionId, entity: u32) -> Vec<u32> {
+        let mut visible = self.in_sight_of(region);
+        visible.retain(|e| *e != entity);
+        visible
+    }
+}
+
+/// Returns whether entities in both regions see each other.
+pub fn in_sight(a: RegionId, b: RegionId) -> bool {
+    if a.is_dungeon() || b.is_dungeon() {
+        return a == b;
+    }
+    (a.x() as i32 - b.x() as i32).abs() <= 1 && (a.y() as i32 - b.y() as i32).abs() <= 1
+}
+
+/// Returns the regions seen from given region.
+fn sight_regions(region: RegionId) -> Vec<RegionId> {
+    if region.is_dungeon() {
+        return vec![region];
+    }
+    (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter_map(|(dx, dy)| region.offset(dx, dy))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JANGAN: RegionId = RegionId(0x61A8);
+
+    #[test]
+    fn sees_neighbouring_regions() {
+        let mut grid = InterestGrid::new();
+        assert!(grid.insert(1, JANGAN).is_empty());
+        assert_eq!(grid.insert(2, RegionId(0x62A9)), vec![1]);
+        assert!(grid.insert(3, RegionId(0x61AB)).is_empty());
+        assert!(grid.insert(4, RegionId::dungeon(1)).is_empty());
+        assert_eq!(grid.in_sight_of(JANGAN), vec![1, 2]);
+        assert_eq!(grid.in_sight_of(RegionId::dungeon(1)), vec![4]);
+        assert!(in_sight(JANGAN, RegionId(0x60A7)));
+        assert!(!in_sight(JANGAN, RegionId(0x5FA8)));
+    }
+
+    #[test]
+    fn reports_sight_changes() {
+        let mut grid = InterestGrid::new();
+        grid.insert(1, JANGAN);
+        grid.insert(2, RegionId(0x61AA));
+        assert_eq!(grid.update(3, RegionId(0x61A7)), SightChange { entered: vec![1], left: vec![] });
+        assert_eq!(grid.update(1, RegionId(0x61A9)), SightChange { entered: vec![2], left: vec![3] });
+        assert_eq!(grid.update(1, RegionId(0x61A9)), SightChange::default());
+        assert_eq!(grid.region(1), Some(RegionId(0x61A9)));
+        assert_eq!(grid.remove(1), vec![2]);
+        assert_eq!(grid.remove(1), Vec::<u32>::new());
+        assert_eq!(grid.len(), 2);
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use crate::world::position::Position;
+
+/// Speed of a character walking, in local units per second.
+pub const WALK_SPEED: f32 = 16.0;
+/// Speed of a character running, in local units per second.
+pub const RUN_SPEED: f32 = 50.0;
+
+/// Where a client wants its character to move.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveTarget {
+    /// Walk to a position, e.g. after clicking on the ground.
+    Destination(Position),
+    /// Keep walking in a direction, e.g. using the keyboard. The angle is a fraction of a full
+    /// turn, counter-clockwise starting at the x axis.
+    Direction(u16),
+}
+
+/// A character moving on a straight line at constant speed, as validated by the server.
+///
+/// The server always derives the current position from the movement itself, so clients can't
+/// move faster than their speed allows, no matter what they send.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Movement {
+    pub source: Position,
+    pub destination: Position,
+    /// Speed in local units per second.
+    pub speed: f32,
+    pub started: Instant,
+}
+
+impl Movement {
+    /// Creates a movement which stands still at given position.
+    pub fn standing(position: Position) -> Movement {
+        Movement { source: position, destination: position, speed: 0.0, started: Instant::now() }
+    }
+
+    pub fn new(source: Position, destination: Position, speed: f32, started: Instant) -> Movement {
+        Movement { source, destination, speed, started }
+    }
+
+    /// Returns the distance between source and destination in local units.
+    pub fn length(&self) -> f32 {
+        self.source.distance(&self.destination).map_or(0.0, |d| d * 10.0)
+    }
+
+    /// Returns how long the whole movement takes.
+    pub fn duration(&self) -> Duration {
+        if self.speed <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f32(self.length() / self.speed)
+    }
+
+    /// Returns whether the character is still moving at given time.
+    pub fn is_moving(&self, now: Instant) -> bool {
+        now < self.started + self.duration()
+    }
+
+    /// Returns the position of the character at given time.
+    pub fn position_at(&self, now: Instant) -> Position {
+        let length = self.length();
+        if length == 0.0 || !self.is_moving(now) {
+            return self.destination;
+        }
+        let travelled = now.saturating_duration_since(self.started).as_secs_f32() * self.speed;
+        let t = (travelled / length).min(1.0);
+        let ((x1, z1), (x2, z2)) = (self.source.world(), self.destination.world());
+        let (x, z) = (x1 + (x2 - x1) * t, z1 + (z2 - z1) * t);
+        let y = self.source.y + (self.destination.y - self.source.y) * t;
+        if self.source.region.is_dungeon() {
+            Position::from_dungeon(self.source.region, x, z, y)
+        } else {
+            Position::from_world(x, z, y)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::position::RegionId;
+
+    use super::*;
+
+    #[test]
+    fn interpolates_across_regions() {
+        let started = Instant::now();
+        let source = Position::new(RegionId(0x61A8), 1900.0, 0.0, 100.0);
+        let destination = Position::new(RegionId(0x61A9), 80.0, 10.0, 100.0);
+        let movement = Movement::new(source, destination, RUN_SPEED, started);
+        assert!((movement.length() - 100.0).abs() < 0.01);
+        assert_eq!(movement.duration(), Duration::from_secs(2));
+
+        let halfway = movement.position_at(started + Duration::from_secs(1));
+        assert_eq!(halfway.region, RegionId(0x61A9));
+        assert!((halfway.x - 30.0).abs() < 0.01 && (halfway.y - 5.0).abs() < 0.01);
+        assert!(movement.is_moving(started + Duration::from_secs(1)));
+        assert!(!movement.is_moving(started + Duration::from_secs(3)));
+        assert_eq!(movement.position_at(started + Duration::from_secs(3)), destination);
+    }
+
+    #[test]
+    fn stands_still() {
+        let position = Position::new(RegionId(0x61A8), 10.0, 0.0, 10.0);
+        let movement = Movement::standing(position);
+        assert!(!movement.is_moving(Instant::now()));
+        assert_eq!(movement.position_at(Instant::now() + Duration::from_secs(5)), position);
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use crate::navmesh::errors::Error;
+use crate::navmesh::geometry::Vec2;
+use crate::navmesh::loader::Loader;
+use crate::navmesh::nvm::{Edge, RegionMesh, EDGE_BLOCKED_DST_TO_SRC, EDGE_BLOCKED_SRC_TO_DST};
+use crate::world::position::{Position, RegionId};
+
+/// Distance between the points sampled along a movement, in local units.
+const SAMPLE_DISTANCE: f32 = 5.0;
+
+/// Terrain navigation meshes of the field regions, used to check where a character can walk.
+///
+/// Positions are walkable if they lie within a cell of a loaded region. Movements are blocked by
+/// blocked edges between cells, including the global edges between regions. Collisions with
+/// objects and dungeon terrain aren't checked yet, so dungeon positions are always walkable.
+/// An empty map, e.g. if no `Data.pk2` is configured, doesn't block anything.
+#[derive(Default)]
+pub struct NavMap {
+    regions: HashMap<RegionId, RegionNav>,
+}
+
+struct RegionNav {
+    mesh: RegionMesh,
+    /// Edges bordering each cell, by index in [RegionMesh::cells].
+    cell_edges: Vec<Vec<EdgeRef>>,
+}
+
+#[derive(Clone, Copy)]
+enum EdgeRef {
+    Internal(usize),
+    Global(usize),
+}
+
+/// A cell of a region's navigation mesh.
+pub(crate) type CellId = (RegionId, usize);
+
+/// A point on the ground plane in local units across all regions (see [local_coordinates]).
+pub(crate) type Point = (f32, f32);
+
+impl NavMap {
+    pub fn new() -> NavMap {
+        NavMap::default()
+    }
+
+    /// Loads the navigation meshes of all regions enabled in the map info (see [Loader::regions]).
+    pub fn load(loader: &Loader) -> Result<NavMap, Error> {
+        let mut map = NavMap::new();
+        for (region, mesh) in loader.regions()? {
+            map.insert(region, mesh);
+        }
+        Ok(map)
+    }
+
+    /// Adds or replaces the navigation mesh of a region.
+    pub fn insert(&mut self, region: RegionId, mesh: RegionMesh) {
+        let mut cell_edges = vec![Vec::new(); mesh.cells.len()];
+        let mut add = |cell: u16, edge: EdgeRef| {
+            if let Some(edges) = cell_edges.get_mut(cell as usize) {
+                edges.push(edge);
+            }
+        };
+        for (i, edge) in mesh.internal_edges.iter().enumerate() {
+            edge.cells.iter().for_each(|cell| add(*cell, EdgeRef::Internal(i)));
+        }
+        for (i, global) in mesh.global_edges.iter().enumerate() {
+            global.edge.cells.iter().zip(global.regions.iter())
+                .filter(|(_, r)| **r == region.0)
+                .for_each(|(cell, _)| add(*cell, EdgeRef::Global(i)));
+        }
+        self.regions.insert(region, RegionNav { mesh, cell_edges });
+    }
+
+    /// Returns the number of loaded regions.
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Returns the navigation mesh of a region.
+    pub fn mesh(&self, region: RegionId) -> Option<&RegionMesh> {
+        self.regions.get(&region).map(|nav| &nav.mesh)
+    }
+
+    /// Returns whether a character can stand at given position.
+    pub fn is_walkable(&self, position: &Position) -> bool {
+        self.unchecked(position) || self.cell_at(position).is_some()
+    }
+
+    /// Returns the terrain height at given position.
+    pub fn height_at(&self, position: &Position) -> Option<f32> {
+        self.mesh(position.region)?.height_at(position.x, position.z)
+    }
+
+    /// Returns the furthest position a character can walk to on the straight line from `from`
+    /// to `to`, stopping before unwalkable positions and blocked edges. The height of the result
+    /// is taken from the terrain where available.
+    pub fn raycast(&self, from: &Position, to: &Position) -> Position {
+        if !from.same_space(to) {
+            return *from;
+        }
+        if self.unchecked(from) {
+            return *to;
+        }
+        let ((x1, z1), (x2, z2)) = (local_coordinates(from), local_coordinates(to));
+        let distance = ((x2 - x1).powi(2) + (z2 - z1).powi(2)).sqrt();
+        let samples = (distance / SAMPLE_DISTANCE).ceil().max(1.0) as usize;
+
+        let mut previous = *from;
+        let mut previous_cell = self.cell_at(from);
+        for i in 1..=samples {
+            let t = i as f32 / samples as f32;
+            let (x, z) = (x1 + (x2 - x1) * t, z1 + (z2 - z1) * t);
+            let sample = if i == samples { to.normalized() } else { from_local_coordinates(x, z, to.y) };
+            let cell = self.cell_at(&sample);
+            if cell != previous_cell {
+                match (previous_cell, cell) {
+                    (_, None) => return self.with_height(previous),
+                    (Some(left), Some(_)) if self.is_blocked(left, &previous, &sample) => return self.with_height(previous),
+                    _ => {}
+                }
+            }
+            previous = sample;
+            previous_cell = cell;
+        }
+        self.with_height(previous)
+    }
+
+    /// Returns the cells reachable from given cell through an edge, together with the end points
+    /// of that edge.
+    pub(crate) fn neighbours(&self, (region, cell): CellId) -> Vec<(CellId, Point, Point)> {
+        let nav = match self.regions.get(&region) {
+            Some(nav) => nav,
+            None => return Vec::new(),
+        };
+        let origin = local_coordinates(&Position::new(region, 0.0, 0.0, 0.0));
+        let at = |v: Vec2| (origin.0 + v.x, origin.1 + v.z);
+        nav.cell_edges[cell].iter()
+            .filter_map(|edge| {
+                let (edge, regions) = match edge {
+                    EdgeRef::Internal(i) => (&nav.mesh.internal_edges[*i], [region.0, region.0]),
+                    EdgeRef::Global(i) => (&nav.mesh.global_edges[*i].edge, nav.mesh.global_edges[*i].regions),
+                };
+                let side = if edge.cells[0] as usize == cell && regions[0] == region.0 { 0 } else { 1 };
+                let blocked = if side == 0 { EDGE_BLOCKED_SRC_TO_DST } else { EDGE_BLOCKED_DST_TO_SRC };
+                if edge.flag & blocked != 0 {
+                    return None;
+                }
+                let other = (RegionId(regions[1 - side]), edge.cells[1 - side] as usize);
+                let exists = self.regions.get(&other.0).is_some_and(|nav| other.1 < nav.mesh.cells.len());
+                Some((other, at(edge.start), at(edge.end))).filter(|_| exists && other != (region, cell))
+            })
+            .collect()
+    }
+
+    /// Returns the center of a cell.
+    pub(crate) fn cell_center(&self, (region, cell): CellId) -> Point {
+        let rect = self.regions[&region].mesh.cells[cell].rect;
+        let origin = local_coordinates(&Position::new(region, 0.0, 0.0, 0.0));
+        (origin.0 + (rect.min.x + rect.max.x) / 2.0, origin.1 + (rect.min.z + rect.max.z) / 2.0)
+    }
+
+    /// Returns whether the position lies where movement isn't checked, see [NavMap].
+    pub(crate) fn unchecked(&self, position: &Position) -> bool {
+        self.regions.is_empty() || position.region.is_dungeon()
+    }
+
+    pub(crate) fn cell_at(&self, position: &Position) -> Option<CellId> {
+        let nav = self.regions.get(&position.region)?;
+        let tile = nav.mesh.tile_at(position.x, position.z)?;
+        Some((position.region, tile.cell as usize))
+    }
+
+    /// Returns whether a blocked edge of the cell crosses the movement between two positions.
+    fn is_blocked(&self, (region, cell): CellId, from: &Position, to: &Position) -> bool {
+        let nav = match self.regions.get(&region) {
+            Some(nav) => nav,
+            None => return false,
+        };
+        let origin = local_coordinates(&Position::new(region, 0.0, 0.0, 0.0));
+        let (a, b) = (local_coordinates(from), local_coordinates(to));
+        nav.cell_edges[cell].iter()
+            .map(|edge| match edge {
+                EdgeRef::Internal(i) => &nav.mesh.internal_edges[*i],
+                EdgeRef::Global(i) => &nav.mesh.global_edges[*i].edge,
+            })
+            .filter(|edge| edge.is_blocked())
+            .any(|edge| crosses(edge, origin, a, b))
+    }
+
+    pub(crate) fn with_height(&self, position: Position) -> Position {
+        match self.height_at(&position) {
+            Some(height) => Position { y: height, ..position },
+            None => position,
+        }
+    }
+}
+
+/// Returns the coordinates of a field position in local units across all regions.
+pub(crate) fn local_coordinates(position: &Position) -> Point {
+    let (x, z) = position.world();
+    (x * 10.0, z * 10.0)
+}
+
+pub(crate) fn from_local_coordinates(x: f32, z: f32, height: f32) -> Position {
+    Position::from_world(x / 10.0, z / 10.0, height)
+}
+
+/// Returns whether the segment from `a` to `b` touches the edge, whose coordinates are relative
+/// to the region at `origin`.
+fn crosses(edge: &Edge, origin: (f32, f32), a: (f32, f32), b: (f32, f32)) -> bool {
+    let at = |v: Vec2| (origin.0 + v.x, origin.1 + v.z);
+    let (c, d) = (at(edge.start), at(edge.end));
+    let orientation = |p: (f32, f32), q: (f32, f32), r: (f32, f32)| {
+        let value = (q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0);
+        if value.abs() < f32::EPSILON { 0 } else { value.signum() as i8 }
+    };
+    let on_segment = |p: (f32, f32), q: (f32, f32), r: (f32, f32)| {
+        r.0 >= p.0.min(q.0) && r.0 <= p.0.max(q.0) && r.1 >= p.1.min(q.1) && r.1 <= p.1.max(q.1)
+    };
+    let (o1, o2, o3, o4) = (orientation(a, b, c), orientation(a, b, d), orientation(c, d, a), orientation(c, d, b));
+    (o1 != o2 && o3 != o4)
+        || (o1 == 0 && on_segment(a, b, c))
+        || (o2 == 0 && on_segment(a, b, d))
+        || (o3 == 0 && on_segment(c, d, a))
+        || (o4 == 0 && on_segment(c, d, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::navmesh::geometry::Rect;
+    use crate::navmesh::nvm::{Cell, Tile, EDGE_BLOCKED_SRC_TO_DST, HEIGHTS_PER_SIDE, TILES_PER_SIDE};
+
+    use super::*;
+
+    const REGION: RegionId = RegionId(0x61A8);
+
+    /// Builds a region whose west and east halves are separate cells, divided by an edge with
+    /// given flag.
+    fn mesh(edge_flag: u8) -> RegionMesh {
+        let cell = |min_x: f32, max_x: f32| Cell { rect: Rect { min: Vec2::new(min_x, 0.0), max: Vec2::new(max_x, 1920.0) }, objects: Vec::new() };
+        let tiles = (0..TILES_PER_SIDE * TILES_PER_SIDE)
+            .map(|i| Tile { cell: if i % TILES_PER_SIDE < TILES_PER_SIDE / 2 { 0 } else { 1 }, flag: 0, texture: 0 })
+            .collect();
+        RegionMesh {
+            objects: Vec::new(),
+            cells: vec![cell(0.0, 960.0), cell(960.0, 1920.0)],
+            open_cells: 2,
+            global_edges: Vec::new(),
+            internal_edges: vec![Edge {
+                start: Vec2::new(960.0, 0.0),
+                end: Vec2::new(960.0, 1920.0),
+                flag: edge_flag,
+                directions: [0, 0],
+                cells: [0, 1],
+            }],
+            tiles,
+            heights: vec![5.0; HEIGHTS_PER_SIDE * HEIGHTS_PER_SIDE],
+        }
+    }
+
+    fn map(edge_flag: u8) -> NavMap {
+        let mut map = NavMap::new();
+        map.insert(REGION, mesh(edge_flag));
+        map
+    }
+
+    #[test]
+    fn walks_through_open_edges() {
+        let map = map(0);
+        let to = Position::new(REGION, 1500.0, 0.0, 100.0);
+        assert_eq!(map.raycast(&Position::new(REGION, 100.0, 0.0, 100.0), &to), Position { y: 5.0, ..to });
+    }
+
+    #[test]
+    fn stops_at_blocked_edges() {
+        let map = map(EDGE_BLOCKED_SRC_TO_DST);
+        let stop = map.raycast(&Position::new(REGION, 100.0, 0.0, 100.0), &Position::new(REGION, 1500.0, 0.0, 100.0));
+        assert!(stop.x < 960.0 && stop.x > 950.0);
+        assert_eq!(stop.y, 5.0);
+        let inside = Position::new(REGION, 500.0, 5.0, 1000.0);
+        assert_eq!(map.raycast(&Position::new(REGION, 100.0, 0.0, 100.0), &inside), inside);
+    }
+
+    #[test]
+    fn stops_before_unloaded_regions() {
+        let map = map(0);
+        assert!(map.is_walkable(&Position::new(REGION, 0.0, 0.0, 0.0)));
+        assert!(!map.is_walkable(&Position::new(RegionId(0x61A9), 0.0, 0.0, 0.0)));
+        let stop = map.raycast(&Position::new(REGION, 1800.0, 0.0, 100.0), &Position::new(RegionId(0x61A9), 100.0, 0.0, 100.0));
+        assert_eq!(stop.region, REGION);
+        assert!(stop.x > 1910.0);
+    }
+
+    #[test]
+    fn leaves_dungeons_and_empty_maps_unchecked() {
+        let (from, to) = (Position::new(REGION, 100.0, 0.0, 100.0), Position::new(RegionId(0x61A9), 100.0, 0.0, 100.0));
+        assert_eq!(NavMap::new().raycast(&from, &to), to);
+        let dungeon = RegionId::dungeon(1);
+        let to = Position::new(dungeon, 5000.0, 0.0, 5000.0);
+        assert_eq!(map(0).raycast(&Position::new(dungeon, 0.0, 0.0, 0.0), &to), to);
+        assert_eq!(map(0).raycast(&from, &to), from);
+    }
+}
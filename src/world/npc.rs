@@ -0,0 +1,32 @@
+use crate::world::position::Position;
+
+/// A non-player character players interact with, e.g. to trade or use their storage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Npc {
+    /// ID of the NPC's entity, assigned when it's added to the [World](crate::world::state::World).
+    pub unique_id: u32,
+    /// ID of the NPC's model in the reference data.
+    pub ref_obj_id: u32,
+    pub position: Position,
+    /// The items sold in each tab of the shop, empty if the NPC doesn't trade.
+    pub goods: Vec<Vec<u32>>,
+    /// Whether players can access their storage at the NPC.
+    pub storage: bool,
+}
+
+impl Npc {
+    /// Creates an NPC without shop or storage.
+    pub fn new(ref_obj_id: u32, position: Position) -> Npc {
+        Npc { unique_id: 0, ref_obj_id, position, goods: Vec::new(), storage: false }
+    }
+
+    /// Returns the item sold in given tab and slot of the shop.
+    pub fn good(&self, tab: u8, slot: u8) -> Option<u32> {
+        self.goods.get(tab as usize)?.get(slot as usize).copied()
+    }
+
+    /// Returns whether the NPC sells or buys back any items.
+    pub fn is_shop(&self) -> bool {
+        !self.goods.is_empty()
+    }
+}
@@ -0,0 +1,362 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use tokio::task::spawn_blocking;
+
+use crate::world::errors::Error;
+use crate::world::errors::Error::{BudgetExceeded, Cancelled, NotWalkable, Unreachable};
+use crate::world::navigation::{from_local_coordinates, local_coordinates, CellId, NavMap, Point};
+use crate::world::position::Position;
+
+/// Default number of cells a search may visit, which is enough to cross a few regions.
+pub const DEFAULT_BUDGET: usize = 4096;
+
+/// A cell waiting to be visited, ordered by its estimated total cost.
+struct Candidate {
+    estimate: f32,
+    /// The cost of reaching the cell when it was queued, which is stale once a cheaper way
+    /// was found.
+    cost: f32,
+    cell: CellId,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate.total_cmp(&other.estimate) == Ordering::Equal
+    }
+}
+impl Eq for Candidate {}
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, so the binary heap pops the lowest estimate first
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl NavMap {
+    /// Finds a path between two positions with A* over the cells of the navigation meshes, also
+    /// across region borders, and smoothes it by pulling it tight along the crossed edges.
+    ///
+    /// Returns the corners of the path, starting with `from` and ending with `to`. At most
+    /// `budget` cells are visited. Where movement isn't checked (see [NavMap]) the path is a
+    /// straight line.
+    pub fn find_path(&self, from: &Position, to: &Position, budget: usize) -> Result<Vec<Position>, Error> {
+        if !from.same_space(to) {
+            return Err(Unreachable);
+        }
+        if self.unchecked(from) {
+            return Ok(vec![*from, *to]);
+        }
+        let start = self.cell_at(from).ok_or(NotWalkable(*from))?;
+        let goal = self.cell_at(to).ok_or(NotWalkable(*to))?;
+        if start == goal {
+            // cells are convex, so the straight line stays within
+            return Ok(vec![*from, *to]);
+        }
+        let (start_point, goal_point) = (local_coordinates(from), local_coordinates(to));
+
+        let mut open = BinaryHeap::new();
+        let mut costs: HashMap<CellId, f32> = HashMap::new();
+        // the cell each cell was reached from, and the end points of the edge crossed
+        let mut came_from: HashMap<CellId, (CellId, Point, Point)> = HashMap::new();
+        open.push(Candidate { estimate: distance(start_point, goal_point), cost: 0.0, cell: start });
+        costs.insert(start, 0.0);
+        let mut visited = 0;
+        while let Some(Candidate { cell, cost, .. }) = open.pop() {
+            if cost > costs[&cell] {
+                continue;
+            }
+            if cell == goal {
+                let portals = portals(&came_from, start, goal);
+                let corners = funnel(start_point, goal_point, &portals);
+                let inner = corners.len().saturating_sub(2);
+                let mut path: Vec<Position> = corners.iter().skip(1).take(inner)
+                    .map(|(x, z)| self.with_height(from_local_coordinates(*x, *z, to.y)))
+                    .collect();
+                path.insert(0, *from);
+                path.push(self.with_height(to.normalized()));
+                return Ok(path);
+            }
+            visited += 1;
+            if visited > budget {
+                return Err(BudgetExceeded(budget));
+            }
+            let center = if cell == start { start_point } else { self.cell_center(cell) };
+            for (neighbour, a, b) in self.neighbours(cell) {
+                let entry = if neighbour == goal { goal_point } else { self.cell_center(neighbour) };
+                let next_cost = cost + distance(center, entry);
+                if costs.get(&neighbour).is_some_and(|known| *known <= next_cost) {
+                    continue;
+                }
+                costs.insert(neighbour, next_cost);
+                came_from.insert(neighbour, (cell, a, b));
+                open.push(Candidate { estimate: next_cost + distance(entry, goal_point), cost: next_cost, cell: neighbour });
+            }
+        }
+        Err(Unreachable)
+    }
+}
+
+/// Finds paths on the blocking thread pool, so AI tasks don't block the runtime's workers.
+/// Clones share the navigation meshes.
+#[derive(Clone)]
+pub struct Pathfinder {
+    navigation: Arc<NavMap>,
+    budget: usize,
+}
+
+impl Pathfinder {
+    /// Creates a pathfinder visiting at most `budget` cells per search (see [NavMap::find_path]).
+    pub fn new(navigation: Arc<NavMap>, budget: usize) -> Pathfinder {
+        Pathfinder { navigation, budget }
+    }
+
+    /// Finds a path between two positions (see [NavMap::find_path]). A panic of the search is
+    /// resumed on the calling task.
+    pub async fn find_path(&self, from: Position, to: Position) -> Result<Vec<Position>, Error> {
+        let (navigation, budget) = (self.navigation.clone(), self.budget);
+        match spawn_blocking(move || navigation.find_path(&from, &to, budget)).await {
+            Ok(path) => path,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(_) => Err(Cancelled),
+        }
+    }
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+/// Twice the signed area of the triangle, positive if `c` lies clockwise of the line from `a` to `b`.
+fn triangle_area(a: Point, b: Point, c: Point) -> f32 {
+    (c.0 - a.0) * (b.1 - a.1) - (b.0 - a.0) * (c.1 - a.1)
+}
+
+/// Returns the edges crossed from the start to the goal cell as (left, right) end points, seen
+/// in the direction of travel.
+fn portals(came_from: &HashMap<CellId, (CellId, Point, Point)>, start: CellId, goal: CellId) -> Vec<(Point, Point)> {
+    let mut portals = Vec::new();
+    let mut cell = goal;
+    while cell != start {
+        let (previous, a, b) = came_from[&cell];
+        portals.push((a, b));
+        cell = previous;
+    }
+    portals.reverse();
+    portals
+}
+
+/// Pulls the path through the portals tight using the simple stupid funnel algorithm and returns
+/// its corners, including start and goal.
+fn funnel(start: Point, goal: Point, edges: &[(Point, Point)]) -> Vec<Point> {
+    // orient each edge by the side of the previous corridor point it lies on
+    let mut portals = vec![(start, start)];
+    let mut previous = start;
+    for (a, b) in edges.iter() {
+        let middle = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        let reference = if middle == previous { goal } else { previous };
+        let (left, right) = if triangle_area(reference, middle, *a) < 0.0 { (*a, *b) } else { (*b, *a) };
+        portals.push((left, right));
+        previous = middle;
+    }
+    portals.push((goal, goal));
+
+    let mut corners = vec![start];
+    let (mut apex, mut left, mut right) = (start, start, start);
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut i = 1;
+    while i < portals.len() {
+        let (portal_left, portal_right) = portals[i];
+        if triangle_area(apex, right, portal_right) <= 0.0 {
+            if apex == right || triangle_area(apex, left, portal_right) > 0.0 {
+                right = portal_right;
+                right_index = i;
+            } else {
+                if corners.last() != Some(&left) {
+                    corners.push(left);
+                }
+                let apex_index = left_index;
+                apex = left;
+                (left, right) = (apex, apex);
+                (left_index, right_index) = (apex_index, apex_index);
+                i = apex_index + 1;
+                continue;
+            }
+        }
+        if triangle_area(apex, left, portal_left) >= 0.0 {
+            if apex == left || triangle_area(apex, right, portal_left) < 0.0 {
+                left = portal_left;
+                left_index = i;
+            } else {
+                if corners.last() != Some(&right) {
+                    corners.push(right);
+                }
+                let apex_index = right_index;
+                apex = right;
+                (left, right) = (apex, apex);
+                (left_index, right_index) = (apex_index, apex_index);
+                i = apex_index + 1;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if corners.last() != Some(&goal) {
+        corners.push(goal);
+    }
+    corners
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::navmesh::geometry::{Rect, Vec2};
+    use crate::navmesh::nvm::{Cell, Edge, GlobalEdge, RegionMesh, Tile, EDGE_BLOCKED_DST_TO_SRC, EDGE_BLOCKED_SRC_TO_DST, HEIGHTS_PER_SIDE, TILES_PER_SIDE};
+    use crate::world::position::RegionId;
+
+    use super::*;
+
+    const REGION: RegionId = RegionId(0x61A8);
+    const EAST: RegionId = RegionId(0x61A9);
+    /// The test regions consist of 4 × 4 square cells.
+    const CELLS: usize = 4;
+    const CELL_SIZE: f32 = 480.0;
+    const BLOCKED: u8 = EDGE_BLOCKED_SRC_TO_DST | EDGE_BLOCKED_DST_TO_SRC;
+
+    /// Builds a region of [CELLS]² cells, numbered row by row. The edges between horizontally
+    /// neighbouring cells are blocked for all rows listed in `walls`, at the given column border.
+    fn grid(walls: &[(usize, usize)]) -> RegionMesh {
+        let index = |column: usize, row: usize| (row * CELLS + column) as u16;
+        let point = |column: usize, row: usize| Vec2::new(column as f32 * CELL_SIZE, row as f32 * CELL_SIZE);
+        let mut cells = Vec::new();
+        let mut internal_edges = Vec::new();
+        for row in 0..CELLS {
+            for column in 0..CELLS {
+                cells.push(Cell { rect: Rect { min: point(column, row), max: point(column + 1, row + 1) }, objects: Vec::new() });
+                if column + 1 < CELLS {
+                    let flag = if walls.contains(&(column + 1, row)) { BLOCKED } else { 0 };
+                    internal_edges.push(Edge { start: point(column + 1, row), end: point(column + 1, row + 1), flag, directions: [0, 0], cells: [index(column, row), index(column + 1, row)] });
+                }
+                if row + 1 < CELLS {
+                    internal_edges.push(Edge { start: point(column, row + 1), end: point(column + 1, row + 1), flag: 0, directions: [0, 0], cells: [index(column, row), index(column, row + 1)] });
+                }
+            }
+        }
+        let tiles_per_cell = TILES_PER_SIDE / CELLS;
+        let tiles = (0..TILES_PER_SIDE * TILES_PER_SIDE)
+            .map(|i| Tile { cell: index(i % TILES_PER_SIDE / tiles_per_cell, i / TILES_PER_SIDE / tiles_per_cell) as u32, flag: 0, texture: 0 })
+            .collect();
+        RegionMesh {
+            objects: Vec::new(),
+            cells,
+            open_cells: (CELLS * CELLS) as u32,
+            global_edges: Vec::new(),
+            internal_edges,
+            tiles,
+            heights: vec![0.0; HEIGHTS_PER_SIDE * HEIGHTS_PER_SIDE],
+        }
+    }
+
+    fn position(region: RegionId, x: f32, z: f32) -> Position {
+        Position::new(region, x, 0.0, z)
+    }
+
+    fn assert_near(actual: &Position, expected: &Position) {
+        assert_eq!(actual.region, expected.region, "{} != {}", actual, expected);
+        assert!((actual.x - expected.x).abs() < 0.1 && (actual.z - expected.z).abs() < 0.1, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn walks_straight_through_open_cells() {
+        let mut map = NavMap::new();
+        map.insert(REGION, grid(&[]));
+        let (from, to) = (position(REGION, 100.0, 100.0), position(REGION, 1800.0, 1700.0));
+        assert_eq!(map.find_path(&from, &to, DEFAULT_BUDGET).unwrap(), vec![from, to]);
+    }
+
+    #[test]
+    fn finds_paths_within_a_cell() {
+        let mut map = NavMap::new();
+        map.insert(REGION, grid(&[]));
+        let (here, other) = (position(REGION, 100.0, 100.0), position(REGION, 300.0, 400.0));
+        assert_eq!(map.find_path(&here, &here, DEFAULT_BUDGET).unwrap(), vec![here, here]);
+        assert_eq!(map.find_path(&here, &other, DEFAULT_BUDGET).unwrap(), vec![here, other]);
+        assert_eq!(funnel((1.0, 1.0), (1.0, 1.0), &[]), vec![(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn goes_around_walls() {
+        let mut map = NavMap::new();
+        map.insert(REGION, grid(&[(1, 0), (1, 1), (1, 2)]));
+        let (from, to) = (position(REGION, 240.0, 240.0), position(REGION, 720.0, 240.0));
+        let path = map.find_path(&from, &to, DEFAULT_BUDGET).unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[0], from);
+        assert_near(&path[1], &position(REGION, 480.0, 1440.0));
+        assert_eq!(path[2], to);
+    }
+
+    #[test]
+    fn respects_one_way_edges() {
+        let mut mesh = grid(&[]);
+        mesh.internal_edges.iter_mut()
+            .filter(|edge| edge.start.x == CELL_SIZE && edge.end.x == CELL_SIZE)
+            .for_each(|edge| edge.flag = EDGE_BLOCKED_DST_TO_SRC);
+        let mut map = NavMap::new();
+        map.insert(REGION, mesh);
+        let (west, east) = (position(REGION, 240.0, 240.0), position(REGION, 720.0, 240.0));
+        assert_eq!(map.find_path(&west, &east, DEFAULT_BUDGET).unwrap().len(), 2);
+        assert_eq!(map.find_path(&east, &west, DEFAULT_BUDGET), Err(Unreachable));
+    }
+
+    #[test]
+    fn crosses_region_borders() {
+        let mut west = grid(&[]);
+        let mut east = grid(&[]);
+        for row in 0..CELLS {
+            let (start, end) = (Vec2::new(1920.0, row as f32 * CELL_SIZE), Vec2::new(1920.0, (row + 1) as f32 * CELL_SIZE));
+            let cells = [(row * CELLS + CELLS - 1) as u16, (row * CELLS) as u16];
+            let edge = GlobalEdge { edge: Edge { start, end, flag: 0, directions: [0, 0], cells }, regions: [REGION.0, EAST.0] };
+            west.global_edges.push(edge);
+            east.global_edges.push(GlobalEdge { edge: Edge { start: Vec2::new(0.0, start.z), end: Vec2::new(0.0, end.z), ..edge.edge }, ..edge });
+        }
+        let mut map = NavMap::new();
+        map.insert(REGION, west);
+        map.insert(EAST, east);
+        let (from, to) = (position(REGION, 1000.0, 100.0), position(EAST, 500.0, 1500.0));
+        assert_eq!(map.find_path(&from, &to, DEFAULT_BUDGET).unwrap(), vec![from, to]);
+        assert_eq!(map.find_path(&to, &from, DEFAULT_BUDGET).unwrap(), vec![to, from]);
+    }
+
+    #[test]
+    fn stops_at_budget() {
+        let mut map = NavMap::new();
+        map.insert(REGION, grid(&[(1, 0), (1, 1), (1, 2)]));
+        let (from, to) = (position(REGION, 240.0, 240.0), position(REGION, 720.0, 240.0));
+        assert_eq!(map.find_path(&from, &to, 3), Err(BudgetExceeded(3)));
+        assert_eq!(map.find_path(&from, &position(EAST, 0.0, 0.0), 3), Err(NotWalkable(position(EAST, 0.0, 0.0))));
+    }
+
+    #[tokio::test]
+    async fn finds_paths_asynchronously() {
+        let mut map = NavMap::new();
+        map.insert(REGION, grid(&[(2, 1), (2, 2), (2, 3)]));
+        let pathfinder = Pathfinder::new(Arc::new(map), DEFAULT_BUDGET);
+        let (from, to) = (position(REGION, 100.0, 1800.0), position(REGION, 1800.0, 1800.0));
+        let path = pathfinder.find_path(from, to).await.unwrap();
+        assert_eq!((path[0], path[path.len() - 1]), (from, to));
+        assert_near(&path[1], &position(REGION, 960.0, 480.0));
+        assert!(path.windows(2).all(|corners| corners[0] != corners[1]));
+    }
+
+    #[test]
+    fn funnel_keeps_straight_corridors_straight() {
+        let portals = [((10.0, 0.0), (10.0, 10.0)), ((20.0, 0.0), (20.0, 10.0))];
+        assert_eq!(funnel((0.0, 5.0), (30.0, 5.0), &portals), vec![(0.0, 5.0), (30.0, 5.0)]);
+    }
+}
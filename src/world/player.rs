@@ -0,0 +1,47 @@
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::world::movement::{Movement, RUN_SPEED, WALK_SPEED};
+use crate::world::position::Position;
+
+/// A character in the game world, controlled by the client of a session.
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub session: Uuid,
+    /// ID of the character's entity, which clients use to refer to it.
+    pub unique_id: u32,
+    /// ID of the character's model in the reference data.
+    pub ref_obj_id: u32,
+    pub name: String,
+    pub movement: Movement,
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub running: bool,
+}
+
+impl Player {
+    /// Creates a running player standing at given position.
+    pub fn new(session: Uuid, unique_id: u32, name: &str, ref_obj_id: u32, position: Position) -> Player {
+        Player {
+            session,
+            unique_id,
+            ref_obj_id,
+            name: name.to_string(),
+            movement: Movement::standing(position),
+            walk_speed: WALK_SPEED,
+            run_speed: RUN_SPEED,
+            running: true,
+        }
+    }
+
+    /// Returns the position of the player at given time.
+    pub fn position(&self, now: Instant) -> Position {
+        self.movement.position_at(now)
+    }
+
+    /// Returns the current movement speed in local units per second.
+    pub fn speed(&self) -> f32 {
+        if self.running { self.run_speed } else { self.walk_speed }
+    }
+}
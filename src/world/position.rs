@@ -0,0 +1,236 @@
+use std::fmt::{Display, Formatter};
+
+/// Width and depth of a region in local units.
+pub const REGION_SIZE: f32 = 1920.0;
+/// Width and depth of a region in world units, which are ten local units.
+pub const REGION_WORLD_SIZE: f32 = 192.0;
+/// Local units per world unit.
+const LOCAL_UNITS: f32 = 10.0;
+/// Region X sector at the origin of the world coordinates.
+const ORIGIN_X: i32 = 135;
+/// Region Y sector at the origin of the world coordinates.
+const ORIGIN_Y: i32 = 92;
+/// Flag of the region ID marking a dungeon.
+const DUNGEON_FLAG: u16 = 0x8000;
+
+/// ID of a region as sent by the client. Field regions consist of a Y sector in the high byte
+/// and an X sector in the low byte. Dungeons have the highest bit set, the remaining bits
+/// identify the dungeon, which has its own coordinate space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RegionId(pub u16);
+
+impl RegionId {
+    /// Creates the ID of the field region at given sectors. The Y sector must be below 128,
+    /// since the highest bit marks dungeons.
+    pub fn field(x: u8, y: u8) -> RegionId {
+        assert!(y < 0x80, "Y sector {} collides with the dungeon flag", y);
+        RegionId((y as u16) << 8 | x as u16)
+    }
+
+    /// Creates the ID of the dungeon with given index.
+    pub fn dungeon(index: u16) -> RegionId {
+        RegionId(DUNGEON_FLAG | (index & !DUNGEON_FLAG))
+    }
+
+    pub fn is_dungeon(&self) -> bool {
+        self.0 & DUNGEON_FLAG != 0
+    }
+
+    /// Returns the X sector of a field region.
+    pub fn x(&self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Returns the Y sector of a field region.
+    pub fn y(&self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    /// Returns the region whose sectors are offset by given amounts, or `None` for dungeons and
+    /// sectors outside the world.
+    pub fn offset(&self, dx: i32, dy: i32) -> Option<RegionId> {
+        if self.is_dungeon() {
+            return None;
+        }
+        let (x, y) = (self.x() as i32 + dx, self.y() as i32 + dy);
+        if !(0..=0xFF).contains(&x) || !(0..0x80).contains(&y) {
+            return None;
+        }
+        Some(RegionId::field(x as u8, y as u8))
+    }
+}
+
+impl Display for RegionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_dungeon() {
+            write!(f, "dungeon {}", self.0 & !DUNGEON_FLAG)
+        } else {
+            write!(f, "{}x{}", self.x(), self.y())
+        }
+    }
+}
+
+/// A position in the game world: a region and an offset within it in local units. `x` and `z`
+/// span the ground plane from 0 to [REGION_SIZE], `y` is the height.
+///
+/// World coordinates, which the client uses for e.g. GM commands, are measured in world units
+/// across all field regions: `X = (region X - 135) * 192 + x / 10` and
+/// `Y = (region Y - 92) * 192 + z / 10`. Within dungeons the offset isn't limited to a region,
+/// and world coordinates are relative to the dungeon's origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub region: RegionId,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Position {
+    pub fn new(region: RegionId, x: f32, y: f32, z: f32) -> Position {
+        Position { region, x, y, z }
+    }
+
+    /// Creates a field position from world coordinates (see [Position]) and a height.
+    pub fn from_world(x: f32, y: f32, height: f32) -> Position {
+        let (rx, ry) = ((x / REGION_WORLD_SIZE).floor() as i32, (y / REGION_WORLD_SIZE).floor() as i32);
+        let region = RegionId((((ry + ORIGIN_Y) as u16 & 0x7F) << 8) | ((rx + ORIGIN_X) as u16 & 0xFF));
+        Position {
+            region,
+            x: (x - rx as f32 * REGION_WORLD_SIZE) * LOCAL_UNITS,
+            y: height,
+            z: (y - ry as f32 * REGION_WORLD_SIZE) * LOCAL_UNITS,
+        }
+    }
+
+    /// Creates a position within a dungeon from coordinates relative to its origin.
+    pub fn from_dungeon(region: RegionId, x: f32, y: f32, height: f32) -> Position {
+        Position { region, x: x * LOCAL_UNITS, y: height, z: y * LOCAL_UNITS }
+    }
+
+    /// Returns the world coordinates on the ground plane (see [Position]).
+    pub fn world(&self) -> (f32, f32) {
+        if self.region.is_dungeon() {
+            return (self.x / LOCAL_UNITS, self.z / LOCAL_UNITS);
+        }
+        (
+            (self.region.x() as i32 - ORIGIN_X) as f32 * REGION_WORLD_SIZE + self.x / LOCAL_UNITS,
+            (self.region.y() as i32 - ORIGIN_Y) as f32 * REGION_WORLD_SIZE + self.z / LOCAL_UNITS,
+        )
+    }
+
+    /// Moves a field position whose offset lies outside of its region into the region
+    /// containing it, e.g. after adding a movement vector. Dungeon positions are returned as they are.
+    pub fn normalized(&self) -> Position {
+        if self.region.is_dungeon() {
+            return *self;
+        }
+        let (x, z) = self.world();
+        Position::from_world(x, z, self.y)
+    }
+
+    /// Returns whether both positions share a coordinate space, i.e. they're both on the field or
+    /// in the same dungeon. Distances can only be measured within the same space.
+    pub fn same_space(&self, other: &Position) -> bool {
+        match (self.region.is_dungeon(), other.region.is_dungeon()) {
+            (false, false) => true,
+            (true, true) => self.region == other.region,
+            _ => false,
+        }
+    }
+
+    /// Returns the distance on the ground plane in world units, also across region borders.
+    /// `None` if the positions are in different coordinate spaces (see [Position::same_space]).
+    pub fn distance(&self, other: &Position) -> Option<f32> {
+        self.distance_squared(other).map(f32::sqrt)
+    }
+
+    /// Returns the squared distance on the ground plane in world units (see [Position::distance]),
+    /// which is cheaper for range checks.
+    pub fn distance_squared(&self, other: &Position) -> Option<f32> {
+        if !self.same_space(other) {
+            return None;
+        }
+        let ((x1, y1), (x2, y2)) = (self.world(), other.world());
+        Some((x2 - x1).powi(2) + (y2 - y1).powi(2))
+    }
+
+    /// Returns whether the other position is within given range in world units.
+    pub fn in_range(&self, other: &Position, range: f32) -> bool {
+        self.distance_squared(other).is_some_and(|d| d <= range * range)
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({:.1}, {:.1}, {:.1})", self.region, self.x, self.y, self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn splits_region_ids() {
+        let jangan = RegionId(0x61A8);
+        assert_eq!((jangan.x(), jangan.y()), (168, 97));
+        assert_eq!(RegionId::field(168, 97), jangan);
+        assert!(!jangan.is_dungeon());
+        assert_eq!(jangan.offset(1, -1), Some(RegionId(0x60A9)));
+        assert_eq!(RegionId::field(255, 0).offset(1, 0), None);
+
+        let dungeon = RegionId::dungeon(1);
+        assert_eq!(dungeon, RegionId(0x8001));
+        assert!(dungeon.is_dungeon());
+        assert_eq!(dungeon.offset(1, 0), None);
+        assert_eq!(dungeon.to_string(), "dungeon 1");
+    }
+
+    #[test]
+    fn converts_to_world_coordinates() {
+        let position = Position::new(RegionId(0x61A8), 960.0, 0.0, 1080.0);
+        assert_eq!(position.world(), ((168 - 135) as f32 * 192.0 + 96.0, (97 - 92) as f32 * 192.0 + 108.0));
+        assert_eq!(Position::from_world(6432.0, 1068.0, 0.0), position);
+        let origin = Position::from_world(-0.5, 0.0, 0.0);
+        assert_eq!(origin.region, RegionId::field(134, 92));
+        assert_eq!(origin.x, 1915.0);
+    }
+
+    #[test]
+    fn measures_across_region_borders() {
+        let a = Position::new(RegionId(0x61A8), 1910.0, 0.0, 0.0);
+        let b = Position::new(RegionId(0x61A9), 10.0, 50.0, 0.0);
+        assert_eq!(a.distance(&b), Some(2.0));
+        assert!(a.in_range(&b, 2.0));
+        assert!(!a.in_range(&b, 1.9));
+        let moved = Position::new(RegionId(0x61A8), 1930.0, 0.0, -10.0).normalized();
+        assert_eq!(moved, Position::new(RegionId(0x60A9), 10.0, 0.0, 1910.0));
+    }
+
+    #[test]
+    fn keeps_dungeons_apart() {
+        let field = Position::new(RegionId(0x61A8), 0.0, 0.0, 0.0);
+        let dungeon = Position::from_dungeon(RegionId::dungeon(1), 100.0, 200.0, 0.0);
+        let other = Position::from_dungeon(RegionId::dungeon(2), 100.0, 200.0, 0.0);
+        assert_eq!(dungeon.world(), (100.0, 200.0));
+        assert_eq!(dungeon.distance(&field), None);
+        assert_eq!(dungeon.distance(&other), None);
+        assert_eq!(dungeon.distance(&Position::from_dungeon(RegionId::dungeon(1), 103.0, 204.0, 0.0)), Some(5.0));
+        assert!(!dungeon.in_range(&field, f32::MAX));
+        let far = Position::new(dungeon.region, 5000.0, 0.0, -300.0);
+        assert_eq!(far.normalized(), far);
+    }
+
+    proptest! {
+        #[test]
+        fn world_coordinates_round_trip(x in 0u8..=255, y in 0u8..0x80, lx in 0.0f32..1919.0, lz in 0.0f32..1919.0) {
+            let position = Position::new(RegionId::field(x, y), lx, 0.0, lz);
+            let (wx, wy) = position.world();
+            let converted = Position::from_world(wx, wy, 0.0);
+            prop_assert_eq!(converted.region, position.region);
+            prop_assert!((converted.x - lx).abs() < 0.1 && (converted.z - lz).abs() < 0.1);
+        }
+    }
+}
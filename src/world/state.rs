@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::character::record::Item;
+use crate::world::ground::GroundItem;
+use crate::world::interest::InterestGrid;
+use crate::world::movement::{MoveTarget, Movement};
+use crate::world::navigation::NavMap;
+use crate::world::npc::Npc;
+use crate::world::pathfinding::{Pathfinder, DEFAULT_BUDGET};
+use crate::world::player::Player;
+use crate::world::position::{Position, RegionId};
+
+/// Maximum distance of a single movement in local units. Farther destinations are cut short, the
+/// client continues with a new movement once it arrives.
+pub const MAX_MOVE_DISTANCE: f32 = 1920.0;
+
+/// Maximum distance in world units at which players can interact with NPCs and items on the
+/// ground.
+pub const INTERACTION_RANGE: f32 = 50.0;
+
+/// State of the game world shared by all sessions: the terrain, the spawned players, NPCs and
+/// items on the ground.
+pub struct World {
+    navigation: Arc<NavMap>,
+    players: HashMap<Uuid, Player>,
+    npcs: HashMap<u32, Npc>,
+    ground: HashMap<u32, GroundItem>,
+    /// Sessions of the spawned players by their unique ID.
+    entities: HashMap<u32, Uuid>,
+    /// Regions of all players, NPCs and items on the ground.
+    interest: InterestGrid,
+    next_unique_id: u32,
+}
+
+/// A change of the entities a session's client sees, which has to be sent as spawn or despawn
+/// packets.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SightEvent {
+    Spawn { observer: Uuid, entities: Vec<u32> },
+    Despawn { observer: Uuid, entities: Vec<u32> },
+}
+
+impl World {
+    pub fn new(navigation: NavMap) -> World {
+        World {
+            navigation: Arc::new(navigation),
+            players: HashMap::new(),
+            npcs: HashMap::new(),
+            ground: HashMap::new(),
+            entities: HashMap::new(),
+            interest: InterestGrid::new(),
+            next_unique_id: 1,
+        }
+    }
+
+    pub fn navigation(&self) -> &Arc<NavMap> {
+        &self.navigation
+    }
+
+    /// Returns a pathfinder sharing the world's navigation meshes, e.g. for monster AI tasks.
+    pub fn pathfinder(&self) -> Pathfinder {
+        Pathfinder::new(self.navigation.clone(), DEFAULT_BUDGET)
+    }
+
+    /// Spawns the character of a session at given position, replacing a previously spawned one.
+    /// Returns the spawn events for the player and everyone seeing it.
+    pub fn spawn(&mut self, session: Uuid, name: &str, ref_obj_id: u32, position: Position) -> Vec<SightEvent> {
+        let mut events = self.despawn(&session).map(|(_, events)| events).unwrap_or_default();
+        let unique_id = self.next_unique_id();
+        self.players.insert(session, Player::new(session, unique_id, name, ref_obj_id, position));
+        self.entities.insert(unique_id, session);
+        let visible = self.interest.insert(unique_id, position.region);
+        events.extend(self.sight_events(session, unique_id, &visible, SightKind::Spawn));
+        events
+    }
+
+    /// Removes the character of a session from the world. Returns it together with the despawn
+    /// events for everyone who saw it.
+    pub fn despawn(&mut self, session: &Uuid) -> Option<(Player, Vec<SightEvent>)> {
+        let player = self.players.remove(session)?;
+        self.entities.remove(&player.unique_id);
+        let events = self.disappear(player.unique_id);
+        Some((player, events))
+    }
+
+    /// Moves all players to the region they're currently in within the [InterestGrid] and returns
+    /// the resulting spawn and despawn events. Called periodically, since moving players cross
+    /// region borders at any time.
+    pub fn update_sight(&mut self, now: Instant) -> Vec<SightEvent> {
+        let moved: Vec<(Uuid, u32, RegionId)> = self.players.values()
+            .map(|player| (player.session, player.unique_id, player.position(now).region))
+            .filter(|(_, unique_id, region)| self.interest.region(*unique_id) != Some(*region))
+            .collect();
+        let mut events = Vec::new();
+        for (session, unique_id, region) in moved {
+            let change = self.interest.update(unique_id, region);
+            events.extend(self.sight_events(session, unique_id, &change.left, SightKind::Despawn));
+            events.extend(self.sight_events(session, unique_id, &change.entered, SightKind::Spawn));
+        }
+        events
+    }
+
+    pub fn player(&self, session: &Uuid) -> Option<&Player> {
+        self.players.get(session)
+    }
+
+    /// Returns the player with given unique ID.
+    pub fn player_by_id(&self, unique_id: u32) -> Option<&Player> {
+        self.entities.get(&unique_id).and_then(|session| self.players.get(session))
+    }
+
+    pub fn player_mut(&mut self, session: &Uuid) -> Option<&mut Player> {
+        self.players.get_mut(session)
+    }
+
+    /// Starts a movement of the session's character from its current position towards the target.
+    /// The destination is shortened to [MAX_MOVE_DISTANCE] and to the furthest walkable position
+    /// (see [NavMap::raycast]). Returns `None` if the session has no spawned character.
+    pub fn move_player(&mut self, session: &Uuid, target: MoveTarget, now: Instant) -> Option<Movement> {
+        let player = self.players.get(session)?;
+        let source = player.position(now);
+        let wanted = match target {
+            MoveTarget::Destination(destination) => destination,
+            MoveTarget::Direction(angle) => {
+                let radians = angle as f32 / 65536.0 * TAU;
+                let (x, z) = source.world();
+                let offset = MAX_MOVE_DISTANCE / 10.0;
+                moved_in_space(&source, x + radians.cos() * offset, z + radians.sin() * offset)
+            }
+        };
+        let wanted = limit_distance(&source, &wanted);
+        let destination = self.navigation.raycast(&source, &wanted);
+        let movement = Movement::new(source, destination, player.speed(), now);
+        self.players.get_mut(session)?.movement = movement;
+        Some(movement)
+    }
+
+    /// Adds an NPC to the world. Returns its unique ID and the spawn events for everyone seeing
+    /// it.
+    pub fn add_npc(&mut self, mut npc: Npc) -> (u32, Vec<SightEvent>) {
+        npc.unique_id = self.next_unique_id();
+        let (unique_id, region) = (npc.unique_id, npc.position.region);
+        self.npcs.insert(unique_id, npc);
+        (unique_id, self.appear(unique_id, region))
+    }
+
+    /// Returns the NPC with given unique ID.
+    pub fn npc(&self, unique_id: u32) -> Option<&Npc> {
+        self.npcs.get(&unique_id)
+    }
+
+    /// Puts an item on the ground. Returns its unique ID and the spawn events for everyone
+    /// seeing it.
+    pub fn drop_item(&mut self, item: Item, position: Position) -> (u32, Vec<SightEvent>) {
+        let unique_id = self.next_unique_id();
+        self.ground.insert(unique_id, GroundItem { unique_id, item, position });
+        (unique_id, self.appear(unique_id, position.region))
+    }
+
+    /// Returns the item on the ground with given unique ID.
+    pub fn ground_item(&self, unique_id: u32) -> Option<&GroundItem> {
+        self.ground.get(&unique_id)
+    }
+
+    /// Removes an item from the ground, e.g. when it's picked up. Returns it together with the
+    /// despawn events for everyone who saw it.
+    pub fn pick_item(&mut self, unique_id: u32) -> Option<(GroundItem, Vec<SightEvent>)> {
+        let item = self.ground.remove(&unique_id)?;
+        let events = self.disappear(unique_id);
+        Some((item, events))
+    }
+
+    /// Returns the sessions whose characters see the given region (see [InterestGrid]).
+    pub fn nearby(&self, region: RegionId) -> Vec<Uuid> {
+        self.interest.in_sight_of(region).iter()
+            .filter_map(|entity| self.entities.get(entity).copied())
+            .collect()
+    }
+
+    fn next_unique_id(&mut self) -> u32 {
+        let unique_id = self.next_unique_id;
+        self.next_unique_id += 1;
+        unique_id
+    }
+
+    /// Adds an entity which doesn't see anything itself to the [InterestGrid]. Returns the spawn
+    /// events for the players seeing it.
+    fn appear(&mut self, unique_id: u32, region: RegionId) -> Vec<SightEvent> {
+        let visible = self.interest.insert(unique_id, region);
+        self.observers(&visible).map(|observer| SightEvent::Spawn { observer, entities: vec![unique_id] }).collect()
+    }
+
+    /// Removes an entity from the [InterestGrid]. Returns the despawn events for the players who
+    /// saw it.
+    fn disappear(&mut self, unique_id: u32) -> Vec<SightEvent> {
+        let visible = self.interest.remove(unique_id);
+        self.observers(&visible).map(|observer| SightEvent::Despawn { observer, entities: vec![unique_id] }).collect()
+    }
+
+    /// Returns the sessions of the players among given entities.
+    fn observers<'a>(&'a self, entities: &'a [u32]) -> impl Iterator<Item = Uuid> + 'a {
+        entities.iter().filter_map(move |entity| self.entities.get(entity).copied())
+    }
+
+    /// Returns the events for a player and the given entities seeing each other, or no longer.
+    fn sight_events(&self, session: Uuid, unique_id: u32, entities: &[u32], kind: SightKind) -> Vec<SightEvent> {
+        if entities.is_empty() {
+            return Vec::new();
+        }
+        let event = |observer, entities| match kind {
+            SightKind::Spawn => SightEvent::Spawn { observer, entities },
+            SightKind::Despawn => SightEvent::Despawn { observer, entities },
+        };
+        let mut events = vec![event(session, entities.to_vec())];
+        events.extend(self.observers(entities).map(|observer| event(observer, vec![unique_id])));
+        events
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SightKind {
+    Spawn,
+    Despawn,
+}
+
+/// Creates a position in the same coordinate space as `origin` from world coordinates.
+fn moved_in_space(origin: &Position, x: f32, z: f32) -> Position {
+    if origin.region.is_dungeon() {
+        Position::from_dungeon(origin.region, x, z, origin.y)
+    } else {
+        Position::from_world(x, z, origin.y)
+    }
+}
+
+/// Moves the destination closer to the source if it's farther away than [MAX_MOVE_DISTANCE].
+fn limit_distance(source: &Position, destination: &Position) -> Position {
+    match source.distance(destination) {
+        Some(distance) if distance * 10.0 > MAX_MOVE_DISTANCE => {
+            let ((x1, z1), (x2, z2)) = (source.world(), destination.world());
+            let t = MAX_MOVE_DISTANCE / 10.0 / distance;
+            moved_in_space(source, x1 + (x2 - x1) * t, z1 + (z2 - z1) * t)
+        }
+        Some(_) => *destination,
+        None => *source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::world::movement::RUN_SPEED;
+
+    use super::*;
+
+    #[test]
+    fn reports_sight_events() {
+        let mut world = World::new(NavMap::new());
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(world.spawn(a, "a", 1907, Position::new(RegionId(0x61A8), 1900.0, 0.0, 100.0)).is_empty());
+        assert!(world.spawn(b, "b", 1907, Position::new(RegionId(0x61AA), 1000.0, 0.0, 100.0)).is_empty());
+        assert_eq!(world.nearby(RegionId(0x61A8)), vec![a]);
+
+        let now = Instant::now();
+        let movement = world.move_player(&a, MoveTarget::Destination(Position::new(RegionId(0x61A9), 100.0, 0.0, 100.0)), now).unwrap();
+        assert_eq!(movement.speed, RUN_SPEED);
+        assert!(world.update_sight(now).is_empty());
+        assert_eq!(world.update_sight(now + Duration::from_secs(1)), vec![
+            SightEvent::Spawn { observer: a, entities: vec![2] },
+            SightEvent::Spawn { observer: b, entities: vec![1] },
+        ]);
+        assert_eq!(world.despawn(&b).unwrap().1, vec![SightEvent::Despawn { observer: a, entities: vec![2] }]);
+        assert!(world.player_by_id(2).is_none());
+    }
+
+    #[test]
+    fn shows_npcs_and_items_on_the_ground() {
+        let mut world = World::new(NavMap::new());
+        let (npc, events) = world.add_npc(Npc::new(2000, Position::new(RegionId(0x61A8), 100.0, 0.0, 100.0)));
+        assert!(events.is_empty());
+        let a = Uuid::new_v4();
+        assert_eq!(world.spawn(a, "a", 1907, Position::new(RegionId(0x61A9), 100.0, 0.0, 100.0)), vec![
+            SightEvent::Spawn { observer: a, entities: vec![npc] },
+        ]);
+        assert_eq!(world.nearby(RegionId(0x61A8)), vec![a]);
+
+        let (item, events) = world.drop_item(Item::default(), Position::new(RegionId(0x61AA), 100.0, 0.0, 100.0));
+        assert_eq!(events, vec![SightEvent::Spawn { observer: a, entities: vec![item] }]);
+        let (far, events) = world.drop_item(Item::default(), Position::new(RegionId(0x61AB), 100.0, 0.0, 100.0));
+        assert!(events.is_empty());
+        let (picked, events) = world.pick_item(item).unwrap();
+        assert_eq!(picked.unique_id, item);
+        assert_eq!(events, vec![SightEvent::Despawn { observer: a, entities: vec![item] }]);
+        assert!(world.pick_item(item).is_none());
+        assert!(world.pick_item(far).unwrap().1.is_empty());
+    }
+}